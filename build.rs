@@ -0,0 +1,607 @@
+//! Generates typed bindings for every canister interface in `candid/`.
+//!
+//! Each `candid/<name>.did` becomes a `pub mod <name>` holding a Rust type
+//! for every named Candid type (and for inline records and variants, named
+//! after where they appear), plus one `Method<Args, Reply>` constant per
+//! service method carrying the interface name, the method name and whether
+//! it is a query. The clients only call through these constants, so a method
+//! that is renamed, removed or changes its signature in a `.did` file fails
+//! the build instead of failing at call time.
+//!
+//! `<name>_ext.did` holds optional methods of the canister `<name>`; they
+//! get a module of their own but share the canister's name in call labels.
+
+use std::{collections::BTreeSet, env, fmt::Write as _, fs, path::Path};
+
+fn main() {
+    let did_dir = Path::new("candid");
+    println!("cargo:rerun-if-changed={}", did_dir.display());
+
+    let mut entries: Vec<_> = fs::read_dir(did_dir)
+        .expect("failed to read candid/")
+        .map(|entry| entry.expect("failed to read candid/ entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "did"))
+        .collect();
+    entries.sort();
+
+    let mut out = String::new();
+    for path in entries {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let module = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let canister = module.strip_suffix("_ext").unwrap_or(&module).to_string();
+        let source = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+        let interface = Parser::new(&source, &path).interface();
+        out.push_str(&Generator::new(&interface, &path).module(&interface, &module, &canister));
+    }
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("canister_methods.rs");
+    fs::write(dest, out).expect("failed to write canister_methods.rs");
+}
+
+/// A Candid type, as far as the interfaces in `candid/` use them.
+#[derive(Clone, Debug)]
+enum Type {
+    /// Rust path of a primitive type.
+    Primitive(&'static str),
+    Named(String),
+    Opt(Box<Type>),
+    Vec(Box<Type>),
+    Record(Vec<Field>),
+    Variant(Vec<Field>),
+}
+
+/// A record field or variant case. Unlabelled record fields are tuple
+/// positions; cases without a type carry `null`.
+#[derive(Clone, Debug)]
+struct Field {
+    label: Option<String>,
+    ty: Type,
+}
+
+struct MethodDecl {
+    name: String,
+    args: Vec<Type>,
+    rets: Vec<Type>,
+    query: bool,
+}
+
+struct Interface {
+    types: Vec<(String, Type)>,
+    methods: Vec<MethodDecl>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Text(String),
+    Punct(&'static str),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    path: &'a Path,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &str, path: &'a Path) -> Self {
+        Self {
+            tokens: tokenize(source, path),
+            pos: 0,
+            path,
+        }
+    }
+
+    fn interface(mut self) -> Interface {
+        let mut interface = Interface {
+            types: Vec::new(),
+            methods: Vec::new(),
+        };
+        while let Some(token) = self.next() {
+            match token {
+                Token::Ident(word) if word == "type" => {
+                    let name = self.ident();
+                    self.expect("=");
+                    let ty = self.data_type();
+                    self.expect(";");
+                    interface.types.push((name, ty));
+                }
+                Token::Ident(word) if word == "service" => {
+                    interface.methods = self.service();
+                    self.eat(";");
+                }
+                other => self.fail(&format!("unexpected {other:?} at top level")),
+            }
+        }
+        if interface.methods.is_empty() {
+            panic!("{} has no service block", self.path.display());
+        }
+        interface
+    }
+
+    /// `service [name] : [(init) ->] { methods }`, after `service`.
+    fn service(&mut self) -> Vec<MethodDecl> {
+        if matches!(self.peek(), Some(Token::Ident(_))) {
+            self.next();
+        }
+        self.expect(":");
+        if self.eat("(") {
+            self.tuple_rest();
+            self.expect("->");
+        }
+        self.expect("{");
+        let mut methods = Vec::new();
+        while !self.eat("}") {
+            let name = self.label();
+            self.expect(":");
+            self.expect("(");
+            let args = self.tuple_rest();
+            self.expect("->");
+            self.expect("(");
+            let rets = self.tuple_rest();
+            let mut query = false;
+            while let Some(Token::Ident(mode)) = self.peek() {
+                query |= mode == "query" || mode == "composite_query";
+                self.next();
+            }
+            self.expect(";");
+            methods.push(MethodDecl {
+                name,
+                args,
+                rets,
+                query,
+            });
+        }
+        methods
+    }
+
+    /// Argument types up to the closing parenthesis, which is consumed.
+    /// Argument names (`name : type`) are dropped.
+    fn tuple_rest(&mut self) -> Vec<Type> {
+        let mut types = Vec::new();
+        while !self.eat(")") {
+            if matches!(self.peek_at(1), Some(Token::Punct(":"))) {
+                self.next();
+                self.next();
+            }
+            types.push(self.data_type());
+            if !self.eat(",") {
+                self.expect(")");
+                break;
+            }
+        }
+        types
+    }
+
+    fn data_type(&mut self) -> Type {
+        let word = self.ident();
+        match word.as_str() {
+            "opt" => Type::Opt(Box::new(self.data_type())),
+            "vec" => Type::Vec(Box::new(self.data_type())),
+            "record" => Type::Record(self.fields(false)),
+            "variant" => Type::Variant(self.fields(true)),
+            _ => match primitive(&word) {
+                Some(path) => Type::Primitive(path),
+                None => Type::Named(word),
+            },
+        }
+    }
+
+    /// `{ field; ... }` of a record or variant.
+    fn fields(&mut self, variant: bool) -> Vec<Field> {
+        self.expect("{");
+        let mut fields = Vec::new();
+        while !self.eat("}") {
+            let labelled = matches!(self.peek_at(1), Some(Token::Punct(":")));
+            let field = if labelled {
+                let label = self.label();
+                self.expect(":");
+                Field {
+                    label: Some(label),
+                    ty: self.data_type(),
+                }
+            } else if variant {
+                Field {
+                    label: Some(self.label()),
+                    ty: Type::Primitive("()"),
+                }
+            } else {
+                Field {
+                    label: None,
+                    ty: self.data_type(),
+                }
+            };
+            fields.push(field);
+            if !self.eat(";") {
+                self.expect("}");
+                break;
+            }
+        }
+        fields
+    }
+
+    fn label(&mut self) -> String {
+        match self.next() {
+            Some(Token::Ident(name) | Token::Text(name)) => name,
+            other => self.fail(&format!("expected a name, found {other:?}")),
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        match self.next() {
+            Some(Token::Ident(name)) => name,
+            other => self.fail(&format!("expected an identifier, found {other:?}")),
+        }
+    }
+
+    fn expect(&mut self, punct: &str) {
+        if !self.eat(punct) {
+            let found = self.peek().cloned();
+            self.fail(&format!("expected `{punct}`, found {found:?}"));
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn fail(&self, message: &str) -> ! {
+        panic!("{}: {message}", self.path.display())
+    }
+}
+
+fn tokenize(source: &str, path: &Path) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                tokens.push(Token::Text(text));
+            }
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push(Token::Punct("->"));
+            }
+            '{' => tokens.push(Token::Punct("{")),
+            '}' => tokens.push(Token::Punct("}")),
+            '(' => tokens.push(Token::Punct("(")),
+            ')' => tokens.push(Token::Punct(")")),
+            ';' => tokens.push(Token::Punct(";")),
+            ':' => tokens.push(Token::Punct(":")),
+            ',' => tokens.push(Token::Punct(",")),
+            '=' => tokens.push(Token::Punct("=")),
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(word));
+            }
+            other => panic!("{}: unexpected character `{other}`", path.display()),
+        }
+    }
+    tokens
+}
+
+fn primitive(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "nat" => "candid::Nat",
+        "int" => "candid::Int",
+        "nat8" => "u8",
+        "nat16" => "u16",
+        "nat32" => "u32",
+        "nat64" => "u64",
+        "int8" => "i8",
+        "int16" => "i16",
+        "int32" => "i32",
+        "int64" => "i64",
+        "float32" => "f32",
+        "float64" => "f64",
+        "bool" => "bool",
+        "text" => "String",
+        "principal" => "candid::Principal",
+        "blob" => "Vec<u8>",
+        "null" => "()",
+        "reserved" => "candid::Reserved",
+        _ => return None,
+    })
+}
+
+const DERIVE: &str = "#[derive(candid::CandidType, candid::Deserialize, Clone, Debug)]";
+
+/// Writes the Rust items of one interface. Inline records and variants are
+/// named after where they appear: field `mint` of `Transaction` becomes
+/// `TransactionMint`, the `Err` of the reply of `get_account_transactions`
+/// becomes `GetAccountTransactionsErr`.
+struct Generator<'a> {
+    /// Rust names of the types the interface defines.
+    defined: BTreeSet<String>,
+    /// Every name an item was generated under, to catch collisions.
+    taken: BTreeSet<String>,
+    items: Vec<String>,
+    path: &'a Path,
+}
+
+impl<'a> Generator<'a> {
+    fn new(interface: &Interface, path: &'a Path) -> Self {
+        let mut defined = BTreeSet::new();
+        for (name, _) in &interface.types {
+            if !defined.insert(pascal(name)) {
+                panic!("{}: type {name} is defined twice", path.display());
+            }
+        }
+        Self {
+            defined,
+            taken: BTreeSet::new(),
+            items: Vec::new(),
+            path,
+        }
+    }
+
+    fn module(mut self, interface: &Interface, module: &str, canister: &str) -> String {
+        for (name, ty) in &interface.types {
+            self.definition(&pascal(name), ty);
+        }
+        let constants: Vec<String> = interface
+            .methods
+            .iter()
+            .map(|method| self.method(method, canister))
+            .collect();
+
+        let mut out = String::new();
+        writeln!(out, "pub mod {module} {{").unwrap();
+        writeln!(out, "#![allow(dead_code, clippy::type_complexity)]").unwrap();
+        writeln!(out, "use super::Method;").unwrap();
+        for item in self.items.iter().chain(&constants) {
+            writeln!(out, "{item}").unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    fn definition(&mut self, name: &str, ty: &Type) {
+        match ty {
+            Type::Record(fields) if fields.iter().all(|f| f.label.is_some()) => {
+                self.record(name, fields)
+            }
+            Type::Variant(fields) if !is_result(fields) => self.variant(name, fields),
+            _ => {
+                self.claim(name);
+                let rust = self.rust(ty, name);
+                self.items.push(format!("pub type {name} = {rust};"));
+            }
+        }
+    }
+
+    fn method(&mut self, method: &MethodDecl, canister: &str) -> String {
+        let base = pascal(&method.name);
+        let args = self.tuple(&method.args, &format!("{base}Arg"));
+        let rets = self.tuple(&method.rets, &base);
+        format!(
+            "pub const {}: Method<{args}, {rets}> = Method::new({canister:?}, {:?}, {});",
+            method.name.to_uppercase(),
+            method.name,
+            method.query
+        )
+    }
+
+    /// Rust tuple of argument or reply types.
+    fn tuple(&mut self, types: &[Type], context: &str) -> String {
+        let parts: Vec<String> = types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let context = if types.len() > 1 {
+                    format!("{context}{i}")
+                } else {
+                    context.to_string()
+                };
+                self.rust(ty, &context)
+            })
+            .collect();
+        match parts.len() {
+            1 => format!("({},)", parts[0]),
+            _ => format!("({})", parts.join(", ")),
+        }
+    }
+
+    /// Rust type for `ty`; inline records and variants become items named
+    /// `context`.
+    fn rust(&mut self, ty: &Type, context: &str) -> String {
+        match ty {
+            Type::Primitive(path) => path.to_string(),
+            Type::Named(name) => {
+                let name = pascal(name);
+                if !self.defined.contains(&name) {
+                    panic!("{}: type {name} is not defined", self.path.display());
+                }
+                name
+            }
+            Type::Opt(inner) => format!("Option<{}>", self.rust(inner, context)),
+            Type::Vec(inner) => format!("Vec<{}>", self.rust(inner, context)),
+            Type::Record(fields) if fields.iter().all(|f| f.label.is_none()) => {
+                let parts: Vec<String> = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| self.rust(&f.ty, &format!("{context}{i}")))
+                    .collect();
+                match parts.len() {
+                    1 => format!("({},)", parts[0]),
+                    _ => format!("({})", parts.join(", ")),
+                }
+            }
+            Type::Record(fields) => {
+                self.record(context, fields);
+                context.to_string()
+            }
+            Type::Variant(fields) if is_result(fields) => {
+                let ok = result_case(fields, "Ok");
+                let err = result_case(fields, "Err");
+                format!(
+                    "std::result::Result<{}, {}>",
+                    self.rust(ok, &format!("{context}Ok")),
+                    self.rust(err, &format!("{context}Err"))
+                )
+            }
+            Type::Variant(fields) => {
+                self.variant(context, fields);
+                context.to_string()
+            }
+        }
+    }
+
+    fn record(&mut self, name: &str, fields: &[Field]) {
+        self.claim(name);
+        let mut item = format!("{DERIVE}\npub struct {name} {{\n");
+        for field in fields {
+            let Some(label) = &field.label else {
+                panic!(
+                    "{}: record {name} mixes named and unnamed fields",
+                    self.path.display()
+                );
+            };
+            let ty = self.rust(&field.ty, &format!("{name}{}", pascal(label)));
+            writeln!(item, "    pub {}: {ty},", field_name(label, self.path)).unwrap();
+        }
+        item.push('}');
+        self.items.push(item);
+    }
+
+    fn variant(&mut self, name: &str, cases: &[Field]) {
+        self.claim(name);
+        let mut item = format!("{DERIVE}\npub enum {name} {{\n");
+        for case in cases {
+            let label = case.label.as_deref().unwrap();
+            let variant = pascal(label);
+            if variant != label {
+                writeln!(item, "    #[serde(rename = {label:?})]").unwrap();
+            }
+            let context = format!("{name}{variant}");
+            match &case.ty {
+                Type::Primitive("()") => writeln!(item, "    {variant},").unwrap(),
+                Type::Record(fields) if fields.iter().all(|f| f.label.is_some()) => {
+                    writeln!(item, "    {variant} {{").unwrap();
+                    for field in fields {
+                        let label = field.label.as_deref().unwrap();
+                        let ty = self.rust(&field.ty, &format!("{context}{}", pascal(label)));
+                        writeln!(item, "        {}: {ty},", field_name(label, self.path)).unwrap();
+                    }
+                    writeln!(item, "    }},").unwrap();
+                }
+                Type::Record(fields) if fields.iter().all(|f| f.label.is_none()) => {
+                    let parts: Vec<String> = fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, f)| self.rust(&f.ty, &format!("{context}{i}")))
+                        .collect();
+                    writeln!(item, "    {variant}({}),", parts.join(", ")).unwrap();
+                }
+                ty => {
+                    let ty = self.rust(ty, &context);
+                    writeln!(item, "    {variant}({ty}),").unwrap();
+                }
+            }
+        }
+        item.push('}');
+        self.items.push(item);
+    }
+
+    fn claim(&mut self, name: &str) {
+        if !self.taken.insert(name.to_string()) {
+            panic!(
+                "{}: two types would both be generated as {name}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+fn is_result(cases: &[Field]) -> bool {
+    let mut labels: Vec<&str> = cases.iter().filter_map(|c| c.label.as_deref()).collect();
+    labels.sort_unstable();
+    labels == ["Err", "Ok"]
+}
+
+fn result_case<'f>(cases: &'f [Field], label: &str) -> &'f Type {
+    &cases
+        .iter()
+        .find(|case| case.label.as_deref() == Some(label))
+        .unwrap()
+        .ty
+}
+
+/// `label` as a Rust field name; labels that are not plain identifiers are
+/// not supported.
+fn field_name(label: &str, path: &Path) -> String {
+    const KEYWORDS: &[&str] = &["type", "move", "ref", "self", "match", "fn", "impl", "mod"];
+    let plain = label
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !plain || KEYWORDS.contains(&label) {
+        panic!("{}: field label `{label}` is not supported", path.display());
+    }
+    label.to_string()
+}
+
+/// `canister_status_result` to `CanisterStatusResult`; names already in
+/// PascalCase are kept.
+fn pascal(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
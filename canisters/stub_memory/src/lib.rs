@@ -0,0 +1,183 @@
+//! Minimal memory canister for integration tests: keeps chunks in heap
+//! memory and ranks them by cosine similarity. It implements the same
+//! interface as the real memory canister (`candid/memory.did`) but performs
+//! no access control and no persistence across upgrades. Without the
+//! `extensions` feature only insert, search, tagged_embeddings, add_new_user
+//! and reset exist, like on the deployed memory canisters.
+
+use std::cell::RefCell;
+
+use candid::Principal;
+use ic_cdk::{init, query, update};
+use serde::Deserialize;
+
+const DEFAULT_DIM: u64 = 1024;
+
+#[derive(Default)]
+struct State {
+    dim: u64,
+    chunks: Vec<(Vec<f32>, String)>,
+    users: Vec<(Principal, u8)>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    tag: String,
+}
+
+#[init]
+fn init() {
+    STATE.with_borrow_mut(|state| state.dim = DEFAULT_DIM);
+}
+
+#[update]
+fn insert(embedding: Vec<f32>, text: String) -> u32 {
+    store(embedding, text)
+}
+
+#[cfg(feature = "extensions")]
+#[update]
+fn insert_f16(embedding: Vec<u16>, text: String) -> u32 {
+    store(embedding.into_iter().map(f16_to_f32).collect(), text)
+}
+
+#[cfg(feature = "extensions")]
+#[update]
+fn insert_i8(embedding: Vec<i8>, scale: f32, text: String) -> u32 {
+    store(
+        embedding
+            .into_iter()
+            .map(|value| f32::from(value) * scale)
+            .collect(),
+        text,
+    )
+}
+
+#[cfg(feature = "extensions")]
+#[query]
+fn capabilities() -> Vec<String> {
+    vec![
+        "dim".to_string(),
+        "insert_f16".to_string(),
+        "insert_i8".to_string(),
+        "search_paged".to_string(),
+    ]
+}
+
+fn store(embedding: Vec<f32>, text: String) -> u32 {
+    STATE.with_borrow_mut(|state| {
+        if embedding.len() as u64 != state.dim {
+            ic_cdk::trap(format!(
+                "embedding has {} dimensions, expected {}",
+                embedding.len(),
+                state.dim
+            ));
+        }
+        state.chunks.push((embedding, text));
+        (state.chunks.len() - 1) as u32
+    })
+}
+
+#[query]
+fn search(embedding: Vec<f32>) -> Vec<(f32, String)> {
+    ranked(&embedding)
+        .into_iter()
+        .map(|(score, _, text)| (score, text))
+        .collect()
+}
+
+#[cfg(feature = "extensions")]
+#[query]
+fn search_paged(embedding: Vec<f32>, offset: u32, limit: u32) -> Vec<(f32, u32, String)> {
+    ranked(&embedding)
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect()
+}
+
+#[cfg(feature = "extensions")]
+#[query]
+fn get(id: u32) -> Option<String> {
+    STATE.with_borrow(|state| state.chunks.get(id as usize).map(|(_, text)| text.clone()))
+}
+
+#[query]
+fn tagged_embeddings(tag: String) -> Vec<Vec<f32>> {
+    STATE.with_borrow(|state| {
+        state
+            .chunks
+            .iter()
+            .filter(|(_, text)| {
+                serde_json::from_str::<Payload>(text).is_ok_and(|payload| payload.tag == tag)
+            })
+            .map(|(embedding, _)| embedding.clone())
+            .collect()
+    })
+}
+
+#[update]
+fn add_new_user(principal: Principal, role: u8) {
+    STATE.with_borrow_mut(|state| {
+        state.users.retain(|(user, _)| *user != principal);
+        state.users.push((principal, role));
+    });
+}
+
+#[update]
+fn reset(dim: u64) {
+    STATE.with_borrow_mut(|state| {
+        state.dim = dim;
+        state.chunks.clear();
+    });
+}
+
+#[cfg(feature = "extensions")]
+#[query]
+fn dim() -> u64 {
+    STATE.with_borrow(|state| state.dim)
+}
+
+/// `(score, id, payload)` for every chunk, best match first.
+fn ranked(query: &[f32]) -> Vec<(f32, u32, String)> {
+    let mut hits: Vec<_> = STATE.with_borrow(|state| {
+        state
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(id, (embedding, text))| (cosine(query, embedding), id as u32, text.clone()))
+            .collect()
+    });
+    hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+    hits
+}
+
+#[cfg(feature = "extensions")]
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f32::from(bits & 0x3ff);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => sign * f32::INFINITY,
+        31 => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,55 @@
+/*
+ * C interface of the Kinic library, built with `cargo build --release
+ * --features c-ffi` into target/release/lib_lib.so (lib_lib.dylib on
+ * macOS). See rust/ffi.rs.
+ *
+ * Every function except kinic_last_error and kinic_string_free returns 0 on
+ * success, or the CLI exit code of the failure (2 identity, 3 payment,
+ * 4 network, 5 canister reject, 6 embedding API, 7 validation, 1 other).
+ * kinic_last_error then describes it. Strings are UTF-8. Null output
+ * pointers are refused with 7 before any work is done; an internal panic
+ * returns 1 instead of unwinding into the caller.
+ */
+#ifndef KINIC_H
+#define KINIC_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Deploys a new memory paid from the identity's balance; *out_id receives
+ * its canister id. */
+int32_t kinic_create_memory(const char *identity, const char *name,
+                            const char *description, bool ic, char **out_id);
+
+/* *out_json receives the identity's memories as a JSON array of ids. */
+int32_t kinic_list_memories(const char *identity, bool ic, char **out_json);
+
+/* Chunks, embeds and stores text under tag; *out_chunks receives the
+ * number of chunks. Text that looks like it contains credentials fails
+ * validation unless allow_secrets is true. */
+int32_t kinic_insert_text(const char *identity, const char *memory_id,
+                          const char *tag, const char *text, bool ic,
+                          bool allow_secrets, size_t *out_chunks);
+
+/* *out_json receives the best top_k matches (all when 0) as a JSON array of
+ * {"score": float, "payload": string}, best first. */
+int32_t kinic_search(const char *identity, const char *memory_id,
+                     const char *query, size_t top_k, bool ic,
+                     char **out_json);
+
+/* Message of the last failure on this thread, or NULL. Do not free. */
+const char *kinic_last_error(void);
+
+/* Frees a string returned through an out-parameter. */
+void kinic_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KINIC_H */
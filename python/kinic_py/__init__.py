@@ -24,8 +24,25 @@ from .memories import (
     add_user,
 )
 
+KinicClient = native.KinicClient
+Memory = native.Memory
+KinicError = native.KinicError
+IdentityError = native.IdentityError
+NetworkError = native.NetworkError
+CanisterRejectError = native.CanisterRejectError
+EmbeddingApiError = native.EmbeddingApiError
+PaymentError = native.PaymentError
+ValidationError = native.ValidationError
+login = native.login
+pdf_to_markdown = native.pdf_to_markdown
+html_to_text = native.html_to_text
+chunk_text = native.chunk_text
+load_identity = native.load_identity
+
 __all__ = [
+    "KinicClient",
     "KinicMemories",
+    "Memory",
     "create_memory",
     "insert_file",
     "insert_markdown",
@@ -43,6 +60,18 @@ __all__ = [
     "update_instance",
     "reset_memory",
     "add_user",
+    "KinicError",
+    "IdentityError",
+    "NetworkError",
+    "CanisterRejectError",
+    "EmbeddingApiError",
+    "PaymentError",
+    "ValidationError",
+    "login",
+    "pdf_to_markdown",
+    "html_to_text",
+    "chunk_text",
+    "load_identity",
     "native",
     "__version__",
 ]
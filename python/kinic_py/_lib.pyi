@@ -0,0 +1,131 @@
+"""Type stubs for the native module built from rust/lib.rs.
+
+Keep in sync with the `#[pyfunction]`s and `#[pymethods]` there.
+"""
+
+from typing import AsyncIterator, Awaitable, Callable, Iterator, List, Optional, Tuple
+
+ProgressCallback = Callable[[int, int, str], None]
+
+class KinicError(RuntimeError): ...
+class IdentityError(KinicError): ...
+class NetworkError(KinicError): ...
+class CanisterRejectError(KinicError): ...
+class EmbeddingApiError(KinicError): ...
+class PaymentError(KinicError): ...
+class ValidationError(KinicError): ...
+
+class KinicClient:
+    """Holds a built agent so the keyring/PEM is read once for many calls."""
+
+    def __init__(
+        self,
+        identity: Optional[str] = None,
+        ic: Optional[bool] = None,
+        *,
+        ii: Optional[bool] = None,
+        identity_path: Optional[str] = None,
+    ) -> None:
+        """Use either a dfx `identity` name or `ii=True` (delegation from `login()`)."""
+    def principal(self) -> str:
+        """Principal of the identity this client acts as."""
+    def memories(self) -> List[Memory]:
+        """List deployed memories as `Memory` handles sharing this client's agent."""
+    def amemories(self) -> Awaitable[List[Memory]]:
+        """Awaitable `memories()`."""
+    def memory(self, memory_id: str) -> Memory:
+        """Get a handle for an existing memory canister."""
+    def create_memory(self, name: str, description: str) -> Memory:
+        """Deploy a new memory canister and return a handle to it."""
+    def acreate_memory(self, name: str, description: str) -> Awaitable[Memory]:
+        """Awaitable `create_memory()`."""
+
+class Memory:
+    """A memory canister bound to the agent of the `KinicClient` that produced it."""
+
+    @property
+    def id(self) -> str: ...
+    def insert(self, text: str, *, tag: str, allow_secrets: bool = False) -> int:
+        """Embed and insert markdown text; returns the number of chunks stored.
+        Text that looks like it contains credentials is refused unless
+        `allow_secrets` is set."""
+    def search(self, query: str, *, top_k: Optional[int] = None) -> List[Tuple[float, str]]:
+        """Semantic search; results are `(score, payload)` sorted by score."""
+    def ainsert(self, text: str, *, tag: str, allow_secrets: bool = False) -> Awaitable[int]:
+        """Awaitable `insert()`."""
+    def asearch(self, query: str, *, top_k: Optional[int] = None) -> Awaitable[List[Tuple[float, str]]]:
+        """Awaitable `search()`."""
+    def iter_chunks(self, *, tag: Optional[str] = None, page_size: int = 100) -> ChunkIterator:
+        """Every chunk as `(id, payload)` in insertion order, read a page of
+        `page_size` chunks at a time; with `tag`, only that tag's chunks.
+        Works with both `for` and `async for`."""
+
+class ChunkIterator(Iterator[Tuple[int, str]], AsyncIterator[Tuple[int, str]]):
+    """Iterator returned by `Memory.iter_chunks`."""
+
+    def __iter__(self) -> ChunkIterator: ...
+    def __next__(self) -> Tuple[int, str]: ...
+    def __aiter__(self) -> ChunkIterator: ...
+    def __anext__(self) -> Awaitable[Tuple[int, str]]: ...
+
+def greet() -> str: ...
+def create_memory(identity: str, name: str, description: str, ic: Optional[bool] = None) -> str: ...
+def list_memories(identity: str, ic: Optional[bool] = None) -> List[str]: ...
+def insert_memory(
+    identity: str,
+    memory_id: str,
+    tag: str,
+    text: Optional[str] = None,
+    file_path: Optional[str] = None,
+    ic: Optional[bool] = None,
+    progress: Optional[ProgressCallback] = None,
+    allow_secrets: bool = False,
+) -> int: ...
+def insert_memory_raw(
+    identity: str,
+    memory_id: str,
+    tag: str,
+    text: str,
+    embedding: List[float],
+    ic: Optional[bool] = None,
+    allow_secrets: bool = False,
+) -> int: ...
+def insert_memory_pdf(
+    identity: str,
+    memory_id: str,
+    tag: str,
+    file_path: str,
+    ic: Optional[bool] = None,
+    progress: Optional[ProgressCallback] = None,
+    allow_secrets: bool = False,
+) -> int: ...
+def pdf_to_markdown(file_path: str) -> str: ...
+def html_to_text(html: str) -> str: ...
+def chunk_text(text: str) -> List[str]: ...
+def search_memories(
+    identity: str, memory_id: str, query: str, ic: Optional[bool] = None
+) -> List[Tuple[float, str]]: ...
+def search_memories_raw(
+    identity: str, memory_id: str, embedding: List[float], ic: Optional[bool] = None
+) -> List[Tuple[float, str]]: ...
+def tagged_embeddings(
+    identity: str, memory_id: str, tag: str, ic: Optional[bool] = None
+) -> List[List[float]]: ...
+def ask_ai(
+    identity: str,
+    memory_id: str,
+    query: str,
+    top_k: Optional[int] = None,
+    language: Optional[str] = None,
+    ic: Optional[bool] = None,
+) -> Tuple[str, str]: ...
+def get_balance(identity: str, ic: Optional[bool] = None) -> Tuple[int, float]: ...
+def update_instance(identity: str, memory_id: str, ic: Optional[bool] = None) -> None: ...
+def reset_memory(identity: str, memory_id: str, dim: int, ic: Optional[bool] = None) -> None: ...
+def add_user(
+    identity: str, memory_id: str, user_id: str, role: str, ic: Optional[bool] = None
+) -> None: ...
+def login(identity_path: Optional[str] = None) -> str:
+    """Run the Internet Identity login flow and return the delegated principal."""
+def load_identity(identity_path: Optional[str] = None) -> str:
+    """Principal of the stored Internet Identity delegation."""
@@ -0,0 +1,128 @@
+"""LangChain retriever and vector store backed by a Kinic memory.
+
+Needs `langchain-core` (`pip install kinic-py[langchain]`):
+
+    from kinic_py import KinicClient
+    from kinic_py.langchain import KinicRetriever
+
+    memory = KinicClient("<identity>").memory("<memory canister id>")
+    retriever = KinicRetriever(memory=memory, top_k=4)
+    docs = retriever.invoke("What did we decide?")
+"""
+
+from __future__ import annotations
+
+from typing import Any, Iterable, List, Optional, Tuple
+
+from langchain_core.callbacks import (
+    AsyncCallbackManagerForRetrieverRun,
+    CallbackManagerForRetrieverRun,
+)
+from langchain_core.documents import Document
+from langchain_core.embeddings import Embeddings
+from langchain_core.retrievers import BaseRetriever
+from langchain_core.vectorstores import VectorStore
+from pydantic import ConfigDict
+
+from . import _lib as native
+from .memories import split_payload
+
+DEFAULT_TAG = "langchain"
+
+
+def _to_document(score: float, payload: str) -> Document:
+    text, metadata = split_payload(payload)
+    metadata["score"] = score
+    return Document(page_content=text, metadata=metadata)
+
+
+class KinicRetriever(BaseRetriever):
+    """Returns the `top_k` chunks of `memory` closest to the query.
+
+    The query is embedded by the Kinic embedding API, like `Memory.search`.
+    Each document carries the chunk's tag, stored metadata and score.
+    """
+
+    model_config = ConfigDict(arbitrary_types_allowed=True)
+
+    memory: native.Memory
+    top_k: int = 4
+
+    def _get_relevant_documents(
+        self, query: str, *, run_manager: CallbackManagerForRetrieverRun
+    ) -> List[Document]:
+        hits = self.memory.search(query, top_k=self.top_k)
+        return [_to_document(score, payload) for score, payload in hits]
+
+    async def _aget_relevant_documents(
+        self, query: str, *, run_manager: AsyncCallbackManagerForRetrieverRun
+    ) -> List[Document]:
+        hits = await self.memory.asearch(query, top_k=self.top_k)
+        return [_to_document(score, payload) for score, payload in hits]
+
+
+class KinicVectorStore(VectorStore):
+    """LangChain vector store over a Kinic memory.
+
+    Kinic embeds text itself, so no `Embeddings` is needed. Texts are chunked
+    and embedded on insert, one chunk or more per text; the memory assigns
+    no ids that could be returned, so `add_texts` returns an empty list.
+    """
+
+    def __init__(self, memory: native.Memory, *, tag: str = DEFAULT_TAG) -> None:
+        self.memory = memory
+        self.tag = tag
+
+    @property
+    def embeddings(self) -> Optional[Embeddings]:
+        return None
+
+    def add_texts(
+        self,
+        texts: Iterable[str],
+        metadatas: Optional[List[dict]] = None,
+        **kwargs: Any,
+    ) -> List[str]:
+        """Insert each text under `metadatas[i]["tag"]`, or the store's tag.
+
+        Other metadata is not stored.
+        """
+        for index, text in enumerate(texts):
+            metadata = metadatas[index] if metadatas else {}
+            self.memory.insert(text, tag=metadata.get("tag", self.tag))
+        return []
+
+    def similarity_search(self, query: str, k: int = 4, **kwargs: Any) -> List[Document]:
+        return [doc for doc, _ in self.similarity_search_with_score(query, k, **kwargs)]
+
+    def similarity_search_with_score(self, query: str, k: int = 4, **kwargs: Any) -> List[Tuple[Document, float]]:
+        return [(_to_document(score, payload), score) for score, payload in self.memory.search(query, top_k=k)]
+
+    async def asimilarity_search(self, query: str, k: int = 4, **kwargs: Any) -> List[Document]:
+        return [doc for doc, _ in await self.asimilarity_search_with_score(query, k, **kwargs)]
+
+    async def asimilarity_search_with_score(
+        self, query: str, k: int = 4, **kwargs: Any
+    ) -> List[Tuple[Document, float]]:
+        hits = await self.memory.asearch(query, top_k=k)
+        return [(_to_document(score, payload), score) for score, payload in hits]
+
+    def _select_relevance_score_fn(self):
+        # Scores are cosine similarities, already higher-is-better.
+        return lambda score: score
+
+    @classmethod
+    def from_texts(
+        cls,
+        texts: List[str],
+        embedding: Optional[Embeddings] = None,
+        metadatas: Optional[List[dict]] = None,
+        *,
+        memory: native.Memory,
+        tag: str = DEFAULT_TAG,
+        **kwargs: Any,
+    ) -> "KinicVectorStore":
+        """Insert `texts` into `memory`; `embedding` is ignored."""
+        store = cls(memory, tag=tag)
+        store.add_texts(texts, metadatas)
+        return store
@@ -0,0 +1,44 @@
+"""LlamaIndex retriever backed by a Kinic memory.
+
+Needs `llama-index-core` (`pip install kinic-py[llama-index]`):
+
+    from kinic_py import KinicClient
+    from kinic_py.llama_index import KinicLlamaRetriever
+
+    memory = KinicClient("<identity>").memory("<memory canister id>")
+    nodes = KinicLlamaRetriever(memory, top_k=4).retrieve("What did we decide?")
+"""
+
+from __future__ import annotations
+
+from typing import Any, List, Tuple
+
+from llama_index.core.retrievers import BaseRetriever
+from llama_index.core.schema import NodeWithScore, QueryBundle, TextNode
+
+from . import _lib as native
+from .memories import split_payload
+
+
+class KinicLlamaRetriever(BaseRetriever):
+    """Returns the `top_k` chunks of `memory` closest to the query as text
+    nodes carrying the chunk's tag and stored metadata."""
+
+    def __init__(self, memory: native.Memory, *, top_k: int = 4, **kwargs: Any) -> None:
+        self._memory = memory
+        self._top_k = top_k
+        super().__init__(**kwargs)
+
+    def _retrieve(self, query_bundle: QueryBundle) -> List[NodeWithScore]:
+        return _to_nodes(self._memory.search(query_bundle.query_str, top_k=self._top_k))
+
+    async def _aretrieve(self, query_bundle: QueryBundle) -> List[NodeWithScore]:
+        return _to_nodes(await self._memory.asearch(query_bundle.query_str, top_k=self._top_k))
+
+
+def _to_nodes(hits: List[Tuple[float, str]]) -> List[NodeWithScore]:
+    nodes = []
+    for score, payload in hits:
+        text, metadata = split_payload(payload)
+        nodes.append(NodeWithScore(node=TextNode(text=text, metadata=metadata), score=score))
+    return nodes
@@ -2,12 +2,16 @@
 
 from __future__ import annotations
 
+import json
 import warnings
-from typing import List, Sequence, Tuple
+from typing import Callable, List, Optional, Sequence, Tuple
 
 from . import _lib as native
 
 ScoreResult = Sequence[Tuple[float, str]]
+# Called as progress(chunk_index, total, status) while inserting; see
+# `insert_markdown`.
+ProgressCallback = Callable[[int, int, str], None]
 
 
 class KinicMemories:
@@ -25,21 +29,53 @@ class KinicMemories:
         """List deployed memories."""
         return list_memories(self.identity, ic=self.ic)
 
-    def insert_markdown(self, memory_id: str, tag: str, text: str) -> int:
+    def insert_markdown(
+        self,
+        memory_id: str,
+        tag: str,
+        text: str,
+        *,
+        progress: Optional[ProgressCallback] = None,
+        allow_secrets: bool = False,
+    ) -> int:
         """Insert markdown text directly."""
-        return insert_markdown(self.identity, memory_id, tag, text, ic=self.ic)
+        return insert_markdown(
+            self.identity, memory_id, tag, text, ic=self.ic, progress=progress, allow_secrets=allow_secrets
+        )
 
-    def insert_markdown_file(self, memory_id: str, tag: str, path: str) -> int:
+    def insert_markdown_file(
+        self,
+        memory_id: str,
+        tag: str,
+        path: str,
+        *,
+        progress: Optional[ProgressCallback] = None,
+        allow_secrets: bool = False,
+    ) -> int:
         """Insert markdown loaded from disk."""
-        return insert_markdown_file(self.identity, memory_id, tag, path, ic=self.ic)
+        return insert_markdown_file(
+            self.identity, memory_id, tag, path, ic=self.ic, progress=progress, allow_secrets=allow_secrets
+        )
 
-    def insert_raw(self, memory_id: str, tag: str, text: str, embedding: Sequence[float]) -> int:
+    def insert_raw(
+        self, memory_id: str, tag: str, text: str, embedding: Sequence[float], *, allow_secrets: bool = False
+    ) -> int:
         """Insert a precomputed embedding with text."""
-        return insert_raw(self.identity, memory_id, tag, text, embedding, ic=self.ic)
+        return insert_raw(self.identity, memory_id, tag, text, embedding, ic=self.ic, allow_secrets=allow_secrets)
 
-    def insert_pdf_file(self, memory_id: str, tag: str, path: str) -> int:
+    def insert_pdf_file(
+        self,
+        memory_id: str,
+        tag: str,
+        path: str,
+        *,
+        progress: Optional[ProgressCallback] = None,
+        allow_secrets: bool = False,
+    ) -> int:
         """Convert a PDF to markdown and insert it."""
-        return insert_pdf_file(self.identity, memory_id, tag, path, ic=self.ic)
+        return insert_pdf_file(
+            self.identity, memory_id, tag, path, ic=self.ic, progress=progress, allow_secrets=allow_secrets
+        )
 
     def insert_pdf(self, memory_id: str, tag: str, path: str) -> int:
         """Deprecated: use insert_pdf_file instead."""
@@ -124,8 +160,22 @@ def insert_markdown(
     text: str,
     *,
     ic: bool | None = None,
+    progress: Optional[ProgressCallback] = None,
+    allow_secrets: bool = False,
 ) -> int:
-    return native.insert_memory(identity, memory_id, tag, text=text, ic=ic)
+    """Chunk, embed and insert markdown text; returns the number of chunks.
+
+    `progress(chunk_index, total, status)` is called with status "chunked"
+    once the text is split, then "inserted" after each chunk. An exception
+    it raises aborts the insert.
+
+    Text that looks like it contains credentials (private keys, API tokens,
+    passwords) raises `ValidationError` like the CLI's `insert`; pass
+    `allow_secrets=True` to insert it anyway.
+    """
+    return native.insert_memory(
+        identity, memory_id, tag, text=text, ic=ic, progress=progress, allow_secrets=allow_secrets
+    )
 
 
 def insert_markdown_file(
@@ -135,8 +185,12 @@ def insert_markdown_file(
     path: str,
     *,
     ic: bool | None = None,
+    progress: Optional[ProgressCallback] = None,
+    allow_secrets: bool = False,
 ) -> int:
-    return native.insert_memory(identity, memory_id, tag, file_path=path, ic=ic)
+    return native.insert_memory(
+        identity, memory_id, tag, file_path=path, ic=ic, progress=progress, allow_secrets=allow_secrets
+    )
 
 
 def insert_raw(
@@ -147,8 +201,11 @@ def insert_raw(
     embedding: Sequence[float],
     *,
     ic: bool | None = None,
+    allow_secrets: bool = False,
 ) -> int:
-    return native.insert_memory_raw(identity, memory_id, tag, text, list(embedding), ic=ic)
+    return native.insert_memory_raw(
+        identity, memory_id, tag, text, list(embedding), ic=ic, allow_secrets=allow_secrets
+    )
 
 
 def insert_pdf_file(
@@ -158,8 +215,14 @@ def insert_pdf_file(
     path: str,
     *,
     ic: bool | None = None,
+    progress: Optional[ProgressCallback] = None,
+    allow_secrets: bool = False,
 ) -> int:
-    return native.insert_memory_pdf(identity, memory_id, tag, path, ic=ic)
+    """Like `insert_markdown`; `progress` first reports status "converting"
+    while the PDF is turned into markdown."""
+    return native.insert_memory_pdf(
+        identity, memory_id, tag, path, ic=ic, progress=progress, allow_secrets=allow_secrets
+    )
 
 
 def insert_pdf(
@@ -267,3 +330,21 @@ def add_user(
     ic: bool | None = None,
 ) -> None:
     return native.add_user(identity, memory_id, user_id, role, ic=ic)
+
+
+def split_payload(payload: str) -> tuple[str, dict]:
+    """Split a stored chunk payload into its text and metadata.
+
+    Chunk payloads are JSON with `tag`, `sentence` and optional `metadata`;
+    anything else (e.g. plain text from `insert_raw` before payloads were
+    JSON) is returned as the text with empty metadata.
+    """
+    try:
+        chunk = json.loads(payload)
+    except ValueError:
+        return payload, {}
+    if not isinstance(chunk, dict) or "sentence" not in chunk:
+        return payload, {}
+    metadata = dict(chunk.get("metadata") or {})
+    metadata["tag"] = chunk.get("tag", "")
+    return chunk["sentence"], metadata
@@ -1,20 +1,63 @@
-use std::{io::Cursor, sync::Arc};
+use std::{
+    fs,
+    io::{Cursor, IsTerminal, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use ic_agent::{
     Agent, Identity,
-    export::reqwest::Url,
+    export::reqwest::{Client, Url},
     identity::{BasicIdentity, Secp256k1Identity},
 };
 
+use k256::{
+    Scalar, SecretKey,
+    elliptic_curve::{PrimeField, sec1::ToEncodedPoint},
+};
+use ring::hmac;
+
+use crate::{clients::cassette, error::KinicError, identity_store, network};
+
 pub const KEYRING_SERVICE_NAME: &str = "internet_computer_identities";
 pub const KEYRING_IDENTITY_PREFIX: &str = "internet_computer_identity_";
+pub const HSM_LIB_ENV_VAR: &str = "KINIC_HSM_LIB";
+pub const HSM_PIN_ENV_VAR: &str = "KINIC_HSM_PIN";
+/// Hardened-index flag of a BIP32 path segment.
+const HARDENED: u32 = 0x8000_0000;
+/// `m/44'/223'/0'/0/0`, the secp256k1 key dfx (`identity import
+/// --seed-file`) and quill derive from a seed phrase; 223 is the Internet
+/// Computer's SLIP-44 coin type.
+const SEED_PHRASE_PATH: [u32; 5] = [44 | HARDENED, 223 | HARDENED, HARDENED, 0, 0];
+
+/// Where `--identity` keys are read from.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialStore {
+    /// The system keyring, as written by `dfx identity new --storage-mode keyring`.
+    Keyring,
+    /// `~/.config/kinic/credentials/<name>.json` (encrypted), else dfx's
+    /// plaintext `~/.config/dfx/identity/<name>/identity.pem`.
+    File,
+}
+
+/// Where to find a signing key held in a PKCS#11 token.
+#[derive(Clone, Debug)]
+pub struct HsmConfig {
+    /// PKCS#11 module; falls back to `KINIC_HSM_LIB`.
+    pub lib_path: Option<PathBuf>,
+    pub slot_index: usize,
+    /// Hex id of the key object on the token.
+    pub key_id: String,
+}
 
 #[derive(Clone)]
 pub struct AgentFactory {
     use_mainnet: bool,
     identity_suffix: String,
     identity_override: Option<Arc<dyn Identity>>,
+    /// `None` tries the keyring, then the file store.
+    credential_store: Option<CredentialStore>,
 }
 
 impl AgentFactory {
@@ -23,6 +66,7 @@ impl AgentFactory {
             use_mainnet,
             identity_suffix: identity_suffix.into(),
             identity_override: None,
+            credential_store: None,
         }
     }
 
@@ -34,47 +78,251 @@ impl AgentFactory {
             use_mainnet,
             identity_suffix: String::new(),
             identity_override: Some(Arc::new(identity)),
+            credential_store: None,
         }
     }
 
-    pub async fn build(&self) -> Result<Agent> {
-        let builder = if let Some(identity) = &self.identity_override {
-            Agent::builder().with_arc_identity(identity.clone())
-        } else {
-            let pem_bytes = load_pem_from_keyring(&self.identity_suffix)?;
-            let pem_text = String::from_utf8(pem_bytes.clone())?;
-            let pem = pem::parse(pem_text.as_bytes())?;
-            match pem.tag() {
-                "PRIVATE KEY" => {
-                    let identity = BasicIdentity::from_pem(Cursor::new(pem_text.clone()))?;
-                    Agent::builder().with_identity(identity)
-                }
-                "EC PRIVATE KEY" => {
-                    let identity = Secp256k1Identity::from_pem(Cursor::new(pem_text.clone()))?;
-                    Agent::builder().with_identity(identity)
-                }
-                _ => anyhow::bail!("Unsupported PEM tag: {}", pem.tag()),
-            }
-        };
+    /// Signs with a key that never leaves the HSM. The user PIN is read from
+    /// `KINIC_HSM_PIN` when the token is opened.
+    pub fn new_with_hsm(use_mainnet: bool, config: &HsmConfig) -> Result<Self> {
+        let identity = load_hsm_identity(config).map_err(|err| {
+            err.context(KinicError::Identity(format!(
+                "Failed to open HSM key {} in slot {}",
+                config.key_id, config.slot_index
+            )))
+        })?;
+        Ok(Self {
+            use_mainnet,
+            identity_suffix: String::new(),
+            identity_override: Some(identity),
+            credential_store: None,
+        })
+    }
+
+    /// Reads `--identity` keys only from `store`, without falling back.
+    pub fn with_credential_store(mut self, store: Option<CredentialStore>) -> Self {
+        self.credential_store = store;
+        self
+    }
 
-        let url = if self.use_mainnet {
+    /// Same network, different identity (e.g. anonymous for `doctor`).
+    pub fn with_identity<I>(&self, identity: I) -> Self
+    where
+        I: Identity + 'static,
+    {
+        Self::new_with_identity(self.use_mainnet, identity)
+    }
+
+    pub fn use_mainnet(&self) -> bool {
+        self.use_mainnet
+    }
+
+    /// The `--identity` keyring name; empty when the identity comes from
+    /// elsewhere.
+    pub fn identity_name(&self) -> &str {
+        &self.identity_suffix
+    }
+
+    /// URL of the replica or boundary node the agent talks to.
+    pub fn url(&self) -> &'static str {
+        if self.use_mainnet {
             "https://ic0.app"
         } else {
             "http://127.0.0.1:4943"
+        }
+    }
+
+    /// Resolves the identity the agent will sign with, reading the keyring
+    /// or file credential store when no identity was supplied up front.
+    pub fn identity(&self) -> Result<Arc<dyn Identity>> {
+        if let Some(identity) = &self.identity_override {
+            return Ok(identity.clone());
+        }
+        let source = match self.credential_store {
+            Some(CredentialStore::File) => "the file credential store",
+            _ => "the system keyring",
         };
-        let url = Url::parse(url)?;
+        self.load_stored_identity().map_err(|err| {
+            err.context(KinicError::Identity(format!(
+                "Failed to load identity \"{}\" from {source}",
+                self.identity_suffix
+            )))
+        })
+    }
+
+    /// PEM key of `--identity`. Without an explicit store, a keyring that
+    /// cannot be used (e.g. no Secret Service on a headless Linux server)
+    /// falls back to the file store when it holds the key.
+    fn load_pem(&self) -> Result<Vec<u8>> {
+        let name = &self.identity_suffix;
+        match self.credential_store {
+            Some(CredentialStore::Keyring) => load_pem_from_keyring(name),
+            Some(CredentialStore::File) => identity_store::load_pem_from_file(name),
+            None => load_pem_from_keyring(name).or_else(|err| {
+                if !identity_store::credential_file_exists(name) {
+                    return Err(err);
+                }
+                tracing::debug!(error = %format!("{err:#}"), "keyring unavailable; using the file credential store");
+                identity_store::load_pem_from_file(name)
+            }),
+        }
+    }
+
+    fn load_stored_identity(&self) -> Result<Arc<dyn Identity>> {
+        let pem_bytes = self.load_pem()?;
+        identity_from_pem(&String::from_utf8(pem_bytes)?)
+    }
+
+    pub async fn build(&self) -> Result<Agent> {
+        let mut builder = Agent::builder().with_arc_identity(self.identity()?);
+        if network::is_configured() {
+            let client = network::configure(Client::builder())?
+                .build()
+                .context("Failed to build HTTP client for the IC agent")?;
+            builder = builder.with_http_client(client);
+        }
+
+        let url = Url::parse(self.url())?;
         let agent = builder.with_url(url).build()?;
 
-        if !self.use_mainnet {
-            agent.fetch_root_key().await?;
+        // A replayed session never talks to the replica.
+        if !self.use_mainnet && !cassette::is_replaying() {
+            network::with_deadline("replica.fetch_root_key", async {
+                Ok(agent.fetch_root_key().await?)
+            })
+            .await?;
         }
         Ok(agent)
     }
 }
 
+#[cfg(feature = "hsm")]
+fn load_hsm_identity(config: &HsmConfig) -> Result<Arc<dyn Identity>> {
+    let lib_path = match &config.lib_path {
+        Some(path) => path.clone(),
+        None => std::env::var_os(HSM_LIB_ENV_VAR)
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("pass --hsm-lib or set {HSM_LIB_ENV_VAR}"))?,
+    };
+    let identity = ic_identity_hsm::HardwareIdentity::new(
+        lib_path,
+        config.slot_index,
+        &config.key_id,
+        || {
+            std::env::var(HSM_PIN_ENV_VAR)
+                .map_err(|_| format!("set {HSM_PIN_ENV_VAR} to the HSM user PIN"))
+        },
+    )?;
+    Ok(Arc::new(identity))
+}
+
+#[cfg(not(feature = "hsm"))]
+fn load_hsm_identity(_config: &HsmConfig) -> Result<Arc<dyn Identity>> {
+    anyhow::bail!("kinic-cli was built without HSM support; rebuild with `--features hsm`")
+}
+
+/// An Ed25519 (`PRIVATE KEY`) or secp256k1 (`EC PRIVATE KEY`) key, the two
+/// kinds dfx creates.
+pub fn identity_from_pem(pem_text: &str) -> Result<Arc<dyn Identity>> {
+    let pem = pem::parse(pem_text.as_bytes())?;
+    let identity: Arc<dyn Identity> = match pem.tag() {
+        "PRIVATE KEY" => Arc::new(BasicIdentity::from_pem(Cursor::new(pem_text))?),
+        "EC PRIVATE KEY" => Arc::new(Secp256k1Identity::from_pem(Cursor::new(pem_text))?),
+        _ => anyhow::bail!("Unsupported PEM tag: {}", pem.tag()),
+    };
+    Ok(identity)
+}
+
+/// The secp256k1 identity dfx and quill derive from a BIP39 seed phrase
+/// (English words, no BIP39 passphrase).
+pub fn identity_from_seed_phrase(phrase: &str) -> Result<Secp256k1Identity> {
+    let phrase = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mnemonic = bip39::Mnemonic::parse_in_normalized(bip39::Language::English, &phrase)
+        .map_err(|err| KinicError::Validation(format!("invalid seed phrase: {err}")))?;
+    let key = derive_bip32(&mnemonic.to_seed_normalized(""), &SEED_PHRASE_PATH)?;
+    Ok(Secp256k1Identity::from_private_key(key))
+}
+
+/// Reads a seed phrase from `path`, or with `-` from the terminal (without
+/// echo) or standard input.
+pub fn read_seed_phrase(path: &Path) -> Result<String> {
+    if path != Path::new("-") {
+        return fs::read_to_string(path)
+            .with_context(|| format!("Failed to read seed phrase from {}", path.display()));
+    }
+    if std::io::stdin().is_terminal() {
+        return rpassword::prompt_password("Seed phrase: ").context("Failed to read seed phrase");
+    }
+    let mut phrase = String::new();
+    std::io::stdin()
+        .read_to_string(&mut phrase)
+        .context("Failed to read seed phrase from stdin")?;
+    Ok(phrase)
+}
+
+/// BIP32 private key derivation of `path` from `seed`.
+fn derive_bip32(seed: &[u8], path: &[u32]) -> Result<SecretKey> {
+    let master = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA512, b"Bitcoin seed"), seed);
+    let (mut key, mut chain_code) = split_extended_key(master.as_ref())?;
+    for &index in path {
+        let mut data = Vec::with_capacity(37);
+        if index & HARDENED != 0 {
+            data.push(0);
+            data.extend_from_slice(&key.to_bytes());
+        } else {
+            data.extend_from_slice(key.public_key().to_encoded_point(true).as_bytes());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+        let child = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA512, &chain_code), &data);
+        let (tweak, child_chain_code) = split_extended_key(child.as_ref())?;
+        let sum = tweak.to_nonzero_scalar().as_ref() + key.to_nonzero_scalar().as_ref();
+        key = SecretKey::from_bytes(&sum.to_bytes())
+            .map_err(|_| anyhow::anyhow!("seed phrase derives an invalid key"))?;
+        chain_code = child_chain_code;
+    }
+    Ok(key)
+}
+
+/// Left half as a private key (rejected when not below the curve order),
+/// right half as the chain code.
+fn split_extended_key(bytes: &[u8]) -> Result<(SecretKey, [u8; 32])> {
+    let (left, right) = bytes.split_at(32);
+    let mut repr = k256::FieldBytes::default();
+    repr.copy_from_slice(left);
+    let scalar: Option<Scalar> = Scalar::from_repr(repr).into();
+    let key = scalar
+        .and_then(|scalar| SecretKey::from_bytes(&scalar.to_bytes()).ok())
+        .ok_or_else(|| anyhow::anyhow!("seed phrase derives an invalid key"))?;
+    Ok((key, right.try_into()?))
+}
+
+fn keyring_identity_entry(suffix: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(
+        KEYRING_SERVICE_NAME,
+        &format!("{KEYRING_IDENTITY_PREFIX}{suffix}"),
+    )
+}
+
+/// Whether the keyring holds a key for `suffix`; `false` when the keyring
+/// cannot be read.
+pub fn keyring_pem_exists(suffix: &str) -> bool {
+    keyring_identity_entry(suffix).is_ok_and(|entry| entry.get_password().is_ok())
+}
+
+/// Stores `pem` the way `dfx identity new --storage-mode keyring` does: hex
+/// encoded, under the dfx service and account.
+pub fn save_pem_to_keyring(suffix: &str, pem: &[u8]) -> Result<()> {
+    keyring_identity_entry(suffix)?
+        .set_password(&hex::encode(pem))
+        .context("Failed to save the identity to the system keyring")
+}
+
 fn load_pem_from_keyring(suffix: &str) -> anyhow::Result<Vec<u8>> {
-    let account = format!("{KEYRING_IDENTITY_PREFIX}{suffix}");
-    let entry = keyring::Entry::new(KEYRING_SERVICE_NAME, &account)?;
+    let entry = keyring_identity_entry(suffix)?;
     let encoded_pem = entry.get_password().map_err(|e| {
         let msg = format!("{e:?}");
         if msg.contains("-67671") || msg.contains("errSecInteractionNotAllowed") {
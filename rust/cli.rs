@@ -6,8 +6,12 @@ use _lib as kinic_cli;
 async fn main() -> ExitCode {
     let _ = dotenvy::dotenv();
     if let Err(e) = kinic_cli::run().await {
+        let class = kinic_cli::error::classify(&e);
         eprintln!("{e:?}");
-        return ExitCode::from(1);
+        if let Some(hint) = class.hint() {
+            eprintln!("\nhint: {hint}");
+        }
+        return ExitCode::from(class.exit_code());
     }
     ExitCode::SUCCESS
 }
@@ -1,6 +1,8 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
-use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use crate::agent::CredentialStore;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -21,6 +23,36 @@ pub struct GlobalOpts {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    #[arg(
+        short,
+        long,
+        conflicts_with = "verbose",
+        help = "Print only data on stdout; suppress info logs and progress messages"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "Log line format"
+    )]
+    pub log_format: LogFormat,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append logs to PATH instead of stderr"
+    )]
+    pub log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILTER",
+        help = "RUST_LOG-style filter, e.g. `warn,_lib::embedding=debug` (default: $RUST_LOG, else -v/-q)"
+    )]
+    pub log_filter: Option<String>,
+
     #[arg(
         long,
         help = "Use the Internet Computer mainnet instead of local replica"
@@ -29,24 +61,105 @@ pub struct GlobalOpts {
 
     #[arg(
         long,
-        conflicts_with = "ii",
-        required_unless_present = "ii",
+        conflicts_with_all = ["ii", "hsm", "session"],
         help = "Dfx identity name used to load credentials from the system keyring"
     )]
     pub identity: Option<String>,
 
     #[arg(
         long,
+        value_name = "PATH",
+        conflicts_with_all = ["identity", "ii", "hsm", "session"],
+        help = "Sign with the secp256k1 key dfx and quill derive from the BIP39 seed phrase in PATH (`-` to type it)"
+    )]
+    pub seed_phrase_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        requires = "identity",
+        help = "Where --identity keys are read from (default: the keyring, falling back to the file store)"
+    )]
+    pub credential_store: Option<CredentialStore>,
+
+    #[arg(
+        long,
+        conflicts_with = "hsm",
         help = "Use Internet Identity login (delegation saved to identity.json)"
     )]
     pub ii: bool,
 
+    #[arg(
+        long,
+        requires = "hsm_key_id",
+        help = "Sign with a key held in a PKCS#11 HSM (requires the `hsm` build feature)"
+    )]
+    pub hsm: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "PKCS#11 module to load (default: $KINIC_HSM_LIB)"
+    )]
+    pub hsm_lib: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 0, value_name = "N", help = "HSM slot index")]
+    pub hsm_slot: usize,
+
+    #[arg(long, value_name = "HEX", help = "Id of the key object in the HSM")]
+    pub hsm_key_id: Option<String>,
+
     #[arg(
         long,
         value_name = "PATH",
         help = "Path to identity.json (default: ~/.config/kinic/identity.json)"
     )]
     pub identity_path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Give up on canister calls and HTTP requests after SECS seconds"
+    )]
+    pub timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Serve Prometheus metrics on http://ADDR/metrics while the command runs (e.g. 127.0.0.1:9464)"
+    )]
+    pub metrics_addr: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["hsm", "identity_path"],
+        help = "Use the Internet Identity session saved with `login --as NAME` (implies --ii)"
+    )]
+    pub session: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "replay",
+        help = "Save every canister reply of this run to FILE for later --replay"
+    )]
+    pub record: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Serve canister replies from a --record file instead of the network"
+    )]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, for log aggregation.
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
@@ -55,32 +168,102 @@ pub enum Command {
     Create(CreateArgs),
     #[command(about = "List deployed memories and their principals")]
     List(ListArgs),
+    #[command(
+        about = "Insert a file, directory or URL, converted according to what it contains"
+    )]
+    Add(AddArgs),
     #[command(about = "Insert text into an existing memory canister")]
     Insert(InsertArgs),
     #[command(about = "Insert a precomputed embedding into a memory canister")]
     InsertRaw(InsertRawArgs),
     #[command(about = "Insert a PDF (converted to markdown) into an existing memory canister")]
     InsertPdf(InsertPdfArgs),
+    #[command(about = "Describe an image with the vision endpoint and insert the description")]
+    InsertImage(InsertImageArgs),
+    #[command(about = "Insert the notes of an Obsidian vault, skipping unchanged ones")]
+    InsertObsidian(InsertObsidianArgs),
+    #[command(about = "Insert an mbox archive or EML files, one document per thread")]
+    InsertMail(InsertMailArgs),
+    #[command(about = "Fetch and insert the pages of a browser bookmark export")]
+    InsertBookmarks(InsertBookmarksArgs),
     #[command(about = "Convert a PDF to markdown and print it (no insert)")]
     ConvertPdf(ConvertPdfArgs),
+    #[command(
+        about = "Estimate the chunks, API calls, time and cycles of inserting a file, without inserting"
+    )]
+    Estimate(EstimateArgs),
     #[command(about = "Search within a memory canister using embeddings")]
     Search(SearchArgs),
+    #[command(about = "Print a single stored chunk by id")]
+    Get(GetArgs),
+    #[command(about = "Search interactively, re-querying as you type, and pick a chunk")]
+    Pick(PickArgs),
     #[command(about = "Search within a memory canister using a precomputed embedding")]
     SearchRaw(SearchRawArgs),
     #[command(about = "Fetch embeddings for a tag from a memory canister")]
     TaggedEmbeddings(TaggedEmbeddingsArgs),
     #[command(about = "Manage Kinic CLI configuration")]
     Config(ConfigArgs),
+    #[command(about = "Grant a principal access to a memory")]
+    Share(ShareArgs),
     #[command(about = "Update a memory canister instance")]
     Update(UpdateArgs),
+    #[command(about = "Change the name or description of a memory")]
+    SetMetadata(SetMetadataArgs),
     #[command(about = "Reset a memory canister and set embedding dimension")]
     Reset(ResetArgs),
+    #[command(about = "Report chunk payload versions and copy chunks, upgraded, into another memory")]
+    MigratePayloads(MigratePayloadsArgs),
+    #[command(about = "Compare the chunks of two memories or export files by content hash")]
+    Diff(DiffArgs),
+    #[command(about = "Re-embed random chunks and check that each one retrieves itself")]
+    Verify(VerifyArgs),
+    #[command(about = "Copy the chunks matching a query into another memory")]
+    Materialize(MaterializeArgs),
+    #[command(about = "List chunks older than a retention age")]
+    Prune(PruneArgs),
     #[command(about = "Check KINIC token balance for the current identity")]
     Balance(BalanceArgs),
+    #[command(about = "Show KINIC ledger transactions for the current identity")]
+    History(HistoryArgs),
+    #[command(about = "Transfer KINIC tokens to another principal")]
+    Transfer(TransferArgs),
+    #[command(about = "Send KINIC to the launcher to top up a memory canister with cycles")]
+    TopUp(TopUpArgs),
+    #[command(about = "Show cycles, memory size, controllers and module hash of a memory canister")]
+    Status(StatusArgs),
+    #[command(about = "Wait until a memory canister answers, e.g. right after create")]
+    Wait(WaitArgs),
+    #[command(about = "Measure embedding, search and end-to-end latency for a list of queries")]
+    Bench(BenchArgs),
+    #[command(about = "Group the embeddings under a tag with k-means and summarize each cluster")]
+    Cluster(ClusterArgs),
+    #[command(about = "Project stored embeddings to 2D for plotting (JSON/CSV, optional HTML)")]
+    Visualize(VisualizeArgs),
     #[command(about = "Ask Kinic AI using memory search results (LLM placeholder)")]
     AskAi(AskAiArgs),
+    #[command(about = "Serve OpenAI-compatible chat and embeddings endpoints for a memory")]
+    Serve(ServeArgs),
     #[command(about = "Login via Internet Identity and store a delegation")]
     Login(LoginArgs),
+    #[command(about = "Encrypt (or decrypt) the session key in an existing identity.json")]
+    EncryptIdentity(EncryptIdentityArgs),
+    #[command(about = "Import PEM keys for use with --identity, or show the current principal")]
+    Identity(IdentityArgs),
+    #[command(about = "Manage saved Internet Identity sessions")]
+    Sessions(SessionsArgs),
+    #[command(about = "Show embedding requests, canister calls and estimated cycles used per command")]
+    Usage(UsageArgs),
+    #[command(about = "Check keyring, identity, replica, embedding API, login port and config")]
+    Doctor(DoctorArgs),
+    #[command(about = "Run the sync jobs defined in the config file")]
+    Sync(SyncArgs),
+    #[command(about = "Insert the clipboard text (optionally edited) into the default memory")]
+    Capture(CaptureArgs),
+    #[command(about = "List the kinic-<name> executables on PATH that run as `kinic-cli <name>`")]
+    Plugins(PluginsArgs),
+    #[command(external_subcommand)]
+    External(Vec<String>),
 }
 
 #[derive(Args, Debug)]
@@ -90,10 +273,58 @@ pub struct CreateArgs {
 
     #[arg(long, required = true, help = "Short description for the new memory")]
     pub description: String,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Embedding dimension of the memory (default: KINIC_EMBEDDING_DIM or 1024)"
+    )]
+    pub dim: Option<usize>,
+
+    #[arg(
+        long,
+        requires = "dim",
+        help = "Keep only the first --dim dimensions of every vector (Matryoshka embeddings)"
+    )]
+    pub truncate: bool,
+
+    #[arg(long, help = "Scale every vector to unit length before storing or searching")]
+    pub normalize: bool,
+
+    #[arg(long, help = "Round every vector component to f16 precision")]
+    pub f16: bool,
+
+    #[arg(
+        long,
+        value_name = "KINIC",
+        help = "Approve this much more than price + fee, in case the price changes before deploying; the rest is revoked afterwards"
+    )]
+    pub approve_buffer: Option<String>,
+
+    #[arg(
+        long,
+        help = "Print the deploy request id and return instead of waiting for the memory to be running"
+    )]
+    pub no_wait: bool,
+
+    #[arg(
+        long,
+        help = "Show price and balances without approving or deploying"
+    )]
+    pub dry_run: bool,
+
+    #[arg(long, short = 'y', help = "Skip the confirmation prompt")]
+    pub yes: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct ListArgs {}
+pub struct ListArgs {
+    #[arg(long, help = "Include empty launcher slots in the output")]
+    pub all_states: bool,
+
+    #[arg(long, help = "Print memories as JSON instead of a table")]
+    pub json: bool,
+}
 
 #[derive(Args, Debug)]
 #[command(group = ArgGroup::new("insert_input").required(true).args(["text", "file_path"]))]
@@ -111,12 +342,133 @@ pub struct InsertArgs {
     #[arg(
         long,
         value_name = "PATH",
-        help = "Read markdown content from a file (conflicts with --text)"
+        help = "Read markdown content from a file, one document per file (repeatable; glob patterns such as 'notes/*.md' are expanded; conflicts with --text)"
     )]
-    pub file_path: Option<PathBuf>,
+    pub file_path: Vec<PathBuf>,
 
-    #[arg(long, required = true, help = "Tag metadata stored alongside the text")]
-    pub tag: String,
+    #[arg(
+        long,
+        required_unless_present = "file_path",
+        help = "Tag metadata stored alongside the text (defaults to each file's name without extension)"
+    )]
+    pub tag: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with every chunk (repeatable)"
+    )]
+    pub meta: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Also store an LLM summary of each document, tagged summary:<TAG>"
+    )]
+    pub summarize: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SOURCE",
+        num_args = 0..=1,
+        default_missing_value = "local",
+        help = "Extract keywords from the document and store them as auto_tags on every chunk"
+    )]
+    pub auto_tag: Option<AutoTagSource>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoTagSource {
+    /// RAKE keyword extraction, offline; best for English text.
+    Local,
+    /// Ask the chat endpoint for topics; works for any language.
+    Llm,
+}
+
+#[derive(Args, Debug)]
+pub struct AddArgs {
+    #[arg(
+        value_name = "PATH_OR_URL",
+        help = "PDF, HTML, .docx or text file, directory of them, or http(s) URL"
+    )]
+    pub input: String,
+
+    #[arg(
+        long,
+        help = "Principal of the target memory canister (defaults to default_memory in the config file)"
+    )]
+    pub memory_id: Option<String>,
+
+    #[arg(
+        long,
+        help = "Tag metadata stored with every chunk (default: the file name, the path within the directory, or the URL)"
+    )]
+    pub tag: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
+}
+
+/// Masking of personal data before ingestion, shared by the insert commands.
+#[derive(Args, Debug)]
+pub struct RedactArgs {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KIND",
+        help = "Mask personal data before it is embedded and stored"
+    )]
+    pub redact: Option<RedactMode>,
+
+    #[arg(
+        long,
+        requires = "redact",
+        help = "Also ask the chat endpoint for person names and postal addresses to mask"
+    )]
+    pub redact_ner: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "redact",
+        help = "Where to write the redaction report (default: ~/.config/kinic/redactions/)"
+    )]
+    pub redact_report: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedactMode {
+    /// Email addresses, phone numbers and card numbers.
+    Pii,
 }
 
 #[derive(Args, Debug)]
@@ -140,10 +492,17 @@ pub struct InsertRawArgs {
 
     #[arg(long, required = true, help = "Tag metadata stored alongside the text")]
     pub tag: String,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with every chunk (repeatable)"
+    )]
+    pub meta: Vec<String>,
 }
 
 #[derive(Args, Debug)]
-pub struct InsertPdfArgs {
+pub struct InsertImageArgs {
     #[arg(
         long,
         required = true,
@@ -152,73 +511,77 @@ pub struct InsertPdfArgs {
     pub memory_id: String,
 
     #[arg(
-        long,
+        long = "file",
         value_name = "PATH",
         required = true,
-        help = "PDF file to convert to markdown and insert"
+        help = "PNG, JPEG, GIF or WebP image to describe and insert"
     )]
     pub file_path: PathBuf,
 
-    #[arg(long, required = true, help = "Tag metadata stored alongside the text")]
+    #[arg(long, required = true, help = "Tag metadata stored alongside the description")]
     pub tag: String,
-}
 
-#[derive(Args, Debug)]
-pub struct ConvertPdfArgs {
     #[arg(
         long,
-        value_name = "PATH",
-        required = true,
-        help = "PDF file to convert to markdown"
+        value_name = "TEXT",
+        help = "Instruction for the vision model, e.g. \"transcribe all text in the diagram\""
     )]
-    pub file_path: PathBuf,
-}
+    pub prompt: Option<String>,
 
-#[derive(Args, Debug)]
-pub struct SearchArgs {
     #[arg(
         long,
-        required = true,
-        help = "Principal of the memory canister to search"
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with the chunk (repeatable)"
     )]
-    pub memory_id: String,
-
-    #[arg(long, required = true, help = "Query text to embed and search")]
-    pub query: String,
+    pub meta: Vec<String>,
 }
 
 #[derive(Args, Debug)]
-pub struct SearchRawArgs {
+pub struct InsertObsidianArgs {
     #[arg(
         long,
         required = true,
-        help = "Principal of the memory canister to search"
+        help = "Principal of the target memory canister"
     )]
     pub memory_id: String,
 
     #[arg(
         long,
+        value_name = "DIR",
         required = true,
-        help = "Embedding as a JSON array of floats, e.g. [0.1, 0.2]"
+        help = "Obsidian vault to ingest"
     )]
-    pub embedding: String,
-}
+    pub vault: PathBuf,
 
-#[derive(Args, Debug)]
-pub struct TaggedEmbeddingsArgs {
     #[arg(
         long,
-        required = true,
-        help = "Principal of the memory canister to query"
+        value_name = "DIR",
+        help = "Vault folder to skip, relative to the vault (repeatable; the templates folder is always skipped)"
     )]
-    pub memory_id: String,
+    pub exclude: Vec<PathBuf>,
 
-    #[arg(long, required = true, help = "Tag to fetch embeddings for")]
-    pub tag: String,
+    #[arg(long, help = "Re-ingest every note, even if unchanged since the last run")]
+    pub full: bool,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
 }
 
 #[derive(Args, Debug)]
-pub struct ConfigArgs {
+pub struct InsertBookmarksArgs {
     #[arg(
         long,
         required = true,
@@ -226,61 +589,1121 @@ pub struct ConfigArgs {
     )]
     pub memory_id: String,
 
+    #[arg(
+        long = "file",
+        value_name = "PATH",
+        required = true,
+        help = "Bookmarks HTML file exported from a browser"
+    )]
+    pub file_path: PathBuf,
+
     #[arg(
         long,
-        value_names = ["USER_ID", "ROLE"],
-        num_args = 2,
-        help = "Add a user with role to the Kinic CLI config (placeholder)"
+        default_value_t = 4,
+        value_name = "N",
+        help = "Maximum number of pages fetched at once"
     )]
-    pub add_user: Option<Vec<String>>,
-}
+    pub concurrency: usize,
 
-#[derive(Args, Debug)]
-pub struct UpdateArgs {
     #[arg(
         long,
-        required = true,
-        help = "Principal of the target memory canister to update"
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with every chunk (repeatable)"
     )]
-    pub memory_id: String,
-}
+    pub meta: Vec<String>,
 
-#[derive(Args, Debug)]
-pub struct ResetArgs {
     #[arg(
         long,
-        required = true,
-        help = "Principal of the target memory canister to reset"
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
     )]
-    pub memory_id: String,
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
 
-    #[arg(long, required = true, help = "Embedding dimension to set after reset")]
-    pub dim: usize,
+    #[command(flatten)]
+    pub redact: RedactArgs,
 }
 
 #[derive(Args, Debug)]
-pub struct BalanceArgs {}
-
-#[derive(Args, Debug)]
-pub struct AskAiArgs {
+#[command(group = ArgGroup::new("mail_input").required(true).multiple(true).args(["mbox", "eml"]))]
+pub struct InsertMailArgs {
     #[arg(
         long,
         required = true,
-        help = "Principal of the memory canister to search"
+        help = "Principal of the target memory canister"
     )]
     pub memory_id: String,
 
-    #[arg(long, required = true, help = "Query text to embed and search")]
-    pub query: String,
+    #[arg(long, value_name = "PATH", help = "mbox archive to ingest")]
+    pub mbox: Option<PathBuf>,
 
     #[arg(
         long,
-        default_value_t = 5,
-        value_name = "N",
-        help = "Number of top search results to include in the LLM prompt"
+        value_name = "PATH",
+        help = "Single .eml message to ingest (repeatable)"
     )]
-    pub top_k: usize,
+    pub eml: Vec<PathBuf>,
+
+    #[arg(long, default_value = "mail", help = "Tag metadata stored with every thread")]
+    pub tag: String,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with every chunk (repeatable)"
+    )]
+    pub meta: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
 }
 
 #[derive(Args, Debug)]
-pub struct LoginArgs {}
+pub struct InsertPdfArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the target memory canister"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        required = true,
+        help = "PDF file to convert to markdown and insert"
+    )]
+    pub file_path: PathBuf,
+
+    #[arg(long, required = true, help = "Tag metadata stored alongside the text")]
+    pub tag: String,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with every chunk (repeatable)"
+    )]
+    pub meta: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertPdfArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        required = true,
+        help = "PDF file to convert to markdown"
+    )]
+    pub file_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct EstimateArgs {
+    #[arg(
+        long = "file",
+        value_name = "PATH",
+        required = true,
+        help = "File to estimate: PDF, HTML or text/markdown"
+    )]
+    pub file_path: PathBuf,
+
+    #[arg(
+        long,
+        help = "Count chunks with the chunking API instead of locally (sends the text, inserts nothing)"
+    )]
+    pub exact: bool,
+
+    #[arg(
+        long,
+        value_name = "DIM",
+        help = "Vector dimension of the target memory (defaults to the embedding API's)"
+    )]
+    pub dim: Option<usize>,
+
+    #[arg(long, help = "Print the estimate as JSON")]
+    pub json: bool,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for the chunking API with --exact"
+    )]
+    pub language: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to search"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Query text to embed and search")]
+    pub query: String,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only return the N best matches (default: all matches)"
+    )]
+    pub top_k: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_name = "N",
+        help = "Also show up to N chunks before and after each match from the same document"
+    )]
+    pub expand: u32,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        help = "Only show chunks inserted under TAG or auto-tagged with it"
+    )]
+    pub tag: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SearchFormat::List,
+        help = "Output format for the results"
+    )]
+    pub format: SearchFormat,
+
+    #[arg(
+        long,
+        help = "Add a metadata column to table, markdown and csv output"
+    )]
+    pub show_metadata: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Show one entry per document with its best snippets; --top-k then counts documents"
+    )]
+    pub group_by: Option<SearchGroupBy>,
+
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "group_by",
+        help = "Snippets shown per document with --group-by"
+    )]
+    pub snippets: u32,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchGroupBy {
+    /// The `source` metadata, or the tag of chunks inserted without one.
+    Source,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchFormat {
+    /// One `- [score] text  (metadata)` line per match.
+    List,
+    /// Aligned table fitted to the terminal width.
+    Table,
+    /// GitHub-flavored markdown table.
+    Markdown,
+    /// Comma-separated values with the full chunk text.
+    Csv,
+}
+
+#[derive(Args, Debug)]
+pub struct PickArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to search"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        default_value_t = 20,
+        value_name = "N",
+        help = "Number of results listed for each query"
+    )]
+    pub top_k: usize,
+
+    #[arg(long, help = "Copy the picked chunk's text to the clipboard instead of printing it")]
+    pub copy: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to search"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_name = "FILE",
+        help = "Text file with one query per line (blank lines and # comments are skipped)"
+    )]
+    pub queries: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "How many times to run every query"
+    )]
+    pub runs: u32,
+
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_name = "N",
+        help = "Number of matches each search fetches"
+    )]
+    pub top_k: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct GetArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to read from"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Chunk id returned by insert or search")]
+    pub id: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchRawArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to search"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        required = true,
+        help = "Embedding as a JSON array of floats, e.g. [0.1, 0.2]"
+    )]
+    pub embedding: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ClusterArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to analyze"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Tag whose embeddings are clustered")]
+    pub tag: String,
+
+    #[arg(
+        long,
+        short = 'k',
+        value_name = "K",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of clusters (default: sqrt(chunks / 2), at most 12)"
+    )]
+    pub clusters: Option<u32>,
+
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_name = "N",
+        help = "Representative sentences to show per cluster"
+    )]
+    pub samples: usize,
+
+    #[arg(long, help = "Print cluster summaries as JSON")]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VisualizeArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to visualize"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        required = true,
+        help = "Tag whose embeddings are plotted (repeat for several tags)"
+    )]
+    pub tag: Vec<String>,
+
+    #[arg(
+        long,
+        required = true,
+        value_name = "FILE",
+        help = "Where to write the points; CSV for a .csv extension, JSON otherwise"
+    )]
+    pub out: PathBuf,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ProjectionMethod::Tsne,
+        help = "Dimensionality reduction to apply"
+    )]
+    pub method: ProjectionMethod,
+
+    #[arg(
+        long,
+        default_value_t = 30.0,
+        help = "t-SNE perplexity (roughly the neighbors each point considers)"
+    )]
+    pub perplexity: f64,
+
+    #[arg(
+        long,
+        help = "Look up the sentence of every point (one search per point)"
+    )]
+    pub labels: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also write a standalone HTML scatter plot"
+    )]
+    pub html: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProjectionMethod {
+    /// t-SNE; preserves local neighborhoods, best for spotting clusters.
+    Tsne,
+    /// Principal component analysis; fast, preserves global layout.
+    Pca,
+}
+
+#[derive(Args, Debug)]
+pub struct TaggedEmbeddingsArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to query"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Tag to fetch embeddings for")]
+    pub tag: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the target memory canister"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        value_names = ["USER_ID", "ROLE"],
+        num_args = 2,
+        help = "Add a user with role to the Kinic CLI config (placeholder)"
+    )]
+    pub add_user: Option<Vec<String>>,
+}
+
+#[derive(Args, Debug)]
+pub struct ShareArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to share"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Principal to grant access to")]
+    pub principal: String,
+
+    #[arg(
+        long,
+        default_value = "reader",
+        value_name = "ROLE",
+        help = "Role to grant: reader, writer or admin"
+    )]
+    pub role: String,
+}
+
+#[derive(Args, Debug)]
+#[command(group = ArgGroup::new("update_target").required(true).args(["memory_id", "all"]))]
+pub struct UpdateArgs {
+    #[arg(long, help = "Principal of the target memory canister to update")]
+    pub memory_id: Option<String>,
+
+    #[arg(long, help = "Update every memory owned by the current identity")]
+    pub all: bool,
+
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_name = "N",
+        requires = "all",
+        help = "Maximum number of concurrent updates with --all"
+    )]
+    pub concurrency: usize,
+
+    #[arg(long, short = 'y', help = "Skip the confirmation prompt")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(group = ArgGroup::new("metadata_fields").required(true).multiple(true).args(["name", "description"]))]
+pub struct SetMetadataArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to update"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, help = "New name for the memory")]
+    pub name: Option<String>,
+
+    #[arg(long, help = "New description for the memory")]
+    pub description: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ResetArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the target memory canister to reset"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        help = "Embedding dimension to set after reset (default: KINIC_EMBEDDING_DIM or 1024)"
+    )]
+    pub dim: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write every stored payload to this file (JSONL) before erasing; payloads only, not a restorable backup"
+    )]
+    pub export: Option<PathBuf>,
+
+    #[arg(long, help = "Erase a memory that holds chunks without --export")]
+    pub force: bool,
+
+    #[arg(long, short = 'y', help = "Skip the confirmation prompt")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BalanceArgs {}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    #[arg(
+        long,
+        default_value_t = 20,
+        value_name = "N",
+        help = "Maximum number of transactions to show (newest first)"
+    )]
+    pub limit: u64,
+
+    #[arg(long, help = "Print transactions as JSON instead of a table")]
+    pub json: bool,
+
+    #[arg(
+        long,
+        value_name = "PRINCIPAL",
+        help = "Index canister to query (default: discovered from the ledger)"
+    )]
+    pub index_id: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct TransferArgs {
+    #[arg(long, required = true, help = "Principal of the recipient")]
+    pub to: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_name = "KINIC",
+        help = "Amount of KINIC to send, e.g. 0.25 (up to 8 decimals)"
+    )]
+    pub amount: String,
+
+    #[arg(long, help = "Optional memo attached to the transfer (max 32 bytes)")]
+    pub memo: Option<String>,
+
+    #[arg(long, short = 'y', help = "Skip the confirmation prompt")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TopUpArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to top up"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_name = "KINIC",
+        help = "Amount of KINIC to spend on cycles, e.g. 1.5"
+    )]
+    pub amount: String,
+
+    #[arg(long, short = 'y', help = "Skip the confirmation prompt")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to inspect"
+    )]
+    pub memory_id: String,
+}
+
+#[derive(Args, Debug)]
+pub struct MigratePayloadsArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister whose chunks are read"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        value_name = "MEMORY_ID",
+        help = "Copy every chunk, re-embedded and upgraded to the current payload version, into this memory"
+    )]
+    pub into: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Memory canister id, or export file (JSONL, one stored payload per line)"
+    )]
+    pub a: String,
+
+    #[arg(
+        long,
+        required = true,
+        help = "Memory canister id, or export file to compare against --a"
+    )]
+    pub b: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister answers are drawn from"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        default_value = "127.0.0.1:8080",
+        value_name = "ADDR",
+        help = "Address to listen on"
+    )]
+    pub addr: SocketAddr,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_name = "N",
+        help = "Number of search results considered for each answer"
+    )]
+    pub top_k: usize,
+
+    #[arg(
+        long,
+        default_value_t = crate::rag::DEFAULT_CONTEXT_TOKENS,
+        value_name = "TOKENS",
+        help = "Token budget for each prompt; the best chunks are packed until it is full"
+    )]
+    pub context_tokens: usize,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Use the LLM settings of [profiles.NAME] in the config file"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Require `Authorization: Bearer KEY` on every request"
+    )]
+    pub api_key: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[arg(long, required = true, help = "Principal of the memory canister")]
+    pub memory_id: String,
+
+    #[arg(long, default_value_t = 50, help = "Number of random chunks to check")]
+    pub sample: usize,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "A chunk passes when it is among this many results"
+    )]
+    pub top_k: usize,
+
+    #[arg(long, help = "Seed for choosing the sample, to repeat a run")]
+    pub seed: Option<u64>,
+
+    #[arg(
+        long,
+        default_value_t = 0.9,
+        value_name = "FRACTION",
+        help = "Fail when fewer than this fraction of chunks retrieve themselves"
+    )]
+    pub min_recall: f32,
+}
+
+#[derive(Args, Debug)]
+pub struct MaterializeArgs {
+    #[arg(long, required = true, help = "Memory canister to copy chunks from")]
+    pub from: String,
+
+    #[arg(long, required = true, help = "Search query selecting the chunks to copy")]
+    pub query: String,
+
+    #[arg(long, required = true, help = "Memory canister to copy chunks into")]
+    pub to: String,
+
+    #[arg(long, default_value_t = 500, help = "Copy at most this many of the best matches")]
+    pub top_k: usize,
+
+    #[arg(long, help = "Skip matches scoring below this similarity")]
+    pub min_score: Option<f32>,
+
+    #[arg(
+        long,
+        help = "List the matching chunks (id, score, payload) without copying them"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PruneArgs {
+    #[arg(long, required = true, help = "Principal of the memory canister")]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        value_name = "AGE",
+        help = "Chunks created longer ago than this (e.g. 90d, 12h) are stale; defaults to the memory's retention policy"
+    )]
+    pub older_than: Option<String>,
+
+    #[arg(long, help = "Only chunks with this tag can be stale")]
+    pub tag: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct UsageArgs {
+    #[arg(
+        long,
+        value_name = "AGE",
+        help = "Only count runs from the last AGE (e.g. 24h, 30d)"
+    )]
+    pub since: Option<String>,
+
+    #[arg(long, help = "Print the totals as JSON")]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {}
+
+#[derive(Args, Debug)]
+pub struct WaitArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to wait for"
+    )]
+    pub memory_id: String,
+
+    #[arg(
+        long,
+        default_value_t = 120,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Give up after SECS seconds"
+    )]
+    pub timeout: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct AskAiArgs {
+    #[arg(
+        long,
+        required = true,
+        help = "Principal of the memory canister to search"
+    )]
+    pub memory_id: String,
+
+    #[arg(long, required = true, help = "Query text to embed and search")]
+    pub query: String,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_name = "N",
+        help = "Number of search results considered for the LLM prompt"
+    )]
+    pub top_k: usize,
+
+    #[arg(
+        long,
+        help = "Before calling the LLM, list the chunks used with their scores and estimated token counts"
+    )]
+    pub show_prompt: bool,
+
+    #[arg(long, help = "Stop after retrieval and print the prompt without calling the LLM")]
+    pub no_llm: bool,
+
+    #[arg(
+        long,
+        default_value_t = crate::rag::DEFAULT_CONTEXT_TOKENS,
+        value_name = "TOKENS",
+        help = "Token budget for the whole prompt; the best chunks are packed until it is full"
+    )]
+    pub context_tokens: usize,
+
+    #[arg(
+        long,
+        help = "Have the LLM split the question into 2-4 sub-queries and search for each of them as well"
+    )]
+    pub multi_query: bool,
+
+    #[arg(
+        long,
+        help = "Answer \"I don't know.\" without calling the LLM when no chunk scores at least --min-score"
+    )]
+    pub strict: bool,
+
+    #[arg(
+        long,
+        default_value_t = crate::commands::ask_ai::DEFAULT_MIN_SCORE,
+        value_name = "SCORE",
+        requires = "strict",
+        help = "Best retrieval score --strict requires"
+    )]
+    pub min_score: f32,
+
+    #[arg(
+        long,
+        help = "After answering, have the LLM check each sentence against the retrieved chunks and flag unsupported ones"
+    )]
+    pub verify: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "no_llm",
+        help = "Also write the question, the answer and the chunks used to a markdown file"
+    )]
+    pub report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Use the LLM settings of [profiles.NAME] in the config file"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "Chat model to request (default: the endpoint's choice)")]
+    pub model: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = parse_temperature,
+        help = "Sampling temperature between 0 and 2"
+    )]
+    pub temperature: Option<f32>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        value_name = "N",
+        help = "Maximum number of tokens to generate"
+    )]
+    pub max_tokens: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "System prompt sent along with the question"
+    )]
+    pub system_prompt: Option<String>,
+}
+
+fn parse_temperature(value: &str) -> Result<f32, String> {
+    let temperature: f32 = value
+        .parse()
+        .map_err(|_| format!("{value} is not a number"))?;
+    if (0.0..=2.0).contains(&temperature) {
+        Ok(temperature)
+    } else {
+        Err("temperature must be between 0 and 2".to_string())
+    }
+}
+
+/// A language tag such as `ja`, `en` or `zh-Hant`, lowercased.
+fn parse_language(value: &str) -> Result<String, String> {
+    let language = value.trim().to_ascii_lowercase();
+    let mut subtags = language.split('-');
+    let primary_ok = subtags.next().is_some_and(|primary| {
+        (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_lowercase())
+    });
+    let rest_ok = subtags.all(|subtag| {
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if primary_ok && rest_ok {
+        Ok(language)
+    } else {
+        Err(format!("{value} is not a language code such as ja, en or zh"))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LoginArgs {
+    #[arg(
+        long = "as",
+        value_name = "NAME",
+        help = "Save the session under NAME instead of the default identity.json"
+    )]
+    pub session: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = IdentityStoreKind::File,
+        help = "Where to save the session: identity.json or the system keyring"
+    )]
+    pub store: IdentityStoreKind,
+
+    #[arg(
+        long,
+        help = "Encrypt the saved session key with a passphrase (or $KINIC_IDENTITY_PASSPHRASE)"
+    )]
+    pub encrypt: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentityStoreKind {
+    File,
+    Keyring,
+}
+
+#[derive(Args, Debug)]
+pub struct EncryptIdentityArgs {
+    #[arg(long, help = "Remove the passphrase and store the session key in plaintext")]
+    pub decrypt: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct IdentityArgs {
+    #[command(subcommand)]
+    pub command: IdentityCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IdentityCommand {
+    #[command(about = "Store a PEM key in the keyring the way dfx does, or in the file store")]
+    Import(IdentityImportArgs),
+    #[command(about = "Print the principal of the selected identity, without network calls")]
+    ShowPrincipal,
+}
+
+#[derive(Args, Debug)]
+pub struct IdentityImportArgs {
+    #[arg(long, required = true, help = "Identity name to pass to --identity")]
+    pub name: String,
+
+    #[arg(
+        long,
+        required = true,
+        value_name = "PATH",
+        help = "Ed25519 or secp256k1 PEM key, e.g. from `dfx identity export`"
+    )]
+    pub pem: PathBuf,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = CredentialStore::Keyring,
+        help = "Where to store the key; the file store encrypts it with a passphrase"
+    )]
+    pub store: CredentialStore,
+
+    #[arg(long, help = "Replace an identity of the same name")]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PluginsArgs {
+    #[command(subcommand)]
+    pub command: PluginsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginsCommand {
+    #[command(about = "Print each plugin name and the executable it runs")]
+    List,
+}
+
+#[derive(Args, Debug)]
+pub struct SessionsArgs {
+    #[command(subcommand)]
+    pub command: SessionsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionsCommand {
+    #[command(about = "List saved sessions with their principals and expirations")]
+    List,
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    #[command(subcommand)]
+    pub command: SyncCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommand {
+    #[command(about = "List configured jobs with their schedule and last run")]
+    List,
+    #[command(about = "Ingest new and changed content of every job (or one job)")]
+    Run(SyncRunArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SyncRunArgs {
+    #[arg(long, value_name = "NAME", help = "Only run the job called NAME")]
+    pub job: Option<String>,
+
+    #[arg(
+        long,
+        help = "Keep running and execute each job on its `every` schedule"
+    )]
+    pub daemon: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CaptureArgs {
+    #[arg(
+        long,
+        help = "Principal of the target memory canister (defaults to default_memory in the config file)"
+    )]
+    pub memory_id: Option<String>,
+
+    #[arg(long, default_value = "inbox", help = "Tag metadata stored with the note")]
+    pub tag: String,
+
+    #[arg(
+        long,
+        help = "Open $EDITOR on the clipboard text to annotate it before inserting"
+    )]
+    pub edit: bool,
+
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Extra metadata stored with the note (repeatable)"
+    )]
+    pub meta: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "LANG",
+        value_parser = parse_language,
+        help = "Language of the text, e.g. ja, en or zh, for sentence segmentation (default: detected by the chunking API)"
+    )]
+    pub language: Option<String>,
+    #[arg(
+        long,
+        help = "Insert even if the content looks like it contains credentials (API keys, private keys, tokens)"
+    )]
+    pub allow_secrets: bool,
+
+    #[command(flatten)]
+    pub redact: RedactArgs,
+}
@@ -0,0 +1,189 @@
+use std::marker::PhantomData;
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+#[cfg(feature = "native")]
+use ic_agent::agent::CallResponse;
+use ic_agent::{Agent, export::Principal};
+
+#[cfg(feature = "native")]
+use super::cassette;
+#[cfg(feature = "native")]
+use crate::{metrics, network::with_deadline, usage};
+
+/// A canister method declared in one of the `candid/*.did` files, taking
+/// the argument tuple `A` and replying with the tuple `R`.
+pub struct Method<A, R> {
+    /// Interface the method belongs to (`memory`, `ledger`, ...).
+    pub canister: &'static str,
+    pub name: &'static str,
+    pub query: bool,
+    signature: PhantomData<fn(A) -> R>,
+}
+
+impl<A, R> Method<A, R> {
+    pub const fn new(canister: &'static str, name: &'static str, query: bool) -> Self {
+        Self {
+            canister,
+            name,
+            query,
+            signature: PhantomData,
+        }
+    }
+}
+
+impl<A, R> Clone for Method<A, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A, R> Copy for Method<A, R> {}
+
+impl<A, R> Method<A, R>
+where
+    A: ArgumentEncoder,
+    R: for<'a> ArgumentDecoder<'a>,
+{
+    fn encode(&self, args: A) -> Result<Vec<u8>> {
+        candid::encode_args(args)
+            .with_context(|| format!("Failed to encode {} arguments", self.name))
+    }
+
+    /// Decodes a reply of this method, e.g. one returned by [`submit`].
+    pub fn decode_reply(&self, reply: &[u8]) -> Result<R> {
+        candid::decode_args(reply)
+            .with_context(|| format!("Failed to decode {} response", self.name))
+    }
+}
+
+// One module per `.did` file (`launcher`, `launcher_ext`, `ledger`,
+// `ledger_index`, `management`, `memory`, `memory_ext`) with its types and
+// methods, generated by build.rs.
+include!(concat!(env!("OUT_DIR"), "/canister_methods.rs"));
+
+/// Encodes `args`, calls `method` as a query or update according to its
+/// interface, and decodes the reply into `R`. The call is abandoned once the
+/// `--timeout` deadline passes. Under `--record`/`--replay` the reply is
+/// captured or served from the cassette. Count, latency and outcome are
+/// recorded for `--metrics-addr`, and the call is counted for `usage`.
+pub async fn call<A, R>(
+    agent: &Agent,
+    canister_id: &Principal,
+    method: Method<A, R>,
+    args: A,
+) -> Result<R>
+where
+    A: ArgumentEncoder,
+    R: for<'a> ArgumentDecoder<'a>,
+{
+    call_with_effective_id(agent, canister_id, *canister_id, method, args).await
+}
+
+/// Like [`call`], for a method of the management canister concerning
+/// `canister_id`, which routes the call to that canister's subnet.
+#[cfg(feature = "native")]
+pub async fn call_management<A, R>(
+    agent: &Agent,
+    canister_id: Principal,
+    method: Method<A, R>,
+    args: A,
+) -> Result<R>
+where
+    A: ArgumentEncoder,
+    R: for<'a> ArgumentDecoder<'a>,
+{
+    call_with_effective_id(
+        agent,
+        &Principal::management_canister(),
+        canister_id,
+        method,
+        args,
+    )
+    .await
+}
+
+async fn call_with_effective_id<A, R>(
+    agent: &Agent,
+    canister_id: &Principal,
+    effective_canister_id: Principal,
+    method: Method<A, R>,
+    args: A,
+) -> Result<R>
+where
+    A: ArgumentEncoder,
+    R: for<'a> ArgumentDecoder<'a>,
+{
+    let payload = method.encode(args)?;
+
+    let request = async {
+        if method.query {
+            agent
+                .query(canister_id, method.name)
+                .with_effective_canister_id(effective_canister_id)
+                .with_arg(payload.clone())
+                .call()
+                .await
+        } else {
+            agent
+                .update(canister_id, method.name)
+                .with_effective_canister_id(effective_canister_id)
+                .with_arg(payload.clone())
+                .call_and_wait()
+                .await
+        }
+        .with_context(|| format!("Failed to call {}", method.name))
+    };
+    #[cfg(feature = "native")]
+    let response = {
+        usage::record_canister_call(method.query, payload.len());
+        let label = format!("{}.{}", method.canister, method.name);
+        let started = Instant::now();
+        let response = cassette::intercept(
+            agent,
+            &effective_canister_id,
+            &label,
+            &payload,
+            with_deadline(&label, request),
+        )
+        .await;
+        metrics::record_canister_call(&label, started.elapsed(), &response);
+        response?
+    };
+    // The core has no cassette, metrics or runtime timer to apply.
+    #[cfg(not(feature = "native"))]
+    let response = request.await?;
+
+    method.decode_reply(&response)
+}
+
+/// Submits an update call without waiting for it to finish. The replica may
+/// still answer right away with the encoded reply; otherwise the result is
+/// the request id to look the call up by later. Not recorded or replayed
+/// by `--record`/`--replay`.
+#[cfg(feature = "native")]
+pub async fn submit<A, R>(
+    agent: &Agent,
+    canister_id: &Principal,
+    method: Method<A, R>,
+    args: A,
+) -> Result<CallResponse<Vec<u8>>>
+where
+    A: ArgumentEncoder,
+    R: for<'a> ArgumentDecoder<'a>,
+{
+    let payload = method.encode(args)?;
+    let label = format!("{}.{}", method.canister, method.name);
+    usage::record_canister_call(false, payload.len());
+    let request = async {
+        agent
+            .update(canister_id, method.name)
+            .with_arg(payload)
+            .call()
+            .await
+            .with_context(|| format!("Failed to call {}", method.name))
+    };
+    with_deadline(&label, request).await
+}
@@ -0,0 +1,179 @@
+//! `--record` / `--replay`: canister replies captured to a JSON cassette and
+//! served back later, so demos and tests run without a replica, keyring
+//! identity or tokens.
+//!
+//! Every canister call goes through [`intercept`]. While recording, the live
+//! reply is appended to the cassette; while replaying, no request is sent and
+//! the reply recorded for the same canister, method and arguments is
+//! returned. Arguments that change between runs (insert timestamps, for
+//! example) fall back to the next unused reply of the same method.
+
+use std::{
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result, anyhow};
+use ic_agent::{Agent, Identity, Signature, agent::EnvelopeContent, export::Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::error::KinicError;
+
+static CASSETTE: OnceLock<Cassette> = OnceLock::new();
+
+struct Cassette {
+    path: PathBuf,
+    replay: bool,
+    state: Mutex<CassetteFile>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CassetteFile {
+    /// Principal the calls were made as; replay acts as it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    principal: Option<String>,
+    calls: Vec<RecordedCall>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedCall {
+    canister: String,
+    method: String,
+    args_hex: String,
+    reply_hex: String,
+    #[serde(skip)]
+    used: bool,
+}
+
+/// Records every canister reply of this invocation to `path`, replacing any
+/// previous cassette there.
+pub fn start_recording(path: &Path) -> Result<()> {
+    let cassette = Cassette {
+        path: path.to_path_buf(),
+        replay: false,
+        state: Mutex::new(CassetteFile::default()),
+    };
+    cassette.save(&CassetteFile::default())?;
+    install(cassette)
+}
+
+/// Serves canister replies from the cassette at `path`. Returns an identity
+/// acting as the recorded principal; it cannot sign, as nothing is sent.
+pub fn start_replay(path: &Path) -> Result<ReplayIdentity> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+    let file: CassetteFile = serde_json::from_str(&raw)
+        .with_context(|| format!("Failed to parse cassette {}", path.display()))?;
+    let principal = match &file.principal {
+        Some(text) => Principal::from_text(text).context("Invalid principal in cassette")?,
+        None => Principal::anonymous(),
+    };
+    install(Cassette {
+        path: path.to_path_buf(),
+        replay: true,
+        state: Mutex::new(file),
+    })?;
+    Ok(ReplayIdentity { principal })
+}
+
+pub fn is_replaying() -> bool {
+    CASSETTE.get().is_some_and(|cassette| cassette.replay)
+}
+
+fn install(cassette: Cassette) -> Result<()> {
+    CASSETTE
+        .set(cassette)
+        .map_err(|_| anyhow!("record/replay is already active"))
+}
+
+/// Runs `live` (the real call) unless a cassette is replaying, recording its
+/// reply when one is recording.
+pub(crate) async fn intercept<F>(
+    agent: &Agent,
+    canister_id: &Principal,
+    method: &str,
+    args: &[u8],
+    live: F,
+) -> Result<Vec<u8>>
+where
+    F: Future<Output = Result<Vec<u8>>>,
+{
+    let Some(cassette) = CASSETTE.get() else {
+        return live.await;
+    };
+    let canister = canister_id.to_text();
+    let args_hex = hex::encode(args);
+    if cassette.replay {
+        return cassette.replay(&canister, method, &args_hex);
+    }
+
+    let reply = live.await?;
+    let mut state = cassette.state.lock().expect("cassette lock poisoned");
+    if state.principal.is_none() {
+        state.principal = agent.get_principal().ok().map(|p| p.to_text());
+    }
+    state.calls.push(RecordedCall {
+        canister,
+        method: method.to_string(),
+        args_hex,
+        reply_hex: hex::encode(&reply),
+        used: false,
+    });
+    cassette.save(&state)?;
+    Ok(reply)
+}
+
+impl Cassette {
+    fn replay(&self, canister: &str, method: &str, args_hex: &str) -> Result<Vec<u8>> {
+        let mut state = self.state.lock().expect("cassette lock poisoned");
+        let same_method = |call: &RecordedCall| call.canister == canister && call.method == method;
+        let index = state
+            .calls
+            .iter()
+            .position(|call| !call.used && same_method(call) && call.args_hex == args_hex)
+            .or_else(|| {
+                state
+                    .calls
+                    .iter()
+                    .position(|call| !call.used && same_method(call))
+            })
+            .or_else(|| state.calls.iter().rposition(same_method))
+            .ok_or_else(|| {
+                KinicError::Validation(format!(
+                    "{} has no recorded reply for {method} on {canister}; record it again with --record",
+                    self.path.display()
+                ))
+            })?;
+        let call = &mut state.calls[index];
+        call.used = true;
+        hex::decode(&call.reply_hex).context("Failed to decode recorded reply")
+    }
+
+    fn save(&self, file: &CassetteFile) -> Result<()> {
+        let payload = serde_json::to_string_pretty(file).context("Failed to encode cassette")?;
+        fs::write(&self.path, payload)
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))
+    }
+}
+
+/// Stands in for the recorded identity during replay. Only its principal is
+/// ever used.
+pub struct ReplayIdentity {
+    principal: Principal,
+}
+
+impl Identity for ReplayIdentity {
+    fn sender(&self) -> Result<Principal, String> {
+        Ok(self.principal)
+    }
+
+    fn public_key(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn sign(&self, _content: &EnvelopeContent) -> Result<Signature, String> {
+        Err("replay mode cannot sign requests".to_string())
+    }
+}
@@ -1,27 +1,45 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use anyhow::{Context, Result};
-use candid::{CandidType, Decode, Deserialize, Nat};
-use ic_agent::{Agent, export::Principal};
-use icrc_ledger_types::{
-    icrc1::{account::Account, transfer::TransferError},
-    icrc2::{
-        approve::{ApproveArgs, ApproveError},
-        transfer_from::TransferFromError,
-    },
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
 };
-use serde_json::json;
-use thiserror::Error;
 
-use crate::clients::{LAUNCHER_CANISTER, LEDGER_CANISTER};
+use anyhow::{Context, Result, anyhow};
+use candid::{Deserialize, Nat};
+use ic_agent::{
+    Agent,
+    agent::{CallResponse, RequestId},
+    export::Principal,
+};
+use serde::Serialize;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+pub use crate::clients::call::launcher::State;
+use crate::{
+    clients::{
+        LAUNCHER_CANISTER, LEDGER_CANISTER,
+        call::{
+            call,
+            launcher::{self, DeployInstanceError, TransferResponseError},
+            launcher_ext,
+            ledger::{self, Account, AllowanceArgs, ApproveArgs},
+            submit,
+        },
+        management::{ManagementClient, declares_method},
+    },
+    error::{payment_failed, rejected},
+    ledger::TRANSFER_FEE_E8S,
+    vectors::VectorProcessing,
+};
 
-const DEFAULT_VECTOR_DIM: u64 = 1024;
 const APPROVAL_TTL_NS: u64 = 10 * 60 * 1_000_000_000;
 
 pub struct LauncherClient {
     agent: Agent,
     launcher_id: Principal,
     ledger_id: Principal,
+    /// The launcher's `candid:service` metadata, read once.
+    interface: OnceCell<Option<String>>,
 }
 
 impl LauncherClient {
@@ -30,6 +48,7 @@ impl LauncherClient {
             agent,
             launcher_id: Principal::from_text(LAUNCHER_CANISTER).unwrap(),
             ledger_id: Principal::from_text(LEDGER_CANISTER).unwrap(),
+            interface: OnceCell::new(),
         }
     }
 
@@ -38,149 +57,301 @@ impl LauncherClient {
     }
 
     pub async fn fetch_deployment_price(&self) -> Result<Nat> {
-        let response = self
-            .agent
-            .query(&self.launcher_id, "get_price")
-            .call()
-            .await
-            .context("Failed to query deployment price")?;
-
-        let price = Decode!(&response, Nat).context("Failed to decode deployment price")?;
+        let (price,) = call(&self.agent, &self.launcher_id, launcher::GET_PRICE, ()).await?;
         Ok(price)
     }
 
+    /// Lets the launcher pull up to `amount` e8s; see
+    /// [`crate::ledger::allowance_for`] for what a payment needs.
     pub async fn approve_launcher(&self, amount: &Nat) -> Result<()> {
+        self.approve(amount.clone(), None).await
+    }
+
+    /// What the launcher may still pull from the caller's account.
+    pub async fn launcher_allowance(&self) -> Result<Nat> {
+        let args = AllowanceArgs {
+            account: Account {
+                owner: self
+                    .agent
+                    .get_principal()
+                    .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?,
+                subaccount: None,
+            },
+            spender: self.launcher_account(),
+        };
+        let (allowance,) = call(
+            &self.agent,
+            &self.ledger_id,
+            ledger::ICRC2_ALLOWANCE,
+            (args,),
+        )
+        .await?;
+        Ok(allowance.allowance)
+    }
+
+    /// Sets the launcher's allowance back to zero, provided it is still
+    /// `remaining`. Costs one ledger fee.
+    pub async fn revoke_launcher(&self, remaining: &Nat) -> Result<()> {
+        self.approve(Nat::from(0u64), Some(remaining.clone())).await
+    }
+
+    /// Revokes what is left of the launcher's allowance and returns it;
+    /// `None` when nothing was left.
+    pub async fn revoke_remaining(&self) -> Result<Option<Nat>> {
+        let remaining = self.launcher_allowance().await?;
+        if remaining == 0u64 {
+            return Ok(None);
+        }
+        self.revoke_launcher(&remaining).await?;
+        info!(%remaining, "revoked leftover launcher allowance");
+        Ok(Some(remaining))
+    }
+
+    fn launcher_account(&self) -> Account {
+        Account {
+            owner: self.launcher_id,
+            subaccount: None,
+        }
+    }
+
+    async fn approve(&self, amount: Nat, expected_allowance: Option<Nat>) -> Result<()> {
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
 
         let args = ApproveArgs {
             from_subaccount: None,
-            spender: Account {
-                owner: self.launcher_id,
-                subaccount: None,
-            },
-            amount: amount.clone(),
-            expected_allowance: None,
+            spender: self.launcher_account(),
+            amount,
+            expected_allowance,
             expires_at: Some(now + APPROVAL_TTL_NS),
-            fee: Some(Nat::from(100_000u64)),
+            fee: Some(Nat::from(TRANSFER_FEE_E8S)),
             memo: None,
             created_at_time: Some(now),
         };
 
-        let payload = candid::encode_one(args)?;
-        let response = self
-            .agent
-            .update(&self.ledger_id, "icrc2_approve")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call icrc2_approve")?;
-
-        Decode!(&response, std::result::Result<Nat, ApproveError>)
-            .context("Failed to decode icrc2_approve response")?
-            .map_err(anyhow::Error::msg)?;
+        let (result,) = call(&self.agent, &self.ledger_id, ledger::ICRC2_APPROVE, (args,)).await?;
+        result.map_err(payment_failed)?;
         Ok(())
     }
 
-    pub async fn deploy_memory(&self, name: &str, description: &str) -> Result<String> {
-        let payload = encode_deploy_args(name, description)?;
-        let response = self
-            .agent
-            .update(&self.launcher_id, "deploy_instance")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call deploy_instance")?;
+    /// Deploys a memory whose vectors have `dim` dimensions; this must match
+    /// the embedding provider used for inserts and searches, after any
+    /// `metadata.vectors` processing.
+    pub async fn deploy_memory(&self, metadata: &MemoryMetadata, dim: u64) -> Result<String> {
+        let metadata = serde_json::to_string(metadata)?;
+        let (result,) = call(
+            &self.agent,
+            &self.launcher_id,
+            launcher::DEPLOY_INSTANCE,
+            (metadata, dim),
+        )
+        .await?;
+        deploy_result(result)
+    }
 
-        let result = Decode!(&response, std::result::Result<String, DeployInstanceError>)
-            .context("Failed to decode deploy_instance response")?;
-        Ok(result?)
+    /// Like [`Self::deploy_memory`], but returns as soon as the launcher has
+    /// accepted the request.
+    pub async fn submit_deploy(&self, metadata: &MemoryMetadata, dim: u64) -> Result<Deployment> {
+        let metadata = serde_json::to_string(metadata)?;
+        let response = submit(
+            &self.agent,
+            &self.launcher_id,
+            launcher::DEPLOY_INSTANCE,
+            (metadata, dim),
+        )
+        .await?;
+        match response {
+            CallResponse::Response(reply) => {
+                let (result,) = launcher::DEPLOY_INSTANCE.decode_reply(&reply)?;
+                deploy_result(result).map(Deployment::Finished)
+            }
+            CallResponse::Poll(request_id) => Ok(Deployment::Submitted(request_id)),
+        }
     }
 
     pub async fn list_memories(&self) -> Result<Vec<State>> {
-        let response = self
-            .agent
-            .update(&self.launcher_id, "list_instance")
-            .call_and_wait()
-            .await
-            .context("Failed to call deploy_instance")?;
-
-        let result =
-            Decode!(&response, Vec<State>).context("Failed to decode deploy_instance response")?;
-        Ok(result)
+        let (states,) = call(&self.agent, &self.launcher_id, launcher::LIST_INSTANCE, ()).await?;
+        Ok(states)
     }
 
     pub async fn update_instance(&self, instance_pid_str: &str) -> Result<()> {
-        let payload = encode_update_instance_args(instance_pid_str)?;
-        let response = self
-            .agent
-            .update(&self.launcher_id, "update_instance")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call update_instance")?;
+        let (result,) = call(
+            &self.agent,
+            &self.launcher_id,
+            launcher::UPDATE_INSTANCE,
+            (instance_pid_str.to_string(),),
+        )
+        .await?;
+        result.map_err(rejected)
+    }
 
-        let result = Decode!(&response, std::result::Result<(), String>)
-            .context("Failed to decode update_instance response")?;
+    /// `None` when the memory has no metadata or the launcher does not
+    /// declare `get_instance_metadata`.
+    pub async fn fetch_metadata(&self, instance_pid_str: &str) -> Result<Option<MemoryMetadata>> {
+        if !self
+            .declares(launcher_ext::GET_INSTANCE_METADATA.name)
+            .await?
+        {
+            return Ok(None);
+        }
+        let (raw,) = call(
+            &self.agent,
+            &self.launcher_id,
+            launcher_ext::GET_INSTANCE_METADATA,
+            (instance_pid_str.to_string(),),
+        )
+        .await?;
+        raw.map(|json| {
+            serde_json::from_str(&json).context("Failed to parse instance metadata JSON")
+        })
+        .transpose()
+    }
 
-        result.map_err(anyhow::Error::msg)
+    pub async fn update_metadata(
+        &self,
+        instance_pid_str: &str,
+        metadata: &MemoryMetadata,
+    ) -> Result<()> {
+        let method = launcher_ext::UPDATE_INSTANCE_METADATA;
+        if !self.declares(method.name).await? {
+            return Err(rejected(format!(
+                "the launcher does not declare {}",
+                method.name
+            )));
+        }
+        let (result,) = call(
+            &self.agent,
+            &self.launcher_id,
+            method,
+            (
+                instance_pid_str.to_string(),
+                serde_json::to_string(metadata)?,
+            ),
+        )
+        .await?;
+        result.map_err(rejected)
     }
-}
 
-fn encode_deploy_args(name: &str, description: &str) -> Result<Vec<u8>> {
-    let payload = json!({
-        "name": name,
-        "description": description})
-    .to_string();
-    Ok(candid::encode_args((payload, DEFAULT_VECTOR_DIM))?)
-}
+    /// Whether the launcher's published Candid interface declares `method`.
+    /// `false` when the launcher publishes no interface.
+    pub async fn declares(&self, method: &str) -> Result<bool> {
+        let did = self
+            .interface
+            .get_or_try_init(|| async {
+                ManagementClient::new(self.agent.clone())
+                    .candid_interface(self.launcher_id)
+                    .await
+            })
+            .await?;
+        Ok(did
+            .as_deref()
+            .is_some_and(|did| declares_method(did, method)))
+    }
 
-fn encode_update_instance_args(instance_pid_str: &str) -> Result<Vec<u8>> {
-    Ok(candid::encode_one(instance_pid_str)?)
+    /// Asks the launcher to pull `amount` e8s (already approved) and convert
+    /// them into cycles for the given memory instance.
+    pub async fn top_up_instance(&self, instance_pid_str: &str, amount: &Nat) -> Result<()> {
+        let (result,) = call(
+            &self.agent,
+            &self.launcher_id,
+            launcher_ext::TOP_UP_INSTANCE,
+            (instance_pid_str.to_string(), amount.clone()),
+        )
+        .await?;
+        result.map_err(rejected)
+    }
 }
 
-#[derive(CandidType, Deserialize, Debug, Error)]
-enum DeployInstanceError {
-    #[error("index out of range")]
-    IndexOutOfLange,
-
-    #[error("failed while setting up canister: {0}")]
-    SettingUpCanister(String),
-
-    #[error("refund required")]
-    Refund,
-
-    #[error("no instances available")]
-    NoInstances,
-
-    #[error("failed to create canister")]
-    CreateCanister,
+/// Outcome of [`LauncherClient::submit_deploy`].
+pub enum Deployment {
+    /// The launcher answered at once with the new canister id.
+    Finished(String),
+    /// Still running; the id of the update call.
+    Submitted(RequestId),
+}
 
-    #[error("failed to install canister")]
-    InstallCanister,
+fn deploy_result(result: std::result::Result<String, DeployInstanceError>) -> Result<String> {
+    result.map_err(|err| match err {
+        DeployInstanceError::CheckBalance(_) => payment_failed(err),
+        _ => rejected(err),
+    })
+}
 
-    #[error("balance check failed: {0}")]
-    CheckBalance(TransferResponseError),
+/// Name, description and vector processing stored by the launcher as a JSON
+/// string.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MemoryMetadata {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "VectorProcessing::is_none")]
+    pub vectors: VectorProcessing,
+    /// RFC 3339 time the deploy was requested. The launcher keeps no
+    /// creation time of its own, so memories deployed before this was
+    /// recorded have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
 
-    #[error("already running")]
-    AlreadyRunning,
+impl fmt::Display for DeployInstanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IndexOutOfLange => f.write_str("index out of range"),
+            Self::SettingUpCanister(detail) => {
+                write!(f, "failed while setting up canister: {detail}")
+            }
+            Self::Refund => f.write_str("refund required"),
+            Self::NoInstances => f.write_str("no instances available"),
+            Self::CreateCanister => f.write_str("failed to create canister"),
+            Self::InstallCanister => f.write_str("failed to install canister"),
+            Self::CheckBalance(err) => write!(f, "balance check failed: {err}"),
+            Self::AlreadyRunning => f.write_str("already running"),
+        }
+    }
 }
 
-#[derive(CandidType, candid::Deserialize, Clone, Debug, Error)]
-enum TransferResponseError {
-    #[error("icrc1 transfer error: {0:?}")]
-    TransferError(TransferError),
-    #[error("icrc2 transfer_from error: {0:?}")]
-    TransferFromError(TransferFromError),
-    #[error("ledger call rejected: {0}")]
-    CallReject(String),
+impl fmt::Display for TransferResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransferError(err) => write!(f, "icrc1 transfer error: {err:?}"),
+            Self::TransferFromError(err) => write!(f, "icrc2 transfer_from error: {err:?}"),
+            Self::CallReject(reason) => write!(f, "ledger call rejected: {reason}"),
+        }
+    }
 }
 
-#[derive(CandidType, candid::Deserialize, Clone, Debug)]
-pub enum State {
-    Empty(String),
-    Pending(String),
-    Creation(String),
-    Installation(Principal, String),
-    SettingUp(Principal),
-    Running(Principal),
+impl State {
+    /// Canister id once the launcher has created one.
+    pub fn principal(&self) -> Option<&Principal> {
+        match self {
+            State::Installation(principal, _)
+            | State::SettingUp(principal)
+            | State::Running(principal) => Some(principal),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            State::Empty(_) => "Empty",
+            State::Pending(_) => "Pending",
+            State::Creation(_) => "Creation",
+            State::Installation(_, _) => "Installation",
+            State::SettingUp(_) => "SettingUp",
+            State::Running(_) => "Running",
+        }
+    }
+
+    /// Free-form detail the launcher attaches to a state, if any.
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            State::Empty(detail)
+            | State::Pending(detail)
+            | State::Creation(detail)
+            | State::Installation(_, detail) => {
+                Some(detail).filter(|d| !d.is_empty()).map(String::as_str)
+            }
+            State::SettingUp(_) | State::Running(_) => None,
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use anyhow::{Result, anyhow};
+use candid::Nat;
+use ic_agent::{Agent, export::Principal};
+
+use crate::clients::call::{
+    call, ledger,
+    ledger_index::{self, Account, GetAccountTransactionsArgs, TransactionWithId},
+};
+
+/// Client for the ICRC index canister that serves per-account history for
+/// the KINIC ledger.
+pub struct LedgerIndexClient {
+    agent: Agent,
+    index_id: Principal,
+}
+
+impl LedgerIndexClient {
+    pub fn new(agent: Agent, index_id: Principal) -> Self {
+        Self { agent, index_id }
+    }
+
+    /// Asks the ledger for its index canister (ICRC-106).
+    pub async fn discover(agent: Agent, ledger_id: Principal) -> Result<Self> {
+        let (result,) = call(&agent, &ledger_id, ledger::ICRC106_GET_INDEX_PRINCIPAL, ()).await?;
+        let index_id =
+            result.map_err(|err| anyhow!("ledger did not report an index canister: {err:?}"))?;
+        Ok(Self::new(agent, index_id))
+    }
+
+    pub fn index_id(&self) -> &Principal {
+        &self.index_id
+    }
+
+    /// Returns up to `max_results` transactions for `account`, newest first,
+    /// starting below transaction id `start` when given.
+    pub async fn account_transactions(
+        &self,
+        account: Account,
+        start: Option<Nat>,
+        max_results: u64,
+    ) -> Result<Vec<TransactionWithId>> {
+        let args = GetAccountTransactionsArgs {
+            account,
+            start,
+            max_results: Nat::from(max_results),
+        };
+        let (result,) = call(
+            &self.agent,
+            &self.index_id,
+            ledger_index::GET_ACCOUNT_TRANSACTIONS,
+            (args,),
+        )
+        .await?;
+        let result = result.map_err(|err| anyhow!("index canister error: {}", err.message))?;
+        Ok(result.transactions)
+    }
+}
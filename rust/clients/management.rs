@@ -0,0 +1,311 @@
+use anyhow::{Context, Result};
+use ic_agent::{Agent, export::Principal};
+
+use std::time::Instant;
+
+pub use super::call::management::{
+    CanisterStatusResult as CanisterStatus, CanisterStatusResultStatus as CanisterRunStatus,
+};
+use super::{
+    call::{
+        call_management,
+        management::{self, CanisterStatusArgs},
+    },
+    cassette,
+};
+use crate::{metrics, network::with_deadline};
+
+pub struct ManagementClient {
+    agent: Agent,
+}
+
+impl ManagementClient {
+    pub fn new(agent: Agent) -> Self {
+        Self { agent }
+    }
+
+    /// Controllers are public state, readable by anyone.
+    pub async fn controllers(&self, canister_id: Principal) -> Result<Vec<Principal>> {
+        let request = async {
+            let controllers = self
+                .agent
+                .read_state_canister_controllers(canister_id)
+                .await
+                .context("Failed to read canister controllers")?;
+            Ok(candid::encode_one(controllers)?)
+        };
+        let label = "management.read_state_controllers";
+        let started = Instant::now();
+        let reply = cassette::intercept(
+            &self.agent,
+            &canister_id,
+            label,
+            &[],
+            with_deadline(label, request),
+        )
+        .await;
+        metrics::record_canister_call(label, started.elapsed(), &reply);
+        let reply = reply?;
+        candid::decode_one(&reply).context("Failed to decode canister controllers")
+    }
+
+    /// SHA-256 of the installed module, from public state; `None` when no
+    /// module is installed.
+    pub async fn module_hash(&self, canister_id: Principal) -> Result<Option<Vec<u8>>> {
+        let request = async {
+            let hash = self
+                .agent
+                .read_state_canister_module_hash(canister_id)
+                .await
+                .ok();
+            Ok(candid::encode_one(hash)?)
+        };
+        let label = "management.read_state_module_hash";
+        let started = Instant::now();
+        let reply = cassette::intercept(
+            &self.agent,
+            &canister_id,
+            label,
+            &[],
+            with_deadline(label, request),
+        )
+        .await;
+        metrics::record_canister_call(label, started.elapsed(), &reply);
+        let reply = reply?;
+        candid::decode_one(&reply).context("Failed to decode canister module hash")
+    }
+
+    /// The Candid interface the canister publishes in its public
+    /// `candid:service` metadata; `None` when it publishes none.
+    pub async fn candid_interface(&self, canister_id: Principal) -> Result<Option<String>> {
+        let request = async {
+            let did = self
+                .agent
+                .read_state_canister_metadata(canister_id, "candid:service")
+                .await
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok());
+            Ok(candid::encode_one(did)?)
+        };
+        let label = "management.read_state_candid_service";
+        let started = Instant::now();
+        let reply = cassette::intercept(
+            &self.agent,
+            &canister_id,
+            label,
+            &[],
+            with_deadline(label, request),
+        )
+        .await;
+        metrics::record_canister_call(label, started.elapsed(), &reply);
+        let reply = reply?;
+        candid::decode_one(&reply).context("Failed to decode candid:service metadata")
+    }
+
+    /// Calls the management canister directly; only succeeds for controllers.
+    pub async fn canister_status(&self, canister_id: Principal) -> Result<CanisterStatus> {
+        let (status,) = call_management(
+            &self.agent,
+            canister_id,
+            management::CANISTER_STATUS,
+            (CanisterStatusArgs { canister_id },),
+        )
+        .await?;
+        Ok(status)
+    }
+}
+
+impl std::fmt::Display for CanisterRunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Running => "running",
+            Self::Stopping => "stopping",
+            Self::Stopped => "stopped",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Whether the `service` block of a Candid interface declares `method`.
+pub fn declares_method(did: &str, method: &str) -> bool {
+    let tokens = tokenize(did);
+    // The actor is the top-level `service` that starts a declaration;
+    // `type T = service { ... }` is only a type.
+    let mut depth = 0usize;
+    let mut actor = None;
+    for (at, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('{' | '(') => depth += 1,
+            Token::Punct('}' | ')') => depth = depth.saturating_sub(1),
+            Token::Ident("service")
+                if depth == 0 && (at == 0 || tokens[at - 1] == Token::Punct(';')) =>
+            {
+                actor = Some(at);
+            }
+            _ => {}
+        }
+    }
+    let Some(actor) = actor else {
+        return false;
+    };
+
+    // `service : (init) -> { ... }` or `service : { ... }`; braces inside
+    // the init arguments sit deeper than the body's.
+    let mut depth = 0usize;
+    let mut body = tokens[actor + 1..].iter();
+    for token in body.by_ref() {
+        match token {
+            Token::Punct('{') if depth == 0 => break,
+            Token::Punct('{' | '(') => depth += 1,
+            Token::Punct('}' | ')') => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    // Methods are `name : type;` directly inside the body.
+    let mut depth = 1usize;
+    let mut previous = Token::Punct('{');
+    let mut body = body.peekable();
+    while let Some(&token) = body.next() {
+        match token {
+            Token::Punct('{' | '(') => depth += 1,
+            Token::Punct('}' | ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            Token::Ident(name) | Token::Text(name)
+                if depth == 1
+                    && name == method
+                    && matches!(previous, Token::Punct('{' | ';'))
+                    && body.peek() == Some(&&Token::Punct(':')) =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+        previous = token;
+    }
+    false
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    /// The contents of a quoted string, escapes left as written.
+    Text(&'a str),
+    Punct(char),
+}
+
+/// Splits Candid into identifiers, strings and punctuation, dropping
+/// whitespace and comments.
+fn tokenize(did: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = did.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if did[at + 1..].starts_with('/') => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            }
+            '/' if did[at + 1..].starts_with('*') => {
+                chars.next();
+                let end = did[at + 2..]
+                    .find("*/")
+                    .map_or(did.len(), |end| at + 4 + end);
+                while chars.next_if(|&(next, _)| next < end).is_some() {}
+            }
+            '"' => {
+                let mut end = did.len();
+                while let Some((next, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = next;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                tokens.push(Token::Text(&did[at + 1..end.max(at + 1)]));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = at + c.len_utf8();
+                while let Some((next, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    end = next + c.len_utf8();
+                }
+                tokens.push(Token::Ident(&did[at..end]));
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAUNCHER: &str = r#"
+type State = variant { Installation : text; Empty : text };
+// service : { get_metadata : () -> (text) }
+type Callback = service { update_metadata : (text) -> () };
+service : (record { owner : principal }) -> {
+  /* get_metadata : (principal) -> (text) query; */
+  deploy_instance : (text, nat64) -> (variant { Ok : text; Err : text });
+  "get_price" : () -> (nat) query;
+  list_instance : () -> (vec State) query;
+  notes : () -> (record { get_metadata : text; text : text }) query;
+  describe : () -> (text) query; // "get_metadata : () -> (text)"
+}
+"#;
+
+    #[test]
+    fn declared_method() {
+        assert!(declares_method(LAUNCHER, "deploy_instance"));
+        assert!(declares_method(LAUNCHER, "list_instance"));
+        assert!(declares_method(LAUNCHER, "describe"));
+    }
+
+    #[test]
+    fn quoted_method_name() {
+        assert!(declares_method(LAUNCHER, "get_price"));
+    }
+
+    #[test]
+    fn missing_method() {
+        assert!(!declares_method(LAUNCHER, "set_price"));
+        assert!(!declares_method(LAUNCHER, "deploy"));
+    }
+
+    #[test]
+    fn names_in_comments_and_strings_are_ignored() {
+        assert!(!declares_method(LAUNCHER, "get_metadata"));
+    }
+
+    #[test]
+    fn nested_service_types_are_ignored() {
+        assert!(!declares_method(LAUNCHER, "update_metadata"));
+        assert!(!declares_method(
+            "service : { get : () -> (service { inner : () -> () }) }",
+            "inner"
+        ));
+    }
+
+    #[test]
+    fn field_and_argument_names_are_ignored() {
+        assert!(!declares_method(LAUNCHER, "owner"));
+        assert!(!declares_method(LAUNCHER, "Ok"));
+        assert!(!declares_method(LAUNCHER, "text"));
+    }
+
+    #[test]
+    fn without_a_service() {
+        assert!(!declares_method("type T = record { a : nat };", "a"));
+        assert!(!declares_method("", "a"));
+    }
+}
@@ -1,84 +1,559 @@
-use anyhow::{Context, Result};
-use candid::Decode;
+use std::{env, sync::Mutex};
+
+use anyhow::{Context, Result, anyhow};
 use ic_agent::{Agent, export::Principal};
+use tracing::debug;
+#[cfg(feature = "native")]
+use tracing::warn;
+
+#[cfg(feature = "native")]
+use crate::clients::launcher::LauncherClient;
+use crate::{
+    clients::call::{call, memory, memory_ext},
+    error::KinicError,
+    payload::{ChunkPayload, summary_tag},
+    vectors::{VectorProcessing, VectorTransport, quantize_i8, to_f16_bits},
+};
+
+/// Default number of hits requested per `search_paged` call.
+pub const SEARCH_PAGE_SIZE: u32 = 100;
+/// Chunks read per page by [`MemoryClient::all_chunks`].
+pub const CHUNK_PAGE_SIZE: u32 = 100;
+
+/// Largest encoded `insert` argument sent, leaving room for the request
+/// envelope within the replica's 2 MiB ingress limit.
+pub const MAX_INSERT_ARG_BYTES: usize = 2 * 1024 * 1024 - 64 * 1024;
+
+/// `auto`, `f32`, `f16` or `int8`; see [`VectorTransport`].
+pub const TRANSPORT_ENV_VAR: &str = "KINIC_VECTOR_TRANSPORT";
+const CAPABILITY_INSERT_F16: &str = "insert_f16";
+const CAPABILITY_INSERT_I8: &str = "insert_i8";
+const CAPABILITY_DIM: &str = "dim";
+const CAPABILITY_SEARCH_PAGED: &str = "search_paged";
+/// Lookup of one chunk by id; everything that reads chunks back needs it.
+pub const CAPABILITY_GET: &str = "get";
+/// Ids probed past a missing one before the end of the memory is assumed.
+const GAP_PROBE: u32 = 8;
 
 pub struct MemoryClient {
     agent: Agent,
     canister_id: Principal,
+    dim: Mutex<Option<usize>>,
+    processing: Mutex<Option<VectorProcessing>>,
+    capabilities: Mutex<Option<Vec<String>>>,
+    transport: VectorTransport,
 }
 
 impl MemoryClient {
     pub fn new(agent: Agent, canister_id: Principal) -> Self {
-        Self { agent, canister_id }
+        Self {
+            agent,
+            canister_id,
+            dim: Mutex::new(None),
+            processing: Mutex::new(None),
+            capabilities: Mutex::new(None),
+            transport: env::var(TRANSPORT_ENV_VAR)
+                .ok()
+                .and_then(|value| VectorTransport::parse(&value))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Encodes inserted vectors with `transport` instead of the one named by
+    /// `KINIC_VECTOR_TRANSPORT`.
+    pub fn with_transport(mut self, transport: VectorTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Uses `processing` instead of reading it from the launcher metadata.
+    pub fn with_processing(self, processing: VectorProcessing) -> Self {
+        *self.processing.lock().unwrap() = Some(processing);
+        self
+    }
+
+    /// Embedding dimension the memory was created (or last reset) with, or
+    /// `None` when the canister does not list `dim` in its capabilities.
+    /// Queried once per client.
+    pub async fn dim(&self) -> Result<Option<usize>> {
+        if let Some(dim) = *self.dim.lock().unwrap() {
+            return Ok(Some(dim));
+        }
+        if !self.supports(CAPABILITY_DIM).await {
+            return Ok(None);
+        }
+        let (dim,): (u64,) = call(&self.agent, &self.canister_id, memory_ext::DIM, ()).await?;
+        let dim = usize::try_from(dim).context("memory dimension does not fit in usize")?;
+        *self.dim.lock().unwrap() = Some(dim);
+        Ok(Some(dim))
+    }
+
+    /// Cheapest call every memory canister answers: embeddings for a tag
+    /// no chunk carries.
+    pub async fn ping(&self) -> Result<()> {
+        self.tagged_embeddings(String::new()).await.map(drop)
+    }
+
+    /// Vector processing recorded in the memory's launcher metadata, applied
+    /// to every vector inserted or searched with. Read once per client; a
+    /// memory the launcher does not know has none.
+    pub async fn processing(&self) -> Result<VectorProcessing> {
+        if let Some(processing) = *self.processing.lock().unwrap() {
+            return Ok(processing);
+        }
+        let processing = self.fetch_processing().await;
+        *self.processing.lock().unwrap() = Some(processing);
+        Ok(processing)
+    }
+
+    #[cfg(feature = "native")]
+    async fn fetch_processing(&self) -> VectorProcessing {
+        let launcher = LauncherClient::new(self.agent.clone());
+        match launcher.fetch_metadata(&self.canister_id.to_text()).await {
+            Ok(metadata) => metadata.map(|m| m.vectors).unwrap_or_default(),
+            Err(err) => {
+                warn!(
+                    canister_id = %self.canister_id,
+                    error = %format!("{err:#}"),
+                    "could not read vector processing from the launcher; using none"
+                );
+                VectorProcessing::default()
+            }
+        }
+    }
+
+    /// Without the launcher client, only [`Self::with_processing`] sets it.
+    #[cfg(not(feature = "native"))]
+    async fn fetch_processing(&self) -> VectorProcessing {
+        VectorProcessing::default()
+    }
+
+    /// Optional methods the canister implements. Queried once per client;
+    /// canisters that predate the query have none.
+    pub async fn capabilities(&self) -> Vec<String> {
+        if let Some(capabilities) = self.capabilities.lock().unwrap().clone() {
+            return capabilities;
+        }
+        let capabilities = match call::<_, (Vec<String>,)>(
+            &self.agent,
+            &self.canister_id,
+            memory_ext::CAPABILITIES,
+            (),
+        )
+        .await
+        {
+            Ok((capabilities,)) => capabilities,
+            Err(err) => {
+                debug!(
+                    canister_id = %self.canister_id,
+                    error = %format!("{err:#}"),
+                    "capabilities query failed; assuming none"
+                );
+                Vec::new()
+            }
+        };
+        *self.capabilities.lock().unwrap() = Some(capabilities.clone());
+        capabilities
+    }
+
+    /// Whether [`Self::capabilities`] lists `capability`.
+    pub async fn supports(&self, capability: &str) -> bool {
+        self.capabilities().await.iter().any(|c| c == capability)
+    }
+
+    /// Fails with a validation error unless [`Self::capabilities`] lists
+    /// `capability`, so a command stops before doing any work.
+    pub async fn require(&self, capability: &str) -> Result<()> {
+        if self.supports(capability).await {
+            return Ok(());
+        }
+        Err(KinicError::Validation(format!(
+            "memory {} does not list `{capability}` in its capabilities, \
+             which this command needs",
+            self.canister_id
+        ))
+        .into())
+    }
+
+    /// The requested transport if the canister supports it, else `F32`.
+    async fn negotiated_transport(&self) -> VectorTransport {
+        let wanted = match self.transport {
+            VectorTransport::F32 => return VectorTransport::F32,
+            VectorTransport::Auto | VectorTransport::F16 => CAPABILITY_INSERT_F16,
+            VectorTransport::Int8 => CAPABILITY_INSERT_I8,
+        };
+        if self.supports(wanted).await {
+            return match self.transport {
+                VectorTransport::Int8 => VectorTransport::Int8,
+                _ => VectorTransport::F16,
+            };
+        }
+        if self.transport != VectorTransport::Auto {
+            debug!(
+                canister_id = %self.canister_id,
+                transport = ?self.transport,
+                "canister lacks {wanted}; sending f32 vectors"
+            );
+        }
+        VectorTransport::F32
+    }
+
+    /// Applies the memory's processing and checks the resulting dimension.
+    async fn prepare(&self, embedding: Vec<f32>) -> Result<Vec<f32>> {
+        let embedding = self.processing().await?.apply(embedding);
+        self.check_dim(&embedding).await?;
+        Ok(embedding)
+    }
+
+    /// Rejects embeddings whose length differs from the memory's dimension,
+    /// which the canister would otherwise answer with an opaque trap. Skipped
+    /// when the canister does not report its dimension.
+    async fn check_dim(&self, embedding: &[f32]) -> Result<()> {
+        let Some(dim) = self.dim().await? else {
+            return Ok(());
+        };
+        if embedding.len() != dim {
+            return Err(KinicError::Validation(format!(
+                "embedding has {} dimensions but memory {} expects {dim}; \
+                 run `kinic-cli reset --memory-id {} --dim {} --export <PATH>` (this erases the memory) \
+                 or use an embedding provider that produces {dim}-dimensional vectors",
+                embedding.len(),
+                self.canister_id,
+                self.canister_id,
+                embedding.len(),
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Stores one chunk and returns its id. Chunks whose arguments exceed
+    /// [`MAX_INSERT_ARG_BYTES`] are refused before the call.
+    pub async fn insert(&self, embedding: Vec<f32>, text: &str) -> Result<u32> {
+        let embedding = self.prepare(embedding).await?;
+        let size = insert_arg_size(&embedding, text);
+        if size > MAX_INSERT_ARG_BYTES {
+            return Err(KinicError::Validation(format!(
+                "chunk is {size} bytes encoded, over the {MAX_INSERT_ARG_BYTES}-byte limit of one insert; split its text"
+            ))
+            .into());
+        }
+        let (id,) = match self.negotiated_transport().await {
+            VectorTransport::F16 => {
+                call(
+                    &self.agent,
+                    &self.canister_id,
+                    memory_ext::INSERT_F16,
+                    (to_f16_bits(&embedding), text.to_string()),
+                )
+                .await?
+            }
+            VectorTransport::Int8 => {
+                let (quantized, scale) = quantize_i8(&embedding);
+                call(
+                    &self.agent,
+                    &self.canister_id,
+                    memory_ext::INSERT_I8,
+                    (quantized, scale, text.to_string()),
+                )
+                .await?
+            }
+            VectorTransport::Auto | VectorTransport::F32 => {
+                call(
+                    &self.agent,
+                    &self.canister_id,
+                    memory::INSERT,
+                    (embedding, text.to_string()),
+                )
+                .await?
+            }
+        };
+        Ok(id)
+    }
+
+    /// The stored payload of chunk `id`, if it exists. Needs the `get`
+    /// capability.
+    pub async fn get(&self, id: u32) -> Result<Option<String>> {
+        self.require(CAPABILITY_GET).await?;
+        let (payload,) = call(&self.agent, &self.canister_id, memory_ext::GET, (id,)).await?;
+        Ok(payload)
+    }
+
+    /// Every chunk with its id, read page by page.
+    pub async fn all_chunks(&self) -> Result<Vec<(u32, String)>> {
+        let mut chunks = Vec::new();
+        let mut next = Some(0);
+        while let Some(start) = next {
+            let (page, rest) = self.chunk_page(start, CHUNK_PAGE_SIZE).await?;
+            chunks.extend(page);
+            next = rest;
+        }
+        Ok(chunks)
     }
 
-    pub async fn insert(&self, embedding: Vec<f32>, text: &str) -> Result<()> {
-        let payload = encode_insert_args(embedding, text)?;
-        let response = self
-            .agent
-            .update(&self.canister_id, "insert")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call insert on memory canister")?;
+    /// Up to `limit` chunks from id `start` on, with the id to continue
+    /// from, or `None` once the last chunk was read. Ids are assigned
+    /// sequentially, so the first missing id past the start ends the
+    /// memory; if one of the next few ids exists after all, the ids have a
+    /// gap and reading fails rather than return part of the memory.
+    pub async fn chunk_page(
+        &self,
+        start: u32,
+        limit: u32,
+    ) -> Result<(Vec<(u32, String)>, Option<u32>)> {
+        let mut chunks = Vec::new();
+        let mut id = start;
+        while chunks.len() < limit.max(1) as usize {
+            match self.get(id).await? {
+                Some(payload) => chunks.push((id, payload)),
+                // Whether the first id is 0 or 1 depends on the canister.
+                None if id == 0 => {}
+                None => {
+                    self.check_no_gap(id).await?;
+                    return Ok((chunks, None));
+                }
+            }
+            let Some(next) = id.checked_add(1) else {
+                return Ok((chunks, None));
+            };
+            id = next;
+        }
+        Ok((chunks, Some(id)))
+    }
 
-        Decode!(&response, u32).context("Failed to decode insert response")?;
+    /// Fails when one of the [`GAP_PROBE`] ids after the missing `id`
+    /// exists.
+    async fn check_no_gap(&self, id: u32) -> Result<()> {
+        for offset in 1..=GAP_PROBE {
+            let Some(probe) = id.checked_add(offset) else {
+                break;
+            };
+            if self.get(probe).await?.is_some() {
+                return Err(anyhow!(
+                    "chunk #{id} of memory {} is missing but #{probe} exists; \
+                     chunk ids are not sequential, so not every chunk can be read",
+                    self.canister_id
+                ));
+            }
+        }
         Ok(())
     }
 
+    /// First and last chunk id, or `None` for an empty memory. Ids are
+    /// sequential, so this takes a logarithmic number of `get` calls.
+    pub async fn id_range(&self) -> Result<Option<(u32, u32)>> {
+        let first: u32 = if self.get(0).await?.is_some() {
+            0
+        } else if self.get(1).await?.is_some() {
+            1
+        } else {
+            return Ok(None);
+        };
+        // Double the step until an id is missing, then bisect.
+        let mut present = first;
+        let mut step = 1u32;
+        let mut missing = loop {
+            let probe = present.saturating_add(step);
+            if probe == present {
+                return Ok(Some((first, present)));
+            }
+            if self.get(probe).await?.is_none() {
+                break probe;
+            }
+            present = probe;
+            step = step.saturating_mul(2);
+        };
+        while missing - present > 1 {
+            let middle = present + (missing - present) / 2;
+            if self.get(middle).await?.is_some() {
+                present = middle;
+            } else {
+                missing = middle;
+            }
+        }
+        Ok(Some((first, present)))
+    }
+
     pub async fn search(&self, embedding: Vec<f32>) -> Result<Vec<(f32, String)>> {
-        let payload = encode_search_args(embedding)?;
-        let response = self
-            .agent
-            .query(&self.canister_id, "search")
-            .with_arg(payload)
-            .call()
-            .await
-            .context("Failed to call search on memory canister")?;
-
-        let results =
-            Decode!(&response, Vec<(f32, String)>).context("Failed to decode search response")?;
+        let embedding = self.prepare(embedding).await?;
+        let (results,) = call(&self.agent, &self.canister_id, memory::SEARCH, (embedding,)).await?;
         Ok(results)
     }
 
+    /// One page of hits ordered by descending score, starting `offset`
+    /// results into the ranking. Canisters without `search_paged` in their
+    /// capabilities are asked for the whole ranking with `search`, which is
+    /// paged here and carries no chunk ids.
+    pub async fn search_page(
+        &self,
+        embedding: &[f32],
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<SearchHit>> {
+        if !self.supports(CAPABILITY_SEARCH_PAGED).await {
+            let ranking = self.ranking(embedding.to_vec()).await?;
+            return Ok(ranking
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect());
+        }
+        let embedding = self.prepare(embedding.to_vec()).await?;
+        let (results,): (Vec<(f32, u32, String)>,) = call(
+            &self.agent,
+            &self.canister_id,
+            memory_ext::SEARCH_PAGED,
+            (embedding, offset, limit),
+        )
+        .await?;
+        Ok(results
+            .into_iter()
+            .map(|(score, id, text)| SearchHit {
+                id: Some(id),
+                score,
+                text,
+            })
+            .collect())
+    }
+
+    /// Every hit from one `search` call, ordered by descending score.
+    async fn ranking(&self, embedding: Vec<f32>) -> Result<Vec<SearchHit>> {
+        let mut hits: Vec<SearchHit> = self
+            .search(embedding)
+            .await?
+            .into_iter()
+            .map(|(score, text)| SearchHit {
+                id: None,
+                score,
+                text,
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(hits)
+    }
+
+    /// Walks the full ranking page by page instead of in one reply.
+    pub fn search_pages(&self, embedding: Vec<f32>, page_size: u32) -> SearchPages<'_> {
+        SearchPages {
+            client: self,
+            embedding,
+            offset: 0,
+            page_size: page_size.max(1),
+            done: false,
+            ranking: None,
+        }
+    }
+
+    /// The `k` best hits, fetching only as many pages as needed.
+    pub async fn search_top_k(&self, embedding: Vec<f32>, k: usize) -> Result<Vec<SearchHit>> {
+        let page_size = u32::try_from(k)
+            .unwrap_or(u32::MAX)
+            .clamp(1, SEARCH_PAGE_SIZE);
+        let mut pages = self.search_pages(embedding, page_size);
+        let mut results = Vec::new();
+        while results.len() < k {
+            match pages.next_page().await? {
+                Some(page) => results.extend(page),
+                None => break,
+            }
+        }
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Every hit in ranking order, collected across as many pages as needed.
+    pub async fn search_all(&self, embedding: Vec<f32>) -> Result<Vec<SearchHit>> {
+        let mut pages = self.search_pages(embedding, SEARCH_PAGE_SIZE);
+        let mut results = Vec::new();
+        while let Some(page) = pages.next_page().await? {
+            results.extend(page);
+        }
+        Ok(results)
+    }
+
+    /// Up to `n` chunks on each side of chunk `id` that belong to the same
+    /// document (same tag), in insertion order. Chunks of one document are
+    /// inserted back to back, so the walk stops at the first id that is
+    /// missing or tagged differently.
+    pub async fn neighbors(&self, id: u32, n: u32) -> Result<Neighbors> {
+        let mut neighbors = Neighbors::default();
+        if n == 0 {
+            return Ok(neighbors);
+        }
+        let Some(center) = self.get(id).await? else {
+            return Ok(neighbors);
+        };
+        let tag = ChunkPayload::parse(&center).map(|payload| payload.tag);
+
+        for offset in 1..=n {
+            let Some(prev) = id.checked_sub(offset) else {
+                break;
+            };
+            match self.get(prev).await? {
+                Some(payload) if same_document(&tag, &payload) => {
+                    neighbors.before.push((prev, payload))
+                }
+                _ => break,
+            }
+        }
+        neighbors.before.reverse();
+
+        for offset in 1..=n {
+            let Some(next) = id.checked_add(offset) else {
+                break;
+            };
+            match self.get(next).await? {
+                Some(payload) if same_document(&tag, &payload) => {
+                    neighbors.after.push((next, payload))
+                }
+                _ => break,
+            }
+        }
+
+        Ok(neighbors)
+    }
+
     pub async fn tagged_embeddings(&self, tag: String) -> Result<Vec<Vec<f32>>> {
-        let payload = encode_tagged_embeddings_args(tag)?;
-        let response = self
-            .agent
-            .query(&self.canister_id, "tagged_embeddings")
-            .with_arg(payload)
-            .call()
-            .await
-            .context("Failed to call tagged_embeddings on memory canister")?;
-
-        let results =
-            Decode!(&response, Vec<Vec<f32>>).context("Failed to decode tagged_embeddings response")?;
+        let (results,) = call(
+            &self.agent,
+            &self.canister_id,
+            memory::TAGGED_EMBEDDINGS,
+            (tag,),
+        )
+        .await?;
         Ok(results)
     }
 
-    pub async fn add_new_user(&self, principal: Principal, role: u8) -> Result<()> {
-        let payload = encode_add_user_args(principal, role)?;
-        self.agent
-            .update(&self.canister_id, "add_new_user")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call add_new_user on memory canister")?;
+    /// Summary stored for the document tagged `tag` by `insert --summarize`.
+    /// The canister cannot look payloads up by tag, so the summary chunk is
+    /// found by searching for its own embedding.
+    pub async fn document_summary(&self, tag: &str) -> Result<Option<String>> {
+        let tag = summary_tag(tag);
+        let Some(embedding) = self.tagged_embeddings(tag.clone()).await?.pop() else {
+            return Ok(None);
+        };
+        Ok(self
+            .search_top_k(embedding, 4)
+            .await?
+            .into_iter()
+            .filter_map(|hit| ChunkPayload::parse(&hit.text))
+            .find(|payload| payload.tag == tag)
+            .map(|payload| payload.sentence))
+    }
 
-        Ok(())
+    pub async fn add_new_user(&self, principal: Principal, role: u8) -> Result<()> {
+        call(
+            &self.agent,
+            &self.canister_id,
+            memory::ADD_NEW_USER,
+            (principal, role),
+        )
+        .await
     }
 
     pub async fn reset(&self, dim: usize) -> Result<()> {
-        let payload = encode_reset_args(dim)?;
-        let response = self
-            .agent
-            .update(&self.canister_id, "reset")
-            .with_arg(payload)
-            .call_and_wait()
-            .await
-            .context("Failed to call reset on memory canister")?;
-
-        Decode!(&response, ()).context("Failed to decode reset response")?;
+        let () = call(&self.agent, &self.canister_id, memory::RESET, (dim as u64,)).await?;
+        *self.dim.lock().unwrap() = Some(dim);
         Ok(())
     }
 
@@ -87,18 +562,79 @@ impl MemoryClient {
     }
 }
 
-fn encode_insert_args(embedding: Vec<f32>, text: &str) -> Result<Vec<u8>> {
-    Ok(candid::encode_args((embedding, text.to_string()))?)
+/// Size of the Candid-encoded `insert` arguments for one chunk, as sent
+/// with the `f32` transport.
+pub fn insert_arg_size(embedding: &[f32], text: &str) -> usize {
+    candid::encode_args((embedding, text)).map_or(usize::MAX, |bytes| bytes.len())
+}
+
+/// Chunks surrounding a hit, as `(id, payload)` pairs.
+#[derive(Debug, Default)]
+pub struct Neighbors {
+    pub before: Vec<(u32, String)>,
+    pub after: Vec<(u32, String)>,
+}
+
+fn same_document(tag: &Option<String>, payload: &str) -> bool {
+    ChunkPayload::parse(payload).map(|payload| payload.tag) == *tag
 }
-fn encode_search_args(embedding: Vec<f32>) -> Result<Vec<u8>> {
-    Ok(candid::encode_one(embedding)?)
+
+/// A search result together with the id of the chunk it matched.
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    /// `None` when the canister lacks `search_paged`; `search` returns no ids.
+    pub id: Option<u32>,
+    pub score: f32,
+    pub text: String,
 }
-fn encode_add_user_args(principal: Principal, role: u8) -> Result<Vec<u8>> {
-    Ok(candid::encode_args((principal, role))?)
+
+impl From<SearchHit> for (f32, String) {
+    fn from(hit: SearchHit) -> Self {
+        (hit.score, hit.text)
+    }
 }
-fn encode_tagged_embeddings_args(tag: String) -> Result<Vec<u8>> {
-    Ok(candid::encode_one(tag)?)
+
+/// Cursor over the pages of a search, in ranking order.
+pub struct SearchPages<'a> {
+    client: &'a MemoryClient,
+    embedding: Vec<f32>,
+    offset: u32,
+    page_size: u32,
+    done: bool,
+    /// The whole ranking, read once from `search` when the canister has no
+    /// `search_paged`.
+    ranking: Option<std::vec::IntoIter<SearchHit>>,
 }
-fn encode_reset_args(dim: usize) -> Result<Vec<u8>> {
-    Ok(candid::encode_one(dim)?)
+
+impl SearchPages<'_> {
+    /// Fetches the next page, or `None` once the ranking is exhausted.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<SearchHit>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let page = if self.client.supports(CAPABILITY_SEARCH_PAGED).await {
+            self.client
+                .search_page(&self.embedding, self.offset, self.page_size)
+                .await?
+        } else {
+            let ranking = match self.ranking.take() {
+                Some(ranking) => ranking,
+                None => self
+                    .client
+                    .ranking(self.embedding.clone())
+                    .await?
+                    .into_iter(),
+            };
+            self.ranking
+                .insert(ranking)
+                .take(self.page_size as usize)
+                .collect()
+        };
+        let len = u32::try_from(page.len()).unwrap_or(u32::MAX);
+        self.offset = self.offset.saturating_add(len);
+        self.done = len < self.page_size;
+
+        Ok(Some(page).filter(|page| !page.is_empty()))
+    }
 }
@@ -1,5 +1,15 @@
+pub mod call;
+#[cfg(feature = "native")]
+pub mod cassette;
+#[cfg(feature = "native")]
 pub mod launcher;
+#[cfg(feature = "native")]
+pub mod ledger_index;
+#[cfg(feature = "native")]
+pub mod management;
 pub mod memory;
 
+#[cfg(feature = "native")]
 pub const LAUNCHER_CANISTER: &str = "xfug4-5qaaa-aaaak-afowa-cai";
+#[cfg(feature = "native")]
 pub const LEDGER_CANISTER: &str = "73mez-iiaaa-aaaaq-aaasq-cai";
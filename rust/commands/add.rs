@@ -0,0 +1,476 @@
+//! `add PATH_OR_URL`: one command for the common case. The input is sniffed
+//! and converted the way the dedicated insert commands would: PDFs through
+//! `pdftotext`, HTML to its visible text, `.docx` documents from their
+//! `word/document.xml`, and any other text as it is. A directory
+//! adds every file it can convert, recursively; a URL is fetched and
+//! converted by its content type.
+
+use std::{
+    fs,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::AddArgs,
+    clients::memory::MemoryClient,
+    commands::{
+        capture::memory_or_default, convert_pdf::pdf_to_markdown, insert::ChunkPipeline,
+        insert_bookmarks::html_to_text, interrupt,
+    },
+    embedding::http_client,
+    error::KinicError,
+    guard,
+    output::note,
+    payload::{InsertMetadata, PageLocator},
+    redact::Redactor,
+    tables::Table,
+};
+
+use super::CommandContext;
+
+/// Largest response `add` downloads from a URL.
+const MAX_FETCH_BYTES: usize = 50 * 1024 * 1024;
+/// Largest `word/document.xml` read from a `.docx`, uncompressed.
+const MAX_DOCX_XML_BYTES: u64 = 100 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Pdf,
+    Html,
+    Docx,
+    Text,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Pdf => "pdf",
+            Kind::Html => "html",
+            Kind::Docx => "docx",
+            Kind::Text => "text",
+        }
+    }
+}
+
+/// One converted document.
+struct Input {
+    kind: Kind,
+    content: String,
+    tag: String,
+    source: String,
+}
+
+pub async fn handle(args: AddArgs, ctx: &CommandContext) -> Result<()> {
+    let memory_id = memory_or_default(args.memory_id.clone())?;
+    let mut inputs = collect(&args).await?;
+    if inputs.is_empty() {
+        return Err(KinicError::Validation(format!(
+            "{} has no text, PDF, HTML or .docx files to add",
+            args.input
+        ))
+        .into());
+    }
+    for input in &inputs {
+        guard::check_secrets(&input.source, &input.content, args.allow_secrets)?;
+    }
+    if let Some(mut redactor) = Redactor::from_args(&args.redact) {
+        for input in &mut inputs {
+            input.content = redactor.redact(&input.source, &input.content).await?;
+        }
+        redactor.write_report("add")?;
+    }
+
+    let memory = Principal::from_text(&memory_id).context("Failed to parse memory canister id")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let mut pipeline = ChunkPipeline::start(
+        inputs.iter().map(|input| input.content.clone()).collect(),
+        args.language.as_deref(),
+    );
+    let mut total_chunks = 0;
+    for (index, input) in inputs.iter().enumerate() {
+        if index > 0 && interrupt::requested() {
+            bail!(
+                "interrupted after {index} of {} documents; the rest were not added",
+                inputs.len()
+            );
+        }
+        let metadata = InsertMetadata::new(&input.tag, Some(input.source.clone()), &[])?;
+        let mut pages = (input.kind == Kind::Pdf).then(|| PageLocator::new(&input.content));
+        let chunks = pipeline
+            .insert_next(&client, |sentence, index| {
+                let page = pages.as_mut().and_then(|pages| pages.page_of(sentence));
+                metadata.payload(sentence, index, page)
+            })
+            .await?;
+        note!(
+            "Added {} ({}, {chunks} chunks, tag {})",
+            input.source,
+            input.kind.name(),
+            input.tag
+        );
+        total_chunks += chunks;
+    }
+    info!(
+        canister_id = %memory,
+        documents = inputs.len(),
+        chunks = total_chunks,
+        "add completed"
+    );
+    Ok(())
+}
+
+async fn collect(args: &AddArgs) -> Result<Vec<Input>> {
+    if args.input.starts_with("http://") || args.input.starts_with("https://") {
+        let (kind, content) = fetch(&args.input).await?;
+        return Ok(vec![Input {
+            kind,
+            content,
+            tag: args.tag.clone().unwrap_or_else(|| args.input.clone()),
+            source: args.input.clone(),
+        }]);
+    }
+
+    let path = PathBuf::from(&args.input);
+    if path.is_dir() {
+        let mut files = Vec::new();
+        walk(&path, &mut files)?;
+        files.sort();
+        let mut inputs = Vec::new();
+        let mut skipped = 0;
+        for file in files {
+            let Some((kind, content)) = convert(&file)? else {
+                skipped += 1;
+                continue;
+            };
+            let relative = file.strip_prefix(&path).unwrap_or(&file);
+            inputs.push(Input {
+                kind,
+                content,
+                tag: args
+                    .tag
+                    .clone()
+                    .unwrap_or_else(|| relative.display().to_string()),
+                source: file.display().to_string(),
+            });
+        }
+        if skipped > 0 {
+            note!("Skipped {skipped} files that are not text, PDF, HTML or .docx.");
+        }
+        return Ok(inputs);
+    }
+
+    let Some((kind, content)) = convert(&path)? else {
+        return Err(KinicError::Validation(format!(
+            "{} is not a text, PDF, HTML or .docx file",
+            path.display()
+        ))
+        .into());
+    };
+    let tag = match &args.tag {
+        Some(tag) => tag.clone(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .filter(|stem| !stem.is_empty())
+            .with_context(|| format!("cannot derive a tag from {}; pass --tag", path.display()))?,
+    };
+    Ok(vec![Input {
+        kind,
+        content,
+        tag,
+        source: path.display().to_string(),
+    }])
+}
+
+/// Every file under `dir`, skipping hidden files and folders.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The kind and text of the file at `path`, or `None` when it is none of
+/// the kinds `add` converts.
+fn convert(path: &Path) -> Result<Option<(Kind, String)>> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let Some(kind) = sniff(path, &bytes) else {
+        return Ok(None);
+    };
+    let content = match kind {
+        Kind::Pdf => pdf_to_markdown(path)?,
+        Kind::Docx => docx_to_text(&bytes, &path.display().to_string())?,
+        Kind::Html | Kind::Text => {
+            let Ok(text) = String::from_utf8(bytes) else {
+                return Ok(None);
+            };
+            if kind == Kind::Html {
+                html_to_text(&text)
+            } else {
+                text
+            }
+        }
+    };
+    Ok(Some((kind, content)))
+}
+
+/// The kind of a file, from its first bytes and, for the formats that are
+/// not recognisable from them alone, its extension.
+fn sniff(path: &Path, bytes: &[u8]) -> Option<Kind> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    if bytes.starts_with(b"%PDF-") {
+        return Some(Kind::Pdf);
+    }
+    // A .docx is a zip archive; other zip-based formats are not converted.
+    if bytes.starts_with(b"PK\x03\x04") {
+        return (extension.as_deref() == Some("docx")).then_some(Kind::Docx);
+    }
+    if guard::looks_binary(bytes) {
+        return None;
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).to_ascii_lowercase();
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    if matches!(extension.as_deref(), Some("html" | "htm"))
+        || head.starts_with("<!doctype html")
+        || head.starts_with("<html")
+    {
+        return Some(Kind::Html);
+    }
+    Some(Kind::Text)
+}
+
+/// Fetches `url`, up to [`MAX_FETCH_BYTES`], and converts it by its content
+/// type. PDFs go through a temporary file for `pdftotext`.
+async fn fetch(url: &str) -> Result<(Kind, String)> {
+    let mut response = http_client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {url}"))?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let too_large = || {
+        KinicError::Validation(format!(
+            "{url} is larger than {MAX_FETCH_BYTES} bytes; download it and add the file instead"
+        ))
+    };
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_FETCH_BYTES as u64)
+    {
+        return Err(too_large().into());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("Failed to read {url}"))?
+    {
+        if bytes.len() + chunk.len() > MAX_FETCH_BYTES {
+            return Err(too_large().into());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let kind = if content_type.contains("pdf") || bytes.starts_with(b"%PDF-") {
+        Kind::Pdf
+    } else if content_type.contains("wordprocessingml") {
+        Kind::Docx
+    } else if content_type.contains("html") {
+        Kind::Html
+    } else if content_type.starts_with("text/") || content_type.is_empty() {
+        Kind::Text
+    } else {
+        return Err(KinicError::Validation(format!(
+            "{url} returned {content_type}, which add cannot convert"
+        ))
+        .into());
+    };
+    let content = match kind {
+        Kind::Pdf => {
+            // Removed when dropped; the random name cannot be raced.
+            let mut file = tempfile::Builder::new()
+                .prefix("kinic-add-")
+                .suffix(".pdf")
+                .tempfile()
+                .context("Failed to create a temporary file")?;
+            file.write_all(&bytes)
+                .and_then(|()| file.flush())
+                .context("Failed to write a temporary file")?;
+            pdf_to_markdown(file.path())?
+        }
+        Kind::Docx => docx_to_text(&bytes, url)?,
+        Kind::Html => html_to_text(&String::from_utf8_lossy(&bytes)),
+        Kind::Text => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    Ok((kind, content))
+}
+
+/// The text of a `.docx` document read from `bytes`: paragraphs separated
+/// by blank lines, headings marked with `#`, and tables as markdown pipe
+/// tables. `source` names the document in errors.
+fn docx_to_text(bytes: &[u8], source: &str) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .with_context(|| format!("{source} is not a valid .docx archive"))?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .with_context(|| format!("{source} has no word/document.xml"))?;
+    let mut xml = String::new();
+    entry
+        .take(MAX_DOCX_XML_BYTES + 1)
+        .read_to_string(&mut xml)
+        .with_context(|| format!("Failed to read word/document.xml of {source}"))?;
+    if xml.len() as u64 > MAX_DOCX_XML_BYTES {
+        return Err(KinicError::Validation(format!(
+            "word/document.xml of {source} is larger than {MAX_DOCX_XML_BYTES} bytes"
+        ))
+        .into());
+    }
+    let document = roxmltree::Document::parse(&xml)
+        .with_context(|| format!("Failed to parse word/document.xml of {source}"))?;
+    let Some(body) = document
+        .root_element()
+        .children()
+        .find(|node| node.tag_name().name() == "body")
+    else {
+        return Ok(String::new());
+    };
+    let mut blocks = Vec::new();
+    for node in body.children() {
+        match node.tag_name().name() {
+            "p" => {
+                let text = paragraph_text(node);
+                if !text.trim().is_empty() {
+                    blocks.push(format!("{}{text}", heading_prefix(node)));
+                }
+            }
+            "tbl" => {
+                let mut rows = node
+                    .children()
+                    .filter(|row| row.tag_name().name() == "tr")
+                    .map(|row| {
+                        row.children()
+                            .filter(|cell| cell.tag_name().name() == "tc")
+                            .map(|cell| {
+                                cell.descendants()
+                                    .filter(|p| p.tag_name().name() == "p")
+                                    .map(paragraph_text)
+                                    .collect::<Vec<_>>()
+                                    .join(" ")
+                                    .trim()
+                                    .to_string()
+                            })
+                            .collect::<Vec<String>>()
+                    });
+                if let Some(headers) = rows.next() {
+                    blocks.push(Table::new(headers, rows.collect()).to_markdown());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(blocks.join("\n\n"))
+}
+
+/// The runs of a `<w:p>`, with tabs and line breaks kept.
+fn paragraph_text(paragraph: roxmltree::Node) -> String {
+    let mut text = String::new();
+    for node in paragraph.descendants() {
+        match node.tag_name().name() {
+            "t" => text.push_str(node.text().unwrap_or_default()),
+            "tab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// `# ` to `###### ` for a paragraph styled `Heading1` to `Heading6`.
+fn heading_prefix(paragraph: roxmltree::Node) -> String {
+    let level = paragraph
+        .descendants()
+        .find(|node| node.tag_name().name() == "pStyle")
+        .and_then(|style| {
+            style
+                .attributes()
+                .find(|attribute| attribute.name() == "val")
+        })
+        .and_then(|value| value.value().strip_prefix("Heading")?.parse::<usize>().ok())
+        .filter(|level| (1..=6).contains(level));
+    match level {
+        Some(level) => format!("{} ", "#".repeat(level)),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    fn docx(document_xml: &str) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        writer.start_file("word/document.xml", options).unwrap();
+        writer.write_all(document_xml.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn docx_headings_paragraphs_and_tables() {
+        let xml = r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Budget</w:t></w:r></w:p>
+            <w:p><w:r><w:t>First</w:t></w:r><w:r><w:tab/><w:t>line</w:t></w:r></w:p>
+            <w:p></w:p>
+            <w:tbl>
+              <w:tr><w:tc><w:p><w:r><w:t>Item</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Cost</w:t></w:r></w:p></w:tc></w:tr>
+              <w:tr><w:tc><w:p><w:r><w:t>Disk</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>40</w:t></w:r></w:p></w:tc></w:tr>
+            </w:tbl>
+        </w:body></w:document>"#;
+        let text = docx_to_text(&docx(xml), "test.docx").unwrap();
+        let blocks: Vec<&str> = text.split("\n\n").collect();
+        assert_eq!(blocks[0], "## Budget");
+        assert_eq!(blocks[1], "First\tline");
+        assert!(blocks[2].contains("Item") && blocks[2].contains("Disk"));
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn docx_without_document_xml() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("other.xml", SimpleFileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let err = docx_to_text(&bytes, "empty.docx").unwrap_err();
+        assert!(err.to_string().contains("no word/document.xml"));
+        assert!(docx_to_text(b"not a zip", "bad.docx").is_err());
+    }
+}
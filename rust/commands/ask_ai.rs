@@ -1,93 +1,589 @@
-use std::cmp::Ordering;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use ic_agent::export::Principal;
-use reqwest::Client;
 use tracing::info;
 
 use crate::{
-    agent::AgentFactory,
     cli::AskAiArgs,
-    clients::memory::MemoryClient,
-    embedding::{embedding_base_url, fetch_embedding},
+    commands::history::format_timestamp,
+    embedding::{embedding_base_url, http_client, send_limited},
+    jobs::LlmSettings,
+    output::note,
+    payload::ChunkPayload,
+    rag::{MAX_QUERY_LEN, PreparedPrompt, clip, prepare_prompt, source_url, strip_tags},
+    sse::EventStream,
+    tokens,
 };
 
 use super::CommandContext;
 
-const MAX_QUERY_LEN: usize = 150;
-const MAX_RESULTS: usize = 5;
-const MAX_HITS_PER_DOC: usize = 6;
-const MAX_HIT_LEN: usize = 600;
-const MAX_FULL_LEN: usize = 4096;
+const MAX_SUMMARY_INPUT_LEN: usize = 8000;
+/// The whole answer when `--strict` refuses.
+const REFUSAL: &str = "I don't know.";
+/// Best retrieval score `--strict` requires when `--min-score` is not given.
+pub const DEFAULT_MIN_SCORE: f32 = 0.5;
+/// Share of unsupported answer sentences above which `--verify` warns that
+/// the whole answer has low confidence.
+const LOW_CONFIDENCE_SHARE: f32 = 0.5;
+/// Sub-queries asked for by `--multi-query`.
+const MAX_SUB_QUERIES: usize = 4;
 const CHAT_PATH: &str = "/chat";
 
-pub struct AskAiResult {
-    pub prompt: String,
-    pub response: String,
-    pub context_count: usize,
-    pub top_k_used: usize,
-}
-
 pub async fn handle(args: AskAiArgs, ctx: &CommandContext) -> Result<()> {
     let memory = Principal::from_text(&args.memory_id)
         .context("Failed to parse canister id for ask-ai command")?;
-    let result = ask_ai_flow(&ctx.agent_factory, &memory, &args.query, args.top_k, "en").await?;
+    // Flags win over the selected profile, which wins over `[llm]`.
+    let settings = LlmSettings {
+        model: args.model.clone(),
+        temperature: args.temperature,
+        max_tokens: args.max_tokens,
+        system_prompt: args.system_prompt.clone(),
+    }
+    .or(&LlmSettings::configured(args.profile.as_deref())?);
+    let sub_queries = if args.multi_query {
+        match decompose_query(&args.query, &settings).await {
+            Ok(sub_queries) => {
+                note!("Searching for {} sub-queries:", sub_queries.len());
+                for sub_query in &sub_queries {
+                    note!("- {sub_query}");
+                }
+                sub_queries
+            }
+            Err(err) => {
+                eprintln!("warning: could not split the question, searching it as is: {err:#}");
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let agent = ctx.agent().await?;
+    let prepared = prepare_prompt(
+        agent,
+        &memory,
+        &args.query,
+        &sub_queries,
+        args.top_k,
+        "en",
+        args.context_tokens,
+    )
+    .await?;
 
     info!(
         canister_id = %memory,
         query = %args.query,
-        result_count = result.context_count,
+        result_count = prepared.results.len(),
         "ask-ai search completed"
     );
 
-    println!("ask-ai for \"{}\":", args.query);
-    if result.context_count == 0 {
-        println!("- No context found to answer the query.");
-    } else {
-        println!(
-            "- Generated prompt for LLM (showing top {}).",
-            result.top_k_used
-        );
-        println!("{}", result.prompt);
+    if args.show_prompt && !prepared.results.is_empty() {
+        print_debug(&args.query, &prepared);
     }
-    println!("\nLLM response:\n{}", result.response);
+    if args.strict {
+        let best = prepared
+            .results
+            .iter()
+            .map(|(score, _)| *score)
+            .fold(f32::NEG_INFINITY, f32::max);
+        if best < args.min_score {
+            if prepared.results.is_empty() {
+                note!("ask-ai for \"{}\": nothing was retrieved.", args.query);
+            } else {
+                note!(
+                    "ask-ai for \"{}\": the best chunk scored {best:.4}, below --min-score {}.",
+                    args.query,
+                    args.min_score
+                );
+            }
+            println!("{REFUSAL}");
+            if let Some(path) = &args.report {
+                write_report(path, &memory, &args.query, &sub_queries, REFUSAL, &[], None)?;
+                note!("Report written to {}", path.display());
+            }
+            return Ok(());
+        }
+    }
+    print_prompt(
+        &args.query,
+        prepared.results.len(),
+        prepared.top_k_used,
+        &prepared.prompt,
+    );
+    if args.no_llm {
+        note!("\nStopped before calling the LLM (--no-llm).");
+        return Ok(());
+    }
+
+    note!("\nLLM response:");
+    let mut answer = AnswerStream::default();
+    let mut printed = String::new();
+    let outcome = {
+        let streaming = stream_llm(&prepared.prompt, &settings, |piece| {
+            let text = answer.push(piece);
+            if !text.is_empty() {
+                print!("{text}");
+                let _ = io::stdout().flush();
+                printed.push_str(&text);
+            }
+        });
+        tokio::select! {
+            result = streaming => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        }
+    };
+    // Whatever was held back is printed even on failure, ending the line.
+    let rest = answer.finish();
+    println!("{rest}");
+    printed.push_str(&rest);
+    match outcome {
+        Some(result) => result?,
+        None => bail!("interrupted; the answer above is incomplete"),
+    };
 
+    let mut checked = None;
+    if args.verify && !prepared.results.is_empty() {
+        match verify_answer(&printed, &prepared.context, &settings).await {
+            Ok(sentences) => {
+                report_verification(&sentences);
+                checked = Some(sentences);
+            }
+            Err(err) => eprintln!("warning: could not verify the answer: {err:#}"),
+        }
+    }
+    if let Some(path) = &args.report {
+        write_report(
+            path,
+            &memory,
+            &args.query,
+            &sub_queries,
+            &printed,
+            &prepared.results[..prepared.top_k_used],
+            checked.as_deref(),
+        )?;
+        note!("Report written to {}", path.display());
+    }
     Ok(())
 }
 
-pub async fn ask_ai_flow(
-    agent_factory: &AgentFactory,
-    memory_id: &Principal,
+/// `--report`: the question, the answer and the chunks it was based on, as
+/// a markdown document.
+fn write_report(
+    path: &Path,
+    memory: &Principal,
     query: &str,
-    top_k: usize,
-    language: &str,
-) -> Result<AskAiResult> {
-    let agent = agent_factory.build().await?;
-    let client = MemoryClient::new(agent, *memory_id);
-
-    let embedding = fetch_embedding(query).await?;
-    let mut results = client.search(embedding).await?;
-
-    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
-
-    let limit = top_k.max(1);
-    let prompt = build_prompt(query, &results, limit, language);
-    let llm_response = call_llm(&prompt).await?;
-
-    Ok(AskAiResult {
-        prompt,
-        response: llm_response,
-        context_count: results.len(),
-        top_k_used: limit.min(results.len()),
-    })
+    sub_queries: &[String],
+    answer: &str,
+    cited: &[(f32, String)],
+    checked: Option<&[(String, bool)]>,
+) -> Result<()> {
+    let asked_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or_else(
+        |_| "unknown".to_string(),
+        |now| format_timestamp(now.as_nanos() as u64),
+    );
+    let mut report = format!(
+        "# {}\n\n- Memory: `{memory}`\n- Asked: {asked_at} UTC\n",
+        query.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    if !sub_queries.is_empty() {
+        report.push_str("- Sub-queries:\n");
+        for sub_query in sub_queries {
+            report.push_str(&format!("  - {sub_query}\n"));
+        }
+    }
+
+    report.push_str(&format!("\n## Answer\n\n{}\n", answer.trim()));
+    if let Some(checked) = checked {
+        let unsupported: Vec<&str> = checked
+            .iter()
+            .filter(|(_, supported)| !supported)
+            .map(|(sentence, _)| sentence.as_str())
+            .collect();
+        report.push_str("\n## Verification\n\n");
+        if unsupported.is_empty() {
+            report.push_str(&format!(
+                "All {} sentences are supported by the sources below.\n",
+                checked.len()
+            ));
+        } else {
+            report.push_str(&format!(
+                "{} of {} sentences are not supported by the sources below:\n\n",
+                unsupported.len(),
+                checked.len()
+            ));
+            for sentence in unsupported {
+                report.push_str(&format!("- {sentence}\n"));
+            }
+        }
+    }
+
+    if !cited.is_empty() {
+        report.push_str("\n## Sources\n");
+    }
+    for (rank, (score, text)) in cited.iter().enumerate() {
+        let (heading, content) = match ChunkPayload::parse(text) {
+            Some(chunk) => {
+                let mut location = Vec::new();
+                if let Some(page) = chunk.metadata.page {
+                    location.push(format!("page {page}"));
+                }
+                if let Some(index) = chunk.metadata.chunk_index {
+                    location.push(format!("chunk {index}"));
+                }
+                let mut heading = format!("`{}`", chunk.tag);
+                if let Some(source) = &chunk.metadata.source {
+                    heading.push_str(&format!(" · {source}"));
+                }
+                if !location.is_empty() {
+                    heading.push_str(&format!(" ({})", location.join(", ")));
+                }
+                (heading, chunk.sentence)
+            }
+            None => ("raw payload".to_string(), text.clone()),
+        };
+        report.push_str(&format!(
+            "\n### {}. {heading}\n\nScore: {score:.4}\n\n",
+            rank + 1
+        ));
+        for line in content.trim().lines() {
+            if line.trim().is_empty() {
+                report.push_str(">\n");
+            } else {
+                report.push_str(&format!("> {line}\n"));
+            }
+        }
+    }
+
+    fs::write(path, report).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Asks the LLM which sentences of `answer` the documents in `context`
+/// support. Sentences it does not mention count as unsupported.
+async fn verify_answer(
+    answer: &str,
+    context: &str,
+    settings: &LlmSettings,
+) -> Result<Vec<(String, bool)>> {
+    let sentences = split_sentences(answer);
+    if sentences.is_empty() {
+        return Ok(Vec::new());
+    }
+    let numbered = sentences
+        .iter()
+        .enumerate()
+        .map(|(i, sentence)| format!("{}. {}", i + 1, strip_tags(sentence)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let prompt = format!(
+        r#"Check each numbered sentence against the documents and decide whether the documents support it.
+
+# Instructions
+- Write one line per sentence within the <answer>...</answer> tag: its number, a colon, then SUPPORTED or UNSUPPORTED, e.g. "1: SUPPORTED".
+- A sentence is SUPPORTED only if the documents state or directly imply it; general knowledge does not count.
+- A sentence saying the documents do not contain something is SUPPORTED when that is true.
+
+<docs>
+{context}
+</docs>
+
+<sentences>
+{numbered}
+</sentences>"#
+    );
+    let verdicts = call_llm(&prompt, settings).await?;
+    let mut supported = vec![false; sentences.len()];
+    for line in verdicts.lines() {
+        let Some((number, verdict)) = line.split_once(':') else {
+            continue;
+        };
+        let Ok(number) = number.trim().trim_start_matches('#').parse::<usize>() else {
+            continue;
+        };
+        let verdict = verdict.trim().to_ascii_uppercase();
+        if let Some(slot) = number.checked_sub(1).and_then(|i| supported.get_mut(i)) {
+            *slot = verdict.starts_with("SUPPORTED");
+        }
+    }
+    Ok(sentences.into_iter().zip(supported).collect())
+}
+
+fn report_verification(checked: &[(String, bool)]) {
+    let unsupported: Vec<&str> = checked
+        .iter()
+        .filter(|(_, supported)| !supported)
+        .map(|(sentence, _)| sentence.as_str())
+        .collect();
+    if unsupported.is_empty() {
+        note!(
+            "\nVerified: all {} sentences are supported by the retrieved documents.",
+            checked.len()
+        );
+        return;
+    }
+    eprintln!(
+        "\nwarning: {} of {} sentences are not supported by the retrieved documents:",
+        unsupported.len(),
+        checked.len()
+    );
+    for sentence in &unsupported {
+        eprintln!("  - {sentence}");
+    }
+    if unsupported.len() as f32 > checked.len() as f32 * LOW_CONFIDENCE_SHARE {
+        eprintln!("warning: low confidence; most of the answer is not grounded in the memory");
+    }
+}
+
+/// Splits text at sentence-ending punctuation followed by whitespace (or,
+/// for CJK punctuation, directly) and at line breaks.
+pub(crate) fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            sentences.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(c);
+        let ends = match c {
+            '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|next| next.is_whitespace()),
+            _ => false,
+        };
+        if ends {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    sentences.push(current);
+    sentences
+        .into_iter()
+        .map(|sentence| sentence.trim().to_string())
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+fn print_prompt(query: &str, context_count: usize, top_k_used: usize, prompt: &str) {
+    note!("ask-ai for \"{query}\":");
+    if context_count == 0 {
+        note!("- No context found to answer the query.");
+    } else {
+        note!("- Generated prompt for LLM (showing top {top_k_used}).");
+        println!("{prompt}");
+    }
 }
 
-async fn call_llm(prompt: &str) -> Result<String> {
+/// Splits a question into 2 to `MAX_SUB_QUERIES` search queries that each
+/// cover one part of it, for `--multi-query`. A simple question may come
+/// back as a single query.
+async fn decompose_query(query: &str, settings: &LlmSettings) -> Result<Vec<String>> {
+    let prompt = format!(
+        r#"Break the following question into separate search queries, one for each piece of information needed to answer it.
+
+# Instructions
+- Write the queries within the <answer>...</answer> tag, one per line.
+- Give between 2 and {MAX_SUB_QUERIES} short, self-contained queries; give one if the question asks for a single thing.
+- Use the language of the question.
+
+<question>
+{question}
+</question>"#,
+        question = strip_tags(&clip(query, MAX_QUERY_LEN)),
+    );
+    let answer = call_llm(&prompt, settings).await?;
+    let mut sub_queries: Vec<String> = Vec::new();
+    for line in answer.lines() {
+        let sub_query = strip_list_marker(line.trim());
+        let known = sub_queries
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(sub_query));
+        if !sub_query.is_empty() && !known && !sub_query.eq_ignore_ascii_case(query.trim()) {
+            sub_queries.push(sub_query.to_string());
+        }
+    }
+    if sub_queries.is_empty() {
+        bail!("the chat endpoint returned no sub-queries");
+    }
+    sub_queries.truncate(MAX_SUB_QUERIES);
+    Ok(sub_queries)
+}
+
+/// `line` without a leading `-`/`*` bullet or `1.`/`1)` number.
+fn strip_list_marker(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix(['-', '*']) {
+        return rest.trim_start();
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match line[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// `--show-prompt`: the chunks that made it into the prompt, with scores,
+/// and token counts. Written to stderr, ahead of the prompt itself.
+fn print_debug(query: &str, prepared: &PreparedPrompt) {
+    eprintln!(
+        "Retrieved {} chunks; {} used in the prompt:",
+        prepared.results.len(),
+        prepared.top_k_used
+    );
+    for (rank, ((score, text), chunk_tokens)) in prepared
+        .results
+        .iter()
+        .zip(&prepared.chunk_tokens)
+        .enumerate()
+    {
+        let chunk = ChunkPayload::parse(text);
+        let content = chunk
+            .as_ref()
+            .map_or(text.as_str(), |chunk| chunk.sentence.as_str());
+        let origin = chunk
+            .as_ref()
+            .map(|chunk| {
+                let source = source_url(&chunk.metadata)
+                    .map(|source| format!(" source={source}"))
+                    .unwrap_or_default();
+                format!("tag={}{source}", chunk.tag)
+            })
+            .unwrap_or_else(|| "(raw payload)".to_string());
+        eprintln!(
+            "  {}. [{score:.4}] {origin} (~{chunk_tokens} tokens)\n     {}",
+            rank + 1,
+            clip(
+                &content.split_whitespace().collect::<Vec<_>>().join(" "),
+                100
+            )
+        );
+    }
+    eprintln!(
+        "Tokens: query ~{}, chunks ~{}, full prompt ~{}",
+        tokens::count(query),
+        prepared.chunk_tokens.iter().sum::<usize>(),
+        tokens::count(&prepared.prompt)
+    );
+    eprintln!();
+}
+
+/// Short LLM summary of a whole document, for `insert --summarize`.
+pub(crate) async fn summarize_document(text: &str) -> Result<String> {
+    let prompt = format!(
+        r#"Summarize the following document so a reader can tell what it covers at a glance.
+
+# Instructions
+- Write your summary within the <answer>...</answer> tag.
+- Use at most 3 sentences and 60 words.
+- Answer in the language of the document.
+
+<document>
+{document}
+</document>"#,
+        document = strip_tags(&clip(text, MAX_SUMMARY_INPUT_LEN)),
+    );
+    let summary = call_llm(&prompt, &LlmSettings::configured(None)?).await?;
+    if summary.is_empty() {
+        anyhow::bail!("chat endpoint returned an empty summary");
+    }
+    Ok(summary)
+}
+
+/// Up to `limit` short topic keywords for a document, for
+/// `insert --auto-tag llm`.
+pub(crate) async fn suggest_tags(text: &str, limit: usize) -> Result<Vec<String>> {
+    let prompt = format!(
+        r#"List the main topics of the following document as search keywords.
+
+# Instructions
+- Write the keywords within the <answer>...</answer> tag, separated by commas.
+- Give at most {limit} keywords of one to three words each, in lowercase.
+- Use the language of the document.
+
+<document>
+{document}
+</document>"#,
+        document = strip_tags(&clip(text, MAX_SUMMARY_INPUT_LEN)),
+    );
+    let answer = call_llm(&prompt, &LlmSettings::configured(None)?).await?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in answer.split([',', '\n']) {
+        let tag = tag
+            .trim()
+            .trim_start_matches(['-', '*'])
+            .trim()
+            .to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(limit);
+    Ok(tags)
+}
+
+/// Person names and postal addresses in `text`, for `--redact-ner`, as
+/// `("name" | "address", entity)` pairs in the exact spelling of the text.
+pub(crate) async fn find_personal_entities(text: &str) -> Result<Vec<(&'static str, String)>> {
+    let prompt = format!(
+        r#"Find the personal data in the following text.
+
+# Instructions
+- Write your findings within the <answer>...</answer> tag, one per line.
+- Write each person's name as `NAME: <name>` and each postal address as `ADDRESS: <address>`.
+- Copy every name and address exactly as it appears in the text, on one line.
+- Do not list companies, products, places without a street address, or fictional characters.
+- Write `NONE` if there are no names or addresses.
+
+<text>
+{text}
+</text>"#,
+        text = strip_tags(text),
+    );
+    let answer = call_llm(&prompt, &LlmSettings::configured(None)?).await?;
+    let mut entities: Vec<(&'static str, String)> = Vec::new();
+    for line in answer.lines() {
+        let line = line.trim().trim_start_matches(['-', '*']).trim();
+        let entity = match line.split_once(':') {
+            Some((label, value)) if label.trim().eq_ignore_ascii_case("name") => ("name", value),
+            Some((label, value)) if label.trim().eq_ignore_ascii_case("address") => {
+                ("address", value)
+            }
+            _ => continue,
+        };
+        let entity = (entity.0, entity.1.trim().trim_matches('`').to_string());
+        if !entity.1.is_empty() && !entities.contains(&entity) {
+            entities.push(entity);
+        }
+    }
+    Ok(entities)
+}
+
+/// Asks the chat endpoint and returns the `<answer>` part of the reply.
+pub(crate) async fn call_llm(prompt: &str, settings: &LlmSettings) -> Result<String> {
+    let raw = stream_llm(prompt, settings, |_| {}).await?;
+    Ok(extract_answer(&raw))
+}
+
+/// Sends `prompt` to the chat endpoint and returns the raw reply. A
+/// `text/event-stream` reply is read event by event and every piece of
+/// content is passed to `on_text` as it arrives; any other reply is read
+/// whole and passed to `on_text` once.
+async fn stream_llm(
+    prompt: &str,
+    settings: &LlmSettings,
+    mut on_text: impl FnMut(&str),
+) -> Result<String> {
     let url = format!("{}{}", embedding_base_url(), CHAT_PATH);
-    let response = Client::new()
-        .post(url)
-        .json(&ChatRequest { message: prompt })
-        .send()
+    let request = ChatRequest {
+        message: prompt,
+        model: settings.model.as_deref(),
+        temperature: settings.temperature,
+        max_tokens: settings.max_tokens,
+        system_prompt: settings.system_prompt.as_deref(),
+    };
+    let response = send_limited(http_client()?.post(url).json(&request))
         .await
         .context("Failed to call chat endpoint")?;
 
@@ -97,6 +593,32 @@ async fn call_llm(prompt: &str) -> Result<String> {
         anyhow::bail!("chat endpoint returned {status}: {body}");
     }
 
+    let is_event_stream = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    if is_event_stream {
+        let mut events = EventStream::new(response);
+        let mut acc = String::new();
+        while let Some(event) = events.next().await? {
+            if event.event.as_deref() == Some("error") {
+                anyhow::bail!("chat endpoint reported an error: {}", event.data);
+            }
+            if event.data.trim() == "[DONE]" {
+                break;
+            }
+            if let Ok(ChatChunk {
+                content: Some(content),
+            }) = serde_json::from_str::<ChatChunk>(&event.data)
+            {
+                on_text(&content);
+                acc.push_str(&content);
+            }
+        }
+        return Ok(acc);
+    }
+
     let body = response
         .text()
         .await
@@ -122,27 +644,96 @@ async fn call_llm(prompt: &str) -> Result<String> {
         acc = body;
     }
 
-    Ok(extract_answer(&acc))
+    on_text(&acc);
+    Ok(acc)
 }
 
-#[derive(Clone, Debug)]
-struct SearchHit {
-    index: usize,
-    score: f32,
-    content: String,
+/// Follows a streamed reply and releases the `<answer>` part as it arrives,
+/// trimmed the way [`extract_answer`] trims a complete reply. Text that
+/// could be the start of `</answer>`, and trailing whitespace, is held back
+/// until more arrives.
+#[derive(Default)]
+struct AnswerStream {
+    raw: String,
+    /// End of the answer text released so far, as a byte offset in `raw`.
+    released: usize,
 }
 
-#[derive(Clone, Debug)]
-struct SearchResult {
-    url: String,
-    title: String,
-    score: f32,
-    hits: Vec<SearchHit>,
+impl AnswerStream {
+    const OPEN: &'static str = "<answer>";
+    const CLOSE: &'static str = "</answer>";
+
+    /// Appends `piece` and returns the answer text it made printable.
+    fn push(&mut self, piece: &str) -> String {
+        self.raw.push_str(piece);
+        // ASCII lowercasing keeps byte offsets valid for `raw`.
+        let lower = self.raw.to_ascii_lowercase();
+        let Some(open) = lower.find(Self::OPEN) else {
+            return String::new();
+        };
+        let start = open + Self::OPEN.len();
+        let end = match lower[start..].find(Self::CLOSE) {
+            Some(close) => start + close,
+            None => {
+                let partial = (1..Self::CLOSE.len())
+                    .rev()
+                    .find(|len| lower.ends_with(&Self::CLOSE[..*len]))
+                    .unwrap_or(0);
+                lower.len() - partial
+            }
+        };
+
+        let mut from = self.released.max(start);
+        if from >= end {
+            return String::new();
+        }
+        if from == start {
+            let body = &self.raw[from..end];
+            from += body.len() - body.trim_start().len();
+        }
+        let until = from + self.raw[from..end].trim_end().len();
+        if until <= from {
+            return String::new();
+        }
+        self.released = until;
+        self.raw[from..until].to_string()
+    }
+
+    /// The rest of the answer once the reply is complete. A reply without an
+    /// `<answer>` tag is returned whole.
+    fn finish(&self) -> String {
+        let lower = self.raw.to_ascii_lowercase();
+        let Some(open) = lower.find(Self::OPEN) else {
+            return extract_answer(&self.raw);
+        };
+        let start = open + Self::OPEN.len();
+        let end = lower[start..]
+            .find(Self::CLOSE)
+            .map_or(self.raw.len(), |close| start + close);
+        let from = self.released.max(start);
+        if from >= end {
+            return String::new();
+        }
+        let rest = &self.raw[from..end];
+        if self.released <= start {
+            rest.trim().to_string()
+        } else {
+            rest.trim_end().to_string()
+        }
+    }
 }
 
 #[derive(serde::Serialize)]
 struct ChatRequest<'a> {
     message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_prompt: Option<&'a str>,
 }
 
 #[derive(serde::Deserialize)]
@@ -167,150 +758,3 @@ fn extract_answer(text: &str) -> String {
         text.trim().to_string()
     }
 }
-
-fn build_prompt(
-    query: &str,
-    raw_results: &[(f32, String)],
-    top_k: usize,
-    language: &str,
-) -> String {
-    let clipped_query = clip(query, MAX_QUERY_LEN);
-
-    let docs: Vec<SearchResult> = raw_results
-        .iter()
-        .take(top_k.min(MAX_RESULTS))
-        .enumerate()
-        .map(|(i, (score, text))| SearchResult {
-            url: format!("memory://{}", i + 1),
-            title: clip(text, 80),
-            score: *score,
-            hits: vec![SearchHit {
-                index: 0,
-                score: *score,
-                content: text.clone(),
-            }],
-        })
-        .collect();
-
-    ask_ai_prompt(&clipped_query, &docs, language)
-}
-
-fn clip(s: &str, max: usize) -> String {
-    let clipped: String = s.chars().take(max).collect();
-    if s.chars().count() > max {
-        format!("{clipped}...")
-    } else {
-        clipped
-    }
-}
-
-fn strip_tags(s: &str) -> String {
-    s.replace("<thinking>", "")
-        .replace("</thinking>", "")
-        .replace("<answer>", "")
-        .replace("</answer>", "")
-        .replace("<THINKING>", "")
-        .replace("</THINKING>", "")
-        .replace("<ANSWER>", "")
-        .replace("</ANSWER>", "")
-}
-
-fn get_language_instruction(lang_code: &str) -> &'static str {
-    match lang_code {
-        "ja" => "日本語 (Japanese)",
-        "ko" => "한국어 (Korean)",
-        "zh" => "中文 (Chinese)",
-        "es" => "Español (Spanish)",
-        "fr" => "Français (French)",
-        "de" => "Deutsch (German)",
-        "it" => "Italiano (Italian)",
-        "pt" => "Português (Portuguese)",
-        "ru" => "Русский (Russian)",
-        _ => "English",
-    }
-}
-
-fn ask_ai_prompt(query: &str, results: &[SearchResult], language: &str) -> String {
-    let language_instruction = get_language_instruction(language);
-
-    let top_results = results.iter().take(MAX_RESULTS).collect::<Vec<_>>();
-
-    let formatted_docs = top_results
-        .iter()
-        .enumerate()
-        .map(|(i, r)| {
-            let hits_xml = r
-                .hits
-                .iter()
-                .take(MAX_HITS_PER_DOC)
-                .map(|h| {
-                    format!(
-                        "<hit index=\"{}\" score=\"{}\">\n{}\n</hit>",
-                        h.index,
-                        h.score,
-                        strip_tags(&clip(&h.content, MAX_HIT_LEN))
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            format!(
-                "<doc index=\"{index}\">\n<url>{url}</url>\n<title>{title}</title>\n<score>{score}</score>\n<hits>\n{hits}\n</hits>\n</doc>",
-                index = i + 1,
-                url = r.url,
-                title = strip_tags(&r.title),
-                score = r.score,
-                hits = if hits_xml.is_empty() { r#"<hit index="0">(no hits)</hit>"#.to_string() } else { hits_xml },
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n\n");
-
-    let docs_block = if formatted_docs.is_empty() {
-        r#"<doc index="1"><url></url><title></title><hits><hit index="0">(no hits)</hit></hits></doc>"#
-            .to_string()
-    } else {
-        formatted_docs
-    };
-
-    let full_document = strip_tags(&clip(
-        &top_results
-            .iter()
-            .flat_map(|r| r.hits.iter().take(MAX_HITS_PER_DOC))
-            .map(|h| h.content.as_str())
-            .collect::<Vec<_>>()
-            .join("\n"),
-        MAX_FULL_LEN,
-    ));
-
-    format!(
-        r#"You are an excellent AI assistant that summarizes the content of documents found as search results.
-Summarize the main points concisely, taking into account their relevance to the user's search query.
-
-# Instructions
-- Before responding, please describe your thinking process within the <thinking>...</thinking> tag (keep under 100 words).
-- After thinking, write your final summary within the <answer>...</answer> tag.
-- The summary should be objective and grounded in the documents.
-- Focus on information related to <user_query>, especially considering the content in <docs>.
-- Limit the final summary to 140 words or less.
-- Answer in {language_instruction} in <answer> tag. << IMPORTANT!!
-
-# Input
-
-<user_query>
-{query}
-</user_query>
-
-<docs>
-{docs}
-</docs>
-
-<full_document>
-{full_document}
-</full_document>"#,
-        docs = docs_block,
-        full_document = full_document,
-        language_instruction = language_instruction,
-        query = query,
-    )
-}
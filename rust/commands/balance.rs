@@ -6,7 +6,7 @@ use crate::{cli::BalanceArgs, ledger::fetch_balance};
 use super::CommandContext;
 
 pub async fn handle(_args: BalanceArgs, ctx: &CommandContext) -> Result<()> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let principal = agent
         .get_principal()
         .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::BenchArgs,
+    clients::memory::MemoryClient,
+    embedding::{embedding_base_url, fetch_embedding_uncached},
+    error::KinicError,
+    output::note,
+};
+
+use super::CommandContext;
+
+pub async fn handle(args: BenchArgs, ctx: &CommandContext) -> Result<()> {
+    let queries = read_queries(&args.queries)?;
+    let memory =
+        Principal::from_text(&args.memory_id).context("Failed to parse memory canister id")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    // Looked up once per client; keep it out of the first timed search.
+    client.dim().await?;
+
+    note!(
+        "Benchmarking {} queries x {} runs against {memory} (embedding API: {})",
+        queries.len(),
+        args.runs,
+        embedding_base_url()
+    );
+
+    let mut embedding_times = Vec::new();
+    let mut search_times = Vec::new();
+    let mut total_times = Vec::new();
+    for run in 1..=args.runs {
+        for query in &queries {
+            let started = Instant::now();
+            // Uncached, so every run pays for a real API call.
+            let embedding = fetch_embedding_uncached(query).await?;
+            let embedded = Instant::now();
+            client.search_top_k(embedding, args.top_k).await?;
+            let finished = Instant::now();
+
+            embedding_times.push(embedded - started);
+            search_times.push(finished - embedded);
+            total_times.push(finished - started);
+        }
+        note!("run {run}/{} done", args.runs);
+    }
+
+    info!(
+        canister_id = %memory,
+        samples = total_times.len(),
+        "bench completed"
+    );
+
+    println!(
+        "{:<12} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "STAGE", "SAMPLES", "MEAN_MS", "P50_MS", "P95_MS", "MAX_MS"
+    );
+    for (stage, times) in [
+        ("embedding", embedding_times),
+        ("search", search_times),
+        ("end-to-end", total_times),
+    ] {
+        let stats = Stats::new(times);
+        println!(
+            "{:<12} {:>7} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            stage,
+            stats.samples,
+            millis(stats.mean),
+            millis(stats.p50),
+            millis(stats.p95),
+            millis(stats.max)
+        );
+    }
+    Ok(())
+}
+
+fn read_queries(path: &Path) -> Result<Vec<String>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read queries from {}", path.display()))?;
+    let queries: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if queries.is_empty() {
+        return Err(
+            KinicError::Validation(format!("{} contains no queries", path.display())).into(),
+        );
+    }
+    Ok(queries)
+}
+
+struct Stats {
+    samples: usize,
+    mean: Duration,
+    p50: Duration,
+    p95: Duration,
+    max: Duration,
+}
+
+impl Stats {
+    /// Summarizes a non-empty list of timings; percentiles use the
+    /// nearest-rank method.
+    fn new(mut times: Vec<Duration>) -> Self {
+        times.sort();
+        let samples = times.len();
+        let total: Duration = times.iter().sum();
+        let percentile =
+            |p: f64| times[((p * samples as f64).ceil() as usize).clamp(1, samples) - 1];
+        Self {
+            samples,
+            mean: total / samples as u32,
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            max: times[samples - 1],
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
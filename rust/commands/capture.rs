@@ -0,0 +1,112 @@
+use std::{fs, process};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::CaptureArgs, clients::memory::MemoryClient, commands::insert::insert_document,
+    error::KinicError, guard, jobs, output::note, payload::InsertMetadata, redact::Redactor,
+};
+
+use super::CommandContext;
+
+#[cfg(windows)]
+const FALLBACK_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const FALLBACK_EDITOR: &str = "vi";
+
+pub async fn handle(args: CaptureArgs, ctx: &CommandContext) -> Result<()> {
+    let memory_id = memory_or_default(args.memory_id)?;
+    let memory =
+        Principal::from_text(&memory_id).context("Failed to parse canister id for capture")?;
+
+    let clipboard = read_clipboard();
+    let text = match (clipboard, args.edit) {
+        (Ok(text), false) => text,
+        (Ok(text), true) => edit(&text)?,
+        // An empty or non-text clipboard still lets the note be typed.
+        (Err(_), true) => edit("")?,
+        (Err(err), false) => {
+            return Err(KinicError::Validation(format!("nothing to capture: {err}")).into());
+        }
+    };
+    if text.trim().is_empty() {
+        note!("Nothing captured; the note is empty.");
+        return Ok(());
+    }
+
+    guard::check_secrets("the captured note", &text, args.allow_secrets)?;
+    let mut text = text;
+    if let Some(mut redactor) = Redactor::from_args(&args.redact) {
+        text = redactor.redact("the captured note", &text).await?;
+        redactor.write_report("capture")?;
+    }
+
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let metadata = InsertMetadata::new(&args.tag, Some("clipboard".to_string()), &args.meta)?;
+    let chunks = insert_document(&client, &text, &metadata, args.language.as_deref()).await?;
+
+    info!(canister_id = %memory, tag = %args.tag, chunks, "capture completed");
+    note!(
+        "Captured {} characters into {memory} [{}]",
+        text.chars().count(),
+        args.tag
+    );
+    Ok(())
+}
+
+/// `memory_id`, or `default_memory` from the config file when it is `None`.
+pub(crate) fn memory_or_default(memory_id: Option<String>) -> Result<String> {
+    match memory_id {
+        Some(id) => Ok(id),
+        None => jobs::load_config()?.default_memory.ok_or_else(|| {
+            KinicError::Validation(format!(
+                "no --memory-id given and no default_memory set in {}",
+                jobs::config_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            ))
+            .into()
+        }),
+    }
+}
+
+fn read_clipboard() -> Result<String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .context("the clipboard has no text")?;
+    if text.trim().is_empty() {
+        bail!("the clipboard is empty");
+    }
+    Ok(text)
+}
+
+/// Opens `$VISUAL`/`$EDITOR` on a temporary file holding `initial` and
+/// returns what was saved.
+fn edit(initial: &str) -> Result<String> {
+    let path = std::env::temp_dir().join(format!("kinic-capture-{}.md", process::id()));
+    fs::write(&path, initial).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| FALLBACK_EDITOR.to_string());
+    // Editors such as `code --wait` come with arguments.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(FALLBACK_EDITOR);
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor {editor}"));
+
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        bail!("editor {editor} exited with {status}; nothing captured");
+    }
+    edited.with_context(|| format!("Failed to read {}", path.display()))
+}
@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    cli::ClusterArgs, clients::memory::MemoryClient, error::KinicError, output::note,
+    payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+const MAX_ITERATIONS: usize = 100;
+
+#[derive(Serialize)]
+struct ClusterSummary {
+    cluster: usize,
+    size: usize,
+    /// Mean cosine similarity of the members to the centroid.
+    cohesion: f32,
+    representatives: Vec<String>,
+}
+
+pub async fn handle(args: ClusterArgs, ctx: &CommandContext) -> Result<()> {
+    let memory =
+        Principal::from_text(&args.memory_id).context("Failed to parse memory canister id")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let embeddings = client.tagged_embeddings(args.tag.clone()).await?;
+    if embeddings.is_empty() {
+        return Err(KinicError::Validation(format!(
+            "no embeddings stored under tag \"{}\"",
+            args.tag
+        ))
+        .into());
+    }
+
+    let points: Vec<Vec<f32>> = embeddings.into_iter().map(normalized).collect();
+    let k = args
+        .clusters
+        .map(|k| k as usize)
+        .unwrap_or_else(|| default_k(points.len()))
+        .min(points.len());
+    note!(
+        "Clustering {} embeddings tagged \"{}\" into {k} clusters",
+        points.len(),
+        args.tag
+    );
+    let clustering = kmeans(&points, k);
+
+    let mut summaries = Vec::new();
+    for (cluster, centroid) in clustering.centroids.iter().enumerate() {
+        let members: Vec<&Vec<f32>> = points
+            .iter()
+            .zip(&clustering.assignments)
+            .filter(|(_, assigned)| **assigned == cluster)
+            .map(|(point, _)| point)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let cohesion = members
+            .iter()
+            .map(|point| dot(point, centroid))
+            .sum::<f32>()
+            / members.len() as f32;
+        summaries.push(ClusterSummary {
+            cluster,
+            size: members.len(),
+            cohesion,
+            representatives: representatives(&client, centroid, &args.tag, args.samples).await?,
+        });
+    }
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.size));
+
+    info!(
+        canister_id = %memory,
+        tag = %args.tag,
+        clusters = summaries.len(),
+        iterations = clustering.iterations,
+        "cluster completed"
+    );
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+        return Ok(());
+    }
+    for summary in &summaries {
+        println!(
+            "cluster {}: {} chunks, cohesion {:.3}",
+            summary.cluster, summary.size, summary.cohesion
+        );
+        for sentence in &summary.representatives {
+            println!("  - {sentence}");
+        }
+    }
+    Ok(())
+}
+
+/// Sentences of the chunks under `tag` nearest to `centroid`. The canister
+/// ranks across all tags, so a few extra hits are fetched and filtered.
+async fn representatives(
+    client: &MemoryClient,
+    centroid: &[f32],
+    tag: &str,
+    samples: usize,
+) -> Result<Vec<String>> {
+    if samples == 0 {
+        return Ok(Vec::new());
+    }
+    let hits = client
+        .search_top_k(centroid.to_vec(), samples.saturating_mul(4))
+        .await?;
+    Ok(hits
+        .into_iter()
+        .filter_map(|hit| ChunkPayload::parse(&hit.text))
+        .filter(|payload| payload.tag == tag)
+        .map(|payload| payload.sentence)
+        .take(samples)
+        .collect())
+}
+
+/// Rule-of-thumb cluster count, `sqrt(n / 2)`, capped to keep the report
+/// readable.
+fn default_k(points: usize) -> usize {
+    ((points as f64 / 2.0).sqrt().round() as usize).clamp(1, 12)
+}
+
+struct Clustering {
+    centroids: Vec<Vec<f32>>,
+    assignments: Vec<usize>,
+    iterations: usize,
+}
+
+/// Spherical k-means over unit vectors: points join the centroid with the
+/// highest cosine similarity. Seeding is deterministic (farthest point
+/// first), so the same memory always clusters the same way.
+fn kmeans(points: &[Vec<f32>], k: usize) -> Clustering {
+    let mut centroids = seed(points, k);
+    let mut assignments = vec![usize::MAX; points.len()];
+    let mut iterations = 0;
+    while iterations < MAX_ITERATIONS {
+        iterations += 1;
+        let mut changed = false;
+        for (point, assigned) in points.iter().zip(assignments.iter_mut()) {
+            let nearest = nearest(point, &centroids);
+            if *assigned != nearest {
+                *assigned = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let dim = points[0].len();
+        let mut sums = vec![vec![0.0f32; dim]; k];
+        for (point, assigned) in points.iter().zip(&assignments) {
+            for (sum, value) in sums[*assigned].iter_mut().zip(point) {
+                *sum += value;
+            }
+        }
+        for (centroid, sum) in centroids.iter_mut().zip(sums) {
+            // An emptied cluster keeps its previous centroid.
+            if sum.iter().any(|value| *value != 0.0) {
+                *centroid = normalized(sum);
+            }
+        }
+    }
+    Clustering {
+        centroids,
+        assignments,
+        iterations,
+    }
+}
+
+/// Starts from the point closest to the overall mean, then repeatedly adds
+/// the point least similar to every centroid chosen so far.
+fn seed(points: &[Vec<f32>], k: usize) -> Vec<Vec<f32>> {
+    let dim = points[0].len();
+    let mut mean = vec![0.0f32; dim];
+    for point in points {
+        for (sum, value) in mean.iter_mut().zip(point) {
+            *sum += value;
+        }
+    }
+    let first = nearest(&normalized(mean), points);
+    let mut centroids = vec![points[first].clone()];
+    while centroids.len() < k {
+        let farthest = points
+            .iter()
+            .enumerate()
+            .map(|(index, point)| {
+                let best = centroids
+                    .iter()
+                    .map(|centroid| dot(point, centroid))
+                    .fold(f32::MIN, f32::max);
+                (index, best)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+            .unwrap_or(0);
+        centroids.push(points[farthest].clone());
+    }
+    centroids
+}
+
+fn nearest(point: &[f32], candidates: &[Vec<f32>]) -> usize {
+    candidates
+        .iter()
+        .enumerate()
+        .max_by(|a, b| dot(point, a.1).total_cmp(&dot(point, b.1)))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+    vector
+}
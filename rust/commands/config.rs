@@ -2,17 +2,17 @@ use anyhow::{Context, Result, bail};
 use ic_agent::export::Principal;
 use tracing::info;
 
-use crate::{cli::ConfigArgs, clients::memory::MemoryClient};
+use crate::{cli::ConfigArgs, clients::memory::MemoryClient, error::KinicError, output::note};
 
 use super::CommandContext;
 
-pub async fn handle(args: ConfigArgs, _ctx: &CommandContext) -> Result<()> {
+pub async fn handle(args: ConfigArgs, ctx: &CommandContext) -> Result<()> {
     let Some(values) = args.add_user else {
         bail!("config requires an operation; use --add-user <user_id> <role>");
     };
 
     let (principal, role) = parse_add_user(values)?;
-    let client = build_memory_client(&args.memory_id, _ctx).await?;
+    let client = build_memory_client(&args.memory_id, ctx).await?;
 
     client
         .add_new_user(principal, role.code())
@@ -25,28 +25,31 @@ pub async fn handle(args: ConfigArgs, _ctx: &CommandContext) -> Result<()> {
         "added user to memory canister"
     );
 
-    println!("User added to memory canister with role {role:?}");
+    note!("User added to memory canister with role {role:?}");
     Ok(())
 }
 
-#[derive(Debug)]
-enum Role {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
     Admin,
     Writer,
     Reader,
 }
 
 impl Role {
-    fn from_str(value: &str) -> Result<Self> {
+    pub(crate) fn from_str(value: &str) -> Result<Self> {
         match value.to_lowercase().as_str() {
             "admin" => Ok(Self::Admin),
             "writer" => Ok(Self::Writer),
             "reader" => Ok(Self::Reader),
-            _ => bail!("role must be one of: admin, writer, reader"),
+            _ => Err(KinicError::Validation(
+                "role must be one of: admin, writer, reader".to_string(),
+            )
+            .into()),
         }
     }
 
-    fn code(&self) -> u8 {
+    pub(crate) fn code(&self) -> u8 {
         match self {
             Role::Admin => 1,
             Role::Writer => 2,
@@ -65,25 +68,67 @@ fn parse_add_user(values: Vec<String>) -> Result<(Principal, Role)> {
         .context("missing user_id value for --add-user")?;
     let role = values.get(1).context("missing role value for --add-user")?;
 
-    let user = if user_id == "anonymous" {
-        Principal::anonymous()
-    } else {
-        Principal::from_text(user_id)
-            .with_context(|| format!("invalid principal text: {user_id}"))?
-    };
+    parse_grant(user_id, role)
+}
 
+/// The principal (or `anonymous`) and role of a grant. The anonymous
+/// principal stands for everyone, so it can only be made a reader, however
+/// it is written. Used by `config --add-user`, `share` and the bindings.
+pub(crate) fn parse_grant(user_id: &str, role: &str) -> Result<(Principal, Role)> {
+    let user = parse_user(user_id)?;
     let role = Role::from_str(role)?;
-
-    if matches!(role, Role::Admin) && user_id == "anonymous" {
-        bail!("cannot grant admin role to anonymous");
+    if user == Principal::anonymous() && role != Role::Reader {
+        return Err(KinicError::Validation(format!(
+            "cannot grant the {role:?} role to anonymous (everyone); only reader is allowed"
+        ))
+        .into());
     }
-
     Ok((user, role))
 }
 
+fn parse_user(user_id: &str) -> Result<Principal> {
+    if user_id == "anonymous" {
+        Ok(Principal::anonymous())
+    } else {
+        Principal::from_text(user_id).map_err(|err| {
+            KinicError::Validation(format!("invalid principal text {user_id}: {err}")).into()
+        })
+    }
+}
+
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for config command")?;
     Ok(MemoryClient::new(agent, memory))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymous_can_only_be_a_reader() {
+        for user in ["anonymous", "2vxsx-fae"] {
+            assert!(parse_grant(user, "admin").is_err(), "{user}");
+            assert!(parse_grant(user, "Writer").is_err(), "{user}");
+            let (principal, role) = parse_grant(user, "reader").unwrap();
+            assert_eq!(principal, Principal::anonymous());
+            assert_eq!(role, Role::Reader);
+        }
+    }
+
+    #[test]
+    fn named_principals_take_any_role() {
+        let user = "aaaaa-aa";
+        for (role, expected) in [
+            ("admin", Role::Admin),
+            ("writer", Role::Writer),
+            ("reader", Role::Reader),
+        ] {
+            assert_eq!(parse_grant(user, role).unwrap().1, expected);
+        }
+        assert!(parse_grant(user, "owner").is_err());
+        assert!(parse_grant("not a principal", "reader").is_err());
+    }
+}
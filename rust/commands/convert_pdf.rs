@@ -4,7 +4,7 @@ use anyhow::{Context, Result};
 use gag::Gag;
 use pdf_extract::extract_text;
 
-use crate::cli::ConvertPdfArgs;
+use crate::{cli::ConvertPdfArgs, tables};
 
 pub async fn handle(args: ConvertPdfArgs) -> Result<()> {
     let markdown = pdf_to_markdown(&args.file_path)?;
@@ -12,15 +12,19 @@ pub async fn handle(args: ConvertPdfArgs) -> Result<()> {
     Ok(())
 }
 
+/// The text of a PDF. With `pdftotext`, aligned columns are kept as pipe
+/// tables; the `pdf-extract` fallback has no layout to find them in.
 pub fn pdf_to_markdown(path: &Path) -> Result<String> {
-    extract_with_pdftotext(path).or_else(|primary_err| {
-        extract_with_pdf_extract_quiet(path).with_context(|| {
+    extract_with_pdftotext(path)
+        .map(|text| tables::layout_to_markdown(&text))
+        .or_else(|primary_err| {
+            extract_with_pdf_extract_quiet(path).with_context(|| {
             format!(
                 "Failed to extract text from {} using pdftotext ({primary_err}) and pdf-extract",
                 path.display()
             )
         })
-    })
+        })
 }
 
 fn extract_with_pdftotext(path: &Path) -> Result<String> {
@@ -1,44 +1,270 @@
-use anyhow::{Result, bail};
+use std::{
+    io::{self, IsTerminal},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
 use candid::Nat;
+use serde_json::json;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use tracing::info;
 
 use crate::{
     cli::CreateArgs,
-    clients::launcher::LauncherClient,
-    ledger::fetch_balance,
+    clients::launcher::{Deployment, LauncherClient, MemoryMetadata, State},
+    embedding::embedding_dim,
+    error::KinicError,
+    hooks,
+    ledger::{
+        TRANSFER_FEE_E8S, allowance_for, fetch_balance, format_e8s, nat_to_e8s, parse_kinic_amount,
+    },
+    output::{is_quiet, note},
+    vectors::VectorProcessing,
 };
 
-use super::CommandContext;
+use super::{
+    CommandContext,
+    prompt::{confirm, show_actor},
+};
 
-const TRANSFER_FEE_E8S: u128 = 100_000;
+/// How often `create` asks the launcher for the state of a running deploy.
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 pub async fn handle(args: CreateArgs, ctx: &CommandContext) -> Result<()> {
-    let agent = ctx.agent_factory.build().await?;
+    let dim = args.dim.unwrap_or_else(embedding_dim);
+    if dim == 0 {
+        return Err(KinicError::Validation("--dim must be greater than 0".to_string()).into());
+    }
+    let metadata = MemoryMetadata {
+        name: args.name.clone(),
+        description: args.description.clone(),
+        vectors: VectorProcessing {
+            truncate: args.truncate.then_some(dim),
+            normalize: args.normalize,
+            f16: args.f16,
+        },
+        created_at: Some(OffsetDateTime::now_utc().format(&Rfc3339)?),
+    };
+
+    let agent = ctx.agent().await?;
     let balance = fetch_balance(&agent).await?;
-    let client = LauncherClient::new(agent);
+    let client = LauncherClient::new(agent.clone());
     let price = client.fetch_deployment_price().await?;
     info!(%price, "fetched deployment price");
 
-    let required = required_balance(&price);
-    let balance_nat = Nat::from(balance);
-    if balance_nat < required {
-        bail!(
-            "Insufficient balance: need {} e8s (price + 2 * fee), have {} e8s",
-            required,
-            balance
+    let price_e8s = nat_to_e8s(&price)?;
+    let buffer = match &args.approve_buffer {
+        Some(amount) => parse_kinic_amount(amount)?,
+        None => 0,
+    };
+    let approval = allowance_for(price_e8s) + buffer;
+    let required = required_balance(price_e8s, buffer);
+
+    note!("Deploy memory \"{}\"", args.name);
+    show_actor(ctx, &agent, &format!("{} (launcher)", client.launcher_id()))?;
+    note!("- vector dimension: {dim}");
+    if !metadata.vectors.is_none() {
+        note!("- vector processing: {}", metadata.vectors);
+    }
+    note!("- price: {} KINIC", format_e8s(price_e8s));
+    note!("- fees: {} KINIC", format_e8s(required - price_e8s));
+    if buffer > 0 {
+        note!(
+            "- approval: {} KINIC (price + fee + {} KINIC buffer, revoked after deploy)",
+            format_e8s(approval),
+            format_e8s(buffer)
         );
+    } else {
+        note!("- approval: {} KINIC (price + fee)", format_e8s(approval));
+    }
+    note!("- balance: {} KINIC", format_e8s(balance));
+    if balance < required {
+        return Err(KinicError::Payment(format!(
+            "Insufficient balance: need {} KINIC (price + fees), have {} KINIC",
+            format_e8s(required),
+            format_e8s(balance)
+        ))
+        .into());
+    }
+    note!(
+        "- balance after deploy: {} KINIC",
+        format_e8s(balance - required)
+    );
+
+    if args.dry_run {
+        note!("Dry run: no approval or deployment was made.");
+        return Ok(());
+    }
+    if !args.yes && !confirm("Proceed?")? {
+        note!("Deployment cancelled.");
+        return Ok(());
+    }
+
+    hooks::before(
+        "create.before",
+        json!({"name": args.name, "description": args.description, "dim": dim}),
+    )
+    .await?;
+    let result = approve_and_deploy(&client, &args, &metadata, approval, dim).await;
+    let mut details = hooks::outcome(&result);
+    details["name"] = args.name.clone().into();
+    match &result {
+        Ok(Deployment::Finished(id)) => details["memory_id"] = id.clone().into(),
+        Ok(Deployment::Submitted(request_id)) => {
+            details["request_id"] = hex::encode(request_id.as_slice()).into();
+        }
+        Err(_) => {}
+    }
+    hooks::after("create.after", details).await;
+    result.map(|_| ())
+}
+
+/// Approves the launcher and deploys; with `--no-wait`, the deploy may
+/// still be running on return. A failed deploy revokes the approval.
+async fn approve_and_deploy(
+    client: &LauncherClient,
+    args: &CreateArgs,
+    metadata: &MemoryMetadata,
+    approval: u128,
+    dim: usize,
+) -> Result<Deployment> {
+    client.approve_launcher(&Nat::from(approval)).await?;
+    info!(
+        approval_e8s = approval,
+        "launcher approved to transfer tokens"
+    );
+
+    let deployment = deploy(client, args, metadata, dim).await;
+    if deployment.is_err() {
+        // Don't leave the launcher able to pull the price later.
+        warn_unrevoked(revoke_leftover(client).await);
     }
+    deployment
+}
 
-    client.approve_launcher(&price).await?;
-    info!("launcher approved to transfer tokens");
+async fn deploy(
+    client: &LauncherClient,
+    args: &CreateArgs,
+    metadata: &MemoryMetadata,
+    dim: usize,
+) -> Result<Deployment> {
+    if args.no_wait {
+        let deployment = client.submit_deploy(metadata, dim as u64).await?;
+        match &deployment {
+            Deployment::Finished(id) => {
+                info!(%id, "memory deployed");
+                note!("Deployed memory \"{}\"; canister id:", args.name);
+                println!("{id}");
+            }
+            Deployment::Submitted(request_id) => {
+                let request_id = hex::encode(request_id.as_slice());
+                info!(%request_id, "deployment submitted");
+                note!(
+                    "Deployment of \"{}\" submitted; follow it with `list --all-states`. Request id:",
+                    args.name
+                );
+                println!("{request_id}");
+            }
+        }
+        return Ok(deployment);
+    }
 
-    let id = client.deploy_memory(&args.name, &args.description).await?;
+    let id = deploy_with_progress(client, metadata, dim as u64).await?;
     info!(%id, "memory deployed");
-    println!("Memory canister id: {id}");
+    note!("Deployed memory \"{}\"; canister id:", args.name);
+    println!("{id}");
+
+    // The memory exists at this point, so a failed cleanup only warns; the
+    // approval expires on its own after ten minutes.
+    warn_unrevoked(revoke_leftover(client).await);
+    Ok(Deployment::Finished(id))
+}
+
+/// Runs the deploy while polling `list_instance` and showing which state the
+/// new instance is in. On a terminal the state is redrawn on one line;
+/// otherwise each change is printed.
+async fn deploy_with_progress(
+    client: &LauncherClient,
+    metadata: &MemoryMetadata,
+    dim: u64,
+) -> Result<String> {
+    // The new instance is the slot whose state moves away from this snapshot.
+    let before: Vec<&'static str> = client
+        .list_memories()
+        .await?
+        .iter()
+        .map(State::label)
+        .collect();
+    let live = io::stderr().is_terminal() && !is_quiet();
+    let started = Instant::now();
+    let deploy = client.deploy_memory(metadata, dim);
+    tokio::pin!(deploy);
+    let mut poll = tokio::time::interval(PROGRESS_POLL_INTERVAL);
+    let mut slot: Option<usize> = None;
+    let mut shown = String::new();
+    let result = loop {
+        tokio::select! {
+            result = &mut deploy => break result,
+            _ = poll.tick() => {
+                // A failed poll only skips one update of the progress line.
+                let Ok(states) = client.list_memories().await else {
+                    continue;
+                };
+                if slot.is_none() {
+                    slot = states
+                        .iter()
+                        .enumerate()
+                        .position(|(i, state)| before.get(i) != Some(&state.label()));
+                }
+                let status = match slot.and_then(|slot| states.get(slot)) {
+                    Some(state) => match state.detail() {
+                        Some(detail) => format!("{} ({detail})", state.label()),
+                        None => state.label().to_string(),
+                    },
+                    None => "waiting for the launcher".to_string(),
+                };
+                if live {
+                    eprint!(
+                        "\r\x1b[2KDeploying: {status} [{}s]",
+                        started.elapsed().as_secs()
+                    );
+                } else if status != shown {
+                    note!("Deploying: {status}");
+                }
+                shown = status;
+            }
+        }
+    };
+    if live {
+        eprint!("\r\x1b[2K");
+    }
+    result
+}
+
+/// Revokes whatever is left of the approval, such as the buffer after a
+/// deploy or the whole allowance after a failure.
+pub(super) async fn revoke_leftover(client: &LauncherClient) -> Result<()> {
+    let Some(remaining) = client.revoke_remaining().await? else {
+        return Ok(());
+    };
+    note!(
+        "Revoked the launcher's remaining allowance of {} KINIC.",
+        format_e8s(nat_to_e8s(&remaining)?)
+    );
     Ok(())
 }
 
-fn required_balance(price: &Nat) -> Nat {
-    let fee = Nat::from(TRANSFER_FEE_E8S);
-    price.clone() + fee.clone() + fee
+/// Reports a failed [`revoke_leftover`]; the approval still expires on its
+/// own after ten minutes.
+pub(super) fn warn_unrevoked(revoked: Result<()>) {
+    if let Err(err) = revoked {
+        eprintln!("warning: could not revoke the launcher's remaining allowance: {err:#}");
+    }
+}
+
+/// The price plus fees for the approval and the launcher's transfer, and
+/// for revoking a buffer that is left over.
+fn required_balance(price_e8s: u128, buffer: u128) -> u128 {
+    let revoke_fee = if buffer > 0 { TRANSFER_FEE_E8S } else { 0 };
+    price_e8s + 2 * TRANSFER_FEE_E8S + revoke_fee
 }
@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::DiffArgs,
+    clients::memory::MemoryClient,
+    error::KinicError,
+    output::note,
+    payload::{ChunkPayload, content_hash},
+};
+
+use super::CommandContext;
+
+/// Characters of chunk text shown per reported chunk.
+const PREVIEW_CHARS: usize = 60;
+
+/// One chunk as compared: its payload normalized to the current schema, so
+/// an upgraded copy of a version 1 chunk still matches the original.
+struct Chunk {
+    normalized: String,
+    label: String,
+}
+
+#[derive(Default)]
+struct Counts {
+    added: usize,
+    removed: usize,
+    changed: usize,
+    unchanged: usize,
+}
+
+pub async fn handle(args: DiffArgs, ctx: &CommandContext) -> Result<()> {
+    let a = load(&args.a, ctx).await?;
+    let b = load(&args.b, ctx).await?;
+
+    let mut counts = Counts::default();
+    let mut hashes: Vec<&String> = a.keys().chain(b.keys()).collect();
+    hashes.sort();
+    hashes.dedup();
+    for hash in hashes {
+        let mut only_a: Vec<&Chunk> = a.get(hash).map(|c| c.iter().collect()).unwrap_or_default();
+        let mut only_b: Vec<&Chunk> = b.get(hash).map(|c| c.iter().collect()).unwrap_or_default();
+        // Identical copies cancel out; what is left differs in tag or
+        // metadata, or exists on one side only.
+        only_a.retain(|chunk| {
+            match only_b
+                .iter()
+                .position(|other| other.normalized == chunk.normalized)
+            {
+                Some(index) => {
+                    only_b.remove(index);
+                    counts.unchanged += 1;
+                    false
+                }
+                None => true,
+            }
+        });
+        let short = &hash[..hash.len().min(12)];
+        let paired = only_a.len().min(only_b.len());
+        for (old, new) in only_a.iter().zip(&only_b) {
+            println!("~ {short} {} -> {}", old.label, new.label);
+        }
+        for chunk in &only_a[paired..] {
+            println!("- {short} {}", chunk.label);
+        }
+        for chunk in &only_b[paired..] {
+            println!("+ {short} {}", chunk.label);
+        }
+        counts.changed += paired;
+        counts.removed += only_a.len() - paired;
+        counts.added += only_b.len() - paired;
+    }
+
+    info!(
+        a = %args.a,
+        b = %args.b,
+        added = counts.added,
+        removed = counts.removed,
+        changed = counts.changed,
+        unchanged = counts.unchanged,
+        "diff completed"
+    );
+    note!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        counts.added,
+        counts.removed,
+        counts.changed,
+        counts.unchanged
+    );
+    if counts.added + counts.removed + counts.changed > 0 {
+        bail!("{} and {} differ", args.a, args.b);
+    }
+    Ok(())
+}
+
+/// Chunks of an export file or a live memory, grouped by content hash.
+async fn load(source: &str, ctx: &CommandContext) -> Result<BTreeMap<String, Vec<Chunk>>> {
+    let payloads = if Path::new(source).is_file() {
+        let text =
+            fs::read_to_string(source).with_context(|| format!("Failed to read {source}"))?;
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    } else {
+        let memory = Principal::from_text(source).map_err(|_| {
+            KinicError::Validation(format!(
+                "{source} is neither an export file nor a memory canister id"
+            ))
+        })?;
+        note!("Reading the chunks of {memory}...");
+        let client = MemoryClient::new(ctx.agent().await?, memory);
+        client
+            .all_chunks()
+            .await?
+            .into_iter()
+            .map(|(_, payload)| payload)
+            .collect()
+    };
+
+    let mut chunks: BTreeMap<String, Vec<Chunk>> = BTreeMap::new();
+    for raw in payloads {
+        let (hash, chunk) = match ChunkPayload::parse(&raw) {
+            Some(payload) => {
+                let payload = payload.upgrade();
+                let label = format!("[{}] {}", payload.tag, preview(&payload.sentence));
+                (
+                    payload.metadata.content_hash.clone().unwrap_or_default(),
+                    Chunk {
+                        normalized: payload.to_json(),
+                        label,
+                    },
+                )
+            }
+            None => (
+                content_hash(&raw),
+                Chunk {
+                    label: preview(&raw),
+                    normalized: raw,
+                },
+            ),
+        };
+        chunks.entry(hash).or_default().push(chunk);
+    }
+    Ok(chunks)
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text;
+    }
+    let mut preview: String = text.chars().take(PREVIEW_CHARS - 1).collect();
+    preview.push('…');
+    preview
+}
@@ -0,0 +1,252 @@
+use std::{
+    net::TcpListener,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::identity::AnonymousIdentity;
+use tracing::info;
+
+use crate::{
+    agent::{KEYRING_IDENTITY_PREFIX, KEYRING_SERVICE_NAME},
+    cli::DoctorArgs,
+    commands::{history::format_timestamp, ii_login::CALLBACK_PORT},
+    embedding::{embedding_base_url, http_client},
+    identity_store::{SessionStore, derive_principal_from_user_key},
+    jobs,
+};
+
+use super::CommandContext;
+
+const KEYRING_FIX: &str = "unlock the login keychain (macOS) or start a Secret Service provider \
+     such as gnome-keyring (Linux); without a keyring, use --credential-store file or --ii";
+
+/// A delegation expiring sooner than this gets a warning.
+const EXPIRY_WARNING: Duration = Duration::from_secs(60 * 60);
+
+enum Outcome {
+    Ok(String),
+    Warn(String, String),
+    Fail(String, String),
+}
+
+pub async fn handle(_args: DoctorArgs, ctx: &CommandContext) -> Result<()> {
+    let checks = [
+        ("config", check_config()),
+        ("keyring", check_keyring()),
+        ("identity", check_identity(ctx)),
+        ("replica", check_replica(ctx).await),
+        ("embedding API", check_embedding_api().await),
+        ("login port", check_callback_port()),
+    ];
+
+    let mut failed = 0;
+    let mut warned = 0;
+    for (name, outcome) in &checks {
+        match outcome {
+            Outcome::Ok(detail) => println!("[ ok ] {name}: {detail}"),
+            Outcome::Warn(detail, fix) => {
+                warned += 1;
+                println!("[warn] {name}: {detail}\n       fix: {fix}");
+            }
+            Outcome::Fail(detail, fix) => {
+                failed += 1;
+                println!("[FAIL] {name}: {detail}\n       fix: {fix}");
+            }
+        }
+    }
+
+    info!(failed, warned, "doctor completed");
+    if failed > 0 {
+        bail!("{failed} of {} checks failed", checks.len());
+    }
+    Ok(())
+}
+
+fn check_config() -> Outcome {
+    let path = match jobs::config_path() {
+        Ok(path) => path,
+        Err(err) => {
+            return Outcome::Fail(
+                format!("{err:#}"),
+                format!("set {} to the config file path", jobs::CONFIG_ENV_VAR),
+            );
+        }
+    };
+    if !path.exists() {
+        return Outcome::Ok(format!("{} not found (optional)", path.display()));
+    }
+    match jobs::load_config() {
+        Ok(config) => Outcome::Ok(format!(
+            "{} ({} jobs, {} profiles)",
+            path.display(),
+            config.jobs.len(),
+            config.profiles.len()
+        )),
+        Err(err) => Outcome::Fail(
+            format!("{err:#}"),
+            format!(
+                "correct {}; docs/cli.md lists the supported keys",
+                path.display()
+            ),
+        ),
+    }
+}
+
+/// Looks up an entry that never exists: "not found" proves the keyring
+/// service answers, anything else means it cannot be used.
+fn check_keyring() -> Outcome {
+    let account = format!("{KEYRING_IDENTITY_PREFIX}kinic-doctor-probe");
+    let lookup = keyring::Entry::new(KEYRING_SERVICE_NAME, &account)
+        .and_then(|entry| entry.get_password().map(|_| ()));
+    match lookup {
+        Ok(()) | Err(keyring::Error::NoEntry) => {
+            Outcome::Ok("system keyring is accessible".to_string())
+        }
+        Err(err) => Outcome::Fail(
+            format!("system keyring is not accessible: {err}"),
+            KEYRING_FIX.to_string(),
+        ),
+    }
+}
+
+fn check_identity(ctx: &CommandContext) -> Outcome {
+    if let Some(path) = &ctx.identity_path {
+        return check_session(SessionStore::locate(path, ctx.session.as_deref()));
+    }
+    let name = ctx.agent_factory.identity_name();
+    if name.is_empty() {
+        return Outcome::Warn(
+            "not checked; doctor checks --identity and --ii identities only".to_string(),
+            "run doctor with --identity NAME or --ii to check that identity".to_string(),
+        );
+    }
+    match ctx
+        .agent_factory
+        .identity()
+        .and_then(|identity| identity.sender().map_err(anyhow::Error::msg))
+    {
+        Ok(principal) => Outcome::Ok(format!("identity \"{name}\" is {principal}")),
+        Err(err) => Outcome::Fail(
+            format!("{err:#}"),
+            format!(
+                "create it with `dfx identity new {name} --storage-mode keyring`, or check the name with `dfx identity list`"
+            ),
+        ),
+    }
+}
+
+fn check_session(store: SessionStore) -> Outcome {
+    let stored = match store.load() {
+        Ok(stored) => stored,
+        Err(err) => {
+            return Outcome::Fail(
+                format!("no usable Internet Identity session in {store}: {err:#}"),
+                "run `kinic-cli --ii login`".to_string(),
+            );
+        }
+    };
+    let principal = match hex::decode(&stored.user_public_key_hex)
+        .context("Failed to decode user public key")
+        .and_then(|key| derive_principal_from_user_key(&key))
+    {
+        Ok(principal) => principal,
+        Err(err) => {
+            return Outcome::Fail(
+                format!("session in {store} is corrupt: {err:#}"),
+                "run `kinic-cli --ii login` to replace it".to_string(),
+            );
+        }
+    };
+    let now_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_nanos() as u64);
+    let expires = format_timestamp(stored.expiration_ns);
+    let encrypted = if stored.is_encrypted() {
+        ", encrypted"
+    } else {
+        ""
+    };
+    if stored.expiration_ns <= now_ns {
+        Outcome::Fail(
+            format!("session for {principal} expired at {expires} UTC"),
+            "run `kinic-cli --ii login` again".to_string(),
+        )
+    } else if stored.expiration_ns - now_ns < EXPIRY_WARNING.as_nanos() as u64 {
+        Outcome::Warn(
+            format!("session for {principal} expires soon, at {expires} UTC{encrypted}"),
+            "run `kinic-cli --ii login` to renew it".to_string(),
+        )
+    } else {
+        Outcome::Ok(format!(
+            "session for {principal} valid until {expires} UTC{encrypted}"
+        ))
+    }
+}
+
+/// Talks to the replica anonymously, so the result does not depend on the
+/// identity check above.
+async fn check_replica(ctx: &CommandContext) -> Outcome {
+    let url = ctx.agent_factory.url();
+    let status = async {
+        let agent = ctx
+            .agent_factory
+            .with_identity(AnonymousIdentity)
+            .build()
+            .await?;
+        agent
+            .status()
+            .await
+            .context("Failed to read replica status")
+    };
+    match status.await {
+        Ok(_) => Outcome::Ok(format!("{url} is reachable")),
+        Err(err) if ctx.agent_factory.use_mainnet() => Outcome::Fail(
+            format!("{url} is not reachable: {err:#}"),
+            "check your connection and proxy settings (KINIC_PROXY, HTTPS_PROXY, KINIC_CA_CERT)"
+                .to_string(),
+        ),
+        Err(err) => Outcome::Fail(
+            format!("{url} is not reachable: {err:#}"),
+            "start a local replica with `dfx start --background`, or pass --ic to use mainnet"
+                .to_string(),
+        ),
+    }
+}
+
+/// Any HTTP response counts: the base URL itself need not serve anything.
+async fn check_embedding_api() -> Outcome {
+    let base_url = embedding_base_url();
+    let response = match http_client() {
+        Ok(client) => client
+            .get(&base_url)
+            .send()
+            .await
+            .map_err(anyhow::Error::from),
+        Err(err) => Err(err),
+    };
+    match response {
+        Ok(response) => Outcome::Ok(format!(
+            "{base_url} is reachable (HTTP {})",
+            response.status().as_u16()
+        )),
+        Err(err) => Outcome::Fail(
+            format!("{base_url} is not reachable: {err:#}"),
+            "check EMBEDDING_API_ENDPOINT and your connection".to_string(),
+        ),
+    }
+}
+
+fn check_callback_port() -> Outcome {
+    match TcpListener::bind(("127.0.0.1", CALLBACK_PORT)) {
+        Ok(_) => Outcome::Ok(format!(
+            "port {CALLBACK_PORT} is free for the `login` callback"
+        )),
+        Err(err) => Outcome::Warn(
+            format!("port {CALLBACK_PORT} is not available: {err}"),
+            format!(
+                "`kinic-cli --ii login` needs it; stop whatever listens there (e.g. find it with `lsof -i :{CALLBACK_PORT}`)"
+            ),
+        ),
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{Result, anyhow};
+
+use crate::{
+    cli::EncryptIdentityArgs,
+    identity_store::{SessionStore, read_new_passphrase, read_passphrase},
+    output::note,
+};
+
+use super::CommandContext;
+
+/// Migrates an identity.json written by an earlier `login` to an encrypted
+/// session key, or back to plaintext with `--decrypt`.
+pub async fn handle(args: EncryptIdentityArgs, ctx: &CommandContext) -> Result<()> {
+    let path = ctx
+        .identity_path
+        .clone()
+        .ok_or_else(|| anyhow!("Identity path is missing"))?;
+    let store = SessionStore::locate(&path, ctx.session.as_deref());
+    let mut stored = store.load()?;
+
+    if args.decrypt {
+        if !stored.is_encrypted() {
+            note!("{} is not encrypted.", store);
+            return Ok(());
+        }
+        stored.decrypt(&read_passphrase("Identity passphrase: ")?)?;
+        store.save(&stored)?;
+        note!("Removed encryption from {}", store);
+    } else {
+        if stored.is_encrypted() {
+            note!("{} is already encrypted.", store);
+            return Ok(());
+        }
+        stored.encrypt(&read_new_passphrase()?)?;
+        store.save(&stored)?;
+        note!("Encrypted session key in {}", store);
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! What inserting a file would take, worked out without inserting: the file
+//! is converted as the insert commands would, then chunked locally by
+//! sentence (or by the chunking API with `--exact`), with each table row
+//! group counted as one chunk. Time uses the chunk
+//! throughput of recent ingestion runs in the usage ledger; cycles use the
+//! same fee model as `usage`.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    cli::EstimateArgs,
+    clients::memory::insert_arg_size,
+    commands::{
+        ask_ai::split_sentences,
+        convert_pdf::pdf_to_markdown,
+        insert::{Section, split_document},
+        insert_bookmarks::html_to_text,
+    },
+    embedding::{embedding_dim, late_chunking},
+    output::note,
+    payload::InsertMetadata,
+    tokens,
+    usage::{self, RunUsage, format_cycles},
+};
+
+/// Ingestion runs whose throughput predicts an insert's.
+const INGEST_COMMANDS: &[&str] = &[
+    "add",
+    "insert",
+    "insert-pdf",
+    "insert-obsidian",
+    "insert-mail",
+    "insert-bookmarks",
+    "sync",
+    "capture",
+];
+/// How many recent ingestion runs the throughput is averaged over.
+const THROUGHPUT_RUNS: usize = 20;
+
+#[derive(Serialize)]
+struct Estimate {
+    file: String,
+    bytes: usize,
+    chunks: usize,
+    /// Whether `chunks` came from the chunking API rather than the local
+    /// sentence count.
+    exact: bool,
+    embedding_requests: usize,
+    api_tokens: usize,
+    update_calls: usize,
+    update_bytes: u64,
+    cycles: u64,
+    /// Chunks per second of recent ingestion runs, if any were timed.
+    chunks_per_sec: Option<f64>,
+    seconds: Option<f64>,
+}
+
+pub async fn handle(args: EstimateArgs) -> Result<()> {
+    let content = load(&args.file_path)?;
+    let sections = split_document(&content);
+    let mut sentences = Vec::new();
+    let mut embedding_requests = 0;
+    let mut api_tokens = 0;
+    for section in sections {
+        match section {
+            // Each table row group is embedded on its own.
+            Section::Table(rows) => {
+                embedding_requests += rows.len();
+                api_tokens += rows.iter().map(|row| tokens::count(row)).sum::<usize>();
+                sentences.extend(rows);
+            }
+            // The chunking API embeds a whole section in one call.
+            Section::Text(text) => {
+                embedding_requests += 1;
+                api_tokens += tokens::count(&text);
+                if args.exact {
+                    sentences.extend(
+                        late_chunking(&text, args.language.as_deref())
+                            .await?
+                            .into_iter()
+                            .map(|chunk| chunk.sentence),
+                    );
+                } else {
+                    sentences.extend(split_sentences(&text));
+                }
+            }
+        }
+    }
+
+    // Payloads carry the metadata an `insert --file-path` would give them.
+    let source = args.file_path.display().to_string();
+    let metadata = InsertMetadata::new(&source, Some(source.clone()), &[])?;
+    let vector = vec![0.0f32; args.dim.unwrap_or_else(embedding_dim)];
+    let update_bytes: u64 = sentences
+        .iter()
+        .enumerate()
+        .map(|(index, sentence)| {
+            insert_arg_size(&vector, &metadata.payload(sentence, index, None)) as u64
+        })
+        .sum();
+    let chunks_per_sec = throughput(&usage::load()?);
+    let estimate = Estimate {
+        file: source,
+        bytes: content.len(),
+        chunks: sentences.len(),
+        exact: args.exact,
+        embedding_requests,
+        api_tokens,
+        update_calls: sentences.len(),
+        update_bytes,
+        cycles: usage::update_cycles(sentences.len() as u64, update_bytes),
+        seconds: chunks_per_sec.map(|rate| sentences.len() as f64 / rate),
+        chunks_per_sec,
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&estimate)?);
+        return Ok(());
+    }
+    print_estimate(&estimate);
+    Ok(())
+}
+
+/// The text an insert of `path` would chunk.
+fn load(path: &Path) -> Result<String> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("pdf") => pdf_to_markdown(path),
+        Some("html" | "htm") => {
+            let html = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok(html_to_text(&html))
+        }
+        _ => fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Chunks stored per second over the recent successful ingestion runs.
+fn throughput(runs: &[RunUsage]) -> Option<f64> {
+    let (chunks, millis) = runs
+        .iter()
+        .rev()
+        .filter(|run| {
+            run.ok
+                && run.update_calls > 0
+                && run.duration_ms > 0
+                && INGEST_COMMANDS.contains(&run.command.as_str())
+        })
+        .take(THROUGHPUT_RUNS)
+        .fold((0, 0), |(chunks, millis), run| {
+            (chunks + run.update_calls, millis + run.duration_ms)
+        });
+    (millis > 0).then(|| chunks as f64 * 1000.0 / millis as f64)
+}
+
+fn print_estimate(estimate: &Estimate) {
+    let how = if estimate.exact {
+        "from the chunking API"
+    } else {
+        "local sentence count; --exact asks the chunking API"
+    };
+    println!(
+        "file:              {} ({} bytes of text)",
+        estimate.file, estimate.bytes
+    );
+    println!("chunks:            {} ({how})", estimate.chunks);
+    println!(
+        "embedding calls:   {} (~{} tokens)",
+        estimate.embedding_requests, estimate.api_tokens
+    );
+    println!(
+        "update calls:      {} (~{} KiB)",
+        estimate.update_calls,
+        estimate.update_bytes / 1024
+    );
+    println!("cycles:            ~{}", format_cycles(estimate.cycles));
+    match (estimate.seconds, estimate.chunks_per_sec) {
+        (Some(seconds), Some(rate)) => println!(
+            "time:              ~{} (at {rate:.1} chunks/s, from recent runs)",
+            format_seconds(seconds)
+        ),
+        _ => note!("No timed ingestion runs in the usage ledger yet; time is not estimated."),
+    }
+}
+
+/// `40s`, `12m 5s` or `2h 3m`.
+fn format_seconds(seconds: f64) -> String {
+    let secs = seconds.ceil() as u64;
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, _) => format!("{h}h {m}m"),
+    }
+}
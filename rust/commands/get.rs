@@ -0,0 +1,39 @@
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{cli::GetArgs, clients::memory::MemoryClient, payload::ChunkPayload};
+
+use super::CommandContext;
+
+pub async fn handle(args: GetArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for get command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    let Some(payload) = client.get(args.id).await? else {
+        bail!("No chunk with id {} in memory {memory}", args.id);
+    };
+
+    info!(canister_id = %memory, id = args.id, "get completed");
+
+    print_chunk(Some(args.id), &payload);
+    Ok(())
+}
+
+/// Prints a chunk with its tag and metadata; raw payloads are printed as is.
+pub(crate) fn print_chunk(id: Option<u32>, payload: &str) {
+    match ChunkPayload::parse(payload) {
+        Some(chunk) => {
+            match id {
+                Some(id) => println!("Chunk #{id} (tag: {})", chunk.tag),
+                None => println!("Chunk (tag: {})", chunk.tag),
+            }
+            for (key, value) in chunk.metadata.summary() {
+                println!("  {key}: {value}");
+            }
+            println!("{}", chunk.sentence);
+        }
+        None => println!("{payload}"),
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result, anyhow};
+use candid::Nat;
+use ic_agent::export::Principal;
+use serde::Serialize;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::info;
+
+use crate::{
+    cli::HistoryArgs,
+    clients::{
+        LAUNCHER_CANISTER, LEDGER_CANISTER,
+        call::ledger_index::{Account, TransactionWithId},
+        ledger_index::LedgerIndexClient,
+    },
+    ledger::{format_e8s, nat_to_e8s},
+    output::note,
+};
+
+use super::CommandContext;
+
+pub async fn handle(args: HistoryArgs, ctx: &CommandContext) -> Result<()> {
+    let agent = ctx.agent().await?;
+    let owner = agent
+        .get_principal()
+        .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
+
+    let client = match &args.index_id {
+        Some(id) => LedgerIndexClient::new(
+            agent,
+            Principal::from_text(id).context("Failed to parse index canister id")?,
+        ),
+        None => {
+            let ledger_id = Principal::from_text(LEDGER_CANISTER)
+                .context("Failed to parse ledger canister id")?;
+            LedgerIndexClient::discover(agent, ledger_id).await?
+        }
+    };
+
+    let transactions = client
+        .account_transactions(
+            Account {
+                owner,
+                subaccount: None,
+            },
+            None,
+            args.limit,
+        )
+        .await?;
+    info!(
+        %owner,
+        index_id = %client.index_id(),
+        count = transactions.len(),
+        "fetched transaction history"
+    );
+
+    let rows: Vec<HistoryRow> = transactions
+        .iter()
+        .map(|tx| HistoryRow::new(tx, &owner))
+        .collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        note!("No transactions found for {owner}.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<10} {:<20} {:<9} {:<9} {:>20}  COUNTERPARTY",
+        "ID", "TIME (UTC)", "KIND", "DIRECTION", "AMOUNT (KINIC)"
+    );
+    for row in &rows {
+        println!(
+            "{:<10} {:<20} {:<9} {:<9} {:>20}  {}",
+            row.id,
+            row.timestamp,
+            row.kind,
+            row.direction,
+            row.amount_kinic,
+            row.counterparty.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct HistoryRow {
+    id: String,
+    timestamp: String,
+    kind: String,
+    direction: &'static str,
+    counterparty: Option<String>,
+    amount_e8s: String,
+    amount_kinic: String,
+    fee_e8s: Option<String>,
+    memo: Option<String>,
+}
+
+impl HistoryRow {
+    fn new(tx: &TransactionWithId, owner: &Principal) -> Self {
+        let inner = &tx.transaction;
+        let (direction, counterparty, amount, fee, memo) = if let Some(t) = &inner.transfer {
+            let outgoing = t.from.owner == *owner;
+            let direction = match (outgoing, t.to.owner == *owner) {
+                (true, true) => "self",
+                (true, false) => "out",
+                _ => "in",
+            };
+            // transfer_from by a spender (e.g. the launcher collecting a
+            // deployment payment) is attributed to the spender.
+            let counterparty = match (&t.spender, outgoing) {
+                (Some(spender), true) if spender.owner != *owner => spender,
+                (_, true) => &t.to,
+                (_, false) => &t.from,
+            };
+            (
+                direction,
+                Some(label(counterparty)),
+                &t.amount,
+                t.fee.as_ref(),
+                &t.memo,
+            )
+        } else if let Some(a) = &inner.approve {
+            (
+                "out",
+                Some(label(&a.spender)),
+                &a.amount,
+                a.fee.as_ref(),
+                &a.memo,
+            )
+        } else if let Some(m) = &inner.mint {
+            ("in", None, &m.amount, None, &m.memo)
+        } else if let Some(b) = &inner.burn {
+            ("out", None, &b.amount, None, &b.memo)
+        } else {
+            return Self {
+                id: tx.id.to_string(),
+                timestamp: format_timestamp(inner.timestamp),
+                kind: inner.kind.clone(),
+                direction: "-",
+                counterparty: None,
+                amount_e8s: "0".to_string(),
+                amount_kinic: "-".to_string(),
+                fee_e8s: None,
+                memo: None,
+            };
+        };
+
+        Self {
+            id: tx.id.to_string(),
+            timestamp: format_timestamp(inner.timestamp),
+            kind: inner.kind.clone(),
+            direction,
+            counterparty,
+            amount_e8s: amount.to_string(),
+            amount_kinic: nat_to_kinic(amount),
+            fee_e8s: fee.map(Nat::to_string),
+            memo: memo
+                .as_ref()
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+}
+
+fn label(account: &Account) -> String {
+    let text = account.owner.to_text();
+    if text == LAUNCHER_CANISTER {
+        format!("{text} (launcher)")
+    } else {
+        text
+    }
+}
+
+fn nat_to_kinic(value: &Nat) -> String {
+    nat_to_e8s(value)
+        .map(format_e8s)
+        .unwrap_or_else(|_| value.to_string())
+}
+
+pub fn format_timestamp(nanos: u64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(nanos as i128)
+        .ok()
+        .and_then(|ts| ts.format(&Rfc3339).ok())
+        .map(|ts| ts.chars().take(19).collect::<String>().replace('T', " "))
+        .unwrap_or_else(|| nanos.to_string())
+}
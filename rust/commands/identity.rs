@@ -0,0 +1,76 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    agent::{self, CredentialStore},
+    cli::{IdentityArgs, IdentityCommand, IdentityImportArgs},
+    error::KinicError,
+    identity_store::{self, read_new_passphrase},
+    output::note,
+};
+
+use super::CommandContext;
+
+pub async fn handle(args: IdentityArgs, ctx: &CommandContext) -> Result<()> {
+    match args.command {
+        IdentityCommand::Import(import_args) => import(import_args),
+        IdentityCommand::ShowPrincipal => show_principal(ctx),
+    }
+}
+
+fn show_principal(ctx: &CommandContext) -> Result<()> {
+    let principal = ctx
+        .agent_factory
+        .identity()?
+        .sender()
+        .map_err(|err| KinicError::Identity(format!("Failed to derive principal: {err}")))?;
+    println!("{principal}");
+    Ok(())
+}
+
+fn import(args: IdentityImportArgs) -> Result<()> {
+    let pem = fs::read_to_string(&args.pem)
+        .with_context(|| format!("Failed to read {}", args.pem.display()))?;
+    let principal = agent::identity_from_pem(&pem)
+        .and_then(|identity| identity.sender().map_err(anyhow::Error::msg))
+        .map_err(|err| {
+            err.context(KinicError::Validation(format!(
+                "{} is not an Ed25519 or secp256k1 PEM key",
+                args.pem.display()
+            )))
+        })?;
+    // Validates the name for both stores.
+    let file_path = identity_store::credential_file_path(&args.name)?;
+
+    let exists = match args.store {
+        CredentialStore::Keyring => agent::keyring_pem_exists(&args.name),
+        CredentialStore::File => file_path.exists(),
+    };
+    if exists && !args.force {
+        return Err(KinicError::Validation(format!(
+            "identity \"{}\" already exists; pass --force to replace it",
+            args.name
+        ))
+        .into());
+    }
+
+    let location = match args.store {
+        CredentialStore::Keyring => {
+            agent::save_pem_to_keyring(&args.name, pem.as_bytes())?;
+            "the system keyring".to_string()
+        }
+        CredentialStore::File => {
+            let passphrase = read_new_passphrase()?;
+            let path = identity_store::save_pem_to_file(&args.name, pem.as_bytes(), &passphrase)?;
+            path.display().to_string()
+        }
+    };
+    println!("{principal}");
+    note!(
+        "Imported identity \"{}\" into {location}; use it with --identity {}.",
+        args.name,
+        args.name
+    );
+    Ok(())
+}
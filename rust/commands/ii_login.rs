@@ -4,6 +4,7 @@
 //! Why: Avoids requiring a keychain-backed dfx identity for CLI-only login.
 
 use std::{
+    fs,
     net::SocketAddr,
     sync::Arc,
     time::{Duration, SystemTime, UNIX_EPOCH},
@@ -32,17 +33,18 @@ use tokio::{
 };
 
 use crate::{
-    cli::LoginArgs,
+    cli::{IdentityStoreKind, LoginArgs},
     commands::CommandContext,
     identity_store::{
-        SessionKeyMaterial, StoredIdentity, derive_principal_from_user_key, generate_session_key,
-        normalize_spki_key, save_identity,
+        SessionKeyMaterial, SessionStore, StoredIdentity, derive_principal_from_user_key,
+        generate_session_key, normalize_spki_key, read_new_passphrase,
     },
+    output::note,
 };
 
 const IDENTITY_PROVIDER_URL: &str = "https://id.ai/#authorize";
 const IDENTITY_PROVIDER_ORIGIN: &str = "https://id.ai";
-const CALLBACK_PORT: u16 = 8620;
+pub(crate) const CALLBACK_PORT: u16 = 8620;
 const CALLBACK_TIMEOUT_SECS: u64 = 300;
 const MAX_CALLBACK_BODY_BYTES: usize = 256 * 1024;
 const DEFAULT_TTL_HOURS: u64 = 6;
@@ -83,11 +85,42 @@ struct BrowserDelegation {
     targets: Option<Vec<String>>,
 }
 
-pub async fn handle(_args: LoginArgs, ctx: &CommandContext) -> Result<()> {
+pub async fn handle(args: LoginArgs, ctx: &CommandContext) -> Result<()> {
     let identity_path = ctx
         .identity_path
         .clone()
         .ok_or_else(|| anyhow!("Identity path is missing"))?;
+    let passphrase = if args.encrypt {
+        Some(read_new_passphrase()?)
+    } else {
+        None
+    };
+    let store = match args.store {
+        IdentityStoreKind::File => SessionStore::File(identity_path.clone()),
+        IdentityStoreKind::Keyring => SessionStore::Keyring {
+            session: ctx.session.clone(),
+        },
+    };
+    let principal = login(&store, passphrase.as_deref()).await?;
+    note!("Saved Internet Identity delegation to {store}");
+    if matches!(store, SessionStore::Keyring { .. }) && identity_path.exists() {
+        // A file at the identity path takes precedence over the keyring.
+        fs::remove_file(&identity_path).with_context(|| {
+            format!(
+                "Failed to remove old identity file {}",
+                identity_path.display()
+            )
+        })?;
+        note!("Removed old identity file {}", identity_path.display());
+    }
+    note!("Principal:");
+    println!("{principal}");
+    Ok(())
+}
+
+/// Runs the browser login and saves the delegation to `store`, encrypting
+/// the session key when a passphrase is given.
+pub async fn login(store: &SessionStore, passphrase: Option<&str>) -> Result<Principal> {
     let ttl_ns = ttl_nanos()?;
     // CSRF mitigation: random state token is generated per session and verified on callback.
     let state_token = generate_state()?;
@@ -151,22 +184,21 @@ pub async fn handle(_args: LoginArgs, ctx: &CommandContext) -> Result<()> {
     let delegations = convert_delegations(callback.payload.delegations, &session_pubkey)?;
     let expiration_ns = delegation_expiration(&delegations)?;
     let principal = callback.principal;
-    let stored = StoredIdentity {
+    let mut stored = StoredIdentity {
         version: 1,
         identity_provider: IDENTITY_PROVIDER_URL.to_string(),
         user_public_key_hex: hex::encode(callback.payload.user_public_key),
         session_pkcs8_hex: hex::encode(session.pkcs8),
+        encrypted_session_key: None,
         delegations,
         expiration_ns,
         created_at_ns: current_time_ns()?,
     };
-    save_identity(&identity_path, &stored)?;
-    println!(
-        "Saved Internet Identity delegation to {}",
-        identity_path.display()
-    );
-    println!("Principal: {}", principal);
-    Ok(())
+    if let Some(passphrase) = passphrase {
+        stored.encrypt(passphrase)?;
+    }
+    store.save(&stored)?;
+    Ok(principal)
 }
 
 fn build_login_page(session: &SessionKeyMaterial, ttl_ns: u64, state: &str) -> String {
@@ -1,72 +1,578 @@
-use std::fs;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result, bail};
+use glob::MatchOptions;
 use ic_agent::export::Principal;
-use serde_json::json;
+use tokio::{sync::mpsc, task::JoinHandle};
 use tracing::info;
 
-use crate::{cli::InsertArgs, clients::memory::MemoryClient, embedding::late_chunking};
+use crate::{
+    cli::{AutoTagSource, InsertArgs},
+    clients::memory::{MAX_INSERT_ARG_BYTES, MemoryClient, insert_arg_size},
+    commands::{
+        ask_ai::{suggest_tags, summarize_document},
+        interrupt,
+    },
+    embedding::{LateChunk, fetch_embedding, fetch_embeddings, late_chunking},
+    error::KinicError,
+    guard, keywords,
+    output::note,
+    payload::{ChunkPayload, InsertMetadata, summary_tag},
+    redact::Redactor,
+    tables::{self, Block},
+};
 
 use super::CommandContext;
 
+const AUTO_TAG_LIMIT: usize = 5;
+/// Documents longer than this are late-chunked in sections of about this
+/// many bytes, split at blank lines, so inserting can start before the whole
+/// document is embedded.
+const SECTION_BYTES: usize = 16_000;
+/// Chunked sections buffered ahead of the canister inserts.
+const PIPELINE_DEPTH: usize = 2;
+
 pub async fn handle(args: InsertArgs, ctx: &CommandContext) -> Result<()> {
+    let mut documents = load_documents(&args)?;
+    for document in &documents {
+        let source = document.source.as_deref().unwrap_or("--text");
+        guard::check_secrets(source, &document.content, args.allow_secrets)?;
+    }
+    if let Some(mut redactor) = Redactor::from_args(&args.redact) {
+        for document in &mut documents {
+            let source = document.source.as_deref().unwrap_or("--text");
+            document.content = redactor.redact(source, &document.content).await?;
+        }
+        redactor.write_report("insert")?;
+    }
     let client = build_memory_client(&args.memory_id, ctx).await?;
-    let content = load_insert_content(&args)?;
-    let chunks = late_chunking(&content).await?;
+    // Chunking runs in the background while the tags are worked out.
+    let mut pipeline = ChunkPipeline::start(
+        documents
+            .iter()
+            .map(|document| document.content.clone())
+            .collect(),
+        args.language.as_deref(),
+    );
+    for (index, document) in documents.iter().enumerate() {
+        if index > 0 && interrupt::requested() {
+            bail!(
+                "interrupted after {index} of {} files; the rest were not inserted",
+                documents.len()
+            );
+        }
+        let chunk_count = insert_one(&args, &client, &mut pipeline, document).await?;
+        if documents.len() > 1 {
+            note!(
+                "Inserted {} ({chunk_count} chunks, tag {})",
+                document.source.as_deref().unwrap_or_default(),
+                document.tag
+            );
+        }
+    }
+    Ok(())
+}
+
+/// One document of an insert: the text, or one of the files.
+struct Document {
+    content: String,
+    tag: String,
+    source: Option<String>,
+}
+
+async fn insert_one(
+    args: &InsertArgs,
+    client: &MemoryClient,
+    pipeline: &mut ChunkPipeline,
+    document: &Document,
+) -> Result<usize> {
+    let metadata = InsertMetadata::new(&document.tag, document.source.clone(), &args.meta)?;
+    let metadata = match args.auto_tag {
+        Some(source) => {
+            let auto_tags = match source {
+                AutoTagSource::Local => keywords::extract(&document.content, AUTO_TAG_LIMIT),
+                AutoTagSource::Llm => suggest_tags(&document.content, AUTO_TAG_LIMIT).await?,
+            };
+            note!("Auto tags: {}", auto_tags.join(", "));
+            metadata.with_auto_tags(auto_tags)
+        }
+        None => metadata,
+    };
 
+    let chunk_count = pipeline
+        .insert_next(client, |sentence, index| {
+            metadata.payload(sentence, index, None)
+        })
+        .await?;
     info!(
         canister_id = %client.canister_id(),
-        chunk_count = chunks.len(),
-        tag = %args.tag,
-        source = %insert_source(&args),
-        "insert command prepared embeddings"
+        chunk_count,
+        tag = %document.tag,
+        source = %insert_source(args),
+        "insert command stored chunks"
     );
 
-    for (index, chunk) in chunks.into_iter().enumerate() {
-        let payload = format_chunk_text(&args.tag, &chunk.sentence);
-        info!(
-            chunk_index = index,
-            sentence_preview = %chunk
-                .sentence
-                .chars()
-                .take(40)
-                .collect::<String>(),
-            "inserting chunk"
-        );
-        client.insert(chunk.embedding, &payload).await?;
+    if args.summarize {
+        let summary = summarize_document(&document.content).await?;
+        let embedding = fetch_embedding(&summary).await?;
+        client
+            .insert(embedding, &metadata.summary_payload(&summary))
+            .await?;
+        info!(tag = %summary_tag(&document.tag), "inserted document summary");
+        note!("Summary: {summary}");
+    }
+    Ok(chunk_count)
+}
+
+/// Chunks `content` with the embedding API and inserts every chunk; returns
+/// the number of chunks stored.
+pub(crate) async fn insert_document(
+    client: &MemoryClient,
+    content: &str,
+    metadata: &InsertMetadata,
+    language: Option<&str>,
+) -> Result<usize> {
+    ChunkPipeline::start(vec![content.to_string()], language)
+        .insert_next(client, |sentence, index| {
+            metadata.payload(sentence, index, None)
+        })
+        .await
+}
+
+/// Producer-consumer pipeline for ingestion: a background task late-chunks
+/// the documents, section by section (tables are embedded row group by row
+/// group instead), and hands the chunks over in order
+/// through a bounded channel, so the next section is embedded while the
+/// caller inserts the previous one. Inserts stay sequential, which keeps the
+/// chunks of a document under consecutive ids.
+///
+/// Ctrl-C is watched for while the pipeline runs. With a single document it
+/// stops between two inserts; with several, callers check
+/// [`interrupt::requested`] between documents, so resume state never records
+/// a half-inserted document. `language` is the segmentation hint passed to
+/// the chunking endpoint.
+pub(crate) struct ChunkPipeline {
+    receiver: mpsc::Receiver<Result<Vec<LateChunk>>>,
+    /// Number of sections of each document not yet inserted.
+    sections: VecDeque<usize>,
+    stop_mid_document: bool,
+    producer: JoinHandle<()>,
+}
+
+impl ChunkPipeline {
+    pub(crate) fn start(documents: Vec<String>, language: Option<&str>) -> Self {
+        interrupt::watch();
+        let stop_mid_document = documents.len() == 1;
+        let documents: Vec<Vec<Section>> = documents
+            .iter()
+            .map(|document| split_document(document))
+            .collect();
+        let sections = documents.iter().map(Vec::len).collect();
+        let (sender, receiver) = mpsc::channel(PIPELINE_DEPTH);
+        let language = language.map(str::to_string);
+        let producer = tokio::spawn(async move {
+            for section in documents.into_iter().flatten() {
+                let chunks = match section {
+                    Section::Text(text) => late_chunking(&text, language.as_deref()).await,
+                    Section::Table(rows) => embed_rows(rows).await,
+                };
+                if sender.send(chunks).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            receiver,
+            sections,
+            stop_mid_document,
+            producer,
+        }
+    }
+
+    /// Inserts the chunks of the next document as its sections arrive and
+    /// returns how many were stored. `payload` builds each chunk's payload
+    /// from its sentence and index within the document. After an error the
+    /// rest of the document is skipped, so the next call starts on the next
+    /// document.
+    pub(crate) async fn insert_next(
+        &mut self,
+        client: &MemoryClient,
+        mut payload: impl FnMut(&str, usize) -> String,
+    ) -> Result<usize> {
+        let sections = self
+            .sections
+            .pop_front()
+            .context("no documents left to insert")?;
+        let mut received = 0;
+        let mut stored = 0;
+        let result: Result<()> = async {
+            while received < sections {
+                let section = self
+                    .receiver
+                    .recv()
+                    .await
+                    .context("chunking task stopped early")?;
+                received += 1;
+                for chunk in section? {
+                    if self.stop_mid_document && interrupt::requested() {
+                        if stored == 0 {
+                            bail!("interrupted before any chunk was inserted");
+                        }
+                        bail!(
+                            "interrupted after {stored} chunks; the rest of the document was not \
+                             inserted, and inserting it again stores those {stored} chunks twice"
+                        );
+                    }
+                    info!(
+                        chunk_index = stored,
+                        sentence_preview = %chunk
+                            .sentence
+                            .chars()
+                            .take(40)
+                            .collect::<String>(),
+                        "inserting chunk"
+                    );
+                    insert_chunk(client, chunk, |sentence| payload(sentence, stored)).await?;
+                    stored += 1;
+                }
+            }
+            Ok(())
+        }
+        .await;
+        if result.is_err() {
+            for _ in received..sections {
+                if self.receiver.recv().await.is_none() {
+                    break;
+                }
+            }
+        }
+        result.map(|()| stored)
+    }
+}
+
+impl Drop for ChunkPipeline {
+    fn drop(&mut self) {
+        self.producer.abort();
+    }
+}
+
+/// Inserts one chunk. When its encoded arguments would exceed the ingress
+/// limit, its text is split into as many parts as needed, each re-embedded
+/// and stored as its own chunk with `part` and `parts` in the metadata.
+async fn insert_chunk(
+    client: &MemoryClient,
+    chunk: LateChunk,
+    mut payload: impl FnMut(&str) -> String,
+) -> Result<()> {
+    let whole = payload(&chunk.sentence);
+    if insert_arg_size(&chunk.embedding, &whole) <= MAX_INSERT_ARG_BYTES {
+        client.insert(chunk.embedding, &whole).await?;
+        return Ok(());
     }
 
+    let chars = chunk.sentence.chars().count();
+    let mut wanted = 2;
+    let (pieces, payloads) = loop {
+        if wanted > chars {
+            bail!("chunk metadata alone exceeds the {MAX_INSERT_ARG_BYTES}-byte insert limit");
+        }
+        let pieces = split_text(&chunk.sentence, wanted);
+        let payloads: Vec<String> = pieces
+            .iter()
+            .enumerate()
+            .map(|(index, piece)| with_part(payload(piece), index + 1, pieces.len()))
+            .collect();
+        if payloads
+            .iter()
+            .all(|payload| insert_arg_size(&chunk.embedding, payload) <= MAX_INSERT_ARG_BYTES)
+        {
+            break (pieces, payloads);
+        }
+        wanted *= 2;
+    };
+    note!(
+        "- a chunk of {chars} characters is too large for one insert; storing it in {} parts",
+        pieces.len()
+    );
+    let texts: Vec<&str> = pieces.iter().map(String::as_str).collect();
+    let embeddings = fetch_embeddings(&texts).await?;
+    for (embedding, payload) in embeddings.into_iter().zip(&payloads) {
+        client.insert(embedding, payload).await?;
+    }
     Ok(())
 }
 
+fn with_part(payload: String, part: usize, parts: usize) -> String {
+    match ChunkPayload::parse(&payload) {
+        Some(mut chunk) => {
+            chunk.metadata.part = u32::try_from(part).ok();
+            chunk.metadata.parts = u32::try_from(parts).ok();
+            chunk.to_json()
+        }
+        None => payload,
+    }
+}
+
+/// Splits `text` into about `parts` pieces of similar length, preferring to
+/// cut after whitespace.
+fn split_text(text: &str, parts: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let size = chars.len().div_ceil(parts.max(1));
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + size).min(chars.len());
+        if end < chars.len()
+            && let Some(space) = (start + size / 2..end)
+                .rev()
+                .find(|&i| chars[i].is_whitespace())
+        {
+            end = space + 1;
+        }
+        pieces.push(chars[start..end].iter().collect());
+        start = end;
+    }
+    pieces
+}
+
+/// A part of a document chunked on its own.
+pub(crate) enum Section {
+    /// Text for the chunking API.
+    Text(String),
+    /// The row groups of a table, each stored as one chunk.
+    Table(Vec<String>),
+}
+
+/// Splits a document into its tables and sections of the text around them.
+pub(crate) fn split_document(text: &str) -> Vec<Section> {
+    tables::split(text)
+        .into_iter()
+        .flat_map(|block| match block {
+            Block::Text(text) => split_sections(&text)
+                .into_iter()
+                .map(Section::Text)
+                .collect(),
+            Block::Table(table) => vec![Section::Table(table.chunks())],
+        })
+        .collect()
+}
+
+async fn embed_rows(rows: Vec<String>) -> Result<Vec<LateChunk>> {
+    let texts: Vec<&str> = rows.iter().map(String::as_str).collect();
+    let embeddings = fetch_embeddings(&texts).await?;
+    Ok(embeddings
+        .into_iter()
+        .zip(rows)
+        .map(|(embedding, sentence)| LateChunk {
+            embedding,
+            sentence,
+        })
+        .collect())
+}
+
+/// Splits `text` at blank lines into sections of at most `SECTION_BYTES`;
+/// a longer paragraph becomes a section of its own.
+fn split_sections(text: &str) -> Vec<String> {
+    if text.len() <= SECTION_BYTES {
+        return vec![text.to_string()];
+    }
+    let mut sections = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n") {
+        if !current.is_empty() && current.len() + 2 + paragraph.len() > SECTION_BYTES {
+            sections.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for insert command")?;
     Ok(MemoryClient::new(agent, memory))
 }
 
-fn format_chunk_text(tag: &str, sentence: &str) -> String {
-    json!({ "tag": tag, "sentence": sentence }).to_string()
-}
-
-fn load_insert_content(args: &InsertArgs) -> Result<String> {
+fn load_documents(args: &InsertArgs) -> Result<Vec<Document>> {
     if let Some(text) = &args.text {
-        return Ok(text.clone());
+        let tag = args.tag.clone().context("--tag is required with --text")?;
+        return Ok(vec![Document {
+            content: text.clone(),
+            tag,
+            source: None,
+        }]);
     }
-
-    if let Some(path) = &args.file_path {
-        return fs::read_to_string(path)
-            .with_context(|| format!("Failed to read --file-path {}", path.display()));
+    if args.file_path.is_empty() {
+        bail!("Either --text or --file-path must be provided");
     }
 
-    bail!("Either --text or --file-path must be provided");
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for pattern in &args.file_path {
+        for path in expand_glob(pattern)? {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let content = guard::read_text(&path)?;
+            let tag = match &args.tag {
+                Some(tag) => tag.clone(),
+                None => path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .filter(|stem| !stem.is_empty())
+                    .with_context(|| {
+                        format!("cannot derive a tag from {}; pass --tag", path.display())
+                    })?,
+            };
+            Ok(Document {
+                content,
+                tag,
+                source: Some(path.display().to_string()),
+            })
+        })
+        .collect()
 }
 
 fn insert_source(args: &InsertArgs) -> &'static str {
-    if args.file_path.is_some() {
-        "file"
-    } else {
-        "text"
+    if args.text.is_none() { "file" } else { "text" }
+}
+
+/// How `--file-path` patterns match: `*` and `?` stay within one path
+/// component, and wildcards never match hidden files or directories.
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: true,
+};
+
+/// The files matching `pattern`, sorted, expanded with the `glob` crate:
+/// `*` and `?` match within one path component, `[abc]`, `[a-z]` and `[!a]`
+/// match one character, and a `**` component matches any number of
+/// directories. A path without these characters is returned as is.
+fn expand_glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let text = pattern.to_string_lossy();
+    if !text.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+    let mut matches = Vec::new();
+    let entries = glob::glob_with(&text, GLOB_OPTIONS).map_err(|err| {
+        KinicError::Validation(format!("invalid --file-path pattern {text}: {err}"))
+    })?;
+    for entry in entries {
+        let path = entry.with_context(|| format!("Failed to expand --file-path {text}"))?;
+        if path.is_file() {
+            matches.push(path);
+        }
+    }
+    matches.sort();
+    if matches.is_empty() {
+        return Err(KinicError::Validation(format!(
+            "no files match --file-path {}",
+            pattern.display()
+        ))
+        .into());
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// A scratch tree: `a.md`, `b.txt`, `.hidden.md`, `x1.md`, `y2.md`,
+    /// `notes/c.md`, `notes/deep/d.md` and `.git/e.md`.
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a.md",
+            "b.txt",
+            ".hidden.md",
+            "x1.md",
+            "y2.md",
+            "notes/c.md",
+            "notes/deep/d.md",
+            ".git/e.md",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "text").unwrap();
+        }
+        dir
+    }
+
+    fn expand(dir: &Path, pattern: &str) -> Vec<String> {
+        expand_glob(&dir.join(pattern))
+            .unwrap()
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(dir).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn star_stays_in_one_directory() {
+        let dir = tree();
+        assert_eq!(expand(dir.path(), "*.md"), ["a.md", "x1.md", "y2.md"]);
+    }
+
+    #[test]
+    fn double_star_descends_but_skips_hidden_directories() {
+        let dir = tree();
+        assert_eq!(
+            expand(dir.path(), "**/*.md"),
+            ["a.md", "notes/c.md", "notes/deep/d.md", "x1.md", "y2.md"]
+        );
+    }
+
+    #[test]
+    fn wildcards_skip_hidden_files() {
+        let dir = tree();
+        assert!(expand_glob(&dir.path().join("?hidden.md")).is_err());
+        assert!(expand_glob(&dir.path().join(".*.md")).is_err());
+        assert_eq!(expand(dir.path(), ".git/*.md"), [".git/e.md"]);
+    }
+
+    #[test]
+    fn character_classes() {
+        let dir = tree();
+        assert_eq!(expand(dir.path(), "[xy][0-9].md"), ["x1.md", "y2.md"]);
+        assert_eq!(expand(dir.path(), "[!x]?.md"), ["y2.md"]);
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        let dir = tree();
+        assert_eq!(expand(dir.path(), "?.*"), ["a.md", "b.txt"]);
+    }
+
+    #[test]
+    fn paths_without_wildcards_are_kept() {
+        let path = Path::new("missing/file.md");
+        assert_eq!(expand_glob(path).unwrap(), [path.to_path_buf()]);
+    }
+
+    #[test]
+    fn no_match_is_a_validation_error() {
+        let dir = tree();
+        let err = expand_glob(&dir.path().join("*.pdf")).unwrap_err();
+        assert!(err.to_string().contains("no files match"));
     }
 }
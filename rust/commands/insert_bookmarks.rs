@@ -0,0 +1,395 @@
+use std::{fs, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::info;
+
+use crate::{
+    cli::InsertBookmarksArgs,
+    clients::memory::MemoryClient,
+    commands::{insert::insert_document, interrupt, sync::strip_html},
+    embedding::http_client,
+    error::KinicError,
+    guard,
+    output::note,
+    payload::InsertMetadata,
+    redact::Redactor,
+    tables::Table,
+};
+
+use super::CommandContext;
+
+/// Tag of bookmarks that sit outside any folder.
+const UNFILED_TAG: &str = "bookmarks";
+
+/// Elements whose content is never page text.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "svg"];
+/// A table cell longer than this is page layout rather than data.
+const MAX_TABLE_CELL_CHARS: usize = 200;
+
+struct Bookmark {
+    url: String,
+    title: String,
+    /// Folder path, e.g. `Bookmarks bar/Rust`; empty when unfiled.
+    folder: String,
+}
+
+pub async fn handle(args: InsertBookmarksArgs, ctx: &CommandContext) -> Result<()> {
+    let html = fs::read_to_string(&args.file_path)
+        .with_context(|| format!("Failed to read {}", args.file_path.display()))?;
+    let bookmarks = parse_bookmarks(&html);
+    if bookmarks.is_empty() {
+        return Err(KinicError::Validation(format!(
+            "no http(s) bookmarks found in {}; expected a Netscape bookmark export",
+            args.file_path.display()
+        ))
+        .into());
+    }
+
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for insert-bookmarks command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    note!(
+        "Fetching {} bookmarks (up to {} at a time)...",
+        bookmarks.len(),
+        args.concurrency.max(1)
+    );
+    let permits = Arc::new(Semaphore::new(args.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let permits = Arc::clone(&permits);
+        let url = bookmark.url.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            (index, fetch_page(&url).await)
+        });
+    }
+
+    // Pages are inserted as they arrive; the canister calls stay sequential.
+    let mut redactor = Redactor::from_args(&args.redact);
+    let mut failed = 0usize;
+    let mut ingested = 0usize;
+    while let Some(joined) = tasks.join_next().await {
+        if interrupt::requested() {
+            tasks.abort_all();
+            break;
+        }
+        let (index, page) = joined.context("fetch task panicked")?;
+        let bookmark = &bookmarks[index];
+        let text = match page {
+            Ok(text) => text,
+            Err(err) => {
+                failed += 1;
+                eprintln!("- {}: FAILED ({err:#})", bookmark.url);
+                continue;
+            }
+        };
+
+        let tag = if bookmark.folder.is_empty() {
+            UNFILED_TAG
+        } else {
+            &bookmark.folder
+        };
+        let mut meta = args.meta.clone();
+        meta.push(format!("title={}", bookmark.title));
+        let metadata = InsertMetadata::new(tag, Some(bookmark.url.clone()), &meta)?;
+        let content = format!("# {}\n\n{text}", bookmark.title);
+        let inserted = async {
+            guard::check_secrets(&bookmark.url, &content, args.allow_secrets)?;
+            let content = match &mut redactor {
+                Some(redactor) => redactor.redact(&bookmark.url, &content).await?,
+                None => content,
+            };
+            insert_document(&client, &content, &metadata, args.language.as_deref()).await
+        }
+        .await;
+        match inserted {
+            Ok(chunks) => {
+                ingested += 1;
+                note!("- {} [{tag}]: {chunks} chunks", bookmark.url);
+            }
+            Err(err) => {
+                failed += 1;
+                eprintln!("- {}: FAILED ({err:#})", bookmark.url);
+            }
+        }
+    }
+
+    info!(
+        canister_id = %memory,
+        ingested,
+        failed,
+        "insert-bookmarks completed"
+    );
+    if let Some(redactor) = &redactor {
+        redactor.write_report("insert-bookmarks")?;
+    }
+    if interrupt::requested() {
+        note!("{ingested} bookmarks ingested, {failed} failed");
+        bail!(
+            "interrupted with {} of {} bookmarks not inserted",
+            bookmarks.len() - ingested - failed,
+            bookmarks.len()
+        );
+    }
+    if failed > 0 {
+        bail!("{failed} of {} bookmarks failed", bookmarks.len());
+    }
+    note!("All {ingested} bookmarks ingested.");
+    Ok(())
+}
+
+/// Bookmarks of a Netscape bookmark file, the format every browser exports:
+/// `<DT><H3>` names a folder whose entries follow in the next `<DL>`, and
+/// `<DT><A HREF=...>` is a bookmark. Non-http(s) links and repeated URLs are
+/// skipped.
+fn parse_bookmarks(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut pending_folder: Option<String> = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name = tag
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "h3" => pending_folder = Some(element_text(&mut rest, "</h3")),
+            "dl" => folders.push(pending_folder.take()),
+            "/dl" => {
+                folders.pop();
+            }
+            "a" => {
+                let title = element_text(&mut rest, "</a");
+                let Some(url) = attribute(tag, "href") else {
+                    continue;
+                };
+                let web = url.starts_with("http://") || url.starts_with("https://");
+                if !web || bookmarks.iter().any(|known| known.url == url) {
+                    continue;
+                }
+                let folder = folders
+                    .iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("/");
+                bookmarks.push(Bookmark {
+                    title: if title.is_empty() { url.clone() } else { title },
+                    url,
+                    folder,
+                });
+            }
+            _ => {}
+        }
+    }
+    bookmarks
+}
+
+/// Text up to the (case-insensitive) `closing` tag, advancing `rest` past it.
+fn element_text(rest: &mut &str, closing: &str) -> String {
+    let end = find_ignore_case(rest, closing).unwrap_or(rest.len());
+    let text = decode_entities(strip_html(&rest[..end]).trim());
+    *rest = &rest[end..];
+    text
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Value of `name` in a tag's attribute list, quoted or not.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let at = search + found;
+        search = at + name.len();
+        let preceded = lower[..at].ends_with(|c: char| c.is_whitespace());
+        let value = lower[search..].trim_start();
+        if !preceded || !value.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - value.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let raw = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split_whitespace().next().unwrap_or_default(),
+        };
+        return Some(decode_entities(raw));
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Fetches a page as plain text. HTML is reduced to its visible text; other
+/// `text/*` responses are used as they are.
+async fn fetch_page(url: &str) -> Result<String> {
+    let response = http_client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {url}"))?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/html")
+        .to_ascii_lowercase();
+    if !content_type.starts_with("text/") && !content_type.contains("html") {
+        bail!("unsupported content type {content_type}");
+    }
+    let body = response
+        .text()
+        .await
+        .with_context(|| format!("Failed to read {url}"))?;
+    let text = if content_type.contains("html") {
+        html_to_text(&body)
+    } else {
+        body
+    };
+    if text.trim().is_empty() {
+        bail!("page has no text");
+    }
+    Ok(text)
+}
+
+/// The visible text of an HTML page: scripts, styles and other
+/// `SKIPPED_ELEMENTS` are dropped, tags become spaces and entities are
+/// decoded. Data tables become markdown pipe tables between blank lines, so
+/// ingestion stores their rows with the column headers.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let html = remove_elements(html);
+    let mut parts = Vec::new();
+    let mut rest = html.as_str();
+    while let Some(start) = find_ignore_case(rest, "<table") {
+        let end = find_ignore_case(&rest[start..], "</table>")
+            .map_or(rest.len(), |at| start + at + "</table>".len());
+        parts.push(visible_text(&rest[..start]));
+        let element = &rest[start..end];
+        match html_table(element) {
+            Some(table) => {
+                // The caption, if any, precedes the first row.
+                let rows = tag_starts(element, "tr").first().copied().unwrap_or(0);
+                parts.push(visible_text(&element[..rows]));
+                parts.push(table.to_markdown());
+            }
+            None => parts.push(visible_text(element)),
+        }
+        rest = &rest[end..];
+    }
+    parts.push(visible_text(rest));
+    parts.retain(|part| !part.is_empty());
+    parts.join("\n\n")
+}
+
+fn visible_text(html: &str) -> String {
+    decode_entities(&strip_html(html))
+}
+
+/// A `<table>` element as a [`Table`], or `None` when it looks like page
+/// layout rather than data: a single row or column, or long text in cells.
+/// The first row gives the headers when it is all `<th>` cells.
+fn html_table(html: &str) -> Option<Table> {
+    let row_starts = tag_starts(html, "tr");
+    let mut rows: Vec<(bool, Vec<String>)> = Vec::new();
+    for (index, &start) in row_starts.iter().enumerate() {
+        let row = &html[start..row_starts.get(index + 1).copied().unwrap_or(html.len())];
+        let mut cells: Vec<(usize, bool)> = tag_starts(row, "td")
+            .into_iter()
+            .map(|at| (at, false))
+            .chain(tag_starts(row, "th").into_iter().map(|at| (at, true)))
+            .collect();
+        cells.sort_unstable();
+        let texts: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(cell, &(at, _))| {
+                let end = cells.get(cell + 1).map_or(row.len(), |&(next, _)| next);
+                visible_text(&row[at..end]).trim().to_string()
+            })
+            .collect();
+        if texts
+            .iter()
+            .any(|text| text.chars().count() > MAX_TABLE_CELL_CHARS)
+        {
+            return None;
+        }
+        if !texts.is_empty() {
+            rows.push((cells.iter().all(|&(_, header)| header), texts));
+        }
+    }
+    let columns = rows.iter().map(|(_, cells)| cells.len()).max()?;
+    if rows.len() < 2 || columns < 2 {
+        return None;
+    }
+    let headers = match rows.first() {
+        Some((true, _)) => rows.remove(0).1,
+        _ => vec![String::new(); columns],
+    };
+    Some(Table::new(
+        headers,
+        rows.into_iter().map(|(_, cells)| cells).collect(),
+    ))
+}
+
+/// Offsets of the `<name>` and `<name ...>` start tags in `html`.
+fn tag_starts(html: &str, name: &str) -> Vec<usize> {
+    let open = format!("<{name}");
+    let mut starts = Vec::new();
+    let mut search = 0;
+    while let Some(found) = find_ignore_case(&html[search..], &open) {
+        let at = search + found;
+        search = at + open.len();
+        if html[search..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            starts.push(at);
+        }
+    }
+    starts
+}
+
+/// Removes `SKIPPED_ELEMENTS` and their content.
+fn remove_elements(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut kept = String::with_capacity(html.len());
+    let mut cursor = 0;
+    while let Some((start, element)) = SKIPPED_ELEMENTS
+        .iter()
+        .filter_map(|element| {
+            lower[cursor..]
+                .find(&format!("<{element}"))
+                .map(|at| (cursor + at, *element))
+        })
+        .min()
+    {
+        kept.push_str(&html[cursor..start]);
+        let closing = format!("</{element}>");
+        cursor = lower[start..]
+            .find(&closing)
+            .map_or(html.len(), |at| start + at + closing.len());
+    }
+    kept.push_str(&html[cursor..]);
+    kept
+}
@@ -0,0 +1,85 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::InsertImageArgs,
+    clients::memory::MemoryClient,
+    embedding::{describe_image, fetch_embedding},
+    error::KinicError,
+    output::note,
+    payload::InsertMetadata,
+};
+
+use super::CommandContext;
+
+/// Larger images are rejected before upload; vision APIs rarely accept more.
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+pub async fn handle(args: InsertImageArgs, ctx: &CommandContext) -> Result<()> {
+    let mime_type = mime_type(&args.file_path)?;
+    let image = fs::read(&args.file_path)
+        .with_context(|| format!("Failed to read image {}", args.file_path.display()))?;
+    if image.len() > MAX_IMAGE_BYTES {
+        return Err(KinicError::Validation(format!(
+            "{} is {} bytes; images are limited to {MAX_IMAGE_BYTES} bytes",
+            args.file_path.display(),
+            image.len()
+        ))
+        .into());
+    }
+
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for insert-image command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    let description = describe_image(&image, mime_type, args.prompt.as_deref()).await?;
+    note!("Description: {description}");
+
+    // The image itself stays where it is; the chunk references it by path
+    // and content hash.
+    let mut meta = args.meta.clone();
+    meta.push(format!("media_type={mime_type}"));
+    meta.push(format!("image_sha256={}", content_hash_bytes(&image)));
+    let metadata =
+        InsertMetadata::new(&args.tag, Some(args.file_path.display().to_string()), &meta)?;
+    let embedding = fetch_embedding(&description).await?;
+    let id = client
+        .insert(embedding, &metadata.payload(&description, 0, None))
+        .await?;
+
+    info!(
+        canister_id = %memory,
+        tag = %args.tag,
+        source = %args.file_path.display(),
+        id,
+        "insert-image completed"
+    );
+    Ok(())
+}
+
+fn mime_type(path: &Path) -> Result<&'static str> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    Ok(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => {
+            return Err(KinicError::Validation(format!(
+                "unsupported image type for {}; use PNG, JPEG, GIF or WebP",
+                path.display()
+            ))
+            .into());
+        }
+    })
+}
+
+fn content_hash_bytes(bytes: &[u8]) -> String {
+    hex::encode(ring::digest::digest(&ring::digest::SHA256, bytes))
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use mail_parser::{MessageParser, mailbox::mbox::MessageIterator};
+use tracing::info;
+
+use crate::{
+    cli::InsertMailArgs,
+    clients::memory::MemoryClient,
+    commands::{insert::ChunkPipeline, interrupt},
+    guard,
+    output::note,
+    payload::InsertMetadata,
+    redact::Redactor,
+};
+
+use super::CommandContext;
+
+/// Reply and forward markers stripped before subjects are compared.
+const SUBJECT_PREFIXES: &[&str] = &["re:", "fw:", "fwd:", "aw:", "wg:", "sv:", "vs:"];
+
+/// The parts of a message that are kept, already decoded and cleaned.
+struct Mail {
+    message_id: Option<String>,
+    /// `References` followed by `In-Reply-To`, oldest first.
+    parents: Vec<String>,
+    subject: String,
+    from: String,
+    /// Unix seconds; messages without a date sort first.
+    timestamp: i64,
+    date: Option<String>,
+    body: String,
+}
+
+struct Thread {
+    subject: String,
+    root_id: Option<String>,
+    messages: Vec<Mail>,
+}
+
+pub async fn handle(args: InsertMailArgs, ctx: &CommandContext) -> Result<()> {
+    let mut mails = Vec::new();
+    let mut skipped = 0;
+    if let Some(path) = &args.mbox {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        for message in MessageIterator::new(BufReader::new(file)) {
+            let message = message.with_context(|| format!("Failed to read {}", path.display()))?;
+            match parse_mail(message.contents()) {
+                Some(mail) => mails.push(mail),
+                None => skipped += 1,
+            }
+        }
+    }
+    for path in &args.eml {
+        let raw = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        match parse_mail(&raw) {
+            Some(mail) => mails.push(mail),
+            None => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        eprintln!("warning: skipped {skipped} messages that could not be parsed or had no text");
+    }
+
+    let threads = group_threads(mails);
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for insert-mail command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let source_name = source_name(&args);
+
+    let mut redactor = Redactor::from_args(&args.redact);
+    let mut documents = Vec::with_capacity(threads.len());
+    for thread in &threads {
+        let first = &thread.messages[0];
+        let mut participants: Vec<&str> = Vec::new();
+        for mail in &thread.messages {
+            if !participants.contains(&mail.from.as_str()) {
+                participants.push(&mail.from);
+            }
+        }
+
+        let mut subject = thread.subject.clone();
+        let mut from = participants.join(", ");
+        if let Some(redactor) = &mut redactor {
+            let label = thread_label(thread);
+            subject = redactor.redact_field(&label, &subject);
+            from = redactor.redact_field(&label, &from);
+        }
+        let mut meta = args.meta.clone();
+        meta.push(format!("subject={subject}"));
+        meta.push(format!("from={from}"));
+        if let Some(date) = &first.date {
+            meta.push(format!("date={date}"));
+        }
+        meta.push(format!("messages={}", thread.messages.len()));
+        let source = match &thread.root_id {
+            Some(id) => format!("{source_name}#<{id}>"),
+            None => source_name.clone(),
+        };
+        let metadata = InsertMetadata::new(&args.tag, Some(source), &meta)?;
+        documents.push((thread, metadata));
+    }
+
+    let mut texts: Vec<String> = documents
+        .iter()
+        .map(|(thread, _)| thread_text(thread))
+        .collect();
+    for ((thread, _), text) in documents.iter().zip(&mut texts) {
+        let label = thread_label(thread);
+        guard::check_secrets(&label, text, args.allow_secrets)?;
+        if let Some(redactor) = &mut redactor {
+            *text = redactor.redact(&label, text).await?;
+        }
+    }
+    if let Some(redactor) = &redactor {
+        redactor.write_report("insert-mail")?;
+    }
+
+    // The next threads are chunked while the current one is inserted.
+    let mut pipeline = ChunkPipeline::start(texts, args.language.as_deref());
+    let mut total_chunks = 0;
+    let mut ingested = 0;
+    for (thread, metadata) in documents {
+        if interrupt::requested() {
+            break;
+        }
+        let chunks = pipeline
+            .insert_next(&client, |sentence, index| {
+                metadata.payload(sentence, index, None)
+            })
+            .await?;
+        note!(
+            "{} ({} messages, {chunks} chunks)",
+            thread.subject,
+            thread.messages.len()
+        );
+        total_chunks += chunks;
+        ingested += 1;
+    }
+
+    info!(
+        canister_id = %memory,
+        tag = %args.tag,
+        threads = ingested,
+        chunks = total_chunks,
+        "insert-mail completed"
+    );
+    note!("{ingested} threads ingested ({total_chunks} chunks)");
+    if ingested < threads.len() {
+        bail!(
+            "interrupted with {} of {} threads not inserted",
+            threads.len() - ingested,
+            threads.len()
+        );
+    }
+    Ok(())
+}
+
+/// How a thread is named in secret warnings and the redaction report.
+fn thread_label(thread: &Thread) -> String {
+    format!("thread \"{}\"", thread.subject)
+}
+
+fn source_name(args: &InsertMailArgs) -> String {
+    match (&args.mbox, args.eml.as_slice()) {
+        (Some(mbox), _) => mbox.display().to_string(),
+        (None, [single]) => single.display().to_string(),
+        (None, _) => "eml".to_string(),
+    }
+}
+
+fn parse_mail(raw: &[u8]) -> Option<Mail> {
+    let message = MessageParser::default().parse(raw)?;
+    let body = clean_body(&message.body_text(0)?);
+    if body.is_empty() {
+        return None;
+    }
+    let mut parents: Vec<String> = Vec::new();
+    for header in [message.references(), message.in_reply_to()] {
+        for id in header.as_text_list().unwrap_or_default() {
+            if !parents.iter().any(|known| known == id) {
+                parents.push(id.to_string());
+            }
+        }
+    }
+    let from = message
+        .from()
+        .and_then(|from| from.first())
+        .map(|addr| match (addr.name(), addr.address()) {
+            (Some(name), Some(address)) => format!("{name} <{address}>"),
+            (Some(name), None) => name.to_string(),
+            (None, address) => address.unwrap_or_default().to_string(),
+        })
+        .filter(|from| !from.is_empty())
+        .unwrap_or_else(|| "unknown sender".to_string());
+    Some(Mail {
+        message_id: message.message_id().map(str::to_string),
+        parents,
+        subject: message
+            .subject()
+            .map(|subject| subject.trim().to_string())
+            .filter(|subject| !subject.is_empty())
+            .unwrap_or_else(|| "(no subject)".to_string()),
+        from,
+        timestamp: message.date().map_or(0, |date| date.to_timestamp()),
+        date: message.date().map(|date| date.to_rfc3339()),
+        body,
+    })
+}
+
+/// Drops what a reply repeats or appends: `>` quoted lines, the
+/// "On ... wrote:" attribution above them, forwarded or Outlook-style
+/// original messages, and the signature after the `-- ` delimiter.
+fn clean_body(text: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_end();
+        if trimmed == "--" || trimmed.starts_with("-----Original Message-----") {
+            break;
+        }
+        if trimmed.trim_start().starts_with('>') {
+            continue;
+        }
+        if trimmed.starts_with("On ") && trimmed.ends_with("wrote:") {
+            continue;
+        }
+        kept.push(trimmed);
+    }
+    // Collapse the blank runs left where quotes were removed.
+    let mut body = String::with_capacity(text.len());
+    let mut blank = false;
+    for line in kept {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !body.is_empty() {
+            body.push('\n');
+        }
+        blank = false;
+        body.push_str(line);
+        body.push('\n');
+    }
+    body.trim_end().to_string()
+}
+
+/// Groups messages by `References`/`In-Reply-To`, falling back to the
+/// subject without reply prefixes for clients that drop those headers.
+fn group_threads(mut mails: Vec<Mail>) -> Vec<Thread> {
+    mails.sort_by_key(|mail| mail.timestamp);
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    let mut by_subject: HashMap<String, usize> = HashMap::new();
+    for mail in mails {
+        let subject_key = normalize_subject(&mail.subject);
+        let index = mail
+            .parents
+            .iter()
+            .rev()
+            .find_map(|id| by_id.get(id).copied())
+            .or_else(|| by_subject.get(&subject_key).copied());
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let subject = strip_reply_prefixes(&mail.subject);
+                threads.push(Thread {
+                    subject: if subject.is_empty() {
+                        mail.subject.clone()
+                    } else {
+                        subject
+                    },
+                    root_id: mail.message_id.clone(),
+                    messages: Vec::new(),
+                });
+                threads.len() - 1
+            }
+        };
+        if let Some(id) = &mail.message_id {
+            by_id.insert(id.clone(), index);
+        }
+        by_subject.entry(subject_key).or_insert(index);
+        threads[index].messages.push(mail);
+    }
+    threads
+}
+
+fn normalize_subject(subject: &str) -> String {
+    strip_reply_prefixes(subject).to_lowercase()
+}
+
+/// The subject with any stack of `Re:`/`Fwd:` prefixes removed.
+fn strip_reply_prefixes(subject: &str) -> String {
+    let mut rest = subject.trim();
+    while let Some(prefix) = SUBJECT_PREFIXES.iter().find(|prefix| {
+        rest.get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+    }) {
+        rest = rest[prefix.len()..].trim_start();
+    }
+    rest.to_string()
+}
+
+/// The thread as one document: a subject heading, then each message with
+/// its sender and date, oldest first.
+fn thread_text(thread: &Thread) -> String {
+    let mut text = format!("# {}\n", thread.subject);
+    for mail in &thread.messages {
+        text.push_str(&format!(
+            "\n## {} ({})\n\n{}\n",
+            mail.from,
+            mail.date.as_deref().unwrap_or("undated"),
+            mail.body
+        ));
+    }
+    text
+}
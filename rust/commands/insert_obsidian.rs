@@ -0,0 +1,344 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::InsertObsidianArgs,
+    clients::memory::MemoryClient,
+    commands::{insert::ChunkPipeline, interrupt},
+    guard,
+    jobs::SyncState,
+    output::note,
+    payload::{InsertMetadata, is_reserved_key},
+    redact::Redactor,
+};
+
+use super::CommandContext;
+
+/// Folder Obsidian's core Templates plugin uses unless configured otherwise.
+const DEFAULT_TEMPLATES_DIR: &str = "Templates";
+
+struct Note {
+    /// Path relative to the vault, with `/` separators; also the tag.
+    path: String,
+    file: PathBuf,
+    mtime: u64,
+}
+
+pub async fn handle(args: InsertObsidianArgs, ctx: &CommandContext) -> Result<()> {
+    let vault = fs::canonicalize(&args.vault)
+        .with_context(|| format!("Failed to open vault {}", args.vault.display()))?;
+    if !vault.join(".obsidian").is_dir() {
+        note!(
+            "warning: {} has no .obsidian folder; treating it as a vault anyway",
+            vault.display()
+        );
+    }
+    let mut excluded: Vec<PathBuf> = args.exclude.iter().map(|dir| vault.join(dir)).collect();
+    excluded.push(vault.join(templates_dir(&vault)));
+
+    let mut notes = Vec::new();
+    collect_notes(&vault, &vault, &excluded, &mut notes)?;
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+    let index = LinkIndex::new(&notes);
+
+    let memory =
+        Principal::from_text(&args.memory_id).context("Failed to parse memory canister id")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    // Incremental state lives next to the sync jobs' state, keyed by memory
+    // and vault, with note mtimes as change markers.
+    let state_key = format!("obsidian:{memory}:{}", vault.display());
+    let mut state = SyncState::load()?;
+    let mut redactor = Redactor::from_args(&args.redact);
+    let mut unchanged = 0;
+    let mut changed = Vec::new();
+    for note in &notes {
+        let mtime = note.mtime.to_string();
+        let seen = state
+            .jobs
+            .get(&state_key)
+            .and_then(|job| job.items.get(&note.path));
+        if !args.full && seen == Some(&mtime) {
+            unchanged += 1;
+            continue;
+        }
+
+        let raw = fs::read_to_string(&note.file)
+            .with_context(|| format!("Failed to read {}", note.file.display()))?;
+        let (frontmatter, body) = split_frontmatter(&raw);
+        let (text, links) = resolve_wikilinks(body, &index);
+        if text.trim().is_empty() {
+            unchanged += 1;
+            continue;
+        }
+        guard::check_secrets(&note.path, &text, args.allow_secrets)?;
+        let (text, frontmatter) = match &mut redactor {
+            Some(redactor) => (
+                redactor.redact(&note.path, &text).await?,
+                frontmatter
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = redactor.redact_field(&note.path, &value);
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            None => (text, frontmatter),
+        };
+
+        let mut meta: Vec<String> = frontmatter
+            .into_iter()
+            .filter(|(key, _)| !is_reserved_key(key))
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        if !links.is_empty() {
+            meta.push(format!("links={}", links.join(", ")));
+        }
+        let metadata = InsertMetadata::new(&note.path, Some(note.path.clone()), &meta)?;
+        changed.push((note, mtime, text, metadata));
+    }
+
+    if let Some(redactor) = &redactor {
+        redactor.write_report("insert-obsidian")?;
+    }
+
+    // The next notes are chunked while the current one is inserted.
+    let mut pipeline = ChunkPipeline::start(
+        changed.iter().map(|(_, _, text, _)| text.clone()).collect(),
+        args.language.as_deref(),
+    );
+    let total = changed.len();
+    let mut ingested = 0;
+    for (note, mtime, _, metadata) in changed {
+        if interrupt::requested() {
+            break;
+        }
+        let chunks = pipeline
+            .insert_next(&client, |sentence, index| {
+                metadata.payload(sentence, index, None)
+            })
+            .await?;
+        note!("{} ({chunks} chunks)", note.path);
+
+        state
+            .jobs
+            .entry(state_key.clone())
+            .or_default()
+            .items
+            .insert(note.path.clone(), mtime);
+        state.save()?;
+        ingested += 1;
+    }
+
+    info!(
+        canister_id = %memory,
+        vault = %vault.display(),
+        ingested,
+        unchanged,
+        "insert-obsidian completed"
+    );
+    note!("{ingested} notes ingested, {unchanged} unchanged");
+    if ingested < total {
+        bail!(
+            "interrupted with {} changed notes left; run the command again to ingest them",
+            total - ingested
+        );
+    }
+    Ok(())
+}
+
+/// Templates folder from `.obsidian/templates.json`, if the plugin is set up.
+fn templates_dir(vault: &Path) -> String {
+    #[derive(serde::Deserialize)]
+    struct TemplatesConfig {
+        folder: Option<String>,
+    }
+    fs::read_to_string(vault.join(".obsidian/templates.json"))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<TemplatesConfig>(&raw).ok())
+        .and_then(|config| config.folder)
+        .filter(|folder| !folder.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_TEMPLATES_DIR.to_string())
+}
+
+/// Markdown notes under `dir`. Attachments (anything not `.md`), hidden
+/// folders such as `.obsidian` and `.trash`, and `excluded` folders are
+/// skipped.
+fn collect_notes(
+    vault: &Path,
+    dir: &Path,
+    excluded: &[PathBuf],
+    notes: &mut Vec<Note>,
+) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if !excluded.contains(&path) {
+                collect_notes(vault, &path, excluded, notes)?;
+            }
+            continue;
+        }
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        let mtime = entry
+            .metadata()?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|age| age.as_secs())
+            .unwrap_or_default();
+        let relative = path.strip_prefix(vault).unwrap_or(&path);
+        notes.push(Note {
+            path: relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            file: path,
+            mtime,
+        });
+    }
+    Ok(())
+}
+
+/// Splits a leading `---` YAML block into flat `key: value` pairs. Lists
+/// (`[a, b]` or `- item` lines) are joined with ", "; nested maps are
+/// ignored.
+fn split_frontmatter(raw: &str) -> (Vec<(String, String)>, &str) {
+    let Some(rest) = raw
+        .strip_prefix("---\n")
+        .or_else(|| raw.strip_prefix("---\r\n"))
+    else {
+        return (Vec::new(), raw);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (Vec::new(), raw);
+    };
+    let block = &rest[..end];
+    let body = rest[end + 4..].trim_start_matches(['\r', '\n']);
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in block.lines() {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if let Some((_, value)) = fields.last_mut() {
+                if !value.is_empty() {
+                    value.push_str(", ");
+                }
+                value.push_str(unquote(item));
+            }
+            continue;
+        }
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        let value = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Some(list) => list
+                .split(',')
+                .map(|item| unquote(item.trim()))
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => unquote(value).to_string(),
+        };
+        fields.push((key.to_string(), value));
+    }
+    fields.retain(|(_, value)| !value.is_empty());
+    (fields, body)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+/// Finds the note a wikilink points to, the way Obsidian does: by path when
+/// the link has one, otherwise by file name.
+struct LinkIndex {
+    by_path: HashMap<String, String>,
+    by_name: HashMap<String, String>,
+}
+
+impl LinkIndex {
+    fn new(notes: &[Note]) -> Self {
+        let mut by_path = HashMap::new();
+        let mut by_name = HashMap::new();
+        for note in notes {
+            let without_ext = note.path.trim_end_matches(".md").to_lowercase();
+            let name = without_ext
+                .rsplit('/')
+                .next()
+                .unwrap_or(&without_ext)
+                .to_string();
+            by_path.insert(without_ext.clone(), note.path.clone());
+            // An ambiguous name resolves to the first note in path order.
+            by_name.entry(name).or_insert_with(|| note.path.clone());
+        }
+        Self { by_path, by_name }
+    }
+
+    fn resolve(&self, target: &str) -> Option<&String> {
+        let key = target.trim().trim_end_matches(".md").to_lowercase();
+        self.by_path.get(&key).or_else(|| self.by_name.get(&key))
+    }
+}
+
+/// Replaces `[[target|alias]]` with its display text and drops `![[embeds]]`.
+/// Returns the text and the vault paths of the linked notes that exist.
+fn resolve_wikilinks(body: &str, index: &LinkIndex) -> (String, Vec<String>) {
+    let mut text = String::with_capacity(body.len());
+    let mut links: Vec<String> = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        let Some(len) = rest[start..].find("]]") else {
+            break;
+        };
+        let embed = rest[..start].ends_with('!');
+        let before = if embed {
+            &rest[..start - 1]
+        } else {
+            &rest[..start]
+        };
+        text.push_str(before);
+
+        let inner = &rest[start + 2..start + len];
+        let (target, alias) = match inner.split_once('|') {
+            Some((target, alias)) => (target, Some(alias)),
+            None => (inner, None),
+        };
+        let note = target.split('#').next().unwrap_or(target);
+        if let Some(path) = index.resolve(note).filter(|path| !links.contains(path)) {
+            links.push(path.clone());
+        }
+        if !embed {
+            text.push_str(alias.unwrap_or(target).trim());
+        }
+        rest = &rest[start + len + 2..];
+    }
+    text.push_str(rest);
+    (text, links)
+}
@@ -3,55 +3,63 @@ use ic_agent::export::Principal;
 use tracing::info;
 
 use crate::{
-    cli::InsertPdfArgs, clients::memory::MemoryClient, commands::convert_pdf::pdf_to_markdown,
-    embedding::late_chunking,
+    cli::InsertPdfArgs,
+    clients::memory::MemoryClient,
+    commands::{convert_pdf::pdf_to_markdown, insert::ChunkPipeline},
+    guard,
+    payload::{InsertMetadata, PageLocator},
+    redact::Redactor,
 };
 
 use super::CommandContext;
 
 pub async fn handle(args: InsertPdfArgs, ctx: &CommandContext) -> Result<()> {
     let client = build_memory_client(&args.memory_id, ctx).await?;
-    let markdown = pdf_to_markdown(&args.file_path).map_err(|e| {
+    let mut markdown = pdf_to_markdown(&args.file_path).map_err(|e| {
         anyhow!(
             "Failed to convert PDF {} to markdown: {e}",
             args.file_path.display()
         )
     })?;
+    guard::check_secrets(
+        &args.file_path.display().to_string(),
+        &markdown,
+        args.allow_secrets,
+    )?;
+    if let Some(mut redactor) = Redactor::from_args(&args.redact) {
+        markdown = redactor
+            .redact(&args.file_path.display().to_string(), &markdown)
+            .await?;
+        redactor.write_report("insert-pdf")?;
+    }
 
-    let chunks = late_chunking(&markdown).await?;
+    let mut pipeline = ChunkPipeline::start(vec![markdown.clone()], args.language.as_deref());
+    let metadata = InsertMetadata::new(
+        &args.tag,
+        Some(args.file_path.display().to_string()),
+        &args.meta,
+    )?;
+    let mut pages = PageLocator::new(&markdown);
 
+    let chunk_count = pipeline
+        .insert_next(&client, |sentence, index| {
+            metadata.payload(sentence, index, pages.page_of(sentence))
+        })
+        .await?;
     info!(
         canister_id = %client.canister_id(),
-        chunk_count = chunks.len(),
+        chunk_count,
         tag = %args.tag,
         source = %args.file_path.display(),
-        "insert-pdf prepared embeddings"
+        "insert-pdf stored chunks"
     );
 
-    for (index, chunk) in chunks.into_iter().enumerate() {
-        let payload = format_chunk_text(&args.tag, &chunk.sentence);
-        info!(
-            chunk_index = index,
-            sentence_preview = %chunk
-                .sentence
-                .chars()
-                .take(40)
-                .collect::<String>(),
-            "inserting chunk"
-        );
-        client.insert(chunk.embedding, &payload).await?;
-    }
-
     Ok(())
 }
 
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for insert-pdf command")?;
     Ok(MemoryClient::new(agent, memory))
 }
-
-fn format_chunk_text(tag: &str, sentence: &str) -> String {
-    serde_json::json!({ "tag": tag, "sentence": sentence }).to_string()
-}
@@ -1,16 +1,15 @@
 use anyhow::{Context, Result, bail};
 use ic_agent::export::Principal;
-use serde_json::json;
 use tracing::info;
 
-use crate::{cli::InsertRawArgs, clients::memory::MemoryClient};
+use crate::{cli::InsertRawArgs, clients::memory::MemoryClient, payload::InsertMetadata};
 
 use super::CommandContext;
 
 pub async fn handle(args: InsertRawArgs, ctx: &CommandContext) -> Result<()> {
     let client = build_memory_client(&args.memory_id, ctx).await?;
     let embedding = parse_embedding(&args.embedding)?;
-    let payload = format_chunk_text(&args.tag, &args.text);
+    let payload = InsertMetadata::new(&args.tag, None, &args.meta)?.payload(&args.text, 0, None);
 
     info!(
         canister_id = %client.canister_id(),
@@ -24,7 +23,7 @@ pub async fn handle(args: InsertRawArgs, ctx: &CommandContext) -> Result<()> {
 }
 
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for insert-raw command")?;
     Ok(MemoryClient::new(agent, memory))
@@ -38,7 +37,3 @@ fn parse_embedding(raw: &str) -> Result<Vec<f32>> {
     }
     Ok(parsed)
 }
-
-fn format_chunk_text(tag: &str, sentence: &str) -> String {
-    json!({ "tag": tag, "sentence": sentence }).to_string()
-}
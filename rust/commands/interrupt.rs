@@ -0,0 +1,50 @@
+//! Ctrl-C handling for long ingestion. The first Ctrl-C only asks the
+//! running command to stop at its next safe point, so an update call in
+//! flight completes and the resume state stays consistent; a second Ctrl-C
+//! exits at once.
+
+use std::{
+    sync::{
+        Once,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::output::note;
+
+/// Exit status of a process killed by SIGINT, used for the second Ctrl-C.
+const SIGINT_EXIT_CODE: i32 = 130;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static WATCH: Once = Once::new();
+
+/// Starts listening for Ctrl-C; later calls do nothing. Until then Ctrl-C
+/// kills the process as usual.
+pub(crate) fn watch() {
+    WATCH.call_once(|| {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            REQUESTED.store(true, Ordering::SeqCst);
+            note!("\nStopping after the current insert; press Ctrl-C again to quit immediately.");
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(SIGINT_EXIT_CODE);
+            }
+        });
+    });
+}
+
+/// Whether Ctrl-C was pressed since [`watch`] was called.
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Resolves once Ctrl-C was pressed.
+pub(crate) async fn wait() {
+    while !requested() {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
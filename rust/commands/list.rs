@@ -1,43 +1,105 @@
 use anyhow::Result;
-use ic_agent::export::Principal;
+use futures::future::join_all;
+use serde::Serialize;
 use tracing::info;
 
 use crate::{
     cli::ListArgs,
     clients::launcher::{LauncherClient, State},
+    output::note,
 };
 
 use super::CommandContext;
 
-pub async fn handle(_args: ListArgs, ctx: &CommandContext) -> Result<()> {
-    let agent = ctx.agent_factory.build().await?;
+pub async fn handle(args: ListArgs, ctx: &CommandContext) -> Result<()> {
+    let agent = ctx.agent().await?;
     let client = LauncherClient::new(agent);
     let states = client.list_memories().await?;
 
-    let principals: Vec<Principal> = states
+    let states: Vec<&State> = states
         .iter()
-        .filter_map(memory_principal)
-        .cloned()
+        .filter(|state| args.all_states || !matches!(state, State::Empty(_)))
         .collect();
-
-    if principals.is_empty() {
-        println!("No memories found.");
-    } else {
-        println!("Memories:");
-        for principal in principals {
-            println!("- {principal}");
+    // Names and descriptions are a nicety: fetch them side by side, and list
+    // the memories without them when the launcher doesn't answer.
+    let lookups = states.iter().map(|state| {
+        let principal = state.principal().map(|p| p.to_text());
+        let client = &client;
+        async move {
+            match principal {
+                Some(pid) => client.fetch_metadata(&pid).await,
+                None => Ok(None),
+            }
         }
+    });
+    let metadata = join_all(lookups).await;
+    if let Some(err) = metadata.iter().find_map(|result| result.as_ref().err()) {
+        eprintln!("warning: could not fetch memory names and descriptions: {err:#}");
+    }
+
+    let mut rows = Vec::new();
+    for (state, metadata) in states.into_iter().zip(metadata) {
+        let principal = state.principal().map(|p| p.to_text());
+        let metadata = metadata.ok().flatten();
+        rows.push(MemoryRow {
+            state: state.label(),
+            principal,
+            name: metadata
+                .as_ref()
+                .map(|m| m.name.clone())
+                .filter(|n| !n.is_empty()),
+            created_at: metadata.as_ref().and_then(|m| m.created_at.clone()),
+            description: metadata.map(|m| m.description).filter(|d| !d.is_empty()),
+            detail: state.detail().map(str::to_string),
+        });
+    }
+
+    info!(count = rows.len(), "listed memories");
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        note!("No memories found.");
+        return Ok(());
     }
 
-    info!("listed memories");
+    println!(
+        "{:<13} {:<27} {:<24} {:<10} DESCRIPTION",
+        "STATE", "PRINCIPAL", "NAME", "CREATED"
+    );
+    for row in &rows {
+        let description = row
+            .description
+            .as_deref()
+            .or(row.detail.as_deref())
+            .unwrap_or("-");
+        // The date is enough to tell memories apart; --json has the time.
+        let created = row
+            .created_at
+            .as_deref()
+            .and_then(|at| at.get(..10))
+            .unwrap_or("-");
+        println!(
+            "{:<13} {:<27} {:<24} {:<10} {}",
+            row.state,
+            row.principal.as_deref().unwrap_or("-"),
+            row.name.as_deref().unwrap_or("-"),
+            created,
+            description
+        );
+    }
     Ok(())
 }
 
-fn memory_principal(state: &State) -> Option<&Principal> {
-    match state {
-        State::Installation(principal, _)
-        | State::SettingUp(principal)
-        | State::Running(principal) => Some(principal),
-        _ => None,
-    }
+#[derive(Serialize)]
+struct MemoryRow {
+    state: &'static str,
+    principal: Option<String>,
+    name: Option<String>,
+    created_at: Option<String>,
+    description: Option<String>,
+    detail: Option<String>,
 }
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::MaterializeArgs,
+    clients::memory::MemoryClient,
+    embedding::{fetch_embedding, fetch_embeddings},
+    error::KinicError,
+    output::note,
+    payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+/// Chunks embedded per embedding API request when copying.
+const EMBED_BATCH: usize = 32;
+
+pub async fn handle(args: MaterializeArgs, ctx: &CommandContext) -> Result<()> {
+    if args.top_k == 0 {
+        return Err(KinicError::Validation("--top-k must be greater than 0".to_string()).into());
+    }
+    let from = Principal::from_text(&args.from).context("Failed to parse --from canister id")?;
+    let to = Principal::from_text(&args.to).context("Failed to parse --to canister id")?;
+    if from == to {
+        return Err(KinicError::Validation(
+            "--to must name a different memory than --from".to_string(),
+        )
+        .into());
+    }
+    let agent = ctx.agent().await?;
+    let source = MemoryClient::new(agent.clone(), from);
+
+    let embedding = fetch_embedding(&args.query).await?;
+    let mut hits = source.search_top_k(embedding, args.top_k).await?;
+    if let Some(min_score) = args.min_score {
+        hits.retain(|hit| hit.score >= min_score);
+    }
+    // Insertion order keeps the chunks of one document adjacent in the new
+    // memory, so neighbor expansion still works there. Hits without ids (the
+    // memory has no `search_paged`) keep their ranking order.
+    hits.sort_by_key(|hit| hit.id);
+    note!(
+        "{} chunks of {from} match \"{}\"{}.",
+        hits.len(),
+        args.query,
+        if args.dry_run { "" } else { "; copying" }
+    );
+    if args.dry_run {
+        for hit in &hits {
+            let id = hit.id.map(|id| id.to_string()).unwrap_or_default();
+            println!("{id}\t{:.4}\t{}", hit.score, hit.text);
+        }
+        return Ok(());
+    }
+
+    let target = MemoryClient::new(agent, to);
+    let mut copied = 0usize;
+    for batch in hits.chunks(EMBED_BATCH) {
+        // The canister cannot return a stored vector by id, so every chunk
+        // is embedded again, from its sentence when it is a chunk payload.
+        let texts: Vec<String> = batch
+            .iter()
+            .map(|hit| {
+                ChunkPayload::parse(&hit.text)
+                    .map(|payload| payload.sentence)
+                    .unwrap_or_else(|| hit.text.clone())
+            })
+            .collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let embeddings = fetch_embeddings(&texts).await?;
+        for (hit, embedding) in batch.iter().zip(embeddings) {
+            let new_id = target.insert(embedding, &hit.text).await?;
+            info!(from = ?hit.id, to = new_id, "copied chunk");
+            copied += 1;
+        }
+        note!("- copied {copied} of {} chunks", hits.len());
+    }
+    info!(
+        source = %from,
+        target = %to,
+        copied,
+        "materialize completed"
+    );
+    note!("Copied {copied} chunks into {to}.");
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::MigratePayloadsArgs,
+    clients::memory::MemoryClient,
+    embedding::fetch_embeddings,
+    error::KinicError,
+    output::note,
+    payload::{ChunkPayload, PAYLOAD_VERSION},
+};
+
+use super::CommandContext;
+
+/// Chunks embedded per embedding API request when copying.
+const EMBED_BATCH: usize = 32;
+
+pub async fn handle(args: MigratePayloadsArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for migrate-payloads command")?;
+    let agent = ctx.agent().await?;
+    let client = MemoryClient::new(agent.clone(), memory);
+
+    note!("Reading the chunks of {memory}...");
+    let chunks = client.all_chunks().await?;
+    let mut payloads = Vec::with_capacity(chunks.len());
+    let mut legacy = 0usize;
+    let mut unreadable = 0usize;
+    for (id, raw) in &chunks {
+        match ChunkPayload::parse(raw) {
+            Some(payload) => {
+                if payload.v < PAYLOAD_VERSION {
+                    legacy += 1;
+                }
+                payloads.push((*id, payload));
+            }
+            None => unreadable += 1,
+        }
+    }
+    println!(
+        "{} chunks: {} at v{PAYLOAD_VERSION}, {legacy} older, {unreadable} not chunk payloads",
+        chunks.len(),
+        payloads.len() - legacy
+    );
+    info!(
+        canister_id = %memory,
+        chunks = chunks.len(),
+        legacy,
+        unreadable,
+        "payload scan completed"
+    );
+
+    let Some(target) = &args.into else {
+        if legacy > 0 {
+            note!(
+                "The memory canister cannot rewrite or delete a chunk, so payloads are not upgraded in place. \
+                 Pass --into <memory-id> to copy every chunk, upgraded to v{PAYLOAD_VERSION}, into another memory."
+            );
+        }
+        return Ok(());
+    };
+    let target = Principal::from_text(target).context("Failed to parse --into canister id")?;
+    if target == memory {
+        return Err(KinicError::Validation(
+            "--into must name a different memory; chunks cannot be rewritten in place".to_string(),
+        )
+        .into());
+    }
+    let target_client = MemoryClient::new(agent, target);
+
+    let mut copied = 0usize;
+    for batch in payloads.chunks(EMBED_BATCH) {
+        let texts: Vec<&str> = batch
+            .iter()
+            .map(|(_, payload)| payload.sentence.as_str())
+            .collect();
+        let embeddings = fetch_embeddings(&texts).await?;
+        for ((id, payload), embedding) in batch.iter().zip(embeddings) {
+            let upgraded = payload.clone().upgrade();
+            let new_id = target_client.insert(embedding, &upgraded.to_json()).await?;
+            info!(from = id, to = new_id, "copied chunk");
+            copied += 1;
+        }
+        note!("- copied {copied} of {} chunks", payloads.len());
+    }
+    if unreadable > 0 {
+        eprintln!("warning: skipped {unreadable} chunks that are not chunk payloads");
+    }
+    info!(canister_id = %target, copied, "payload migration completed");
+    note!("Copied {copied} chunks into {target}.");
+    Ok(())
+}
@@ -1,45 +1,148 @@
+use std::{path::PathBuf, sync::Arc};
+
 use anyhow::Result;
+use ic_agent::Agent;
+use tokio::sync::OnceCell;
 
 use crate::{agent::AgentFactory, cli::Command};
 
+pub mod add;
 pub mod ask_ai;
 pub mod balance;
+pub mod bench;
+pub mod capture;
+pub mod cluster;
 pub mod config;
 pub mod convert_pdf;
 pub mod create;
+pub mod diff;
+pub mod doctor;
+pub mod encrypt_identity;
+pub mod estimate;
+pub mod get;
+pub mod history;
+pub mod identity;
 pub mod ii_login;
 pub mod insert;
+pub mod insert_bookmarks;
+pub mod insert_image;
+pub mod insert_mail;
+pub mod insert_obsidian;
 pub mod insert_raw;
 pub mod insert_pdf;
+pub mod interrupt;
 pub mod list;
+pub mod materialize;
+pub mod migrate_payloads;
+pub mod notify;
+pub mod pick;
+pub mod plugins;
+pub mod preflight;
+pub mod prompt;
+pub mod prune;
 pub mod search;
 pub mod search_raw;
+pub mod serve;
+pub mod sessions;
+pub mod status;
+pub mod sync;
+pub mod set_metadata;
+pub mod share;
 pub mod tagged_embeddings;
+pub mod top_up;
+pub mod transfer;
 pub mod update;
+pub mod usage;
+pub mod verify;
+pub mod visualize;
+pub mod wait;
 pub mod reset;
 
 #[derive(Clone)]
 pub struct CommandContext {
     pub agent_factory: AgentFactory,
-    pub identity_path: Option<std::path::PathBuf>,
+    pub identity_path: Option<PathBuf>,
+    /// Named Internet Identity session (`--session` / `login --as`), `None`
+    /// for the default one.
+    pub session: Option<String>,
+    agent: Arc<OnceCell<Agent>>,
+}
+
+impl CommandContext {
+    pub fn new(
+        agent_factory: AgentFactory,
+        identity_path: Option<PathBuf>,
+        session: Option<String>,
+    ) -> Self {
+        Self {
+            agent_factory,
+            identity_path,
+            session,
+            agent: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Builds the agent (keyring read + root key fetch) on first use and
+    /// shares it with every client created during this invocation.
+    pub async fn agent(&self) -> Result<Agent> {
+        let agent = self
+            .agent
+            .get_or_try_init(|| self.agent_factory.build())
+            .await?;
+        Ok(agent.clone())
+    }
 }
 
 pub async fn run_command(command: Command, ctx: CommandContext) -> Result<()> {
     match command {
         Command::Create(args) => create::handle(args, &ctx).await,
         Command::List(args) => list::handle(args, &ctx).await,
+        Command::Add(args) => add::handle(args, &ctx).await,
         Command::Insert(args) => insert::handle(args, &ctx).await,
         Command::InsertRaw(args) => insert_raw::handle(args, &ctx).await,
         Command::InsertPdf(args) => insert_pdf::handle(args, &ctx).await,
+        Command::InsertImage(args) => insert_image::handle(args, &ctx).await,
+        Command::InsertMail(args) => insert_mail::handle(args, &ctx).await,
+        Command::InsertBookmarks(args) => insert_bookmarks::handle(args, &ctx).await,
+        Command::InsertObsidian(args) => insert_obsidian::handle(args, &ctx).await,
         Command::Search(args) => search::handle(args, &ctx).await,
+        Command::Get(args) => get::handle(args, &ctx).await,
+        Command::Pick(args) => pick::handle(args, &ctx).await,
         Command::SearchRaw(args) => search_raw::handle(args, &ctx).await,
         Command::TaggedEmbeddings(args) => tagged_embeddings::handle(args, &ctx).await,
         Command::ConvertPdf(args) => convert_pdf::handle(args).await,
+        Command::Estimate(args) => estimate::handle(args).await,
         Command::Config(args) => config::handle(args, &ctx).await,
+        Command::Share(args) => share::handle(args, &ctx).await,
         Command::Update(args) => update::handle(args, &ctx).await,
+        Command::SetMetadata(args) => set_metadata::handle(args, &ctx).await,
         Command::Reset(args) => reset::handle(args, &ctx).await,
+        Command::MigratePayloads(args) => migrate_payloads::handle(args, &ctx).await,
+        Command::Diff(args) => diff::handle(args, &ctx).await,
+        Command::Verify(args) => verify::handle(args, &ctx).await,
+        Command::Materialize(args) => materialize::handle(args, &ctx).await,
+        Command::Prune(args) => prune::handle(args, &ctx).await,
         Command::Balance(args) => balance::handle(args, &ctx).await,
+        Command::History(args) => history::handle(args, &ctx).await,
+        Command::Transfer(args) => transfer::handle(args, &ctx).await,
+        Command::TopUp(args) => top_up::handle(args, &ctx).await,
+        Command::Status(args) => status::handle(args, &ctx).await,
+        Command::Wait(args) => wait::handle(args, &ctx).await,
+        Command::Bench(args) => bench::handle(args, &ctx).await,
+        Command::Cluster(args) => cluster::handle(args, &ctx).await,
+        Command::Visualize(args) => visualize::handle(args, &ctx).await,
         Command::AskAi(args) => ask_ai::handle(args, &ctx).await,
+        Command::Serve(args) => serve::handle(args, &ctx).await,
         Command::Login(args) => ii_login::handle(args, &ctx).await,
+        Command::EncryptIdentity(args) => encrypt_identity::handle(args, &ctx).await,
+        Command::Identity(args) => identity::handle(args, &ctx).await,
+        Command::Sessions(args) => sessions::handle(args).await,
+        Command::Usage(args) => usage::handle(args).await,
+        Command::Doctor(args) => doctor::handle(args, &ctx).await,
+        Command::Sync(args) => sync::handle(args, &ctx).await,
+        Command::Capture(args) => capture::handle(args, &ctx).await,
+        Command::Plugins(args) => plugins::handle(args).await,
+        // Runs before the agent is built; see `crate::run`.
+        Command::External(args) => unreachable!("external subcommand {args:?} reached run_command"),
     }
 }
@@ -0,0 +1,180 @@
+//! Webhook notifications from the long-running modes, `serve` and
+//! `sync run --daemon`, configured by `[notify]` in the config file. Events:
+//! `ingest.completed` and `ingest.failed` after each daemon job, and
+//! `balance.low` and `delegation.expiring` from the hourly health check,
+//! each sent once when the condition starts.
+//!
+//! Requests carry `X-Kinic-Event`, `X-Kinic-Timestamp` (Unix seconds) and,
+//! with a secret, `X-Kinic-Signature: sha256=<hex>`: the HMAC-SHA256 of
+//! `<timestamp>.<body>`, so receivers can reject forged and replayed events.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use ring::hmac;
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::{
+    commands::history::format_timestamp,
+    embedding::http_client,
+    hooks,
+    identity_store::{SessionStore, derive_principal_from_user_key},
+    jobs::{self, Job},
+    ledger::{fetch_balance, format_e8s},
+};
+
+use super::CommandContext;
+
+/// Overrides `secret` so it can stay out of the config file.
+pub const SECRET_ENV_VAR: &str = "KINIC_WEBHOOK_SECRET";
+/// How often [`Notifier::check_health`] is due in long-running modes.
+pub const HEALTH_CHECK: Duration = Duration::from_secs(60 * 60);
+
+pub struct Notifier {
+    url: String,
+    key: Option<hmac::Key>,
+    low_balance_e8s: Option<u128>,
+    delegation_warning: Duration,
+    /// Conditions already reported, so each alert is sent once until it
+    /// clears.
+    balance_low: bool,
+    delegation_expiring: bool,
+}
+
+impl Notifier {
+    /// The notifier of `[notify]`, or `None` when the section is absent.
+    pub fn configured() -> Result<Option<Self>> {
+        let Some(settings) = jobs::load_config()?.notify else {
+            return Ok(None);
+        };
+        let secret = std::env::var(SECRET_ENV_VAR)
+            .ok()
+            .or_else(|| settings.secret.clone())
+            .filter(|secret| !secret.is_empty());
+        Ok(Some(Self {
+            key: secret.map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())),
+            low_balance_e8s: settings.low_balance_e8s()?,
+            delegation_warning: settings.delegation_warning()?,
+            url: settings.url,
+            balance_low: false,
+            delegation_expiring: false,
+        }))
+    }
+
+    /// Sends `ingest.completed` or `ingest.failed` for a finished job.
+    pub async fn job_finished(&self, job: &Job, result: &Result<(usize, usize)>) {
+        let mut details = json!({"job": job.name, "memory_id": job.memory_id});
+        let event = match result {
+            Ok((ingested, unchanged)) => {
+                details["ingested"] = json!(ingested);
+                details["unchanged"] = json!(unchanged);
+                "ingest.completed"
+            }
+            Err(err) => {
+                details["error"] = format!("{err:#}").into();
+                "ingest.failed"
+            }
+        };
+        self.send(event, details).await;
+    }
+
+    /// Checks the balance and the delegation lifetime, sending an alert for
+    /// each that newly crossed its threshold. Failed checks only warn.
+    pub async fn check_health(&mut self, ctx: &CommandContext) {
+        if let Err(err) = self.check_balance(ctx).await {
+            warn!(error = %format!("{err:#}"), "balance check failed");
+            eprintln!("warning: balance check failed: {err:#}");
+        }
+        if let Err(err) = self.check_delegation(ctx).await {
+            warn!(error = %format!("{err:#}"), "delegation check failed");
+            eprintln!("warning: delegation check failed: {err:#}");
+        }
+    }
+
+    async fn check_balance(&mut self, ctx: &CommandContext) -> Result<()> {
+        let Some(threshold) = self.low_balance_e8s else {
+            return Ok(());
+        };
+        let agent = ctx.agent().await?;
+        let balance = fetch_balance(&agent).await?;
+        let low = balance < threshold;
+        if low && !self.balance_low {
+            let principal = agent.get_principal().map_err(anyhow::Error::msg)?;
+            self.send(
+                "balance.low",
+                json!({
+                    "principal": principal.to_text(),
+                    "balance": format_e8s(balance),
+                    "threshold": format_e8s(threshold),
+                }),
+            )
+            .await;
+        }
+        self.balance_low = low;
+        Ok(())
+    }
+
+    /// Only `--ii` and `--session` identities expire.
+    async fn check_delegation(&mut self, ctx: &CommandContext) -> Result<()> {
+        let Some(path) = &ctx.identity_path else {
+            return Ok(());
+        };
+        let stored = SessionStore::locate(path, ctx.session.as_deref()).load()?;
+        let now_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos() as u64);
+        let expiring =
+            stored.expiration_ns.saturating_sub(now_ns) < self.delegation_warning.as_nanos() as u64;
+        if expiring && !self.delegation_expiring {
+            let principal = hex::decode(&stored.user_public_key_hex)
+                .context("Failed to decode user public key")
+                .and_then(|key| derive_principal_from_user_key(&key))?;
+            self.send(
+                "delegation.expiring",
+                json!({
+                    "principal": principal.to_text(),
+                    "session": ctx.session,
+                    "expires_at": format_timestamp(stored.expiration_ns),
+                }),
+            )
+            .await;
+        }
+        self.delegation_expiring = expiring;
+        Ok(())
+    }
+
+    /// POSTs the event; a failed delivery only warns.
+    async fn send(&self, event: &str, details: Value) {
+        if let Err(err) = self.post(event, details).await {
+            warn!(%event, error = %format!("{err:#}"), "webhook notification failed");
+            eprintln!("warning: {event} notification failed: {err:#}");
+        }
+    }
+
+    async fn post(&self, event: &str, details: Value) -> Result<()> {
+        let payload = hooks::payload(event, details);
+        let timestamp = payload["time"].to_string();
+        let body = serde_json::to_string(&payload)?;
+        let mut request = http_client()?
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Kinic-Event", event)
+            .header("X-Kinic-Timestamp", &timestamp);
+        if let Some(key) = &self.key {
+            let signature = hmac::sign(key, format!("{timestamp}.{body}").as_bytes());
+            request = request.header(
+                "X-Kinic-Signature",
+                format!("sha256={}", hex::encode(signature.as_ref())),
+            );
+        }
+        debug!(%event, url = %self.url, "sending webhook notification");
+        request
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("POST {}", self.url))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ic_agent::export::Principal;
+use tokio::{sync::mpsc, time::Instant};
+use tracing::info;
+
+use crate::{
+    cli::PickArgs,
+    clients::memory::{MemoryClient, SearchHit},
+    commands::get::print_chunk,
+    embedding::fetch_embedding,
+    error::KinicError,
+    output::note,
+    payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+/// Typing pause before the query is embedded and searched.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Rows above the result list: the prompt and the status line.
+const HEADER_ROWS: u16 = 2;
+
+#[derive(Default)]
+struct Picker {
+    query: String,
+    results: Vec<SearchHit>,
+    selected: usize,
+    status: String,
+}
+
+enum Action {
+    None,
+    QueryChanged,
+    Pick,
+    Quit,
+}
+
+pub async fn handle(args: PickArgs, ctx: &CommandContext) -> Result<()> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(KinicError::Validation(
+            "pick is interactive and needs a terminal; use search instead".to_string(),
+        )
+        .into());
+    }
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for pick command")?;
+    let client = Arc::new(MemoryClient::new(ctx.agent().await?, memory));
+
+    let picked = {
+        let _screen = Screen::enter()?;
+        run(client, args.top_k).await?
+    };
+    let Some(hit) = picked else {
+        return Ok(());
+    };
+    info!(canister_id = %memory, id = ?hit.id, "pick completed");
+
+    if args.copy {
+        let text = ChunkPayload::parse(&hit.text)
+            .map(|chunk| chunk.sentence)
+            .unwrap_or(hit.text);
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .context("Failed to copy the chunk to the clipboard")?;
+        match hit.id {
+            Some(id) => note!("Copied chunk #{id} to the clipboard"),
+            None => note!("Copied the chunk to the clipboard"),
+        }
+    } else {
+        print_chunk(hit.id, &hit.text);
+    }
+    Ok(())
+}
+
+/// Raw mode on the alternate screen for as long as the picker is open.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode().context("Failed to switch the terminal to raw mode")?;
+        execute!(io::stderr(), EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+async fn run(client: Arc<MemoryClient>, top_k: usize) -> Result<Option<SearchHit>> {
+    // crossterm's reader blocks, so it gets a thread of its own.
+    let (events_tx, mut events) = mpsc::channel(32);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if events_tx.blocking_send(event).is_err() {
+                break;
+            }
+        }
+    });
+    let (results_tx, mut results) = mpsc::channel(4);
+
+    let mut picker = Picker {
+        status: "Type to search".to_string(),
+        ..Picker::default()
+    };
+    // Every search carries the generation it was started in; replies to an
+    // outdated query are dropped.
+    let mut generation = 0u64;
+    let mut deadline: Option<Instant> = None;
+    loop {
+        draw(&picker)?;
+        let debounce = async {
+            match deadline {
+                Some(at) => tokio::time::sleep_until(at).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            Some(event) = events.recv() => match handle_event(&mut picker, event) {
+                Action::None => {}
+                Action::QueryChanged => {
+                    generation += 1;
+                    if picker.query.trim().is_empty() {
+                        deadline = None;
+                        picker.results.clear();
+                        picker.selected = 0;
+                        picker.status = "Type to search".to_string();
+                    } else {
+                        deadline = Some(Instant::now() + DEBOUNCE);
+                    }
+                }
+                Action::Pick => return Ok(picker.results.get(picker.selected).cloned()),
+                Action::Quit => return Ok(None),
+            },
+            () = debounce => {
+                deadline = None;
+                picker.status = "Searching...".to_string();
+                let client = Arc::clone(&client);
+                let query = picker.query.clone();
+                let results_tx = results_tx.clone();
+                let current = generation;
+                tokio::spawn(async move {
+                    let result = async {
+                        let embedding = fetch_embedding(&query).await?;
+                        client.search_top_k(embedding, top_k).await
+                    }
+                    .await;
+                    let _ = results_tx.send((current, result)).await;
+                });
+            }
+            Some((current, result)) = results.recv() => {
+                if current != generation {
+                    continue;
+                }
+                match result {
+                    Ok(hits) => {
+                        picker.status = format!("{} results", hits.len());
+                        picker.results = hits;
+                        picker.selected = 0;
+                    }
+                    Err(err) => picker.status = format!("Search failed: {err:#}"),
+                }
+            }
+            else => bail!("terminal input closed"),
+        }
+    }
+}
+
+fn handle_event(picker: &mut Picker, event: Event) -> Action {
+    let Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind: KeyEventKind::Press,
+        ..
+    }) = event
+    else {
+        // Resizes and other events only need a redraw.
+        return Action::None;
+    };
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+    match code {
+        KeyCode::Esc => Action::Quit,
+        KeyCode::Char('c' | 'd') if ctrl => Action::Quit,
+        KeyCode::Enter if picker.results.is_empty() => Action::None,
+        KeyCode::Enter => Action::Pick,
+        KeyCode::Up => select(picker, -1),
+        KeyCode::Char('p') if ctrl => select(picker, -1),
+        KeyCode::Down => select(picker, 1),
+        KeyCode::Char('n') if ctrl => select(picker, 1),
+        KeyCode::Char('u') if ctrl => {
+            picker.query.clear();
+            Action::QueryChanged
+        }
+        KeyCode::Backspace => match picker.query.pop() {
+            Some(_) => Action::QueryChanged,
+            None => Action::None,
+        },
+        KeyCode::Char(c) if !ctrl => {
+            picker.query.push(c);
+            Action::QueryChanged
+        }
+        _ => Action::None,
+    }
+}
+
+fn select(picker: &mut Picker, step: isize) -> Action {
+    if !picker.results.is_empty() {
+        picker.selected = picker
+            .selected
+            .saturating_add_signed(step)
+            .min(picker.results.len() - 1);
+    }
+    Action::None
+}
+
+fn draw(picker: &Picker) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let width = usize::from(width);
+    let rows = usize::from(height.saturating_sub(HEADER_ROWS)).max(1);
+    // Scroll just far enough to keep the selection on screen.
+    let offset = picker.selected.saturating_sub(rows - 1);
+
+    let mut out = io::stderr().lock();
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 1),
+        SetAttribute(Attribute::Dim),
+        Print(truncate(&picker.status, width)),
+        SetAttribute(Attribute::Reset)
+    )?;
+    for (row, (index, hit)) in picker
+        .results
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(rows)
+        .enumerate()
+    {
+        let (tag, text) = match ChunkPayload::parse(&hit.text) {
+            Some(chunk) => (chunk.tag, chunk.sentence),
+            None => (String::new(), hit.text.clone()),
+        };
+        let marker = if index == picker.selected { '>' } else { ' ' };
+        let line = format!(
+            "{marker} {:.3}  {tag}: {}",
+            hit.score,
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        );
+        queue!(out, MoveTo(0, HEADER_ROWS + row as u16))?;
+        if index == picker.selected {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(truncate(&line, width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(truncate(&line, width)))?;
+        }
+    }
+    // The prompt goes last so the cursor rests at the end of the query.
+    let prompt = truncate(&format!("> {}", picker.query), width);
+    queue!(out, MoveTo(0, 0), Print(&prompt))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,161 @@
+//! External subcommands: `kinic-cli foo ARGS` runs the first `kinic-foo`
+//! executable on `PATH` with ARGS, git-style. The global options given
+//! before `foo` reach the plugin as `KINIC_*` environment variables, so it
+//! can act as the same identity on the same network, e.g. by calling back
+//! into `$KINIC_CLI`.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use crate::{
+    agent::AgentFactory,
+    cli::{GlobalOpts, PluginsArgs, PluginsCommand},
+    error::KinicError,
+    output::note,
+};
+
+/// File name prefix of plugin executables.
+const PLUGIN_PREFIX: &str = "kinic-";
+/// The CLI's own binary matches the prefix but is not a plugin.
+const CLI_NAME: &str = "kinic-cli";
+
+pub async fn handle(args: PluginsArgs) -> Result<()> {
+    match args.command {
+        PluginsCommand::List => list(),
+    }
+}
+
+fn list() -> Result<()> {
+    let plugins = discover();
+    if plugins.is_empty() {
+        note!("No plugins found. Put an executable named {PLUGIN_PREFIX}<name> on PATH.");
+        return Ok(());
+    }
+    for (name, path) in &plugins {
+        println!("{name}\t{}", path.display());
+    }
+    Ok(())
+}
+
+/// Runs plugin `args[0]` with the rest of `args` and exits with its status.
+pub fn run_external(args: &[String], global: &GlobalOpts) -> Result<()> {
+    let (name, rest) = args
+        .split_first()
+        .context("external subcommand without a name")?;
+    let Some(path) = find(name) else {
+        return Err(KinicError::Validation(format!(
+            "unrecognized subcommand '{name}' and no {PLUGIN_PREFIX}{name} executable on PATH; \
+             run `kinic-cli --help` or `kinic-cli plugins list`"
+        ))
+        .into());
+    };
+    let status = Command::new(&path)
+        .args(rest)
+        .envs(plugin_env(global))
+        .status()
+        .with_context(|| format!("Failed to run {}", path.display()))?;
+    // A plugin killed by a signal has no code; report it as a failure.
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// Every plugin on `PATH` by name; an earlier `PATH` entry shadows a later
+/// one of the same name.
+fn discover() -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for dir in path_dirs() {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = plugin_name(&path) else {
+                continue;
+            };
+            if is_executable(&path) {
+                plugins.entry(name).or_insert(path);
+            }
+        }
+    }
+    plugins
+}
+
+fn find(name: &str) -> Option<PathBuf> {
+    path_dirs().into_iter().find_map(|dir| {
+        let path = dir.join(format!("{PLUGIN_PREFIX}{name}{}", env::consts::EXE_SUFFIX));
+        is_executable(&path).then_some(path)
+    })
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
+
+/// `foo` for `kinic-foo` (or `kinic-foo.exe` on Windows).
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let stem = file_name
+        .strip_suffix(env::consts::EXE_SUFFIX)
+        .unwrap_or(file_name);
+    let name = stem.strip_prefix(PLUGIN_PREFIX)?;
+    (!name.is_empty() && stem != CLI_NAME).then(|| name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The global options a plugin needs to act like the CLI would.
+fn plugin_env(global: &GlobalOpts) -> Vec<(&'static str, String)> {
+    let flag = |set: bool| if set { "1" } else { "0" }.to_string();
+    let mut vars = vec![
+        ("KINIC_IC", flag(global.ic)),
+        (
+            "KINIC_NETWORK_URL",
+            AgentFactory::new(global.ic, String::new())
+                .url()
+                .to_string(),
+        ),
+        ("KINIC_II", flag(global.ii || global.session.is_some())),
+        ("KINIC_QUIET", flag(global.quiet)),
+        ("KINIC_VERBOSE", global.verbose.to_string()),
+    ];
+    if let Ok(exe) = env::current_exe() {
+        vars.push(("KINIC_CLI", exe.display().to_string()));
+    }
+    if let Some(identity) = &global.identity {
+        vars.push(("KINIC_IDENTITY", identity.clone()));
+    }
+    if let Some(value) = global
+        .credential_store
+        .and_then(|store| store.to_possible_value())
+    {
+        vars.push(("KINIC_CREDENTIAL_STORE", value.get_name().to_string()));
+    }
+    if let Some(session) = &global.session {
+        vars.push(("KINIC_SESSION", session.clone()));
+    }
+    if let Some(path) = &global.identity_path {
+        vars.push(("KINIC_IDENTITY_PATH", path.display().to_string()));
+    }
+    if let Some(secs) = global.timeout {
+        vars.push(("KINIC_TIMEOUT_SECS", secs.to_string()));
+    }
+    vars
+}
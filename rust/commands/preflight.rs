@@ -0,0 +1,89 @@
+//! Warnings printed before a command that writes, so a delegation about to
+//! expire or a balance too low to deploy is noticed before work is lost to
+//! it. Thresholds come from `[preflight]` in the config file. The checks
+//! never fail the command: anything they cannot determine is skipped.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::{
+    clients::launcher::LauncherClient,
+    identity_store::SessionStore,
+    jobs::{self, PreflightSettings},
+    ledger::{fetch_balance, format_e8s, nat_to_e8s},
+};
+
+use super::CommandContext;
+
+pub async fn warn(ctx: &CommandContext) {
+    let settings = match jobs::load_config() {
+        Ok(config) => config.preflight,
+        Err(err) => {
+            debug!(error = %format!("{err:#}"), "preflight skipped");
+            return;
+        }
+    };
+    if !settings.is_enabled() {
+        return;
+    }
+    if let Err(err) = check_delegation(ctx, &settings) {
+        debug!(error = %format!("{err:#}"), "delegation preflight skipped");
+    }
+    if let Err(err) = check_balance(ctx, &settings).await {
+        debug!(error = %format!("{err:#}"), "balance preflight skipped");
+    }
+}
+
+/// Only `--ii` and `--session` identities expire. An expired delegation is
+/// left to the command, which refuses to use it.
+fn check_delegation(ctx: &CommandContext, settings: &PreflightSettings) -> Result<()> {
+    let Some(path) = &ctx.identity_path else {
+        return Ok(());
+    };
+    let stored = SessionStore::locate(path, ctx.session.as_deref()).load()?;
+    let now_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_nanos() as u64);
+    let remaining = Duration::from_nanos(stored.expiration_ns.saturating_sub(now_ns));
+    if !remaining.is_zero() && remaining < settings.delegation_warning()? {
+        eprintln!(
+            "warning: delegation expires in {}; run `kinic-cli --ii login` to renew it",
+            format_remaining(remaining)
+        );
+    }
+    Ok(())
+}
+
+async fn check_balance(ctx: &CommandContext, settings: &PreflightSettings) -> Result<()> {
+    let agent = ctx.agent().await?;
+    let balance = fetch_balance(&agent).await?;
+    let (threshold, what) = match settings.low_balance_e8s()? {
+        Some(threshold) => (threshold, "the configured minimum"),
+        None => {
+            let price = LauncherClient::new(agent).fetch_deployment_price().await?;
+            (nat_to_e8s(&price)?, "the deployment price")
+        }
+    };
+    if balance < threshold {
+        eprintln!(
+            "warning: balance {} KINIC is below {what} ({} KINIC)",
+            format_e8s(balance),
+            format_e8s(threshold)
+        );
+    }
+    Ok(())
+}
+
+/// `45s`, `12m`, `3h 5m` or `2d 4h`.
+fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3_600, secs % 3_600 / 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, _) => format!("{minutes}m"),
+        (0, _, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h"),
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::{Context, Result, anyhow, bail};
+use ic_agent::Agent;
+
+use crate::output::note;
+
+use super::CommandContext;
+
+/// Notes who is about to act, on which canister and network, so a wrong
+/// `--identity` or a stray `--ic` shows before a token-spending or
+/// destructive call is confirmed.
+pub fn show_actor(ctx: &CommandContext, agent: &Agent, target: &str) -> Result<()> {
+    let principal = agent
+        .get_principal()
+        .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
+    let network = if ctx.agent_factory.use_mainnet() {
+        "mainnet"
+    } else {
+        "local replica"
+    };
+    note!("- acting principal: {principal}");
+    note!("- target canister: {target}");
+    note!("- network: {network} ({})", ctx.agent_factory.url());
+    Ok(())
+}
+
+/// Asks a yes/no question on stderr and returns whether the user agreed.
+/// Refuses to guess when stdin is not a terminal; callers offer `--yes` for
+/// scripted use.
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = read_answer(&format!("{question} [y/N]"))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn read_answer(prompt: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        bail!("confirmation required but stdin is not a terminal; pass --yes to proceed");
+    }
+
+    eprint!("{prompt} ");
+    io::stderr().flush().ok();
+
+    let mut answer = String::new();
+    stdin
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read confirmation")?;
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::info;
+
+use crate::{
+    cli::PruneArgs,
+    clients::memory::MemoryClient,
+    error::KinicError,
+    jobs::{self, RetentionPolicy},
+    output::note,
+    payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+/// Characters of chunk text shown per stale chunk.
+const PREVIEW_CHARS: usize = 60;
+
+/// A memory's chunks split by a retention cutoff.
+pub(crate) struct Scan {
+    /// Chunks past the cutoff, with their `created_at`.
+    pub stale: Vec<(u32, String, String)>,
+    /// Number of the other chunks.
+    pub kept: usize,
+    /// Kept chunks without a `created_at` (version 1 or plain-text payloads).
+    pub undated: usize,
+}
+
+pub async fn handle(args: PruneArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for prune command")?;
+    let (max_age, tag) = match &args.older_than {
+        Some(older_than) => (jobs::parse_age(older_than)?, args.tag.clone()),
+        None => {
+            let config = jobs::load_config()?;
+            let policy = config.retention_for(&args.memory_id).ok_or_else(|| {
+                KinicError::Validation(format!(
+                    "pass --older-than or add a [[retention]] policy for {} to {}",
+                    args.memory_id,
+                    jobs::config_path()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default()
+                ))
+            })?;
+            (policy.max_age()?, args.tag.clone().or(policy.tag.clone()))
+        }
+    };
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    note!("Reading the chunks of {memory}...");
+    let scan = scan(&client, max_age, tag.as_deref()).await?;
+    for (id, created_at, raw) in &scan.stale {
+        let label = match ChunkPayload::parse(raw) {
+            Some(payload) => format!("[{}] {}", payload.tag, preview(&payload.sentence)),
+            None => preview(raw),
+        };
+        println!("- #{id} {created_at} {label}");
+    }
+    info!(
+        canister_id = %memory,
+        stale = scan.stale.len(),
+        kept = scan.kept,
+        undated = scan.undated,
+        "retention scan completed"
+    );
+    note!(
+        "{} stale, {} kept ({} without created_at)",
+        scan.stale.len(),
+        scan.kept,
+        scan.undated
+    );
+    if !scan.stale.is_empty() {
+        note!(
+            "The memory canister cannot delete a chunk, so stale chunks are only reported. \
+             To drop them, create a new memory and insert the sources you still need."
+        );
+    }
+    Ok(())
+}
+
+/// Splits the chunks of `client` into those created more than `max_age` ago
+/// (and tagged `tag`, when given) and the rest.
+pub(crate) async fn scan(
+    client: &MemoryClient,
+    max_age: Duration,
+    tag: Option<&str>,
+) -> Result<Scan> {
+    let cutoff = OffsetDateTime::now_utc() - max_age;
+    let mut scan = Scan {
+        stale: Vec::new(),
+        kept: 0,
+        undated: 0,
+    };
+    for (id, raw) in client.all_chunks().await? {
+        let payload = ChunkPayload::parse(&raw);
+        let created_at = payload.as_ref().and_then(|payload| {
+            let created_at = payload.metadata.created_at.as_deref()?;
+            OffsetDateTime::parse(created_at, &Rfc3339)
+                .ok()
+                .map(|time| (created_at.to_string(), time))
+        });
+        let Some((created_at, time)) = created_at else {
+            scan.undated += 1;
+            scan.kept += 1;
+            continue;
+        };
+        let tagged = tag.is_none_or(|tag| payload.is_some_and(|payload| payload.has_tag(tag)));
+        if tagged && time < cutoff {
+            scan.stale.push((id, created_at, raw));
+        } else {
+            scan.kept += 1;
+        }
+    }
+    Ok(scan)
+}
+
+/// Reports the stale chunks of one policy on stderr; used by daemon mode,
+/// which cannot remove them either.
+pub(crate) async fn check_policy(policy: &RetentionPolicy, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&policy.memory_id)
+        .context("Failed to parse memory_id of retention policy")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let scan = scan(&client, policy.max_age()?, policy.tag.as_deref()).await?;
+    info!(
+        canister_id = %memory,
+        stale = scan.stale.len(),
+        "retention check completed"
+    );
+    if !scan.stale.is_empty() {
+        eprintln!(
+            "warning: {} chunks of {memory} are older than {}; run `prune --memory-id {memory}` to list them",
+            scan.stale.len(),
+            policy.older_than
+        );
+    }
+    Ok(())
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text;
+    }
+    let mut preview: String = text.chars().take(PREVIEW_CHARS - 1).collect();
+    preview.push('…');
+    preview
+}
@@ -1,28 +1,114 @@
+use std::{fs, path::Path};
+
 use anyhow::{Context, Result};
 use ic_agent::export::Principal;
-use tracing::info;
+use tracing::{debug, info};
 
-use crate::{cli::ResetArgs, clients::memory::MemoryClient};
+use crate::{
+    cli::ResetArgs,
+    clients::memory::{CAPABILITY_GET, MemoryClient},
+    embedding::embedding_dim,
+    error::KinicError,
+    output::note,
+};
 
-use super::CommandContext;
+use super::{
+    CommandContext,
+    prompt::{confirm, show_actor},
+};
 
 pub async fn handle(args: ResetArgs, ctx: &CommandContext) -> Result<()> {
     let client = build_memory_client(&args.memory_id, ctx).await?;
+    let dim = args.dim.unwrap_or_else(embedding_dim);
+
+    if args.export.is_some() {
+        client.require(CAPABILITY_GET).await?;
+    }
+    // Counting needs `get`; with --force the count is not needed, and when it
+    // fails the memory is treated as holding chunks.
+    let stored = if args.force {
+        None
+    } else {
+        match client.id_range().await {
+            Ok(range) => Some(range.map_or(0, |(first, last)| last - first + 1)),
+            Err(err) => {
+                debug!(error = %format!("{err:#}"), "could not count chunks");
+                None
+            }
+        }
+    };
+    if stored != Some(0) && args.export.is_none() && !args.force {
+        let held = match stored {
+            Some(stored) => format!("holds {stored} chunks"),
+            None => "may hold chunks (they could not be counted)".to_string(),
+        };
+        return Err(KinicError::Validation(format!(
+            "memory {} {held}; pass --export <PATH> to save their payloads first, \
+             or --force to erase them without a copy",
+            args.memory_id
+        ))
+        .into());
+    }
+
+    let erased = match stored {
+        Some(stored) => format!("{stored} chunks"),
+        None => "all chunks".to_string(),
+    };
+    note!(
+        "Reset memory {}: erase {erased} and set the dimension to {dim}",
+        args.memory_id
+    );
+    if let Some(path) = &args.export {
+        note!(
+            "{} will hold the payloads only, without their vectors, and no command loads it back: \
+             it is a record of the text, not a backup that restores the memory.",
+            path.display()
+        );
+    }
+    show_actor(ctx, &ctx.agent().await?, &args.memory_id)?;
+    if !args.yes && !confirm("Proceed?")? {
+        note!("Reset cancelled.");
+        return Ok(());
+    }
 
-    client.reset(args.dim).await?;
+    if let Some(path) = &args.export {
+        let exported = export(&client, path).await?;
+        note!("Exported {exported} chunks to {}", path.display());
+    }
+
+    client.reset(dim).await?;
 
     info!(
         canister_id = %client.canister_id(),
-        dim = args.dim,
+        dim,
         "memory reset completed"
     );
-    println!("Reset memory canister {} to dim {}", args.memory_id, args.dim);
+    note!("Reset memory canister {} to dim {dim}", args.memory_id);
     Ok(())
 }
 
+/// Writes every stored payload to `path`, one per line, in the export
+/// format `diff` reads. Vectors are not included.
+async fn export(client: &MemoryClient, path: &Path) -> Result<usize> {
+    let chunks = client.all_chunks().await?;
+    let mut lines = String::new();
+    for (_, payload) in &chunks {
+        // A payload is a single line unless it is raw text with newlines;
+        // keep those on one line as a JSON string.
+        if payload.contains('\n') {
+            lines.push_str(&serde_json::to_string(payload)?);
+        } else {
+            lines.push_str(payload);
+        }
+        lines.push('\n');
+    }
+    fs::write(path, lines).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(chunks.len())
+}
+
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
-    let memory = Principal::from_text(id)
-        .context("Failed to parse canister id for reset command")?;
+    let agent = ctx.agent().await?;
+    let memory =
+        Principal::from_text(id).context("Failed to parse canister id for reset command")?;
     Ok(MemoryClient::new(agent, memory))
 }
@@ -1,41 +1,423 @@
-use std::cmp::Ordering;
+use std::io::{self, IsTerminal};
 
 use anyhow::{Context, Result};
 use ic_agent::export::Principal;
+use tabled::{
+    builder::Builder,
+    settings::{Style, Width, peaker::Priority},
+};
 use tracing::info;
 
-use crate::{cli::SearchArgs, clients::memory::MemoryClient, embedding::fetch_embedding};
+use crate::{
+    cli::{SearchArgs, SearchFormat},
+    clients::memory::{CAPABILITY_GET, MemoryClient, SEARCH_PAGE_SIZE, SearchHit},
+    embedding::fetch_embedding,
+    error::KinicError,
+    output::{csv_field, note},
+    payload::{ChunkMetadata, ChunkPayload},
+};
 
 use super::CommandContext;
 
+/// Table width when stdout is not a terminal.
+const DEFAULT_TABLE_WIDTH: usize = 120;
+
+/// Longest chunk text in a markdown cell; markdown tables do not wrap well.
+const MARKDOWN_TEXT_CHARS: usize = 200;
+
 pub async fn handle(args: SearchArgs, ctx: &CommandContext) -> Result<()> {
+    if args.expand > 0 && args.format != SearchFormat::List {
+        return Err(
+            KinicError::Validation("--expand only works with --format list".to_string()).into(),
+        );
+    }
+    if args.group_by.is_some() && (args.expand > 0 || args.format != SearchFormat::List) {
+        return Err(KinicError::Validation(
+            "--group-by only works with --format list and without --expand".to_string(),
+        )
+        .into());
+    }
     let client = build_memory_client(&args.memory_id, ctx).await?;
+    if args.expand > 0 {
+        client.require(CAPABILITY_GET).await?;
+    }
     let embedding = fetch_embedding(&args.query).await?;
-    let mut results = client.search(embedding).await?;
 
-    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    // Tables need every row before the column widths are known, so only the
+    // list format prints as results arrive.
+    let mut collected: Vec<SearchHit> = Vec::new();
+    let result_count = match args.top_k {
+        _ if args.group_by.is_some() => {
+            let groups = group_by_source(&client, embedding, &args).await?;
+            print_groups(&args.query, &groups);
+            groups.len()
+        }
+        Some(k) if args.tag.is_none() => {
+            let results = client.search_top_k(embedding, k).await?;
+            let count = results.len();
+            if args.format == SearchFormat::List {
+                print_results(&client, &args.query, &results, true, args.expand).await?;
+            } else {
+                collected = results;
+            }
+            count
+        }
+        _ => {
+            // Print page by page so large memories never need the whole
+            // ranking in one canister reply, and so a --tag filter keeps
+            // reading until it has enough matches.
+            let limit = args.top_k.unwrap_or(usize::MAX);
+            let mut pages = client.search_pages(embedding, SEARCH_PAGE_SIZE);
+            let mut count = 0;
+            while count < limit {
+                let Some(mut page) = pages.next_page().await? else {
+                    break;
+                };
+                if let Some(tag) = &args.tag {
+                    page.retain(|hit| {
+                        ChunkPayload::parse(&hit.text).is_some_and(|chunk| chunk.has_tag(tag))
+                    });
+                }
+                page.truncate(limit - count);
+                let first_page = count == 0;
+                count += page.len();
+                if args.format == SearchFormat::List {
+                    print_results(&client, &args.query, &page, first_page, args.expand).await?;
+                } else {
+                    collected.extend(page);
+                }
+            }
+            count
+        }
+    };
+    if !collected.is_empty() {
+        print_formatted(&collected, args.format, args.show_metadata);
+    }
 
     info!(
         canister_id = %client.canister_id(),
         query = %args.query,
-        result_count = results.len(),
+        result_count,
         "search completed"
     );
 
+    if result_count == 0 {
+        note!("No matches found for query \"{}\".", args.query);
+    }
+
+    Ok(())
+}
+
+async fn print_results(
+    client: &MemoryClient,
+    query: &str,
+    results: &[SearchHit],
+    with_header: bool,
+    expand: u32,
+) -> Result<()> {
     if results.is_empty() {
-        println!("No matches found for query \"{}\".", args.query);
-    } else {
-        println!("Search results for \"{}\":", args.query);
-        for (score, text) in results {
-            println!("- [{score:.4}] {text}");
-        }
+        return Ok(());
+    }
+    if with_header {
+        note!("Search results for \"{query}\":");
     }
+    for hit in results {
+        let chunk = ChunkPayload::parse(&hit.text);
+        let metadata = chunk
+            .as_ref()
+            .map(|chunk| format_metadata(&chunk.metadata))
+            .unwrap_or_default();
 
+        // Hits from a canister without `search_paged` have no id to expand.
+        let Some(id) = hit.id.filter(|_| expand > 0) else {
+            println!("- [{:.4}] {}{metadata}", hit.score, sentence(&hit.text));
+            continue;
+        };
+
+        let neighbors = client.neighbors(id, expand).await?;
+        println!("- [{:.4}] #{id}{metadata}", hit.score);
+        for (id, payload) in &neighbors.before {
+            println!("    #{id}  {}", sentence(payload));
+        }
+        println!("  > #{id}  {}", sentence(&hit.text));
+        for (id, payload) in &neighbors.after {
+            println!("    #{id}  {}", sentence(payload));
+        }
+    }
     Ok(())
 }
 
+/// Matches from one source document, in ranking order.
+struct SourceGroup {
+    /// The `source` metadata, or the tag of chunks inserted without one.
+    name: String,
+    tag: String,
+    created_at: Option<String>,
+    best_score: f32,
+    matches: usize,
+    snippets: Vec<Snippet>,
+}
+
+struct Snippet {
+    id: Option<u32>,
+    score: f32,
+    text: String,
+    metadata: ChunkMetadata,
+}
+
+impl SourceGroup {
+    /// Counts `hit` as a match and keeps it as a snippet while there is room,
+    /// unless a kept snippet has the same text up to case, spacing and
+    /// punctuation.
+    fn add(&mut self, hit: SearchHit, chunk: Option<ChunkPayload>, max_snippets: usize) {
+        self.matches += 1;
+        if self.snippets.len() == max_snippets {
+            return;
+        }
+        let (text, metadata) = match chunk {
+            Some(chunk) => (chunk.sentence, chunk.metadata),
+            None => (hit.text, ChunkMetadata::default()),
+        };
+        let key = dedup_key(&text);
+        if self
+            .snippets
+            .iter()
+            .any(|snippet| dedup_key(&snippet.text) == key)
+        {
+            return;
+        }
+        self.snippets.push(Snippet {
+            id: hit.id,
+            score: hit.score,
+            text,
+            metadata,
+        });
+    }
+}
+
+/// Reads the ranking page by page into one group per source document,
+/// stopping once `--top-k` documents have a match. Match counts cover the
+/// pages read, so a document may have more matches further down.
+async fn group_by_source(
+    client: &MemoryClient,
+    embedding: Vec<f32>,
+    args: &SearchArgs,
+) -> Result<Vec<SourceGroup>> {
+    let limit = args.top_k.unwrap_or(usize::MAX);
+    let max_snippets = args.snippets as usize;
+    let mut pages = client.search_pages(embedding, SEARCH_PAGE_SIZE);
+    let mut groups: Vec<SourceGroup> = Vec::new();
+    while groups.len() < limit {
+        let Some(page) = pages.next_page().await? else {
+            break;
+        };
+        for hit in page {
+            let chunk = ChunkPayload::parse(&hit.text);
+            if let Some(tag) = &args.tag
+                && !chunk.as_ref().is_some_and(|chunk| chunk.has_tag(tag))
+            {
+                continue;
+            }
+            // Text that is not a chunk payload is its own document.
+            let name = match &chunk {
+                Some(chunk) => chunk
+                    .metadata
+                    .source
+                    .clone()
+                    .unwrap_or_else(|| chunk.tag.clone()),
+                None => hit.text.clone(),
+            };
+            let index = match groups.iter().position(|group| group.name == name) {
+                Some(index) => index,
+                None if groups.len() < limit => {
+                    groups.push(SourceGroup {
+                        name,
+                        tag: chunk
+                            .as_ref()
+                            .map(|chunk| chunk.tag.clone())
+                            .unwrap_or_default(),
+                        created_at: chunk
+                            .as_ref()
+                            .and_then(|chunk| chunk.metadata.created_at.clone()),
+                        best_score: hit.score,
+                        matches: 0,
+                        snippets: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+                None => continue,
+            };
+            groups[index].add(hit, chunk, max_snippets);
+        }
+    }
+    Ok(groups)
+}
+
+/// Prints each document as `- [best score] source  (tag=..., N matches)`
+/// followed by its snippets with their position in the document.
+fn print_groups(query: &str, groups: &[SourceGroup]) {
+    if groups.is_empty() {
+        return;
+    }
+    note!("Search results for \"{query}\", grouped by source:");
+    for group in groups {
+        let mut details = Vec::new();
+        if !group.tag.is_empty() && group.tag != group.name {
+            details.push(format!("tag={}", group.tag));
+        }
+        if let Some(created_at) = &group.created_at {
+            details.push(format!("created_at={created_at}"));
+        }
+        details.push(match group.matches {
+            1 => "1 match".to_string(),
+            matches => format!("{matches} matches"),
+        });
+        println!(
+            "- [{:.4}] {}  ({})",
+            group.best_score,
+            group.name,
+            details.join(", ")
+        );
+        for snippet in &group.snippets {
+            let position = snippet
+                .metadata
+                .summary()
+                .into_iter()
+                .filter(|(key, _)| matches!(key.as_str(), "page" | "chunk" | "part"))
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let position = if position.is_empty() {
+                String::new()
+            } else {
+                format!("  ({position})")
+            };
+            let id = snippet.id.map(|id| format!("#{id}  ")).unwrap_or_default();
+            println!("    [{:.4}] {id}{}{position}", snippet.score, snippet.text);
+        }
+    }
+}
+
+/// `text` lowercased with only its letters and digits, so snippets that
+/// differ in spacing or punctuation compare equal.
+fn dedup_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Metadata rendered as a trailing `  (key=value, ...)`, or nothing.
+fn format_metadata(metadata: &ChunkMetadata) -> String {
+    let joined = join_metadata(metadata);
+    if joined.is_empty() {
+        return String::new();
+    }
+    format!("  ({joined})")
+}
+
+fn join_metadata(metadata: &ChunkMetadata) -> String {
+    metadata
+        .summary()
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints results as a table, markdown table or CSV. Table cells are kept
+/// to one line and the widest column is cut (with `…`) to fit the terminal;
+/// markdown cuts long chunk text; CSV keeps everything.
+fn print_formatted(results: &[SearchHit], format: SearchFormat, show_metadata: bool) {
+    let mut header = vec!["score", "id", "tag", "text"];
+    if show_metadata {
+        header.push("metadata");
+    }
+    let rows = results.iter().map(|hit| {
+        let chunk = ChunkPayload::parse(&hit.text);
+        let mut row = vec![
+            format!("{:.4}", hit.score),
+            hit.id.map(|id| id.to_string()).unwrap_or_default(),
+            chunk
+                .as_ref()
+                .map(|chunk| chunk.tag.clone())
+                .unwrap_or_default(),
+            chunk
+                .as_ref()
+                .map(|chunk| chunk.sentence.clone())
+                .unwrap_or_else(|| hit.text.clone()),
+        ];
+        if show_metadata {
+            row.push(
+                chunk
+                    .as_ref()
+                    .map(|chunk| join_metadata(&chunk.metadata))
+                    .unwrap_or_default(),
+            );
+        }
+        row
+    });
+
+    if format == SearchFormat::Csv {
+        println!("{}", header.join(","));
+        for row in rows {
+            let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+            println!("{}", fields.join(","));
+        }
+        return;
+    }
+
+    let mut builder = Builder::default();
+    builder.push_record(header);
+    for row in rows {
+        builder.push_record(row.into_iter().map(|cell| {
+            let cell = cell.split_whitespace().collect::<Vec<_>>().join(" ");
+            if format == SearchFormat::Markdown {
+                truncate_chars(&cell, MARKDOWN_TEXT_CHARS).replace('|', "\\|")
+            } else {
+                cell
+            }
+        }));
+    }
+    let mut table = builder.build();
+    if format == SearchFormat::Markdown {
+        table.with(Style::markdown());
+    } else {
+        table.with(Style::sharp()).with(
+            Width::truncate(table_width())
+                .priority(Priority::max(true))
+                .suffix("…"),
+        );
+    }
+    println!("{table}");
+}
+
+fn table_width() -> usize {
+    if !io::stdout().is_terminal() {
+        return DEFAULT_TABLE_WIDTH;
+    }
+    crossterm::terminal::size()
+        .map(|(columns, _)| usize::from(columns))
+        .unwrap_or(DEFAULT_TABLE_WIDTH)
+}
+
+fn truncate_chars(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(limit.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn sentence(payload: &str) -> String {
+    ChunkPayload::parse(payload)
+        .map(|chunk| chunk.sentence)
+        .unwrap_or_else(|| payload.to_string())
+}
+
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for search command")?;
     Ok(MemoryClient::new(agent, memory))
@@ -27,7 +27,7 @@ pub async fn handle(args: SearchRawArgs, ctx: &CommandContext) -> Result<()> {
 }
 
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory =
         Principal::from_text(id).context("Failed to parse canister id for search-raw command")?;
     Ok(MemoryClient::new(agent, memory))
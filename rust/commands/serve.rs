@@ -0,0 +1,446 @@
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ic_agent::{Agent, export::Principal};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::info;
+
+use crate::{
+    cli::ServeArgs,
+    clients::memory::MemoryClient,
+    commands::ask_ai::call_llm,
+    embedding::fetch_embeddings,
+    error::{ErrorClass, KinicError, classify},
+    jobs::LlmSettings,
+    output::note,
+    rag::prepare_prompt,
+    tokens,
+};
+
+use super::{
+    CommandContext,
+    notify::{HEALTH_CHECK, Notifier},
+};
+
+/// Model name reported to clients that ask for none, and by `/v1/models`
+/// when no model is configured.
+const DEFAULT_MODEL_NAME: &str = "kinic";
+
+struct ServeState {
+    agent: Agent,
+    memory: Principal,
+    client: MemoryClient,
+    settings: LlmSettings,
+    top_k: usize,
+    context_tokens: usize,
+    api_key: Option<String>,
+}
+
+impl ServeState {
+    fn model_name(&self) -> &str {
+        self.settings.model.as_deref().unwrap_or(DEFAULT_MODEL_NAME)
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionRequest {
+    #[serde(default)]
+    model: Option<String>,
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    temperature: Option<f32>,
+    #[serde(default, alias = "max_completion_tokens")]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsRequest {
+    input: EmbeddingsInput,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    encoding_format: Option<String>,
+    #[serde(default)]
+    dimensions: Option<usize>,
+}
+
+/// Token-id inputs are not accepted: the provider embeds text.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EmbeddingsInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    role: String,
+    /// A string, or an array of content parts of which only `text` parts
+    /// are read.
+    #[serde(default)]
+    content: Value,
+}
+
+pub async fn handle(args: ServeArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for serve command")?;
+    let agent = ctx.agent().await?;
+    let state = Arc::new(ServeState {
+        client: MemoryClient::new(agent.clone(), memory),
+        agent,
+        memory,
+        settings: LlmSettings::configured(args.profile.as_deref())?,
+        top_k: args.top_k,
+        context_tokens: args.context_tokens,
+        api_key: args.api_key,
+    });
+    if state.api_key.is_none() && !args.addr.ip().is_loopback() {
+        eprintln!(
+            "warning: {} is reachable from other machines and --api-key is not set; anyone who can connect can query {memory}",
+            args.addr
+        );
+    }
+
+    if let Some(mut notifier) = Notifier::configured()? {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            loop {
+                notifier.check_health(&ctx).await;
+                tokio::time::sleep(HEALTH_CHECK).await;
+            }
+        });
+    }
+
+    let app = router(state);
+    serve(args.addr, app, memory).await
+}
+
+fn router(state: Arc<ServeState>) -> Router {
+    Router::new()
+        .route("/v1/models", get(models_handler))
+        .route("/v1/chat/completions", post(chat_completions_handler))
+        .route("/v1/embeddings", post(embeddings_handler))
+        .with_state(state)
+}
+
+async fn serve(addr: SocketAddr, app: Router, memory: Principal) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind to {addr}"))?;
+    info!(%addr, canister_id = %memory, "serving OpenAI-compatible API");
+    note!("Serving {memory} on http://{addr}/v1 (Ctrl-C to stop)");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .context("API server failed")
+}
+
+async fn models_handler(State(state): State<Arc<ServeState>>, headers: HeaderMap) -> Response {
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+    axum::Json(json!({
+        "object": "list",
+        "data": [{
+            "id": state.model_name(),
+            "object": "model",
+            "created": 0,
+            "owned_by": "kinic",
+        }],
+    }))
+    .into_response()
+}
+
+async fn chat_completions_handler(
+    State(state): State<Arc<ServeState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+    let request: ChatCompletionRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => {
+            return api_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &format!("invalid request body: {err}"),
+            );
+        }
+    };
+    chat_completion(&state, request)
+        .await
+        .unwrap_or_else(|err| failure(&err))
+}
+
+async fn embeddings_handler(
+    State(state): State<Arc<ServeState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+    let request: EmbeddingsRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => {
+            return api_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &format!("invalid request body: {err}"),
+            );
+        }
+    };
+    embeddings(&state, request)
+        .await
+        .unwrap_or_else(|err| failure(&err))
+}
+
+/// Answers the last user message from the memory, like `ask-ai`. Earlier
+/// turns are not searched; a system message replaces the configured system
+/// prompt.
+async fn chat_completion(state: &ServeState, request: ChatCompletionRequest) -> Result<Response> {
+    let query = request
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(|message| message_text(&message.content))
+        .filter(|text| !text.trim().is_empty())
+        .ok_or_else(|| KinicError::Validation("messages has no user message".to_string()))?;
+    if let Some(temperature) = request.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+        return Err(KinicError::Validation(format!(
+            "temperature must be between 0 and 2, got {temperature}"
+        ))
+        .into());
+    }
+    let system_prompt = request
+        .messages
+        .iter()
+        .find(|message| message.role == "system")
+        .map(|message| message_text(&message.content));
+    let settings = LlmSettings {
+        model: None,
+        temperature: request.temperature,
+        max_tokens: request.max_tokens,
+        system_prompt,
+    }
+    .or(&state.settings);
+
+    let prepared = prepare_prompt(
+        state.agent.clone(),
+        &state.memory,
+        &query,
+        &[],
+        state.top_k,
+        "en",
+        state.context_tokens,
+    )
+    .await?;
+    let answer = call_llm(&prepared.prompt, &settings).await?;
+    info!(
+        canister_id = %state.memory,
+        query = %query,
+        result_count = prepared.results.len(),
+        "chat completion answered"
+    );
+
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let id = format!("chatcmpl-{:x}", created.as_nanos());
+    let model = request
+        .model
+        .unwrap_or_else(|| state.model_name().to_string());
+    if request.stream {
+        // The answer is only known once the LLM has finished and the
+        // thinking part is stripped, so it goes out as a single chunk.
+        let chunk = json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created.as_secs(),
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "delta": {"role": "assistant", "content": answer},
+                "finish_reason": "stop",
+            }],
+        });
+        return Ok((
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            format!("data: {chunk}\n\ndata: [DONE]\n\n"),
+        )
+            .into_response());
+    }
+    let prompt_tokens = tokens::count(&prepared.prompt);
+    let completion_tokens = tokens::count(&answer);
+    Ok(axum::Json(json!({
+        "id": id,
+        "object": "chat.completion",
+        "created": created.as_secs(),
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": answer},
+            "finish_reason": "stop",
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    }))
+    .into_response())
+}
+
+/// Embeds the inputs exactly as `insert` does, including the memory's vector
+/// processing, so the vectors can be stored with `insert-raw` and searched
+/// alongside inserted chunks.
+async fn embeddings(state: &ServeState, request: EmbeddingsRequest) -> Result<Response> {
+    let inputs = match request.input {
+        EmbeddingsInput::One(text) => vec![text],
+        EmbeddingsInput::Many(texts) => texts,
+    };
+    if inputs.is_empty() || inputs.iter().any(|text| text.is_empty()) {
+        return Err(KinicError::Validation("input must not be empty".to_string()).into());
+    }
+    let base64 = match request.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(KinicError::Validation(format!(
+                "encoding_format must be float or base64, got {other}"
+            ))
+            .into());
+        }
+    };
+    let dim = state.client.dim().await?;
+    if let (Some(dim), Some(dimensions)) = (dim, request.dimensions)
+        && dimensions != dim
+    {
+        return Err(KinicError::Validation(format!(
+            "dimensions must be {dim}, the dimension of {}; got {dimensions}",
+            state.memory
+        ))
+        .into());
+    }
+
+    let texts: Vec<&str> = inputs.iter().map(String::as_str).collect();
+    let processing = state.client.processing().await?;
+    let vectors: Vec<Vec<f32>> = fetch_embeddings(&texts)
+        .await?
+        .into_iter()
+        .map(|vector| processing.apply(vector))
+        .collect();
+    if let Some(dim) = dim
+        && let Some(vector) = vectors.iter().find(|vector| vector.len() != dim)
+    {
+        return Err(KinicError::EmbeddingApi(format!(
+            "the embedding provider returned {} dimensions but {} stores {dim}",
+            vector.len(),
+            state.memory
+        ))
+        .into());
+    }
+    let prompt_tokens: usize = texts.iter().map(|text| tokens::count(text)).sum();
+    info!(
+        canister_id = %state.memory,
+        inputs = texts.len(),
+        "embeddings served"
+    );
+
+    let data: Vec<Value> = vectors
+        .iter()
+        .enumerate()
+        .map(|(index, vector)| {
+            let embedding = if base64 {
+                let bytes: Vec<u8> = vector.iter().flat_map(|x| x.to_le_bytes()).collect();
+                Value::String(STANDARD.encode(bytes))
+            } else {
+                json!(vector)
+            };
+            json!({"object": "embedding", "index": index, "embedding": embedding})
+        })
+        .collect();
+    Ok(axum::Json(json!({
+        "object": "list",
+        "data": data,
+        "model": request.model.unwrap_or_else(|| DEFAULT_MODEL_NAME.to_string()),
+        "usage": {"prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens},
+    }))
+    .into_response())
+}
+
+fn message_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| part["type"] == "text")
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Checks `Authorization: Bearer <key>` when `--api-key` is set.
+fn authorized(state: &ServeState, headers: &HeaderMap) -> bool {
+    let Some(expected) = &state.api_key else {
+        return true;
+    };
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        == Some(expected.as_str())
+}
+
+fn unauthorized() -> Response {
+    api_error(
+        StatusCode::UNAUTHORIZED,
+        "invalid_request_error",
+        "missing or invalid API key",
+    )
+}
+
+fn failure(err: &anyhow::Error) -> Response {
+    let (status, kind) = match classify(err) {
+        ErrorClass::Validation => (StatusCode::BAD_REQUEST, "invalid_request_error"),
+        ErrorClass::Network | ErrorClass::CanisterReject | ErrorClass::EmbeddingApi => {
+            (StatusCode::BAD_GATEWAY, "api_error")
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "api_error"),
+    };
+    api_error(status, kind, &format!("{err:#}"))
+}
+
+/// An error in the shape OpenAI clients parse.
+fn api_error(status: StatusCode, kind: &str, message: &str) -> Response {
+    (
+        status,
+        axum::Json(json!({
+            "error": {"message": message, "type": kind, "code": null},
+        })),
+    )
+        .into_response()
+}
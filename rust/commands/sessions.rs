@@ -0,0 +1,56 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    cli::{SessionsArgs, SessionsCommand},
+    identity_store::list_sessions,
+    output::note,
+};
+
+use super::history::format_timestamp;
+
+pub async fn handle(args: SessionsArgs) -> Result<()> {
+    match args.command {
+        SessionsCommand::List => list(),
+    }
+}
+
+fn list() -> Result<()> {
+    let sessions = list_sessions()?;
+    if sessions.is_empty() {
+        note!("No saved sessions. Run `kinic-cli --ii login [--as NAME]` to create one.");
+        return Ok(());
+    }
+
+    let now_ns = u64::try_from(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("System time before UNIX_EPOCH")?
+            .as_nanos(),
+    )
+    .context("System time overflow")?;
+
+    println!(
+        "{:<16} {:<64} {:<28} STORE",
+        "SESSION", "PRINCIPAL", "EXPIRES (UTC)"
+    );
+    for session in &sessions {
+        let mut expires = format_timestamp(session.expiration_ns);
+        if session.expiration_ns <= now_ns {
+            expires.push_str(" (expired)");
+        }
+        let mut store = session.store.to_string();
+        if session.encrypted {
+            store.push_str(" [encrypted]");
+        }
+        println!(
+            "{:<16} {:<64} {:<28} {}",
+            session.name,
+            session.principal.to_text(),
+            expires,
+            store
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{cli::SetMetadataArgs, clients::launcher::LauncherClient, output::note};
+
+use super::CommandContext;
+
+pub async fn handle(args: SetMetadataArgs, ctx: &CommandContext) -> Result<()> {
+    let pid = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for set-metadata command")?
+        .to_text();
+
+    let agent = ctx.agent().await?;
+    let client = LauncherClient::new(agent);
+
+    // Only the given fields change; keep the rest as the launcher has them.
+    let mut metadata = client.fetch_metadata(&pid).await?.unwrap_or_default();
+    if let Some(name) = args.name {
+        metadata.name = name;
+    }
+    if let Some(description) = args.description {
+        metadata.description = description;
+    }
+
+    client
+        .update_metadata(&pid, &metadata)
+        .await
+        .context("Failed to update metadata via launcher canister")?;
+
+    info!(instance = %pid, name = %metadata.name, "metadata updated");
+    note!("Updated metadata for {pid}");
+    note!("- name: {}", metadata.name);
+    note!("- description: {}", metadata.description);
+    Ok(())
+}
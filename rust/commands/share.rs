@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{cli::ShareArgs, clients::memory::MemoryClient, output::note};
+
+use super::{CommandContext, config::parse_grant};
+
+pub async fn handle(args: ShareArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for share command")?;
+    let (principal, role) = parse_grant(&args.principal, &args.role)?;
+
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    client
+        .add_new_user(principal, role.code())
+        .await
+        .context("Failed to add new user to memory canister")?;
+
+    info!(canister_id = %memory, %principal, role = ?role, "shared memory");
+    note!("Shared {memory} with {principal} as {role:?}");
+    Ok(())
+}
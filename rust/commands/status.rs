@@ -0,0 +1,97 @@
+use anyhow::{Context, Result, anyhow};
+use candid::Nat;
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::StatusArgs,
+    clients::{
+        management::{CanisterStatus, ManagementClient},
+        memory::MemoryClient,
+    },
+    vectors::VectorProcessing,
+};
+
+use super::CommandContext;
+
+pub async fn handle(args: StatusArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for status command")?;
+    let agent = ctx.agent().await?;
+    let caller = agent
+        .get_principal()
+        .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
+
+    let processing = MemoryClient::new(agent.clone(), memory)
+        .processing()
+        .await?;
+    let management = ManagementClient::new(agent.clone());
+    let controllers = management.controllers(memory).await?;
+
+    // canister_status is restricted to controllers; memories deployed by the
+    // launcher are normally controlled by it, so others see public state only.
+    let status = if controllers.contains(&caller) {
+        let status = management.canister_status(memory).await?;
+        info!(%memory, status = %status.status, cycles = %status.cycles, "fetched canister status");
+        Some(status)
+    } else {
+        None
+    };
+    let module_hash = match &status {
+        Some(status) => status.module_hash.clone(),
+        None => management.module_hash(memory).await?,
+    };
+    print_status(
+        &memory,
+        status.as_ref(),
+        module_hash.as_deref(),
+        &controllers,
+        &processing,
+    );
+    Ok(())
+}
+
+fn print_status(
+    memory: &Principal,
+    status: Option<&CanisterStatus>,
+    module_hash: Option<&[u8]>,
+    controllers: &[Principal],
+    processing: &VectorProcessing,
+) {
+    println!("Memory canister {memory}");
+    match status {
+        Some(status) => {
+            println!("- status: {}", status.status);
+            println!(
+                "- cycles: {} ({:.3} T)",
+                status.cycles,
+                nat_to_f64(&status.cycles) / 1e12
+            );
+            println!(
+                "- idle burn: {} cycles/day",
+                status.idle_cycles_burned_per_day
+            );
+            println!(
+                "- memory size: {} bytes ({:.2} MiB)",
+                status.memory_size,
+                nat_to_f64(&status.memory_size) / (1024.0 * 1024.0)
+            );
+        }
+        None => println!(
+            "- status, cycles and memory size: only visible to controllers of the canister"
+        ),
+    }
+    match module_hash {
+        Some(hash) => println!("- module hash: 0x{}", hex::encode(hash)),
+        None => println!("- module hash: (no module installed)"),
+    }
+    println!("- vector processing: {processing}");
+    println!("- controllers:");
+    for controller in controllers {
+        println!("  - {controller}");
+    }
+}
+
+fn nat_to_f64(value: &Nat) -> f64 {
+    value.0.to_string().parse().unwrap_or(f64::NAN)
+}
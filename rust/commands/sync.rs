@@ -0,0 +1,463 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::{info, warn};
+
+use crate::{
+    cli::{SyncArgs, SyncCommand, SyncRunArgs},
+    clients::memory::MemoryClient,
+    commands::{
+        history::format_timestamp,
+        insert::ChunkPipeline,
+        interrupt,
+        notify::{HEALTH_CHECK, Notifier},
+        prune,
+    },
+    embedding::http_client,
+    error::KinicError,
+    guard, hooks,
+    jobs::{self, Job, JobState, RetentionPolicy, Source, SyncState},
+    output::note,
+    payload::{InsertMetadata, content_hash},
+};
+
+use super::CommandContext;
+
+/// Longest daemon sleep between schedule checks. State is reloaded on every
+/// check, so runs made meanwhile with `sync run` are taken into account.
+const DAEMON_POLL: Duration = Duration::from_secs(60);
+/// How often daemon mode checks each retention policy.
+const RETENTION_CHECK: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// One document a job ingests.
+struct Item {
+    /// Stable identity: relative path, URL or feed entry id. Also the tag.
+    key: String,
+    source: String,
+    content: String,
+}
+
+pub async fn handle(args: SyncArgs, ctx: &CommandContext) -> Result<()> {
+    match args.command {
+        SyncCommand::List => list(),
+        SyncCommand::Run(run) => handle_run(run, ctx).await,
+    }
+}
+
+fn list() -> Result<()> {
+    let config = jobs::load_config()?;
+    if config.jobs.is_empty() {
+        note!("No jobs defined in {}.", jobs::config_path()?.display());
+        return Ok(());
+    }
+    let state = SyncState::load()?;
+    println!(
+        "{:<16} {:<8} {:<8} {:<20} SOURCE",
+        "NAME", "TYPE", "EVERY", "LAST_RUN"
+    );
+    for job in &config.jobs {
+        let (kind, source) = match job.source()? {
+            Source::Directory { root, .. } => ("dir", root.display().to_string()),
+            Source::Urls(urls) => ("urls", format!("{} urls", urls.len())),
+            Source::Rss(feed) => ("rss", feed.to_string()),
+        };
+        let last_run = state
+            .jobs
+            .get(&job.name)
+            .and_then(|job| job.last_run)
+            .map(|secs| format_timestamp(secs.saturating_mul(1_000_000_000)))
+            .unwrap_or_else(|| "never".to_string());
+        println!(
+            "{:<16} {:<8} {:<8} {:<20} {source}",
+            job.name,
+            kind,
+            job.every.as_deref().unwrap_or("-"),
+            last_run
+        );
+    }
+    Ok(())
+}
+
+async fn handle_run(args: SyncRunArgs, ctx: &CommandContext) -> Result<()> {
+    let config = jobs::load_config()?;
+    let selected: Vec<Job> = match &args.job {
+        Some(name) => {
+            let job = config
+                .jobs
+                .iter()
+                .find(|job| &job.name == name)
+                .cloned()
+                .ok_or_else(|| {
+                    KinicError::Validation(format!(
+                        "no job named \"{name}\" in {}",
+                        jobs::config_path()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_default()
+                    ))
+                })?;
+            vec![job]
+        }
+        None => config.jobs.clone(),
+    };
+    if selected.is_empty() {
+        return Err(KinicError::Validation(format!(
+            "no jobs defined in {}",
+            jobs::config_path()?.display()
+        ))
+        .into());
+    }
+
+    if args.daemon {
+        // Retention policies belong to memories, not jobs, so `--job` skips them.
+        let policies = if args.job.is_some() {
+            Vec::new()
+        } else {
+            config.retention.clone()
+        };
+        return run_daemon(&selected, &policies, ctx).await;
+    }
+
+    let mut state = SyncState::load()?;
+    let mut failed = 0;
+    for job in &selected {
+        if let Err(err) = run_job(job, &mut state, ctx).await {
+            eprintln!("job {} failed: {err:#}", job.name);
+            failed += 1;
+        }
+        if interrupt::requested() {
+            anyhow::bail!("interrupted; later jobs were not run");
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} jobs failed", selected.len());
+    }
+    Ok(())
+}
+
+/// Runs every job with an `every` schedule whenever it is due, forever.
+/// Failures are reported and retried at the next due time. Retention
+/// policies are checked daily; with `[notify]`, job outcomes are posted and
+/// the balance and delegation are checked hourly.
+async fn run_daemon(
+    selected: &[Job],
+    policies: &[RetentionPolicy],
+    ctx: &CommandContext,
+) -> Result<()> {
+    let mut scheduled = Vec::new();
+    for job in selected {
+        match job.interval()? {
+            Some(interval) => scheduled.push((job, interval)),
+            None => note!("Skipping job {}: no `every` schedule", job.name),
+        }
+    }
+    if scheduled.is_empty() && policies.is_empty() {
+        return Err(KinicError::Validation(
+            "daemon mode needs at least one job with an `every` schedule or a retention policy"
+                .to_string(),
+        )
+        .into());
+    }
+
+    note!(
+        "Sync daemon started with {} jobs and {} retention policies",
+        scheduled.len(),
+        policies.len()
+    );
+    let mut notifier = Notifier::configured()?;
+    interrupt::watch();
+    let mut retention_checked: Option<Instant> = None;
+    let mut health_checked: Option<Instant> = None;
+    loop {
+        if let Some(notifier) = &mut notifier
+            && health_checked.is_none_or(|checked| checked.elapsed() >= HEALTH_CHECK)
+        {
+            notifier.check_health(ctx).await;
+            health_checked = Some(Instant::now());
+        }
+        if retention_checked.is_none_or(|checked| checked.elapsed() >= RETENTION_CHECK) {
+            for policy in policies {
+                if let Err(err) = prune::check_policy(policy, ctx).await {
+                    warn!(memory_id = %policy.memory_id, error = %format!("{err:#}"), "retention check failed");
+                    eprintln!("retention check of {} failed: {err:#}", policy.memory_id);
+                }
+            }
+            retention_checked = Some(Instant::now());
+        }
+        let mut state = SyncState::load()?;
+        let mut next_wake = DAEMON_POLL;
+        for (job, interval) in &scheduled {
+            let last_run = state.jobs.get(&job.name).and_then(|job| job.last_run);
+            let due_in = last_run
+                .map(|last| {
+                    Duration::from_secs(last)
+                        .saturating_add(*interval)
+                        .saturating_sub(now())
+                })
+                .unwrap_or_default();
+            if !due_in.is_zero() {
+                next_wake = next_wake.min(due_in);
+                continue;
+            }
+            if interrupt::requested() {
+                break;
+            }
+            let result = run_job(job, &mut state, ctx).await;
+            if let Some(notifier) = &notifier {
+                notifier.job_finished(job, &result).await;
+            }
+            if let Err(err) = result {
+                if interrupt::requested() {
+                    eprintln!("{err:#}");
+                    break;
+                }
+                warn!(job = %job.name, error = %format!("{err:#}"), "sync job failed");
+                eprintln!("job {} failed: {err:#}", job.name);
+                // Count the attempt so a failing job waits for its interval.
+                state.jobs.entry(job.name.clone()).or_default().last_run = Some(now().as_secs());
+                state.save()?;
+            }
+            next_wake = next_wake.min(*interval);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(next_wake) => {}
+            _ = interrupt::wait() => {}
+        }
+        if interrupt::requested() {
+            note!("Sync daemon stopped");
+            return Ok(());
+        }
+    }
+}
+
+/// Runs `job` and then the `sync.after` hooks with its outcome; returns how
+/// many items were ingested and how many were unchanged.
+async fn run_job(job: &Job, state: &mut SyncState, ctx: &CommandContext) -> Result<(usize, usize)> {
+    let result = ingest(job, state, ctx).await;
+    let mut details = hooks::outcome(&result);
+    details["job"] = job.name.clone().into();
+    details["memory_id"] = job.memory_id.clone().into();
+    if let Ok((ingested, unchanged)) = result {
+        details["ingested"] = ingested.into();
+        details["unchanged"] = unchanged.into();
+    }
+    hooks::after("sync.after", details).await;
+    result
+}
+
+/// Ingests what changed since the last run.
+async fn ingest(job: &Job, state: &mut SyncState, ctx: &CommandContext) -> Result<(usize, usize)> {
+    let memory = Principal::from_text(&job.memory_id)
+        .with_context(|| format!("Failed to parse memory_id of job {}", job.name))?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let items = collect_items(job).await?;
+    let job_meta = [format!("job={}", job.name)];
+
+    let mut unchanged = 0;
+    let mut changed = Vec::new();
+    let job_state: &JobState = state.jobs.entry(job.name.clone()).or_default();
+    for item in items {
+        let hash = content_hash(&item.content);
+        if job_state.items.get(&item.key) == Some(&hash) {
+            unchanged += 1;
+            continue;
+        }
+        // Not recorded in the state, so the document is checked again once
+        // it changes or the job allows secrets.
+        if !job.allow_secrets
+            && let Some(finding) = guard::scan(&item.content).first()
+        {
+            eprintln!(
+                "warning: [{}] skipped {}: {} on line {} ({}); set allow_secrets = true on the job to ingest it",
+                job.name, item.key, finding.rule, finding.line, finding.preview
+            );
+            continue;
+        }
+        let metadata = InsertMetadata::new(&item.key, Some(item.source.clone()), &job_meta)?;
+        changed.push((item, hash, metadata));
+    }
+
+    // The next items are chunked while the current one is inserted.
+    let mut pipeline = ChunkPipeline::start(
+        changed
+            .iter()
+            .map(|(item, _, _)| item.content.clone())
+            .collect(),
+        None,
+    );
+    let total = changed.len();
+    let mut ingested = 0;
+    for (item, hash, metadata) in changed {
+        if interrupt::requested() {
+            break;
+        }
+        let chunks = pipeline
+            .insert_next(&client, |sentence, index| {
+                metadata.payload(sentence, index, None)
+            })
+            .await?;
+        note!("[{}] ingested {} ({chunks} chunks)", job.name, item.key);
+        let job_state: &mut JobState = state.jobs.entry(job.name.clone()).or_default();
+        job_state.items.insert(item.key, hash);
+        // Saved per item so an interrupted run does not redo finished work.
+        state.save()?;
+        ingested += 1;
+    }
+
+    if ingested < total {
+        info!(job = %job.name, ingested, unchanged, "sync job interrupted");
+        anyhow::bail!(
+            "[{}] interrupted after {ingested} of {total} changed items; the rest are ingested on the next run",
+            job.name
+        );
+    }
+    state.jobs.entry(job.name.clone()).or_default().last_run = Some(now().as_secs());
+    state.save()?;
+    info!(job = %job.name, ingested, unchanged, "sync job completed");
+    note!("[{}] {ingested} ingested, {unchanged} unchanged", job.name);
+    Ok((ingested, unchanged))
+}
+
+async fn collect_items(job: &Job) -> Result<Vec<Item>> {
+    match job.source()? {
+        Source::Directory { root, extensions } => {
+            let mut items = Vec::new();
+            walk(&root, &root, &extensions, &mut items)?;
+            items.sort_by(|a, b| a.key.cmp(&b.key));
+            Ok(items)
+        }
+        Source::Urls(urls) => {
+            let mut items = Vec::new();
+            for url in urls {
+                items.push(Item {
+                    key: url.clone(),
+                    source: url.clone(),
+                    content: fetch_text(url).await?,
+                });
+            }
+            Ok(items)
+        }
+        Source::Rss(feed) => feed_items(&fetch_text(feed).await?)
+            .with_context(|| format!("Failed to parse feed {feed}")),
+    }
+}
+
+/// Files under `dir` with one of `extensions`, skipping hidden entries.
+fn walk(root: &Path, dir: &Path, extensions: &[String], items: &mut Vec<Item>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, extensions, items)?;
+            continue;
+        }
+        let matches = path.extension().is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|wanted| ext.eq_ignore_ascii_case(wanted.as_str()))
+        });
+        if !matches {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let key = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned();
+        items.push(Item {
+            key,
+            source: path.display().to_string(),
+            content,
+        });
+    }
+    Ok(())
+}
+
+async fn fetch_text(url: &str) -> Result<String> {
+    http_client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {url}"))?
+        .text()
+        .await
+        .with_context(|| format!("Failed to read {url}"))
+}
+
+/// Entries of an RSS 2.0 (`<item>`) or Atom (`<entry>`) feed, as title plus
+/// text with HTML markup removed.
+fn feed_items(xml: &str) -> Result<Vec<Item>> {
+    let document = roxmltree::Document::parse(xml)?;
+    let child_text = |node: roxmltree::Node, names: &[&str]| {
+        names.iter().find_map(|name| {
+            node.children()
+                .find(|child| child.tag_name().name() == *name)
+                .and_then(|child| child.text().map(str::trim).filter(|t| !t.is_empty()))
+                .map(str::to_string)
+        })
+    };
+
+    let mut items = Vec::new();
+    for node in document
+        .descendants()
+        .filter(|node| matches!(node.tag_name().name(), "item" | "entry"))
+    {
+        // Atom links carry the URL in an attribute.
+        let link = child_text(node, &["link"]).or_else(|| {
+            node.children()
+                .find(|child| child.tag_name().name() == "link")
+                .and_then(|child| child.attribute("href"))
+                .map(str::to_string)
+        });
+        let Some(key) = child_text(node, &["guid", "id"]).or_else(|| link.clone()) else {
+            continue;
+        };
+        let title = child_text(node, &["title"]).unwrap_or_default();
+        let body = child_text(node, &["encoded", "content", "description", "summary"])
+            .map(|html| strip_html(&html))
+            .unwrap_or_default();
+        items.push(Item {
+            source: link.unwrap_or_else(|| key.clone()),
+            key,
+            content: format!("# {title}\n\n{body}"),
+        });
+    }
+    Ok(items)
+}
+
+pub(crate) fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
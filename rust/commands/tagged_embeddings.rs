@@ -18,12 +18,26 @@ pub async fn handle(args: TaggedEmbeddingsArgs, ctx: &CommandContext) -> Result<
         "tagged-embeddings fetched"
     );
 
+    let processing = client.processing().await?;
+    let stray = embeddings
+        .iter()
+        .filter(|embedding| !processing.conforms(embedding))
+        .count();
+    if stray > 0 {
+        eprintln!(
+            "warning: {stray} of {} vectors under \"{}\" do not match the memory's vector processing ({processing}); \
+             they were stored by a client that ignores it and will rank inconsistently",
+            embeddings.len(),
+            args.tag
+        );
+    }
+
     println!("{}", to_string(&embeddings)?);
     Ok(())
 }
 
 async fn build_memory_client(id: &str, ctx: &CommandContext) -> Result<MemoryClient> {
-    let agent = ctx.agent_factory.build().await?;
+    let agent = ctx.agent().await?;
     let memory = Principal::from_text(id)
         .context("Failed to parse canister id for tagged-embeddings command")?;
     Ok(MemoryClient::new(agent, memory))
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use candid::Nat;
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::TopUpArgs,
+    clients::launcher::LauncherClient,
+    error::KinicError,
+    ledger::{TRANSFER_FEE_E8S, allowance_for, fetch_balance, format_e8s, parse_kinic_amount},
+    output::note,
+};
+
+use super::{
+    CommandContext,
+    create::{revoke_leftover, warn_unrevoked},
+    prompt::{confirm, show_actor},
+};
+
+pub async fn handle(args: TopUpArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for top-up command")?;
+    let amount = parse_kinic_amount(&args.amount)?;
+    if amount == 0 {
+        return Err(
+            KinicError::Validation("top-up amount must be greater than zero".to_string()).into(),
+        );
+    }
+
+    let agent = ctx.agent().await?;
+    let balance = fetch_balance(&agent).await?;
+    // One fee for the approval, one for the launcher's transfer_from.
+    let total = amount
+        .checked_add(2 * TRANSFER_FEE_E8S)
+        .ok_or_else(|| KinicError::Validation("amount is too large".to_string()))?;
+    if balance < total {
+        return Err(KinicError::Payment(format!(
+            "Insufficient balance: need {} KINIC (amount + 2 * fee), have {} KINIC",
+            format_e8s(total),
+            format_e8s(balance)
+        ))
+        .into());
+    }
+
+    note!(
+        "Top up {memory} with {} KINIC (+ {} KINIC in ledger fees, {} KINIC total).",
+        format_e8s(amount),
+        format_e8s(2 * TRANSFER_FEE_E8S),
+        format_e8s(total)
+    );
+    let client = LauncherClient::new(agent.clone());
+    // The approval costs a fee, so make sure the launcher can use it first.
+    if !client.declares("top_up_instance").await? {
+        return Err(KinicError::Validation(format!(
+            "the launcher {} does not declare top_up_instance in its Candid interface; \
+             nothing was approved",
+            client.launcher_id()
+        ))
+        .into());
+    }
+    show_actor(ctx, &agent, &format!("{} (launcher)", client.launcher_id()))?;
+    if !args.yes && !confirm("Proceed?")? {
+        note!("Top-up cancelled.");
+        return Ok(());
+    }
+
+    let amount_nat = Nat::from(amount);
+    client
+        .approve_launcher(&Nat::from(allowance_for(amount)))
+        .await?;
+    info!(%memory, amount_e8s = amount, "launcher approved for top-up");
+
+    let topped_up = client
+        .top_up_instance(&memory.to_text(), &amount_nat)
+        .await
+        .context("Failed to top up instance via launcher canister");
+    if let Err(err) = topped_up {
+        // Don't leave the launcher able to pull the amount later.
+        warn_unrevoked(revoke_leftover(&client).await);
+        return Err(err);
+    }
+    info!(%memory, amount_e8s = amount, "memory topped up");
+    note!("Topped up {memory} with {} KINIC.", format_e8s(amount));
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result, anyhow};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::TransferArgs,
+    clients::{LEDGER_CANISTER, call::ledger::Account},
+    error::KinicError,
+    ledger::{TRANSFER_FEE_E8S, fetch_balance, format_e8s, parse_kinic_amount, transfer},
+    output::note,
+};
+
+use super::{
+    CommandContext,
+    prompt::{confirm, show_actor},
+};
+
+const MAX_MEMO_BYTES: usize = 32;
+
+pub async fn handle(args: TransferArgs, ctx: &CommandContext) -> Result<()> {
+    let to = Principal::from_text(&args.to).context("Failed to parse recipient principal")?;
+    let amount = parse_kinic_amount(&args.amount)?;
+    if amount == 0 {
+        return Err(KinicError::Validation(
+            "transfer amount must be greater than zero".to_string(),
+        )
+        .into());
+    }
+    let memo = args.memo.map(String::into_bytes);
+    if memo
+        .as_ref()
+        .is_some_and(|memo| memo.len() > MAX_MEMO_BYTES)
+    {
+        return Err(
+            KinicError::Validation(format!("memo must be at most {MAX_MEMO_BYTES} bytes")).into(),
+        );
+    }
+
+    let agent = ctx.agent().await?;
+    let from = agent
+        .get_principal()
+        .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
+    let balance = fetch_balance(&agent).await?;
+    let total = amount
+        .checked_add(TRANSFER_FEE_E8S)
+        .ok_or_else(|| KinicError::Validation("amount is too large".to_string()))?;
+    if balance < total {
+        return Err(KinicError::Payment(format!(
+            "Insufficient balance: need {} KINIC (amount + fee), have {} KINIC",
+            format_e8s(total),
+            format_e8s(balance)
+        ))
+        .into());
+    }
+
+    note!("Transfer {} KINIC from {from} to {to}", format_e8s(amount));
+    show_actor(ctx, &agent, &format!("{LEDGER_CANISTER} (ledger)"))?;
+    note!("- fee: {} KINIC", format_e8s(TRANSFER_FEE_E8S));
+    note!("- total: {} KINIC", format_e8s(total));
+    note!("- balance after: {} KINIC", format_e8s(balance - total));
+    if !args.yes && !confirm("Proceed?")? {
+        note!("Transfer cancelled.");
+        return Ok(());
+    }
+
+    let block = transfer(
+        &agent,
+        Account {
+            owner: to,
+            subaccount: None,
+        },
+        amount,
+        memo,
+    )
+    .await?;
+    info!(%from, %to, amount_e8s = amount, %block, "transfer completed");
+    note!(
+        "Transferred {} KINIC to {to}; block index:",
+        format_e8s(amount)
+    );
+    println!("{block}");
+    Ok(())
+}
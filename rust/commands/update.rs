@@ -1,19 +1,49 @@
-use anyhow::{Context, Result};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
 use ic_agent::export::Principal;
+use tokio::{sync::Semaphore, task::JoinSet};
 use tracing::info;
 
-use crate::{cli::UpdateArgs, clients::launcher::LauncherClient};
+use crate::{cli::UpdateArgs, clients::launcher::LauncherClient, output::note};
 
-use super::CommandContext;
+use super::{
+    CommandContext,
+    prompt::{confirm, show_actor},
+};
 
 pub async fn handle(args: UpdateArgs, ctx: &CommandContext) -> Result<()> {
-    let agent = ctx.agent_factory.build().await?;
-    let client = LauncherClient::new(agent);
+    let agent = ctx.agent().await?;
+    let client = LauncherClient::new(agent.clone());
+
+    if args.all {
+        note!("Update every memory of this identity to the latest memory canister module");
+        show_actor(ctx, &agent, &format!("{} (launcher)", client.launcher_id()))?;
+        if !args.yes && !confirm("Proceed?")? {
+            note!("Update cancelled.");
+            return Ok(());
+        }
+        return update_all(client, args.concurrency).await;
+    }
 
-    let pid = Principal::from_text(&args.memory_id)
+    let memory_id = args
+        .memory_id
+        .context("--memory-id is required unless --all is set")?;
+    let pid = Principal::from_text(&memory_id)
         .context("Failed to parse canister id for update command")?
         .to_text();
 
+    note!("Update memory {pid} to the latest memory canister module");
+    show_actor(
+        ctx,
+        &agent,
+        &format!("{pid} via {} (launcher)", client.launcher_id()),
+    )?;
+    if !args.yes && !confirm("Proceed?")? {
+        note!("Update cancelled.");
+        return Ok(());
+    }
+
     client
         .update_instance(&pid)
         .await
@@ -25,6 +55,63 @@ pub async fn handle(args: UpdateArgs, ctx: &CommandContext) -> Result<()> {
         "update instance completed"
     );
 
-    println!("Updated memory canister instance {pid}");
+    note!("Updated memory canister instance {pid}");
+    Ok(())
+}
+
+async fn update_all(client: LauncherClient, concurrency: usize) -> Result<()> {
+    let pids: Vec<String> = client
+        .list_memories()
+        .await?
+        .iter()
+        .filter_map(|state| state.principal())
+        .map(Principal::to_text)
+        .collect();
+
+    if pids.is_empty() {
+        note!("No memories found.");
+        return Ok(());
+    }
+
+    note!(
+        "Updating {} memories (up to {} at a time)...",
+        pids.len(),
+        concurrency.max(1)
+    );
+
+    let client = Arc::new(client);
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for pid in pids {
+        let client = Arc::clone(&client);
+        let permits = Arc::clone(&permits);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = client.update_instance(&pid).await;
+            (pid, result)
+        });
+    }
+
+    let mut failed = 0usize;
+    let mut total = 0usize;
+    while let Some(joined) = tasks.join_next().await {
+        let (pid, result) = joined.context("update task panicked")?;
+        total += 1;
+        match result {
+            Ok(()) => {
+                info!(instance = %pid, "update instance completed");
+                note!("- {pid}: updated");
+            }
+            Err(err) => {
+                failed += 1;
+                eprintln!("- {pid}: FAILED ({err:#})");
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} of {total} memory updates failed");
+    }
+    note!("All {total} memories updated.");
     Ok(())
 }
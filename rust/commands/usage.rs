@@ -0,0 +1,90 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    cli::UsageArgs,
+    jobs,
+    output::note,
+    usage::{self, RunUsage, format_cycles},
+};
+
+/// Totals of one command, or of every command under `TOTAL`.
+#[derive(Serialize)]
+struct Row {
+    command: String,
+    runs: u64,
+    failed: u64,
+    #[serde(flatten)]
+    totals: RunUsage,
+}
+
+pub async fn handle(args: UsageArgs) -> Result<()> {
+    let since = match &args.since {
+        Some(age) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            now.saturating_sub(jobs::parse_age(age)?).as_secs()
+        }
+        None => 0,
+    };
+    let runs: Vec<RunUsage> = usage::load()?
+        .into_iter()
+        .filter(|run| run.time >= since)
+        .collect();
+    if runs.is_empty() && !args.json {
+        note!("No usage recorded.");
+        return Ok(());
+    }
+
+    let mut rows: BTreeMap<String, Row> = BTreeMap::new();
+    let mut total = Row {
+        command: "TOTAL".to_string(),
+        runs: 0,
+        failed: 0,
+        totals: RunUsage::default(),
+    };
+    for run in &runs {
+        let row = rows.entry(run.command.clone()).or_insert_with(|| Row {
+            command: run.command.clone(),
+            runs: 0,
+            failed: 0,
+            totals: RunUsage::default(),
+        });
+        for row in [row, &mut total] {
+            row.runs += 1;
+            row.failed += u64::from(!run.ok);
+            row.totals.add(run);
+        }
+    }
+    let mut rows: Vec<Row> = rows.into_values().collect();
+    rows.push(total);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    println!(
+        "{:<20} {:>6} {:>10} {:>6} {:>12} {:>8} {:>8} {:>10}",
+        "COMMAND", "RUNS", "EMBEDDING", "CHAT", "TOKENS", "UPDATES", "QUERIES", "CYCLES"
+    );
+    for row in &rows {
+        println!(
+            "{:<20} {:>6} {:>10} {:>6} {:>12} {:>8} {:>8} {:>10}",
+            row.command,
+            row.runs,
+            row.totals.embedding_requests,
+            row.totals.chat_requests,
+            row.totals.api_tokens,
+            row.totals.update_calls,
+            row.totals.query_calls,
+            format_cycles(row.totals.cycles)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use ic_agent::export::Principal;
+use tracing::info;
+
+use crate::{
+    cli::VerifyArgs, clients::memory::MemoryClient, embedding::fetch_embedding_uncached,
+    error::KinicError, output::note, payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+/// Characters of chunk text shown per miss.
+const PREVIEW_CHARS: usize = 60;
+/// Below this mean self-similarity the stored vectors were most likely made
+/// by a different model than the one now configured.
+const DRIFT_SCORE: f32 = 0.95;
+
+pub async fn handle(args: VerifyArgs, ctx: &CommandContext) -> Result<()> {
+    if args.sample == 0 || args.top_k == 0 {
+        return Err(KinicError::Validation(
+            "--sample and --top-k must be greater than 0".to_string(),
+        )
+        .into());
+    }
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for verify command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    let Some((first, last)) = client.id_range().await? else {
+        bail!("{memory} has no chunks to verify");
+    };
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos() as u64)
+    });
+    let ids = sample_ids(first, last, args.sample, seed);
+    note!(
+        "Verifying {} of {} chunks of {memory} (seed {seed}, top {})...",
+        ids.len(),
+        u64::from(last - first) + 1,
+        args.top_k
+    );
+
+    let mut checked = 0usize;
+    let mut found = 0usize;
+    let mut ranks = 0usize;
+    let mut self_scores = Vec::new();
+    for id in &ids {
+        let Some(raw) = client.get(*id).await? else {
+            continue;
+        };
+        checked += 1;
+        let (text, label) = match ChunkPayload::parse(&raw) {
+            Some(payload) => {
+                let label = format!("[{}] {}", payload.tag, preview(&payload.sentence));
+                (payload.sentence, label)
+            }
+            None => (raw.clone(), preview(&raw)),
+        };
+        // Uncached, so a cached vector cannot hide a provider change.
+        let embedding = fetch_embedding_uncached(&text).await?;
+        let hits = client.search_top_k(embedding, args.top_k).await?;
+        // An identical copy of the chunk ranking first counts as a hit.
+        match hits
+            .iter()
+            .position(|hit| hit.id == Some(*id) || hit.text == raw)
+        {
+            Some(rank) => {
+                found += 1;
+                ranks += rank + 1;
+                self_scores.push(hits[rank].score);
+            }
+            None => {
+                let best = hits
+                    .first()
+                    .map(|hit| match hit.id {
+                        Some(best) => format!("best: #{best} {:.3}", hit.score),
+                        None => format!("best: {:.3}", hit.score),
+                    })
+                    .unwrap_or_else(|| "no results".to_string());
+                println!("MISS #{id} {label} ({best})");
+            }
+        }
+    }
+
+    let recall = found as f32 / checked.max(1) as f32;
+    let mean_score = if self_scores.is_empty() {
+        0.0
+    } else {
+        self_scores.iter().sum::<f32>() / self_scores.len() as f32
+    };
+    info!(
+        canister_id = %memory,
+        checked,
+        found,
+        recall,
+        mean_score,
+        "verify completed"
+    );
+    println!(
+        "{found} of {checked} chunks retrieved themselves in the top {} ({:.1}%); mean rank {:.2}, mean self-score {mean_score:.3}",
+        args.top_k,
+        recall * 100.0,
+        ranks as f32 / found.max(1) as f32
+    );
+    if found > 0 && mean_score < DRIFT_SCORE {
+        eprintln!(
+            "warning: re-embedded chunks score only {mean_score:.3} against their stored vectors; \
+             the embedding provider or model has likely changed since they were inserted"
+        );
+    }
+    if recall < args.min_recall {
+        bail!(
+            "self-retrieval {:.1}% is below --min-recall {:.1}%",
+            recall * 100.0,
+            args.min_recall * 100.0
+        );
+    }
+    Ok(())
+}
+
+/// Up to `count` distinct ids in `first..=last`, drawn with a SplitMix64
+/// generator so that `--seed` reproduces a run.
+fn sample_ids(first: u32, last: u32, count: usize, seed: u64) -> Vec<u32> {
+    let total = u64::from(last - first) + 1;
+    if count as u64 >= total {
+        return (first..=last).collect();
+    }
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut seen = HashSet::new();
+    let mut ids = Vec::with_capacity(count);
+    while ids.len() < count {
+        let id = first + (next() % total) as u32;
+        if seen.insert(id) {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    ids
+}
+
+fn preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text;
+    }
+    let mut preview: String = text.chars().take(PREVIEW_CHARS - 1).collect();
+    preview.push('…');
+    preview
+}
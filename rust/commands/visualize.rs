@@ -0,0 +1,361 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    cli::{ProjectionMethod, VisualizeArgs},
+    clients::memory::MemoryClient,
+    error::KinicError,
+    output::{csv_field, note},
+    payload::ChunkPayload,
+};
+
+use super::CommandContext;
+
+const TSNE_ITERATIONS: usize = 500;
+/// Iterations during which attractive forces are exaggerated, letting
+/// clusters form before they settle.
+const EARLY_EXAGGERATION_ITERATIONS: usize = 100;
+const EARLY_EXAGGERATION: f64 = 12.0;
+
+#[derive(Serialize)]
+struct Point {
+    tag: String,
+    /// Position of the embedding within its tag.
+    index: usize,
+    x: f64,
+    y: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+pub async fn handle(args: VisualizeArgs, ctx: &CommandContext) -> Result<()> {
+    let memory =
+        Principal::from_text(&args.memory_id).context("Failed to parse memory canister id")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+
+    let mut tagged = Vec::new();
+    for tag in &args.tag {
+        let embeddings = client.tagged_embeddings(tag.clone()).await?;
+        note!("{tag}: {} embeddings", embeddings.len());
+        tagged.extend(
+            embeddings
+                .into_iter()
+                .enumerate()
+                .map(|(index, embedding)| (tag.clone(), index, normalized(embedding))),
+        );
+    }
+    if tagged.len() < 2 {
+        return Err(KinicError::Validation(
+            "at least two embeddings are needed for a projection".to_string(),
+        )
+        .into());
+    }
+
+    let vectors: Vec<&[f32]> = tagged.iter().map(|(_, _, v)| v.as_slice()).collect();
+    note!("Projecting {} embeddings to 2D", vectors.len());
+    let coords = match args.method {
+        ProjectionMethod::Pca => pca(&vectors),
+        ProjectionMethod::Tsne => tsne(&vectors, args.perplexity),
+    };
+
+    let mut points = Vec::with_capacity(tagged.len());
+    for ((tag, index, embedding), (x, y)) in tagged.into_iter().zip(coords) {
+        let text = if args.labels {
+            label(&client, &embedding, &tag).await?
+        } else {
+            None
+        };
+        points.push(Point {
+            tag,
+            index,
+            x,
+            y,
+            text,
+        });
+    }
+
+    write_points(&args.out, &points)?;
+    note!("Wrote {} points to {}", points.len(), args.out.display());
+    if let Some(path) = &args.html {
+        write_html(path, &points)?;
+        note!("Wrote scatter plot to {}", path.display());
+    }
+
+    info!(
+        canister_id = %memory,
+        points = points.len(),
+        method = ?args.method,
+        "visualize completed"
+    );
+    Ok(())
+}
+
+/// Sentence of the chunk stored with `embedding`, found by searching for the
+/// embedding itself.
+async fn label(client: &MemoryClient, embedding: &[f32], tag: &str) -> Result<Option<String>> {
+    let hits = client.search_top_k(embedding.to_vec(), 4).await?;
+    Ok(hits
+        .into_iter()
+        .filter_map(|hit| ChunkPayload::parse(&hit.text))
+        .find(|payload| payload.tag == tag)
+        .map(|payload| payload.sentence))
+}
+
+/// Writes CSV when `path` ends in `.csv`, JSON otherwise.
+fn write_points(path: &Path, points: &[Point]) -> Result<()> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let body = if is_csv {
+        let mut csv = String::from("tag,index,x,y,text\n");
+        for point in points {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(&point.tag),
+                point.index,
+                point.x,
+                point.y,
+                csv_field(point.text.as_deref().unwrap_or(""))
+            ));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(points)?
+    };
+    fs::write(path, body).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn write_html(path: &Path, points: &[Point]) -> Result<()> {
+    let template = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/rust/commands/visualize_page.html"
+    ));
+    // Keep the payload from closing the surrounding <script> element.
+    let json = serde_json::to_string(points)?.replace("</", "<\\/");
+    fs::write(path, template.replace("{{POINTS_JSON}}", &json))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Projects onto the two principal components, found by power iteration on
+/// the centered data.
+fn pca(vectors: &[&[f32]]) -> Vec<(f64, f64)> {
+    let dim = vectors[0].len();
+    let n = vectors.len() as f64;
+    let mut mean = vec![0.0f64; dim];
+    for vector in vectors {
+        for (sum, value) in mean.iter_mut().zip(vector.iter()) {
+            *sum += f64::from(*value) / n;
+        }
+    }
+    let centered: Vec<Vec<f64>> = vectors
+        .iter()
+        .map(|vector| {
+            vector
+                .iter()
+                .zip(&mean)
+                .map(|(value, mean)| f64::from(*value) - mean)
+                .collect()
+        })
+        .collect();
+
+    let first = principal_component(&centered, &[]);
+    let second = principal_component(&centered, std::slice::from_ref(&first));
+    centered
+        .iter()
+        .map(|row| (dot64(row, &first), dot64(row, &second)))
+        .collect()
+}
+
+/// Dominant eigenvector of the covariance of `rows`, orthogonal to `found`.
+fn principal_component(rows: &[Vec<f64>], found: &[Vec<f64>]) -> Vec<f64> {
+    let dim = rows[0].len();
+    // A fixed, non-degenerate start keeps the projection reproducible.
+    let mut component: Vec<f64> = (0..dim).map(|i| 1.0 + (i % 7) as f64 / 7.0).collect();
+    for _ in 0..100 {
+        let mut next = vec![0.0f64; dim];
+        for row in rows {
+            let weight = dot64(row, &component);
+            for (sum, value) in next.iter_mut().zip(row) {
+                *sum += weight * value;
+            }
+        }
+        for previous in found {
+            let overlap = dot64(&next, previous);
+            for (value, p) in next.iter_mut().zip(previous) {
+                *value -= overlap * p;
+            }
+        }
+        let norm = dot64(&next, &next).sqrt();
+        if norm == 0.0 {
+            break;
+        }
+        next.iter_mut().for_each(|value| *value /= norm);
+        let converged = dot64(&next, &component).abs() > 1.0 - 1e-9;
+        component = next;
+        if converged {
+            break;
+        }
+    }
+    component
+}
+
+/// Exact t-SNE (van der Maaten & Hinton, 2008), initialized from PCA so the
+/// layout is reproducible. Quadratic in the number of points.
+fn tsne(vectors: &[&[f32]], perplexity: f64) -> Vec<(f64, f64)> {
+    let n = vectors.len();
+    let perplexity = perplexity.min((n - 1) as f64 / 3.0).max(1.0);
+    let affinities = joint_probabilities(vectors, perplexity);
+
+    let init = pca(vectors);
+    let spread = (init.iter().map(|(x, y)| x * x + y * y).sum::<f64>() / n as f64)
+        .sqrt()
+        .max(f64::EPSILON);
+    let mut y: Vec<[f64; 2]> = init
+        .iter()
+        .map(|(a, b)| [a / spread * 1e-4, b / spread * 1e-4])
+        .collect();
+    let mut velocity = vec![[0.0f64; 2]; n];
+    let mut gains = vec![[1.0f64; 2]; n];
+    let learning_rate = (n as f64 / EARLY_EXAGGERATION).max(50.0);
+
+    let mut kernel = vec![0.0f64; n * n];
+    for iteration in 0..TSNE_ITERATIONS {
+        let (exaggeration, momentum) = if iteration < EARLY_EXAGGERATION_ITERATIONS {
+            (EARLY_EXAGGERATION, 0.5)
+        } else {
+            (1.0, 0.8)
+        };
+
+        let mut total = 0.0;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let dx = y[i][0] - y[j][0];
+                let dy = y[i][1] - y[j][1];
+                let q = 1.0 / (1.0 + dx * dx + dy * dy);
+                kernel[i * n + j] = q;
+                kernel[j * n + i] = q;
+                total += 2.0 * q;
+            }
+        }
+
+        for i in 0..n {
+            let mut gradient = [0.0f64; 2];
+            for j in 0..n {
+                if i == j {
+                    continue;
+                }
+                let q = kernel[i * n + j];
+                let force = (exaggeration * affinities[i * n + j] - q / total) * q;
+                gradient[0] += 4.0 * force * (y[i][0] - y[j][0]);
+                gradient[1] += 4.0 * force * (y[i][1] - y[j][1]);
+            }
+            for axis in 0..2 {
+                let gain = &mut gains[i][axis];
+                *gain = if (gradient[axis] > 0.0) != (velocity[i][axis] > 0.0) {
+                    *gain + 0.2
+                } else {
+                    (*gain * 0.8).max(0.01)
+                };
+                velocity[i][axis] =
+                    momentum * velocity[i][axis] - learning_rate * *gain * gradient[axis];
+            }
+        }
+        for (point, step) in y.iter_mut().zip(&velocity) {
+            point[0] += step[0];
+            point[1] += step[1];
+        }
+    }
+    y.into_iter().map(|[x, y]| (x, y)).collect()
+}
+
+/// Symmetric input similarities `p_ij`, with each point's Gaussian bandwidth
+/// searched so its neighborhood has the requested perplexity.
+fn joint_probabilities(vectors: &[&[f32]], perplexity: f64) -> Vec<f64> {
+    let n = vectors.len();
+    let mut distances = vec![0.0f64; n * n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let d: f64 = vectors[i]
+                .iter()
+                .zip(vectors[j].iter())
+                .map(|(a, b)| f64::from(a - b).powi(2))
+                .sum();
+            distances[i * n + j] = d;
+            distances[j * n + i] = d;
+        }
+    }
+
+    let target_entropy = perplexity.ln();
+    let mut conditional = vec![0.0f64; n * n];
+    for i in 0..n {
+        let row = &distances[i * n..(i + 1) * n];
+        let (mut low, mut high, mut beta) = (0.0f64, f64::INFINITY, 1.0f64);
+        for _ in 0..50 {
+            let mut sum = 0.0;
+            let mut weighted = 0.0;
+            for (j, d) in row.iter().enumerate() {
+                if j != i {
+                    let p = (-d * beta).exp();
+                    sum += p;
+                    weighted += d * p;
+                }
+            }
+            let sum = sum.max(f64::MIN_POSITIVE);
+            let entropy = sum.ln() + beta * weighted / sum;
+            if (entropy - target_entropy).abs() < 1e-5 {
+                break;
+            }
+            if entropy > target_entropy {
+                low = beta;
+                beta = if high.is_finite() {
+                    (beta + high) / 2.0
+                } else {
+                    beta * 2.0
+                };
+            } else {
+                high = beta;
+                beta = (beta + low) / 2.0;
+            }
+        }
+        let sum: f64 = row
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, d)| (-d * beta).exp())
+            .sum::<f64>()
+            .max(f64::MIN_POSITIVE);
+        for (j, d) in row.iter().enumerate() {
+            if j != i {
+                conditional[i * n + j] = (-d * beta).exp() / sum;
+            }
+        }
+    }
+
+    let mut joint = vec![0.0f64; n * n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j {
+                joint[i * n + j] = ((conditional[i * n + j] + conditional[j * n + i])
+                    / (2.0 * n as f64))
+                    .max(1e-12);
+            }
+        }
+    }
+    joint
+}
+
+fn dot64(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+    vector
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ic_agent::export::Principal;
+use tracing::{debug, info};
+
+use crate::{cli::WaitArgs, clients::memory::MemoryClient, error::KinicError, output::note};
+
+use super::CommandContext;
+
+/// Pause between readiness checks.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub async fn handle(args: WaitArgs, ctx: &CommandContext) -> Result<()> {
+    let memory = Principal::from_text(&args.memory_id)
+        .context("Failed to parse canister id for wait command")?;
+    let client = MemoryClient::new(ctx.agent().await?, memory);
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.timeout);
+
+    note!(
+        "Waiting for {memory} to answer (up to {}s)...",
+        args.timeout
+    );
+    loop {
+        let err = match client.ping().await {
+            Ok(()) => {
+                let waited = started.elapsed().as_secs();
+                info!(%memory, waited, "memory ready");
+                match client.dim().await.ok().flatten() {
+                    Some(dim) => note!("{memory} is ready (dimension {dim}) after {waited}s."),
+                    None => note!("{memory} is ready after {waited}s."),
+                }
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+        debug!(%memory, error = %format!("{err:#}"), "memory not ready yet");
+        if Instant::now() + POLL_INTERVAL > deadline {
+            return Err(KinicError::Network(format!(
+                "{memory} did not answer within {}s; last error: {err:#}",
+                args.timeout
+            ))
+            .into());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -1,22 +1,51 @@
-use std::env;
+#[cfg(feature = "native")]
+use std::time::Instant;
+use std::{collections::HashMap, env, fs, path::PathBuf, sync::OnceLock, time::Duration};
 
-use anyhow::{Context, Result, bail};
-use reqwest::Client;
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use reqwest::{Client, RequestBuilder, Response};
+use ring::digest;
 use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+#[cfg(feature = "native")]
+use tokio::{sync::Mutex, task::JoinSet};
+use tracing::debug;
+
+use crate::{error::KinicError, network};
+#[cfg(feature = "native")]
+use crate::{metrics, usage};
 
 pub(crate) const EMBEDDING_API_ENV_VAR: &str = "EMBEDDING_API_ENDPOINT";
 pub(crate) const DEFAULT_EMBEDDING_API_ENDPOINT: &str = "https://api.kinic.io";
 const LATE_CHUNKING_PATH: &str = "/late-chunking";
 const EMBEDDING_PATH: &str = "/embedding";
+const CAPTION_PATH: &str = "/caption";
+pub(crate) const VISION_API_ENV_VAR: &str = "KINIC_VISION_ENDPOINT";
+const EMBEDDING_CACHE_DISABLE_ENV_VAR: &str = "KINIC_NO_EMBEDDING_CACHE";
+const TIMEOUT_ENV_VAR: &str = "KINIC_EMBEDDING_TIMEOUT_SECS";
+const MAX_IN_FLIGHT_ENV_VAR: &str = "KINIC_EMBEDDING_MAX_IN_FLIGHT";
+#[cfg(feature = "native")]
+const RATE_LIMIT_ENV_VAR: &str = "KINIC_EMBEDDING_RATE_PER_SEC";
+const DIM_ENV_VAR: &str = "KINIC_EMBEDDING_DIM";
+/// Vector size produced by the default Kinic embedding API.
+const DEFAULT_EMBEDDING_DIM: usize = 1024;
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+#[cfg(feature = "native")]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
-pub async fn late_chunking(text: &str) -> Result<Vec<LateChunk>> {
+/// Splits `text` into sentence chunks with their embeddings. `language`
+/// (e.g. `ja`) selects the segmentation rules; without it the endpoint
+/// detects the language.
+pub async fn late_chunking(text: &str, language: Option<&str>) -> Result<Vec<LateChunk>> {
     let url = format!("{}{}", embedding_base_url(), LATE_CHUNKING_PATH);
-    let response = Client::new()
-        .post(url)
-        .json(&LateChunkingRequest { markdown: text })
-        .send()
-        .await
-        .context("Failed to call late chunking endpoint")?;
+    let response = send_limited(http_client()?.post(url).json(&LateChunkingRequest {
+        markdown: text,
+        language,
+    }))
+    .await
+    .context("Failed to call late chunking endpoint")?;
 
     let payload = ensure_success(response)
         .await?
@@ -27,13 +56,125 @@ pub async fn late_chunking(text: &str) -> Result<Vec<LateChunk>> {
 }
 
 pub async fn fetch_embedding(text: &str) -> Result<Vec<f32>> {
-    let url = format!("{}{}", embedding_base_url(), EMBEDDING_PATH);
-    let response = Client::new()
-        .post(url)
-        .json(&EmbeddingRequest { content: text })
-        .send()
+    let mut embeddings = fetch_embeddings(&[text]).await?;
+    Ok(embeddings.remove(0))
+}
+
+/// Embeds every input, in order. Cached vectors are served from
+/// `~/.cache/kinic/embeddings`; duplicate inputs hit the API once and misses
+/// are fetched concurrently up to the in-flight limit.
+pub async fn fetch_embeddings(texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+    let base_url = embedding_base_url();
+    let cache = EmbeddingCache::open(&base_url);
+    let mut resolved: HashMap<&str, Vec<f32>> = HashMap::new();
+    let mut missing = Vec::new();
+
+    for &text in texts {
+        if resolved.contains_key(text) {
+            continue;
+        }
+        match cache.as_ref().and_then(|cache| cache.get(text)) {
+            Some(hit) => {
+                resolved.insert(text, hit);
+            }
+            None => {
+                resolved.insert(text, Vec::new());
+                missing.push(text.to_string());
+            }
+        }
+    }
+
+    for (text, embedding) in request_embeddings(&base_url, missing).await? {
+        if let Some(cache) = &cache {
+            cache.put(&text, &embedding);
+        }
+        if let Some(slot) = resolved.get_mut(text.as_str()) {
+            *slot = embedding;
+        }
+    }
+
+    Ok(texts.iter().map(|text| resolved[text].clone()).collect())
+}
+
+/// Fetches `texts` concurrently, up to the in-flight limit.
+#[cfg(feature = "native")]
+async fn request_embeddings(base_url: &str, texts: Vec<String>) -> Result<Vec<(String, Vec<f32>)>> {
+    let mut pending = JoinSet::new();
+    for text in texts {
+        let base_url = base_url.to_string();
+        pending.spawn(async move {
+            let embedding = request_embedding(&base_url, &text).await;
+            (text, embedding)
+        });
+    }
+    let mut fetched = Vec::with_capacity(pending.len());
+    while let Some(joined) = pending.join_next().await {
+        let (text, embedding) = joined.context("embedding task panicked")?;
+        fetched.push((text, embedding?));
+    }
+    Ok(fetched)
+}
+
+/// Without a tokio runtime to spawn on, `texts` are fetched one at a time.
+#[cfg(not(feature = "native"))]
+async fn request_embeddings(base_url: &str, texts: Vec<String>) -> Result<Vec<(String, Vec<f32>)>> {
+    let mut fetched = Vec::with_capacity(texts.len());
+    for text in texts {
+        let embedding = request_embedding(base_url, &text).await?;
+        fetched.push((text, embedding));
+    }
+    Ok(fetched)
+}
+
+/// Asks the vision endpoint for a searchable description of an image.
+/// `prompt` optionally steers the model (e.g. "transcribe the diagram").
+pub async fn describe_image(image: &[u8], mime_type: &str, prompt: Option<&str>) -> Result<String> {
+    let response = send_limited(http_client()?.post(vision_url()).json(&CaptionRequest {
+        image_base64: STANDARD.encode(image),
+        mime_type,
+        prompt,
+    }))
+    .await
+    .context("Failed to call vision endpoint")?;
+
+    let payload = ensure_success(response)
+        .await?
+        .json::<CaptionResponse>()
         .await
-        .context("Failed to call embedding endpoint")?;
+        .context("Failed to decode vision response")?;
+    let description = payload.description.trim().to_string();
+    if description.is_empty() {
+        return Err(KinicError::EmbeddingApi(
+            "vision endpoint returned an empty description".to_string(),
+        )
+        .into());
+    }
+    Ok(description)
+}
+
+/// `KINIC_VISION_ENDPOINT` as a full URL, else `/caption` on the embedding API.
+fn vision_url() -> String {
+    env::var(VISION_API_ENV_VAR)
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| format!("{}{CAPTION_PATH}", embedding_base_url()))
+}
+
+/// Embeds `text` with a fresh API call, bypassing the cache, for timing the
+/// provider.
+pub async fn fetch_embedding_uncached(text: &str) -> Result<Vec<f32>> {
+    request_embedding(&embedding_base_url(), text).await
+}
+
+async fn request_embedding(base_url: &str, text: &str) -> Result<Vec<f32>> {
+    let url = format!("{base_url}{EMBEDDING_PATH}");
+    let response = send_limited(
+        http_client()?
+            .post(url)
+            .json(&EmbeddingRequest { content: text }),
+    )
+    .await
+    .context("Failed to call embedding endpoint")?;
 
     let payload = ensure_success(response)
         .await?
@@ -43,23 +184,246 @@ pub async fn fetch_embedding(text: &str) -> Result<Vec<f32>> {
     Ok(payload.embedding)
 }
 
-async fn ensure_success(response: reqwest::Response) -> Result<reqwest::Response> {
+/// Shared HTTP client for the embedding/chat API, with the configured
+/// request timeout, proxy and CA certificates applied.
+#[cfg(feature = "native")]
+pub(crate) fn http_client() -> Result<&'static Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let builder = Client::builder()
+        .timeout(limits().timeout)
+        .connect_timeout(CONNECT_TIMEOUT);
+    let client = network::configure(builder)?
+        .build()
+        .context("Failed to build embedding API client")?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// In the browser, `fetch` applies its own timeouts and proxy settings.
+#[cfg(not(feature = "native"))]
+pub(crate) fn http_client() -> Result<&'static Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    Ok(CLIENT.get_or_init(Client::new))
+}
+
+/// Sends a request once an in-flight slot and a rate-limit token are free.
+pub(crate) async fn send_limited(request: RequestBuilder) -> Result<Response> {
+    let _permit = in_flight()
+        .acquire()
+        .await
+        .context("embedding request limiter closed")?;
+    #[cfg(feature = "native")]
+    if let Some(bucket) = rate_limiter() {
+        bucket.acquire().await;
+    }
+    let (client, request) = request.build_split();
+    let request = request.context("Failed to build embedding API request")?;
+    #[cfg(feature = "native")]
+    let (endpoint, started) = (request.url().path().to_string(), Instant::now());
+    #[cfg(feature = "native")]
+    usage::record_api_request(&endpoint, request.body().and_then(|body| body.as_bytes()));
+    let response = client.execute(request).await.map_err(|err| {
+        if err.is_timeout() {
+            KinicError::EmbeddingApi(format!(
+                "embedding API request timed out after {}s (use --timeout or {TIMEOUT_ENV_VAR} to adjust)",
+                limits().timeout.as_secs()
+            ))
+            .into()
+        } else {
+            anyhow::Error::new(err).context(KinicError::EmbeddingApi(
+                "embedding API is unreachable".to_string(),
+            ))
+        }
+    });
+    #[cfg(feature = "native")]
+    metrics::record_embedding_request(&endpoint, started.elapsed(), &response);
+    response
+}
+
+struct Limits {
+    timeout: Duration,
+    max_in_flight: usize,
+    #[cfg(feature = "native")]
+    rate_per_sec: Option<f64>,
+}
+
+fn limits() -> &'static Limits {
+    static LIMITS: OnceLock<Limits> = OnceLock::new();
+    LIMITS.get_or_init(|| Limits {
+        timeout: network::timeout().unwrap_or_else(|| {
+            Duration::from_secs(
+                env_number(TIMEOUT_ENV_VAR)
+                    .filter(|secs| *secs > 0)
+                    .unwrap_or(DEFAULT_TIMEOUT_SECS),
+            )
+        }),
+        max_in_flight: env_number(MAX_IN_FLIGHT_ENV_VAR)
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_MAX_IN_FLIGHT),
+        #[cfg(feature = "native")]
+        rate_per_sec: env_number::<f64>(RATE_LIMIT_ENV_VAR).filter(|rate| *rate > 0.0),
+    })
+}
+
+fn env_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let raw = env::var(name).ok()?;
+    match raw.trim().parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            debug!(name, value = %raw, "ignoring unparsable setting");
+            None
+        }
+    }
+}
+
+fn in_flight() -> &'static Semaphore {
+    static SEMAPHORE: OnceLock<Semaphore> = OnceLock::new();
+    SEMAPHORE.get_or_init(|| Semaphore::new(limits().max_in_flight))
+}
+
+#[cfg(feature = "native")]
+fn rate_limiter() -> Option<&'static TokenBucket> {
+    static BUCKET: OnceLock<Option<TokenBucket>> = OnceLock::new();
+    BUCKET
+        .get_or_init(|| limits().rate_per_sec.map(TokenBucket::new))
+        .as_ref()
+}
+
+/// Token bucket allowing bursts of up to one second's worth of requests.
+#[cfg(feature = "native")]
+struct TokenBucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+#[cfg(feature = "native")]
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            state: Mutex::new((rate.max(1.0), Instant::now())),
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let (tokens, last) = &mut *state;
+                let now = Instant::now();
+                let capacity = self.rate.max(1.0);
+                *tokens =
+                    (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(capacity);
+                *last = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - *tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// On-disk cache of embedding vectors keyed by SHA-256 of endpoint + text.
+/// Failures to read or write are logged and treated as misses.
+struct EmbeddingCache {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl EmbeddingCache {
+    fn open(base_url: &str) -> Option<Self> {
+        // The core has no file system to cache on.
+        if cfg!(not(feature = "native")) || env::var_os(EMBEDDING_CACHE_DISABLE_ENV_VAR).is_some() {
+            return None;
+        }
+        let home = env::var_os("HOME")?;
+        let dir = PathBuf::from(home).join(".cache/kinic/embeddings");
+        if let Err(err) = fs::create_dir_all(&dir) {
+            debug!(error = %err, "embedding cache disabled");
+            return None;
+        }
+        Some(Self {
+            dir,
+            base_url: base_url.to_string(),
+        })
+    }
+
+    fn entry_path(&self, text: &str) -> PathBuf {
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        ctx.update(self.base_url.as_bytes());
+        ctx.update(b"\n");
+        ctx.update(text.as_bytes());
+        self.dir
+            .join(format!("{}.json", hex::encode(ctx.finish().as_ref())))
+    }
+
+    fn get(&self, text: &str) -> Option<Vec<f32>> {
+        let bytes = fs::read(self.entry_path(text)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn put(&self, text: &str, embedding: &[f32]) {
+        let path = self.entry_path(text);
+        let tmp = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(embedding)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| fs::write(&tmp, bytes).map_err(anyhow::Error::from))
+            .and_then(|_| fs::rename(&tmp, &path).map_err(anyhow::Error::from));
+        if let Err(err) = result {
+            debug!(error = %err, path = %path.display(), "failed to write embedding cache entry");
+        }
+    }
+}
+
+async fn ensure_success(response: Response) -> Result<Response> {
     if response.status().is_success() {
         return Ok(response);
     }
 
     let status = response.status();
     let body = response.text().await.unwrap_or_default();
-    bail!("embedding API request failed with status {status}: {body}");
+    Err(KinicError::EmbeddingApi(format!(
+        "embedding API request failed with status {status}: {body}"
+    ))
+    .into())
+}
+
+/// Dimension of the vectors returned by the configured embedding provider,
+/// used as the default for new memories.
+pub fn embedding_dim() -> usize {
+    env_number::<usize>(DIM_ENV_VAR)
+        .filter(|dim| *dim > 0)
+        .unwrap_or(DEFAULT_EMBEDDING_DIM)
 }
 
 pub(crate) fn embedding_base_url() -> String {
     env::var(EMBEDDING_API_ENV_VAR).unwrap_or_else(|_| DEFAULT_EMBEDDING_API_ENDPOINT.to_string())
 }
 
+#[derive(Serialize)]
+struct CaptionRequest<'a> {
+    image_base64: String,
+    mime_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct CaptionResponse {
+    #[serde(alias = "caption")]
+    description: String,
+}
+
 #[derive(Serialize)]
 struct LateChunkingRequest<'a> {
     markdown: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
 }
 
 #[derive(Debug, Deserialize)]
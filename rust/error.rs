@@ -0,0 +1,130 @@
+//! Error classes surfaced to scripts (exit codes) and Python (exception types).
+//!
+//! Code keeps using `anyhow`; call sites that know what went wrong attach a
+//! [`KinicError`] (as the error or as context) and [`classify`] recovers it.
+
+#[cfg(feature = "native")]
+use std::fmt::Display;
+
+use candid::types::principal::PrincipalError;
+use ic_agent::AgentError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KinicError {
+    #[error("{0}")]
+    Identity(String),
+    #[error("{0}")]
+    Network(String),
+    #[error("{0}")]
+    CanisterReject(String),
+    #[error("{0}")]
+    EmbeddingApi(String),
+    #[error("{0}")]
+    Payment(String),
+    #[error("{0}")]
+    Validation(String),
+}
+
+impl KinicError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            KinicError::Identity(_) => ErrorClass::Identity,
+            KinicError::Network(_) => ErrorClass::Network,
+            KinicError::CanisterReject(_) => ErrorClass::CanisterReject,
+            KinicError::EmbeddingApi(_) => ErrorClass::EmbeddingApi,
+            KinicError::Payment(_) => ErrorClass::Payment,
+            KinicError::Validation(_) => ErrorClass::Validation,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Identity,
+    Network,
+    CanisterReject,
+    EmbeddingApi,
+    Payment,
+    Validation,
+    Other,
+}
+
+impl ErrorClass {
+    /// Process exit code. Usage errors from argument parsing also exit with
+    /// the `Validation` code.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorClass::Other => 1,
+            ErrorClass::Identity => 2,
+            ErrorClass::Payment => 3,
+            ErrorClass::Network => 4,
+            ErrorClass::CanisterReject => 5,
+            ErrorClass::EmbeddingApi => 6,
+            ErrorClass::Validation => 7,
+        }
+    }
+
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorClass::Identity => {
+                Some("check --identity, or run `kinic-cli login` again when using --ii")
+            }
+            ErrorClass::Network => {
+                Some("check your connection and that --ic matches where the canister lives")
+            }
+            ErrorClass::CanisterReject => Some(
+                "the canister refused the call; check the canister id and that your identity has a role on it",
+            ),
+            ErrorClass::EmbeddingApi => {
+                Some("check EMBEDDING_API_ENDPOINT and the KINIC_EMBEDDING_* limits")
+            }
+            ErrorClass::Payment => Some("check your balance with `kinic-cli balance`"),
+            ErrorClass::Validation | ErrorClass::Other => None,
+        }
+    }
+}
+
+/// Finds the most specific class for an error: an attached [`KinicError`]
+/// wins, otherwise well-known causes in the chain are mapped.
+pub fn classify(err: &anyhow::Error) -> ErrorClass {
+    if let Some(kinic) = err.downcast_ref::<KinicError>() {
+        return kinic.class();
+    }
+
+    for cause in err.chain() {
+        if let Some(kinic) = cause.downcast_ref::<KinicError>() {
+            return kinic.class();
+        }
+        if let Some(agent) = cause.downcast_ref::<AgentError>() {
+            return match agent {
+                AgentError::CertifiedReject { .. } | AgentError::UncertifiedReject { .. } => {
+                    ErrorClass::CanisterReject
+                }
+                AgentError::TransportError(_)
+                | AgentError::TimeoutWaitingForResponse()
+                | AgentError::HttpError(_) => ErrorClass::Network,
+                _ => ErrorClass::Other,
+            };
+        }
+        if cause.is::<reqwest::Error>() {
+            return ErrorClass::Network;
+        }
+        if cause.is::<PrincipalError>() {
+            return ErrorClass::Validation;
+        }
+    }
+    ErrorClass::Other
+}
+
+/// Maps an application-level `Err` returned by a canister.
+#[cfg(feature = "native")]
+pub(crate) fn rejected(err: impl Display) -> anyhow::Error {
+    KinicError::CanisterReject(err.to_string()).into()
+}
+
+/// Maps a ledger error (approve/transfer) to a payment failure.
+#[cfg(feature = "native")]
+pub(crate) fn payment_failed(err: impl Display) -> anyhow::Error {
+    KinicError::Payment(err.to_string()).into()
+}
@@ -0,0 +1,253 @@
+//! Stable C ABI over the same operations as the Python module, for Swift
+//! and Kotlin apps; `include/kinic.h` declares it. Every function returns 0
+//! on success or the CLI exit code of the failure (see `docs/cli.md`), with
+//! the message available from `kinic_last_error` on the same thread.
+//! Strings returned through out-parameters are freed with
+//! `kinic_string_free`. A panic is caught at the boundary and reported as a
+//! failure instead of unwinding into the caller.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result, anyhow};
+use ic_agent::export::Principal;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+use crate::{
+    error::{self, KinicError},
+    python,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+#[derive(Serialize)]
+struct Hit<'a> {
+    score: f32,
+    payload: &'a str,
+}
+
+/// Deploys a new memory, paying the launcher from `identity`'s balance, and
+/// writes its canister id to `out_id`.
+///
+/// # Safety
+///
+/// The string arguments must be valid NUL-terminated UTF-8 and `out_id` a
+/// valid pointer to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kinic_create_memory(
+    identity: *const c_char,
+    name: *const c_char,
+    description: *const c_char,
+    ic: bool,
+    out_id: *mut *mut c_char,
+) -> i32 {
+    run(|| {
+        check_out(out_id, "out_id")?;
+        let identity = unsafe { read(identity, "identity") }?;
+        let name = unsafe { read(name, "name") }?;
+        let description = unsafe { read(description, "description") }?;
+        let id = block_on(python::create_memory(ic, identity, name, description))?;
+        unsafe { write_string(out_id, id) }
+    })
+}
+
+/// Writes the canister ids of `identity`'s memories to `out_json` as a JSON
+/// array of strings.
+///
+/// # Safety
+///
+/// `identity` must be valid NUL-terminated UTF-8 and `out_json` a valid
+/// pointer to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kinic_list_memories(
+    identity: *const c_char,
+    ic: bool,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    run(|| {
+        check_out(out_json, "out_json")?;
+        let identity = unsafe { read(identity, "identity") }?;
+        let memories = block_on(python::list_memories(ic, identity))?;
+        unsafe { write_string(out_json, serde_json::to_string(&memories)?) }
+    })
+}
+
+/// Chunks and embeds `text` and stores it in `memory_id` under `tag`;
+/// writes the number of chunks to `out_chunks`. Text that looks like it
+/// contains credentials is refused unless `allow_secrets` is set.
+///
+/// # Safety
+///
+/// The string arguments must be valid NUL-terminated UTF-8 and `out_chunks`
+/// a valid pointer to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kinic_insert_text(
+    identity: *const c_char,
+    memory_id: *const c_char,
+    tag: *const c_char,
+    text: *const c_char,
+    ic: bool,
+    allow_secrets: bool,
+    out_chunks: *mut usize,
+) -> i32 {
+    run(|| {
+        check_out(out_chunks, "out_chunks")?;
+        let identity = unsafe { read(identity, "identity") }?;
+        let memory_id = unsafe { read(memory_id, "memory_id") }?;
+        let tag = unsafe { read(tag, "tag") }?;
+        let text = unsafe { read(text, "text") }?;
+        let chunks = block_on(python::insert_memory(
+            ic,
+            identity,
+            memory_id,
+            tag,
+            Some(text),
+            None,
+            allow_secrets,
+            None,
+        ))?;
+        unsafe { *out_chunks = chunks };
+        Ok(())
+    })
+}
+
+/// Searches `memory_id` for `query` and writes the best `top_k` matches
+/// (every match when 0) to `out_json` as a JSON array of
+/// `{"score": ..., "payload": ...}` objects, best first.
+///
+/// # Safety
+///
+/// The string arguments must be valid NUL-terminated UTF-8 and `out_json` a
+/// valid pointer to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kinic_search(
+    identity: *const c_char,
+    memory_id: *const c_char,
+    query: *const c_char,
+    top_k: usize,
+    ic: bool,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    run(|| {
+        check_out(out_json, "out_json")?;
+        let identity = unsafe { read(identity, "identity") }?;
+        let memory_id = unsafe { read(memory_id, "memory_id") }?;
+        let query = unsafe { read(query, "query") }?;
+        let memory =
+            Principal::from_text(memory_id).context("Failed to parse memory canister id")?;
+        let results = block_on(async move {
+            let agent = python::build_agent(ic, identity).await?;
+            let top_k = (top_k > 0).then_some(top_k);
+            python::search_memories_with_agent(agent, memory, query, top_k).await
+        })?;
+        let hits: Vec<Hit> = results
+            .iter()
+            .map(|(score, payload)| Hit {
+                score: *score,
+                payload,
+            })
+            .collect();
+        unsafe { write_string(out_json, serde_json::to_string(&hits)?) }
+    })
+}
+
+/// The message of the last failure on this thread, or null. Valid until the
+/// next call on this thread; not to be freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn kinic_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Frees a string returned through an out-parameter. Null is ignored.
+///
+/// # Safety
+///
+/// `value` must come from this library and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kinic_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+/// Runs `body`, recording its error for `kinic_last_error`. A panic is
+/// reported like any other failure, since unwinding across `extern "C"`
+/// aborts the caller.
+fn run(body: impl FnOnce() -> Result<()>) -> i32 {
+    LAST_ERROR.with(|last| last.borrow_mut().take());
+    let result = panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(anyhow!("internal error: {message}"))
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let code = error::classify(&err).exit_code();
+            let message = CString::new(format!("{err:#}").replace('\0', " ")).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            i32::from(code)
+        }
+    }
+}
+
+fn block_on<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    shared_runtime()?.block_on(future)
+}
+
+fn shared_runtime() -> Result<&'static Runtime> {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new().context("failed to start tokio runtime")?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+unsafe fn read(value: *const c_char, name: &str) -> Result<String> {
+    if value.is_null() {
+        return Err(null_argument(name));
+    }
+    let value = unsafe { CStr::from_ptr(value) };
+    value
+        .to_str()
+        .map(str::to_string)
+        .map_err(|_| KinicError::Validation(format!("{name} is not valid UTF-8")).into())
+}
+
+unsafe fn write_string(out: *mut *mut c_char, value: String) -> Result<()> {
+    if out.is_null() {
+        return Err(null_argument("output pointer"));
+    }
+    let value = CString::new(value).context("result contains a NUL byte")?;
+    unsafe { *out = value.into_raw() };
+    Ok(())
+}
+
+/// Refuses a null out-parameter before any work is done.
+fn check_out<T>(out: *mut T, name: &str) -> Result<()> {
+    if out.is_null() {
+        return Err(null_argument(name));
+    }
+    Ok(())
+}
+
+fn null_argument(name: &str) -> anyhow::Error {
+    KinicError::Validation(format!("{name} is null")).into()
+}
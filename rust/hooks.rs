@@ -0,0 +1,157 @@
+//! User hooks declared as `[[hooks]]` in the config file, run around
+//! ingestion, deployment and sync jobs. Each hook is a shell command that
+//! reads the event as JSON on stdin, or a URL the same JSON is POSTed to:
+//!
+//! ```json
+//! {"event": "sync.after", "time": 1760000000, "job": "notes",
+//!  "memory_id": "yta6k-5x777-77774-aaaaa-cai", "ok": true,
+//!  "ingested": 3, "unchanged": 41}
+//! ```
+//!
+//! A failing `*.before` hook aborts the operation; a failing `*.after` hook
+//! only warns, since the work is already done.
+
+use std::{
+    io::{self, Write as _},
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use tracing::debug;
+
+use crate::{
+    embedding::http_client,
+    jobs::{self, Hook},
+};
+
+/// Events a hook can subscribe to.
+pub const EVENTS: &[&str] = &[
+    "insert.before",
+    "insert.after",
+    "create.before",
+    "create.after",
+    "sync.after",
+];
+
+/// Runs the hooks of `event` in config order and stops at the first failure.
+pub async fn before(event: &str, details: Value) -> Result<()> {
+    let hooks = subscribed(event)?;
+    let payload = payload(event, details);
+    for hook in &hooks {
+        run(hook, event, &payload)
+            .await
+            .with_context(|| format!("{event} hook failed; aborting"))?;
+    }
+    Ok(())
+}
+
+/// Runs every hook of `event`, warning about the ones that fail.
+pub async fn after(event: &str, details: Value) {
+    let hooks = match subscribed(event) {
+        Ok(hooks) => hooks,
+        Err(err) => {
+            eprintln!("warning: {event} hooks not run: {err:#}");
+            return;
+        }
+    };
+    let payload = payload(event, details);
+    for hook in &hooks {
+        if let Err(err) = run(hook, event, &payload).await {
+            eprintln!("warning: {event} hook failed: {err:#}");
+        }
+    }
+}
+
+/// `ok` and, on failure, `error` of a finished operation.
+pub fn outcome<T>(result: &Result<T>) -> Value {
+    match result {
+        Ok(_) => json!({"ok": true}),
+        Err(err) => json!({"ok": false, "error": format!("{err:#}")}),
+    }
+}
+
+fn subscribed(event: &str) -> Result<Vec<Hook>> {
+    Ok(jobs::load_config()?
+        .hooks
+        .into_iter()
+        .filter(|hook| hook.event == event)
+        .collect())
+}
+
+/// `event` and `time` (Unix seconds) followed by the fields of `details`.
+pub fn payload(event: &str, details: Value) -> Value {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs());
+    let mut payload = json!({"event": event, "time": time});
+    if let (Some(fields), Value::Object(details)) = (payload.as_object_mut(), details) {
+        fields.extend(details);
+    }
+    payload
+}
+
+async fn run(hook: &Hook, event: &str, payload: &Value) -> Result<()> {
+    let body = serde_json::to_vec(payload)?;
+    if let Some(url) = &hook.url {
+        debug!(%event, %url, "posting hook");
+        http_client()?
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("POST {url}"))?;
+        return Ok(());
+    }
+    let Some(command) = hook.command.clone() else {
+        return Ok(());
+    };
+    debug!(%event, %command, "running hook");
+    let event = event.to_string();
+    tokio::task::spawn_blocking(move || run_command(&command, &event, &body))
+        .await
+        .context("hook task panicked")?
+}
+
+/// Runs `command` through the shell with `body` on stdin. Its stdout goes to
+/// stderr so it cannot mix with the command's own output.
+fn run_command(command: &str, event: &str, body: &[u8]) -> Result<()> {
+    let mut child = shell(command)
+        .env("KINIC_EVENT", event)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(io::stderr()))
+        .spawn()
+        .with_context(|| format!("Failed to run `{command}`"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores its input may exit before reading it.
+        if let Err(err) = stdin.write_all(body)
+            && err.kind() != io::ErrorKind::BrokenPipe
+        {
+            return Err(err).with_context(|| format!("Failed to write to `{command}`"));
+        }
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for `{command}`"))?;
+    if !status.success() {
+        bail!("`{command}` exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
@@ -1,5 +1,8 @@
 use std::{
-    fs,
+    collections::BTreeSet,
+    fmt, fs,
+    io::IsTerminal,
+    num::NonZeroU32,
     path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -11,31 +14,220 @@ use ic_agent::export::Principal;
 use ic_agent::identity::{BasicIdentity, DelegatedIdentity, DelegationError, SignedDelegation};
 use ic_ed25519::PublicKey;
 use pkcs8::{ObjectIdentifier, spki::SubjectPublicKeyInfoRef};
-use ring::signature::Ed25519KeyPair;
+use ring::{aead, pbkdf2, rand::SecureRandom, signature::Ed25519KeyPair};
 use serde::{Deserialize, Serialize};
 use std::fs::OpenOptions;
 use std::io::Write;
 use tracing::warn;
 
+use crate::{agent::KEYRING_SERVICE_NAME, error::KinicError};
+
+pub const PASSPHRASE_ENV_VAR: &str = "KINIC_IDENTITY_PASSPHRASE";
+/// Keyring account holding the session saved by `login --store keyring`.
+/// Named sessions append `:<name>`.
+pub const KEYRING_SESSION_ACCOUNT: &str = "kinic_ii_session";
+/// Keyring account listing the named sessions saved to the keyring, since
+/// keyrings cannot enumerate their entries.
+const KEYRING_SESSION_INDEX_ACCOUNT: &str = "kinic_ii_sessions";
+/// Name `sessions list` shows for the unnamed session in identity.json.
+pub const DEFAULT_SESSION_NAME: &str = "default";
+const KDF_NAME: &str = "pbkdf2-hmac-sha256";
+const PBKDF2_ITERATIONS: u32 = 600_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredIdentity {
     pub version: u8,
     pub identity_provider: String,
     pub user_public_key_hex: String,
+    /// Plaintext session key; empty when `encrypted_session_key` is set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub session_pkcs8_hex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_session_key: Option<EncryptedSessionKey>,
     pub delegations: Vec<SignedDelegation>,
     pub expiration_ns: u64,
     pub created_at_ns: u64,
 }
 
+/// Session key sealed with AES-256-GCM under a PBKDF2-HMAC-SHA256 key
+/// derived from a passphrase. The user public key is bound as associated
+/// data, so the blob cannot be moved to another identity file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedSessionKey {
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt_hex: String,
+    pub nonce_hex: String,
+    pub ciphertext_hex: String,
+}
+
+impl StoredIdentity {
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted_session_key.is_some()
+    }
+
+    /// Replaces the plaintext session key with an encrypted one.
+    pub fn encrypt(&mut self, passphrase: &str) -> Result<()> {
+        if self.is_encrypted() {
+            return Ok(());
+        }
+        let pkcs8 = hex::decode(&self.session_pkcs8_hex).context("Failed to decode session key")?;
+        self.encrypted_session_key = Some(seal_session_key(
+            &pkcs8,
+            passphrase,
+            self.user_public_key_hex.as_bytes(),
+        )?);
+        self.session_pkcs8_hex.clear();
+        Ok(())
+    }
+
+    /// Replaces the encrypted session key with the plaintext one.
+    pub fn decrypt(&mut self, passphrase: &str) -> Result<()> {
+        let Some(sealed) = &self.encrypted_session_key else {
+            return Ok(());
+        };
+        let pkcs8 = open_session_key(sealed, passphrase, self.user_public_key_hex.as_bytes())?;
+        self.session_pkcs8_hex = hex::encode(pkcs8);
+        self.encrypted_session_key = None;
+        Ok(())
+    }
+
+    /// The session key, asking for the passphrase if it is encrypted.
+    fn session_pkcs8(&self) -> Result<Vec<u8>> {
+        match &self.encrypted_session_key {
+            Some(sealed) => open_session_key(
+                sealed,
+                &read_passphrase("Identity passphrase: ")?,
+                self.user_public_key_hex.as_bytes(),
+            ),
+            None => hex::decode(&self.session_pkcs8_hex).context("Failed to decode session key"),
+        }
+    }
+}
+
 pub struct SessionKeyMaterial {
     pub pkcs8: Vec<u8>,
     pub public_key: Vec<u8>,
 }
 
 pub fn default_identity_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("identity.json"))
+}
+
+/// Identity file of a named session (`login --as <name>`); `None` is the
+/// default session in identity.json.
+pub fn session_identity_path(session: Option<&str>) -> Result<PathBuf> {
+    match session {
+        None => default_identity_path(),
+        Some(name) => Ok(sessions_dir()?.join(format!("{name}.json"))),
+    }
+}
+
+/// Validates a `--session` / `--as` name. `default` refers to the unnamed
+/// session and is returned as `None`.
+pub fn parse_session_name(name: Option<&str>) -> Result<Option<String>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    if name == DEFAULT_SESSION_NAME {
+        return Ok(None);
+    }
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(KinicError::Validation(format!(
+            "invalid session name \"{name}\"; use letters, digits, '-' and '_'"
+        ))
+        .into());
+    }
+    Ok(Some(name.to_string()))
+}
+
+pub(crate) fn config_dir() -> Result<PathBuf> {
+    let home = std::env::var("HOME").context("HOME is not set")?;
+    Ok(PathBuf::from(home).join(".config/kinic"))
+}
+
+fn sessions_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("sessions"))
+}
+
+/// Encrypted PEM key of `--identity name` in the file credential store.
+pub fn credential_file_path(name: &str) -> Result<PathBuf> {
+    Ok(config_dir()?
+        .join("credentials")
+        .join(format!("{}.json", identity_file_name(name)?)))
+}
+
+/// PEM key of a dfx identity created with `--storage-mode plaintext`.
+pub fn dfx_plaintext_pem_path(name: &str) -> Result<PathBuf> {
     let home = std::env::var("HOME").context("HOME is not set")?;
-    Ok(PathBuf::from(home).join(".config/kinic/identity.json"))
+    Ok(PathBuf::from(home)
+        .join(".config/dfx/identity")
+        .join(identity_file_name(name)?)
+        .join("identity.pem"))
+}
+
+/// Whether the file credential store holds a key for `name`.
+pub fn credential_file_exists(name: &str) -> bool {
+    [credential_file_path(name), dfx_plaintext_pem_path(name)]
+        .into_iter()
+        .any(|path| path.is_ok_and(|path| path.is_file()))
+}
+
+/// PEM key of `name` from the file credential store: the encrypted file
+/// (asking for its passphrase), else dfx's plaintext PEM.
+pub fn load_pem_from_file(name: &str) -> Result<Vec<u8>> {
+    let path = credential_file_path(name)?;
+    if path.is_file() {
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let sealed: EncryptedSessionKey = serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let passphrase = read_passphrase(&format!("Passphrase for identity {name}: "))?;
+        return open_session_key(&sealed, &passphrase, name.as_bytes());
+    }
+    let dfx_path = dfx_plaintext_pem_path(name)?;
+    if dfx_path.is_file() {
+        return fs::read(&dfx_path)
+            .with_context(|| format!("Failed to read {}", dfx_path.display()));
+    }
+    Err(anyhow!(
+        "no credential file for \"{name}\"; expected {} or {}",
+        path.display(),
+        dfx_path.display()
+    ))
+}
+
+/// Seals `pem` with `passphrase` into the file credential store and returns
+/// the path written.
+pub fn save_pem_to_file(name: &str, pem: &[u8], passphrase: &str) -> Result<PathBuf> {
+    let path = credential_file_path(name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let sealed = seal_session_key(pem, passphrase, name.as_bytes())?;
+    let payload =
+        serde_json::to_string_pretty(&sealed).context("Failed to encode credential file")?;
+    write_private_file(&path, payload.as_bytes())?;
+    Ok(path)
+}
+
+/// `name` as a file name: dfx identity names are letters, digits and
+/// `.`, `-`, `_`, `@`, never starting with a dot.
+fn identity_file_name(name: &str) -> Result<&str> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '@'));
+    if !valid {
+        return Err(KinicError::Validation(format!("invalid identity name \"{name}\"")).into());
+    }
+    Ok(name)
 }
 
 pub fn generate_session_key() -> Result<SessionKeyMaterial> {
@@ -53,22 +245,39 @@ pub fn generate_session_key() -> Result<SessionKeyMaterial> {
     Ok(SessionKeyMaterial { pkcs8, public_key })
 }
 
-pub fn load_delegated_identity(path: &Path) -> Result<DelegatedIdentity> {
-    let payload = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read identity file at {}", path.display()))?;
-    let stored: StoredIdentity =
-        serde_json::from_str(&payload).context("Failed to parse identity.json")?;
+/// Loads the session saved at `path`, or in the keyring when `login
+/// --store keyring` was used and no file exists.
+pub fn load_delegated_identity(path: &Path, session: Option<&str>) -> Result<DelegatedIdentity> {
+    let store = SessionStore::locate(path, session);
+    read_delegated_identity(&store).map_err(|err| {
+        err.context(KinicError::Identity(format!(
+            "Failed to load Internet Identity delegation from {store}"
+        )))
+    })
+}
+
+fn read_delegated_identity(store: &SessionStore) -> Result<DelegatedIdentity> {
+    let stored = store.load()?;
     ensure_not_expired(&stored)?;
 
     let user_public_key_raw =
         hex::decode(&stored.user_public_key_hex).context("Failed to decode user public key")?;
+    let pkcs8 = stored.session_pkcs8()?;
+    delegated_identity_from_parts(&user_public_key_raw, &pkcs8, &stored.delegations)
+}
+
+/// Builds a delegated identity from a root public key, an Ed25519 session key
+/// (PKCS#8) and the delegation chain linking the two.
+pub fn delegated_identity_from_parts(
+    user_public_key_raw: &[u8],
+    pkcs8: &[u8],
+    delegations: &[SignedDelegation],
+) -> Result<DelegatedIdentity> {
     let user_public_key =
-        normalize_spki_key(&user_public_key_raw).context("Unsupported user public key format")?;
-    let pkcs8 = hex::decode(&stored.session_pkcs8_hex).context("Failed to decode session key")?;
-    let key_pair =
-        Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|_| anyhow!("Invalid session key"))?;
+        normalize_spki_key(user_public_key_raw).context("Unsupported user public key format")?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8).map_err(|_| anyhow!("Invalid session key"))?;
     let session_identity = BasicIdentity::from_key_pair(key_pair);
-    let delegations = normalize_delegations(&stored.delegations)?;
+    let delegations = normalize_delegations(delegations)?;
 
     if is_canister_signature_key(&user_public_key)? {
         warn!("Delegation chain uses canister signature keys; skipping local verification.");
@@ -91,7 +300,7 @@ pub fn load_delegated_identity(path: &Path) -> Result<DelegatedIdentity> {
             warn!("Delegation chain uses an unknown algorithm; skipping local verification.");
             eprintln!("Warning: delegation uses an unknown algorithm; skipped local verification.");
             let key_pair =
-                Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|_| anyhow!("Invalid session key"))?;
+                Ed25519KeyPair::from_pkcs8(pkcs8).map_err(|_| anyhow!("Invalid session key"))?;
             let session_identity = BasicIdentity::from_key_pair(key_pair);
             Ok(DelegatedIdentity::new_unchecked(
                 user_public_key,
@@ -110,6 +319,269 @@ pub fn derive_principal_from_user_key(user_public_key_raw: &[u8]) -> Result<Prin
     Ok(Principal::self_authenticating(&user_public_key))
 }
 
+/// Where a saved Internet Identity session lives.
+#[derive(Clone, Debug)]
+pub enum SessionStore {
+    File(PathBuf),
+    /// Entry in the system keyring, under the same service dfx uses.
+    Keyring {
+        session: Option<String>,
+    },
+}
+
+impl SessionStore {
+    /// The identity file at `path` if it exists, otherwise the keyring entry
+    /// of `session` if there is one. Defaults to the file so errors name the
+    /// path.
+    pub fn locate(path: &Path, session: Option<&str>) -> Self {
+        if !path.exists() && keyring_session_exists(session) {
+            return Self::Keyring {
+                session: session.map(str::to_string),
+            };
+        }
+        Self::File(path.to_path_buf())
+    }
+
+    pub fn load(&self) -> Result<StoredIdentity> {
+        let payload = match self {
+            Self::File(path) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read identity file at {}", path.display()))?,
+            Self::Keyring { session } => keyring_session_entry(session.as_deref())?
+                .get_password()
+                .context("Failed to read Internet Identity session from the keyring")?,
+        };
+        serde_json::from_str(&payload).context("Failed to parse identity.json")
+    }
+
+    pub fn save(&self, stored: &StoredIdentity) -> Result<()> {
+        match self {
+            Self::File(path) => save_identity(path, stored),
+            Self::Keyring { session } => {
+                let payload =
+                    serde_json::to_string(stored).context("Failed to encode identity.json")?;
+                keyring_session_entry(session.as_deref())?
+                    .set_password(&payload)
+                    .context("Failed to save Internet Identity session to the keyring")?;
+                if let Some(name) = session {
+                    remember_keyring_session(name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for SessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Keyring { session } => write!(
+                f,
+                "system keyring ({KEYRING_SERVICE_NAME}/{})",
+                keyring_account(session.as_deref())
+            ),
+        }
+    }
+}
+
+fn keyring_account(session: Option<&str>) -> String {
+    match session {
+        None => KEYRING_SESSION_ACCOUNT.to_string(),
+        Some(name) => format!("{KEYRING_SESSION_ACCOUNT}:{name}"),
+    }
+}
+
+fn keyring_session_entry(session: Option<&str>) -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(
+        KEYRING_SERVICE_NAME,
+        &keyring_account(session),
+    )?)
+}
+
+fn keyring_session_exists(session: Option<&str>) -> bool {
+    keyring_session_entry(session)
+        .and_then(|entry| Ok(entry.get_password()?))
+        .is_ok()
+}
+
+fn keyring_session_index() -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(
+        KEYRING_SERVICE_NAME,
+        KEYRING_SESSION_INDEX_ACCOUNT,
+    )?)
+}
+
+/// Named sessions recorded in the keyring index; empty if there is none.
+fn keyring_session_names() -> Vec<String> {
+    keyring_session_index()
+        .and_then(|entry| Ok(entry.get_password()?))
+        .map(|names| names.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn remember_keyring_session(name: &str) -> Result<()> {
+    let mut names = keyring_session_names();
+    if names.iter().any(|known| known == name) {
+        return Ok(());
+    }
+    names.push(name.to_string());
+    keyring_session_index()?
+        .set_password(&names.join("\n"))
+        .context("Failed to update the keyring session index")
+}
+
+/// A saved session as shown by `sessions list`.
+pub struct SessionSummary {
+    pub name: String,
+    pub store: SessionStore,
+    pub principal: Principal,
+    pub expiration_ns: u64,
+    pub encrypted: bool,
+}
+
+/// Every saved session: identity.json, the files under
+/// `~/.config/kinic/sessions/` and the named keyring entries. Sessions that
+/// cannot be read are reported on stderr and skipped.
+pub fn list_sessions() -> Result<Vec<SessionSummary>> {
+    let mut names = BTreeSet::new();
+    let dir = sessions_dir()?;
+    if dir.is_dir() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read sessions directory {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            if let Some(stem) = path.file_stem() {
+                names.insert(stem.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.extend(keyring_session_names());
+
+    let sessions = std::iter::once(None).chain(names.into_iter().map(Some));
+    let mut summaries = Vec::new();
+    for session in sessions {
+        let path = session_identity_path(session.as_deref())?;
+        let store = SessionStore::locate(&path, session.as_deref());
+        if matches!(&store, SessionStore::File(path) if !path.exists()) {
+            continue;
+        }
+        let name = session.unwrap_or_else(|| DEFAULT_SESSION_NAME.to_string());
+        match summarize_session(name.clone(), store) {
+            Ok(summary) => summaries.push(summary),
+            Err(err) => eprintln!("Warning: skipping session {name}: {err:#}"),
+        }
+    }
+    Ok(summaries)
+}
+
+fn summarize_session(name: String, store: SessionStore) -> Result<SessionSummary> {
+    let stored = store.load()?;
+    let user_public_key_raw =
+        hex::decode(&stored.user_public_key_hex).context("Failed to decode user public key")?;
+    Ok(SessionSummary {
+        name,
+        principal: derive_principal_from_user_key(&user_public_key_raw)?,
+        expiration_ns: stored.expiration_ns,
+        encrypted: stored.is_encrypted(),
+        store,
+    })
+}
+
+/// Passphrase from `KINIC_IDENTITY_PASSPHRASE`, or typed without echo.
+pub fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "the identity is encrypted; set {PASSPHRASE_ENV_VAR} when stdin is not a terminal"
+        ));
+    }
+    rpassword::prompt_password(prompt).context("Failed to read passphrase")
+}
+
+/// Asks for a new passphrase twice, unless it comes from the environment.
+pub fn read_new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase("New identity passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(KinicError::Validation("passphrase must not be empty".to_string()).into());
+    }
+    if read_passphrase("Repeat passphrase: ")? != passphrase {
+        return Err(KinicError::Validation("passphrases do not match".to_string()).into());
+    }
+    Ok(passphrase)
+}
+
+fn seal_session_key(pkcs8: &[u8], passphrase: &str, aad: &[u8]) -> Result<EncryptedSessionKey> {
+    let rng = ring::rand::SystemRandom::new();
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|()| rng.fill(&mut nonce))
+        .map_err(|_| anyhow!("Failed to generate random salt"))?;
+
+    let key = derive_key(passphrase, &salt, PBKDF2_ITERATIONS)?;
+    let mut in_out = pkcs8.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce),
+        aead::Aad::from(aad),
+        &mut in_out,
+    )
+    .map_err(|_| anyhow!("Failed to encrypt session key"))?;
+
+    Ok(EncryptedSessionKey {
+        kdf: KDF_NAME.to_string(),
+        iterations: PBKDF2_ITERATIONS,
+        salt_hex: hex::encode(salt),
+        nonce_hex: hex::encode(nonce),
+        ciphertext_hex: hex::encode(in_out),
+    })
+}
+
+fn open_session_key(sealed: &EncryptedSessionKey, passphrase: &str, aad: &[u8]) -> Result<Vec<u8>> {
+    if sealed.kdf != KDF_NAME {
+        return Err(anyhow!("Unsupported identity encryption: {}", sealed.kdf));
+    }
+    let salt = hex::decode(&sealed.salt_hex).context("Failed to decode salt")?;
+    let nonce: [u8; aead::NONCE_LEN] = hex::decode(&sealed.nonce_hex)
+        .ok()
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| anyhow!("Invalid nonce in identity.json"))?;
+    let mut in_out = hex::decode(&sealed.ciphertext_hex).context("Failed to decode ciphertext")?;
+
+    let key = derive_key(passphrase, &salt, sealed.iterations)?;
+    let plaintext = key
+        .open_in_place(
+            aead::Nonce::assume_unique_for_key(nonce),
+            aead::Aad::from(aad),
+            &mut in_out,
+        )
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted identity.json"))?;
+    Ok(plaintext.to_vec())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<aead::LessSafeKey> {
+    let iterations =
+        NonZeroU32::new(iterations).ok_or_else(|| anyhow!("Invalid PBKDF2 iteration count"))?;
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = aead::UnboundKey::new(&aead::AES_256_GCM, &key)
+        .map_err(|_| anyhow!("Failed to build encryption key"))?;
+    Ok(aead::LessSafeKey::new(key))
+}
+
 pub fn save_identity(path: &Path, stored: &StoredIdentity) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
@@ -120,8 +592,11 @@ pub fn save_identity(path: &Path, stored: &StoredIdentity) -> Result<()> {
         })?;
     }
     let payload = serde_json::to_string_pretty(stored).context("Failed to encode identity.json")?;
+    write_private_file(path, payload.as_bytes())
+}
 
-    // Write atomically with restricted permissions (0600) to protect the session key.
+/// Writes `payload` to `path` atomically, readable by the owner only (0600).
+fn write_private_file(path: &Path, payload: &[u8]) -> Result<()> {
     let tmp_path = path.with_extension("tmp");
     {
         let mut file = OpenOptions::new()
@@ -142,7 +617,7 @@ pub fn save_identity(path: &Path, stored: &StoredIdentity) -> Result<()> {
             fs::set_permissions(&tmp_path, perm)
                 .with_context(|| format!("Failed to set permissions on {}", tmp_path.display()))?;
         }
-        file.write_all(payload.as_bytes())
+        file.write_all(payload)
             .context("Failed to write identity payload")?;
         file.sync_all().context("Failed to sync identity file")?;
     }
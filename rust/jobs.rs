@@ -0,0 +1,476 @@
+//! Sync jobs declared in `~/.config/kinic/config.toml` (or `KINIC_CONFIG`),
+//! run by `sync run`, and the per-job state recording what was ingested.
+//! The same file names the default memory used by `capture` and the chat
+//! generation settings, with optional named profiles.
+//!
+//! ```toml
+//! default_memory = "yta6k-5x777-77774-aaaaa-cai"
+//!
+//! [llm]
+//! temperature = 0.2
+//!
+//! [profiles.precise]
+//! temperature = 0.0
+//! max_tokens = 300
+//!
+//! [[jobs]]
+//! name = "notes"
+//! memory_id = "yta6k-5x777-77774-aaaaa-cai"
+//! directory = "~/notes"
+//! extensions = ["md", "txt"]
+//! every = "1h"
+//!
+//! [[retention]]
+//! memory_id = "yta6k-5x777-77774-aaaaa-cai"
+//! older_than = "90d"
+//! tag = "scratch"
+//!
+//! [[hooks]]
+//! event = "sync.after"
+//! command = "~/bin/notify-slack"
+//!
+//! [notify]
+//! url = "https://ops.example.com/kinic"
+//! low_balance = "5"
+//!
+//! [preflight]
+//! delegation_warning = "2h"
+//! low_balance = "10"
+//! ```
+//!
+//! Each job has exactly one source: `directory`, `urls` or `rss`. Each hook
+//! has exactly one action: `command` or `url`.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::KinicError, hooks, identity_store::config_dir, ledger::parse_kinic_amount};
+
+pub const CONFIG_ENV_VAR: &str = "KINIC_CONFIG";
+const DEFAULT_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+const DEFAULT_DELEGATION_WARNING: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_PREFLIGHT_DELEGATION_WARNING: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Memory used by commands that take no `--memory-id`, such as `capture`.
+    #[serde(default)]
+    pub default_memory: Option<String>,
+    /// Chat generation settings used when no profile is selected, and as
+    /// the fallback for fields a profile leaves unset.
+    #[serde(default)]
+    pub llm: LlmSettings,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, LlmSettings>,
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    #[serde(default)]
+    pub retention: Vec<RetentionPolicy>,
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    /// Webhook for events of `serve` and `sync run --daemon`.
+    #[serde(default)]
+    pub notify: Option<NotifySettings>,
+    /// Thresholds of the warnings printed before commands that write.
+    #[serde(default)]
+    pub preflight: PreflightSettings,
+}
+
+impl Config {
+    /// The retention policy of `memory_id`, if one is declared.
+    pub fn retention_for(&self, memory_id: &str) -> Option<&RetentionPolicy> {
+        self.retention
+            .iter()
+            .find(|policy| policy.memory_id == memory_id)
+    }
+}
+
+/// Generation parameters sent with chat requests. Unset fields are left to
+/// the chat endpoint.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LlmSettings {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+impl LlmSettings {
+    /// Fields set here win; the rest come from `fallback`.
+    pub fn or(self, fallback: &LlmSettings) -> LlmSettings {
+        LlmSettings {
+            model: self.model.or_else(|| fallback.model.clone()),
+            temperature: self.temperature.or(fallback.temperature),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            system_prompt: self
+                .system_prompt
+                .or_else(|| fallback.system_prompt.clone()),
+        }
+    }
+
+    /// Settings from the config file: `profile` over `[llm]`.
+    pub fn configured(profile: Option<&str>) -> Result<LlmSettings> {
+        let config = load_config()?;
+        let Some(name) = profile else {
+            return Ok(config.llm);
+        };
+        let selected = config.profiles.get(name).cloned().ok_or_else(|| {
+            KinicError::Validation(format!(
+                "no profile named \"{name}\" in {}",
+                config_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            ))
+        })?;
+        Ok(selected.or(&config.llm))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub name: String,
+    pub memory_id: String,
+    /// Ingest every matching file under this directory, recursively.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// File extensions a `directory` job picks up.
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+    /// Ingest each of these URLs as one document.
+    #[serde(default)]
+    pub urls: Option<Vec<String>>,
+    /// Ingest each entry of this RSS or Atom feed as one document.
+    #[serde(default)]
+    pub rss: Option<String>,
+    /// How often daemon mode runs the job, e.g. `30m`, `6h`, `1d`.
+    #[serde(default)]
+    pub every: Option<String>,
+    /// Ingest documents that look like they contain credentials instead of
+    /// skipping them.
+    #[serde(default)]
+    pub allow_secrets: bool,
+}
+
+pub enum Source<'a> {
+    Directory {
+        root: PathBuf,
+        extensions: Vec<String>,
+    },
+    Urls(&'a [String]),
+    Rss(&'a str),
+}
+
+impl Job {
+    pub fn source(&self) -> Result<Source<'_>> {
+        match (&self.directory, &self.urls, &self.rss) {
+            (Some(directory), None, None) => Ok(Source::Directory {
+                root: expand_home(directory)?,
+                extensions: self.extensions.clone().unwrap_or_else(|| {
+                    DEFAULT_EXTENSIONS
+                        .iter()
+                        .map(|ext| ext.to_string())
+                        .collect()
+                }),
+            }),
+            (None, Some(urls), None) => Ok(Source::Urls(urls)),
+            (None, None, Some(feed)) => Ok(Source::Rss(feed)),
+            _ => Err(KinicError::Validation(format!(
+                "job \"{}\" must set exactly one of directory, urls or rss",
+                self.name
+            ))
+            .into()),
+        }
+    }
+
+    /// Schedule for daemon mode; jobs without `every` only run on demand.
+    pub fn interval(&self) -> Result<Option<Duration>> {
+        self.every
+            .as_deref()
+            .map(|every| {
+                parse_interval(every).ok_or_else(|| {
+                    KinicError::Validation(format!(
+                        "job \"{}\": invalid every = \"{every}\"; use e.g. 30m, 6h or 1d",
+                        self.name
+                    ))
+                    .into()
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Chunks of a memory that `prune` and daemon mode treat as stale.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionPolicy {
+    pub memory_id: String,
+    /// Maximum age by `created_at`, e.g. `90d`.
+    pub older_than: String,
+    /// Only chunks with this tag expire; every chunk when unset.
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl RetentionPolicy {
+    pub fn max_age(&self) -> Result<Duration> {
+        parse_age(&self.older_than)
+    }
+}
+
+/// Runs when `event` fires; see [`crate::hooks`].
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Hook {
+    /// One of [`crate::hooks::EVENTS`], e.g. `insert.after`.
+    pub event: String,
+    /// Shell command that reads the event as JSON on stdin.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// URL the event is POSTed to as JSON.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// Where long-running modes report events; see
+/// [`crate::commands::notify`].
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NotifySettings {
+    pub url: String,
+    /// HMAC-SHA256 key the requests are signed with; `KINIC_WEBHOOK_SECRET`
+    /// takes precedence.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Send `balance.low` once the balance falls below this many KINIC.
+    #[serde(default)]
+    pub low_balance: Option<String>,
+    /// Send `delegation.expiring` when the Internet Identity delegation
+    /// expires within this long, e.g. `1d` (the default).
+    #[serde(default)]
+    pub delegation_warning: Option<String>,
+}
+
+impl NotifySettings {
+    pub fn low_balance_e8s(&self) -> Result<Option<u128>> {
+        self.low_balance
+            .as_deref()
+            .map(parse_kinic_amount)
+            .transpose()
+    }
+
+    pub fn delegation_warning(&self) -> Result<Duration> {
+        self.delegation_warning
+            .as_deref()
+            .map_or(Ok(DEFAULT_DELEGATION_WARNING), parse_age)
+    }
+}
+
+/// See [`crate::commands::preflight`].
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PreflightSettings {
+    /// `false` turns the checks off.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Warn when the Internet Identity delegation expires within this long;
+    /// `1h` by default.
+    #[serde(default)]
+    pub delegation_warning: Option<String>,
+    /// Warn when the balance is below this many KINIC; below the deployment
+    /// price by default.
+    #[serde(default)]
+    pub low_balance: Option<String>,
+}
+
+impl PreflightSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn low_balance_e8s(&self) -> Result<Option<u128>> {
+        self.low_balance
+            .as_deref()
+            .map(parse_kinic_amount)
+            .transpose()
+    }
+
+    pub fn delegation_warning(&self) -> Result<Duration> {
+        self.delegation_warning
+            .as_deref()
+            .map_or(Ok(DEFAULT_PREFLIGHT_DELEGATION_WARNING), parse_age)
+    }
+}
+
+/// A `--older-than` or `older_than` value such as `90d` or `12h`.
+pub fn parse_age(text: &str) -> Result<Duration> {
+    parse_interval(text).ok_or_else(|| {
+        KinicError::Validation(format!("invalid age \"{text}\"; use e.g. 12h, 30d or 90d")).into()
+    })
+}
+
+/// `<number><unit>` with unit `s`, `m`, `h` or `d`.
+fn parse_interval(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (split, _) = text.char_indices().last()?;
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.trim().parse().ok().filter(|n| *n > 0)?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+fn expand_home(path: &Path) -> Result<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(rest) => {
+            let home = std::env::var("HOME").context("HOME is not set")?;
+            Ok(PathBuf::from(home).join(rest))
+        }
+        Err(_) => Ok(path.to_path_buf()),
+    }
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    match std::env::var_os(CONFIG_ENV_VAR) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(config_dir()?.join("config.toml")),
+    }
+}
+
+/// Loads and validates the config; a missing file has no jobs.
+pub fn load_config() -> Result<Config> {
+    let path = config_path()?;
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    let config: Config = toml::from_str(&raw).map_err(|err| {
+        KinicError::Validation(format!("invalid config {}: {err}", path.display()))
+    })?;
+    for settings in std::iter::once(&config.llm).chain(config.profiles.values()) {
+        if settings
+            .temperature
+            .is_some_and(|temperature| !(0.0..=2.0).contains(&temperature))
+        {
+            return Err(KinicError::Validation(format!(
+                "invalid config {}: temperature must be between 0 and 2",
+                path.display()
+            ))
+            .into());
+        }
+    }
+    for (index, job) in config.jobs.iter().enumerate() {
+        job.source()?;
+        job.interval()?;
+        if config.jobs[..index]
+            .iter()
+            .any(|other| other.name == job.name)
+        {
+            return Err(
+                KinicError::Validation(format!("duplicate job name \"{}\"", job.name)).into(),
+            );
+        }
+    }
+    for (index, policy) in config.retention.iter().enumerate() {
+        policy.max_age()?;
+        if config.retention[..index]
+            .iter()
+            .any(|other| other.memory_id == policy.memory_id)
+        {
+            return Err(KinicError::Validation(format!(
+                "duplicate retention policy for {}",
+                policy.memory_id
+            ))
+            .into());
+        }
+    }
+    for hook in &config.hooks {
+        if !hooks::EVENTS.contains(&hook.event.as_str()) {
+            return Err(KinicError::Validation(format!(
+                "unknown hook event \"{}\"; use one of {}",
+                hook.event,
+                hooks::EVENTS.join(", ")
+            ))
+            .into());
+        }
+        if hook.command.is_some() == hook.url.is_some() {
+            return Err(KinicError::Validation(format!(
+                "{} hook must set exactly one of command or url",
+                hook.event
+            ))
+            .into());
+        }
+    }
+    if let Some(notify) = &config.notify {
+        notify.low_balance_e8s()?;
+        notify.delegation_warning()?;
+    }
+    config.preflight.low_balance_e8s()?;
+    config.preflight.delegation_warning()?;
+    Ok(config)
+}
+
+/// What each job has ingested, so unchanged content is skipped.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SyncState {
+    #[serde(default)]
+    pub jobs: BTreeMap<String, JobState>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct JobState {
+    /// Unix seconds of the last completed run.
+    #[serde(default)]
+    pub last_run: Option<u64>,
+    /// Change marker of every ingested item (content hash, or mtime for
+    /// `insert-obsidian`), keyed by path, URL or entry id.
+    #[serde(default)]
+    pub items: BTreeMap<String, String>,
+}
+
+fn state_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("sync-state.json"))
+}
+
+impl SyncState {
+    pub fn load() -> Result<Self> {
+        let path = state_path()?;
+        match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("Failed to parse {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = state_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let payload = serde_json::to_string_pretty(self)?;
+        fs::write(&path, payload).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,97 @@
+//! Local keyword extraction for `insert --auto-tag`, using RAKE (Rapid
+//! Automatic Keyword Extraction, Rose et al. 2010): candidate phrases are
+//! the runs of words between stopwords and punctuation, and each phrase is
+//! scored by how often its words co-occur with other words versus alone.
+//!
+//! Works on whitespace-separated languages; for others use the LLM source.
+
+use std::collections::HashMap;
+
+/// Longer candidates are usually sentence fragments rather than topics.
+const MAX_PHRASE_WORDS: usize = 3;
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "also", "am", "an", "and", "any",
+    "are", "as", "at", "be", "because", "been", "before", "being", "below", "between", "both",
+    "but", "by", "can", "could", "did", "do", "does", "doing", "done", "down", "during", "each",
+    "either", "etc", "even", "every", "few", "for", "from", "further", "get", "gets", "got", "had",
+    "has", "have", "having", "he", "her", "here", "hers", "him", "his", "how", "however", "i",
+    "if", "in", "into", "is", "it", "its", "itself", "just", "let", "like", "made", "make", "many",
+    "may", "me", "might", "more", "most", "much", "must", "my", "new", "no", "nor", "not", "now",
+    "of", "off", "often", "on", "once", "one", "only", "or", "other", "our", "ours", "out", "over",
+    "own", "per", "same", "she", "should", "so", "some", "such", "than", "that", "the", "their",
+    "them", "then", "there", "these", "they", "this", "those", "through", "to", "too", "two",
+    "under", "until", "up", "upon", "us", "use", "used", "using", "very", "via", "was", "we",
+    "well", "were", "what", "when", "where", "whether", "which", "while", "who", "whom", "why",
+    "will", "with", "within", "without", "would", "yet", "you", "your", "yours",
+];
+
+/// Up to `limit` key phrases of `text`, best first, lowercased.
+pub fn extract(text: &str, limit: usize) -> Vec<String> {
+    let phrases = candidate_phrases(text);
+
+    let mut frequency: HashMap<&str, f64> = HashMap::new();
+    let mut degree: HashMap<&str, f64> = HashMap::new();
+    for phrase in &phrases {
+        for word in phrase {
+            *frequency.entry(word).or_default() += 1.0;
+            *degree.entry(word).or_default() += phrase.len() as f64;
+        }
+    }
+
+    let mut scored: HashMap<String, f64> = HashMap::new();
+    for phrase in &phrases {
+        let score = phrase
+            .iter()
+            .map(|word| degree[word.as_str()] / frequency[word.as_str()])
+            .sum();
+        scored.insert(phrase.join(" "), score);
+    }
+
+    let mut ranked: Vec<(String, f64)> = scored.into_iter().collect();
+    // Ties broken alphabetically so the same text always yields the same tags.
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+        .into_iter()
+        .map(|(phrase, _)| phrase)
+        .take(limit)
+        .collect()
+}
+
+fn candidate_phrases(text: &str) -> Vec<Vec<String>> {
+    let mut phrases = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut flush = |current: &mut Vec<String>| {
+        if !current.is_empty() && current.len() <= MAX_PHRASE_WORDS {
+            phrases.push(std::mem::take(current));
+        }
+        current.clear();
+    };
+
+    for token in text.split_inclusive(|c: char| c.is_whitespace() || is_boundary(c)) {
+        let ends_phrase = token.ends_with(is_boundary);
+        let word: String = token
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        if is_keyword_word(&word) {
+            current.push(word);
+        } else if !word.is_empty() {
+            flush(&mut current);
+        }
+        if ends_phrase {
+            flush(&mut current);
+        }
+    }
+    flush(&mut current);
+    phrases
+}
+
+/// Punctuation that separates phrases. Apostrophes and hyphens stay inside
+/// words.
+fn is_boundary(c: char) -> bool {
+    c.is_ascii_punctuation() && c != '\'' && c != '-'
+}
+
+fn is_keyword_word(word: &str) -> bool {
+    word.chars().count() >= 3 && !word.chars().all(|c| c.is_numeric()) && !STOPWORDS.contains(&word)
+}
@@ -1,8 +1,29 @@
-use anyhow::{Context, Result, anyhow};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow, bail};
+use candid::Nat;
 use ic_agent::export::Principal;
-use icrc_ledger_types::icrc1::account::Account;
 
-use crate::clients::LEDGER_CANISTER;
+use crate::{
+    clients::{
+        LEDGER_CANISTER,
+        call::{
+            call,
+            ledger::{self, Account, TransferArg, TransferError},
+        },
+    },
+    error::{KinicError, payment_failed},
+};
+
+/// Ledger fee charged per transfer or approval, in e8s.
+pub const TRANSFER_FEE_E8S: u128 = 100_000;
+pub const E8S_PER_KINIC: u128 = 100_000_000;
+
+/// Allowance a spender needs to pull `amount` e8s with `icrc2_transfer_from`:
+/// the ledger charges that call's fee against the allowance as well.
+pub fn allowance_for(amount: u128) -> u128 {
+    amount + TRANSFER_FEE_E8S
+}
 
 pub async fn fetch_balance(agent: &ic_agent::Agent) -> Result<u128> {
     let principal = agent
@@ -17,15 +38,153 @@ pub async fn fetch_balance(agent: &ic_agent::Agent) -> Result<u128> {
         subaccount: None,
     };
 
-    let payload = candid::encode_one(account)?;
-    let response = agent
-        .query(&ledger_id, "icrc1_balance_of")
-        .with_arg(payload)
-        .call()
+    let (balance,) = call(agent, &ledger_id, ledger::ICRC1_BALANCE_OF, (account,))
         .await
         .context("Failed to query ledger balance")?;
+    nat_to_e8s(&balance)
+}
+
+/// Sends `amount` e8s from the caller's default account via `icrc1_transfer`
+/// and returns the ledger block index.
+pub async fn transfer(
+    agent: &ic_agent::Agent,
+    to: Account,
+    amount: u128,
+    memo: Option<Vec<u8>>,
+) -> Result<Nat> {
+    let ledger_id =
+        Principal::from_text(LEDGER_CANISTER).context("Failed to parse ledger canister id")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+
+    let args = TransferArg {
+        from_subaccount: None,
+        to,
+        fee: Some(Nat::from(TRANSFER_FEE_E8S)),
+        created_at_time: Some(now),
+        memo,
+        amount: Nat::from(amount),
+    };
+
+    let (result,) = call(agent, &ledger_id, ledger::ICRC1_TRANSFER, (args,)).await?;
+    result.map_err(payment_failed)
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadFee { expected_fee } => write!(f, "transaction fee should be {expected_fee}"),
+            Self::BadBurn { min_burn_amount } => {
+                write!(
+                    f,
+                    "the minimum number of tokens to be burned is {min_burn_amount}"
+                )
+            }
+            Self::InsufficientFunds { balance } => {
+                write!(
+                    f,
+                    "the debit account doesn't have enough funds to complete the transaction, current balance: {balance}"
+                )
+            }
+            Self::TooOld => f.write_str("transaction's created_at_time is too far in the past"),
+            Self::CreatedInFuture { ledger_time } => write!(
+                f,
+                "transaction's created_at_time is in future, current ledger time is {ledger_time}"
+            ),
+            Self::TemporarilyUnavailable => f.write_str("the ledger is temporarily unavailable"),
+            Self::Duplicate { duplicate_of } => {
+                write!(
+                    f,
+                    "transaction is a duplicate of another transaction in block {duplicate_of}"
+                )
+            }
+            Self::GenericError {
+                error_code,
+                message,
+            } => write!(f, "{error_code} {message}"),
+        }
+    }
+}
+
+impl std::fmt::Display for ledger::ApproveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadFee { expected_fee } => write!(f, "transaction fee should be {expected_fee}"),
+            Self::InsufficientFunds { balance } => {
+                write!(
+                    f,
+                    "the debit account doesn't have enough funds to complete the transaction, current balance: {balance}"
+                )
+            }
+            Self::AllowanceChanged { current_allowance } => {
+                write!(f, "current allowance is {current_allowance}")
+            }
+            Self::Expired { ledger_time } => {
+                write!(f, "the approval expired before ledger time {ledger_time}")
+            }
+            Self::TooOld => f.write_str("transaction's created_at_time is too far in the past"),
+            Self::CreatedInFuture { ledger_time } => write!(
+                f,
+                "transaction's created_at_time is in future, current ledger time is {ledger_time}"
+            ),
+            Self::Duplicate { duplicate_of } => {
+                write!(
+                    f,
+                    "transaction is a duplicate of another transaction in block {duplicate_of}"
+                )
+            }
+            Self::TemporarilyUnavailable => f.write_str("the ledger is temporarily unavailable"),
+            Self::GenericError {
+                error_code,
+                message,
+            } => write!(f, "{error_code} {message}"),
+        }
+    }
+}
+
+/// Parses a KINIC amount such as `1`, `0.5` or `12.34567890` into e8s.
+pub fn parse_kinic_amount(input: &str) -> Result<u128> {
+    parse_amount(input.trim()).map_err(|err| KinicError::Validation(format!("{err:#}")).into())
+}
+
+fn parse_amount(input: &str) -> Result<u128> {
+    let (whole, frac) = input.split_once('.').unwrap_or((input, ""));
+    if whole.is_empty() && frac.is_empty() {
+        bail!("amount must not be empty");
+    }
+    if !whole.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+        bail!("invalid KINIC amount: {input}");
+    }
+    if frac.len() > 8 {
+        bail!("KINIC amounts support at most 8 decimal places: {input}");
+    }
+
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole
+            .parse()
+            .with_context(|| format!("invalid KINIC amount: {input}"))?
+    };
+    let frac: u128 = format!("{frac:0<8}")
+        .parse()
+        .with_context(|| format!("invalid KINIC amount: {input}"))?;
+
+    whole
+        .checked_mul(E8S_PER_KINIC)
+        .and_then(|e8s| e8s.checked_add(frac))
+        .ok_or_else(|| anyhow!("KINIC amount is too large: {input}"))
+}
+
+/// Converts a ledger amount to e8s; amounts beyond u128 are rejected.
+pub fn nat_to_e8s(value: &Nat) -> Result<u128> {
+    value
+        .0
+        .to_string()
+        .parse()
+        .with_context(|| format!("token amount {value} does not fit in u128"))
+}
 
-    let balance: u128 =
-        candid::decode_one(&response).context("Failed to decode balance response")?;
-    Ok(balance)
+/// Formats e8s as a KINIC amount with all 8 decimal places.
+pub fn format_e8s(e8s: u128) -> String {
+    format!("{}.{:08}", e8s / E8S_PER_KINIC, e8s % E8S_PER_KINIC)
 }
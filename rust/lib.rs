@@ -1,99 +1,388 @@
+//! Kinic CLI and Python module. Without the default `native` feature only
+//! the core is built: [`clients::memory::MemoryClient`], the embedding API
+//! requests in [`embedding`] and the `ask-ai` prompt builder in [`rag`], with
+//! no keyring, file or tokio runtime use, so it compiles to
+//! wasm32-unknown-unknown.
+
+#[cfg(feature = "native")]
 pub mod agent;
+#[cfg(feature = "native")]
 #[path = "cli_defs.rs"]
 pub mod cli;
-pub(crate) mod clients;
+pub mod clients;
+#[cfg(feature = "native")]
 mod commands;
-mod embedding;
+pub mod embedding;
+pub mod error;
+#[cfg(feature = "c-ffi")]
+mod ffi;
+#[cfg(feature = "native")]
+mod guard;
+#[cfg(feature = "native")]
+mod hooks;
+#[cfg(feature = "native")]
 pub(crate) mod identity_store;
+#[cfg(feature = "native")]
+mod jobs;
+#[cfg(feature = "native")]
+mod keywords;
+#[cfg(feature = "native")]
 mod ledger;
-#[cfg(feature = "python-bindings")]
+#[cfg(feature = "native")]
+mod metrics;
+mod network;
+#[cfg(feature = "native")]
+mod output;
+pub mod payload;
+#[cfg(all(test, feature = "integration-tests"))]
+mod pocket_ic_tests;
+// Operations shared by the Python module and the C ABI.
+#[cfg(any(feature = "python-bindings", feature = "c-ffi"))]
+#[cfg_attr(not(feature = "python-bindings"), allow(dead_code))]
 mod python;
+pub mod rag;
+#[cfg(feature = "native")]
+mod redact;
+#[cfg(feature = "native")]
+mod sse;
+#[cfg(feature = "native")]
+mod tables;
+pub mod tokens;
+#[cfg(feature = "native")]
+mod usage;
+pub mod vectors;
+
+#[cfg(feature = "native")]
+use std::{fs::OpenOptions, sync::Arc, time::Duration};
 
-use anyhow::Result;
+#[cfg(feature = "native")]
+use anyhow::{Context, Result};
+#[cfg(feature = "native")]
 use clap::Parser;
+#[cfg(feature = "native")]
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::fmt;
+#[cfg(feature = "native")]
+use tracing_subscriber::{EnvFilter, fmt, fmt::writer::BoxMakeWriter};
 
+#[cfg(feature = "native")]
 use crate::{
-    agent::AgentFactory,
-    cli::Cli,
+    agent::{AgentFactory, HsmConfig},
+    cli::{Cli, GlobalOpts, LogFormat},
+    clients::cassette,
     commands::{CommandContext, run_command},
+    error::{ErrorClass, KinicError as CliError},
 };
 
+#[cfg(feature = "python-bindings")]
+use ic_agent::{Agent, export::Principal};
 #[cfg(feature = "python-bindings")]
 use pyo3::{
-    exceptions::{PyRuntimeError, PyValueError},
+    create_exception,
+    exceptions::{PyRuntimeError, PyStopAsyncIteration, PyValueError},
     prelude::*,
     types::PyModule,
     wrap_pyfunction,
 };
 #[cfg(feature = "python-bindings")]
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::OnceLock};
 #[cfg(feature = "python-bindings")]
 use tokio::runtime::Runtime;
 
+#[cfg(feature = "native")]
+const II_TRANSFER_WARNING: &str = "For security reasons, using a locally hosted origin Internet Identity is not recommended for commands involving asset transfers.";
+
+#[cfg(feature = "native")]
 pub async fn run() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        if err.use_stderr() {
+            // Usage errors share the exit code of other invalid input.
+            let _ = err.print();
+            std::process::exit(ErrorClass::Validation.exit_code().into());
+        }
+        err.exit()
+    });
 
-    let max = match cli.global.verbose {
-        0 => LevelFilter::INFO,
-        1 => LevelFilter::DEBUG,
-        _ => LevelFilter::TRACE,
-    };
+    usage::start();
+    output::set_quiet(cli.global.quiet);
+    init_logging(&cli.global)?;
 
-    fmt().with_max_level(max).without_time().try_init().ok();
+    if let Some(secs) = cli.global.timeout {
+        network::set_timeout(Duration::from_secs(secs));
+    }
+    if let Some(addr) = cli.global.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+
+    if let cli::Command::External(args) = &cli.command {
+        return commands::plugins::run_external(args, &cli.global);
+    }
 
-    if cli.global.ii
+    let use_ii = cli.global.ii || cli.global.session.is_some();
+    if use_ii
         && matches!(
             cli.command,
-            cli::Command::Create(_) | cli::Command::Balance(_)
+            cli::Command::Create(_)
+                | cli::Command::Balance(_)
+                | cli::Command::Transfer(_)
+                | cli::Command::TopUp(_)
         )
+        && !cfg!(feature = "experimental")
     {
-        if !cfg!(feature = "experimental") {
-            anyhow::bail!(
-                "For security reasons, using a locally hosted origin Internet Identity is not recommended for commands involving asset transfers."
-            );
-        }
+        anyhow::bail!(II_TRANSFER_WARNING);
     }
 
-    let needs_identity_path = matches!(cli.command, cli::Command::Login(_)) || cli.global.ii;
+    let manages_identity_file = matches!(
+        cli.command,
+        cli::Command::Login(_) | cli::Command::EncryptIdentity(_) | cli::Command::Sessions(_)
+    );
+    let session = match &cli.command {
+        cli::Command::Login(args) if args.session.is_some() => args.session.as_deref(),
+        _ => cli.global.session.as_deref(),
+    };
+    let session = identity_store::parse_session_name(session)?;
+    let needs_identity_path = manages_identity_file || use_ii;
     let identity_path = if needs_identity_path {
         Some(match cli.global.identity_path.clone() {
             Some(path) => path,
-            None => identity_store::default_identity_path()?,
+            None => identity_store::session_identity_path(session.as_deref())?,
         })
     } else {
         None
     };
 
-    let agent_factory = if matches!(cli.command, cli::Command::Login(_)) {
+    if let Some(path) = &cli.global.record {
+        cassette::start_recording(path)?;
+    }
+
+    // Commands that make no canister calls need no identity.
+    let local_only = matches!(
+        cli.command,
+        cli::Command::Usage(_)
+            | cli::Command::Estimate(_)
+            | cli::Command::Plugins(_)
+            | cli::Command::Identity(cli::IdentityArgs {
+                command: cli::IdentityCommand::Import(_)
+            })
+    );
+    let agent_factory = if manages_identity_file || local_only {
         AgentFactory::new(cli.global.ic, String::new())
-    } else if cli.global.ii {
+    } else if matches!(cli.command, cli::Command::Doctor(_)) {
+        // `doctor` loads the identity itself so it can report what fails.
+        AgentFactory::new(
+            cli.global.ic,
+            cli.global.identity.clone().unwrap_or_default(),
+        )
+        .with_credential_store(cli.global.credential_store)
+    } else if let Some(path) = &cli.global.replay {
+        AgentFactory::new_with_identity(cli.global.ic, cassette::start_replay(path)?)
+    } else if let Some(path) = &cli.global.seed_phrase_file {
+        let phrase = agent::read_seed_phrase(path)?;
+        AgentFactory::new_with_identity(cli.global.ic, agent::identity_from_seed_phrase(&phrase)?)
+    } else if cli.global.hsm
+        && let Some(key_id) = &cli.global.hsm_key_id
+    {
+        // clap's `requires` makes `--hsm-key-id` present with `--hsm`.
+        let config = HsmConfig {
+            lib_path: cli.global.hsm_lib.clone(),
+            slot_index: cli.global.hsm_slot,
+            key_id: key_id.clone(),
+        };
+        AgentFactory::new_with_hsm(cli.global.ic, &config)?
+    } else if use_ii {
         let path = identity_path
             .clone()
             .ok_or_else(|| anyhow::anyhow!("Identity path is missing"))?;
-        let delegated = identity_store::load_delegated_identity(&path)?;
+        let delegated = identity_store::load_delegated_identity(&path, session.as_deref())?;
         AgentFactory::new_with_identity(cli.global.ic, delegated)
     } else {
-        let identity_suffix = cli
-            .global
-            .identity
-            .clone()
-            .ok_or_else(|| anyhow::anyhow!("--identity is required unless --ii is set"))?;
+        let identity_suffix = cli.global.identity.clone().ok_or_else(|| {
+            CliError::Validation(
+                "--identity is required unless --ii, --session, --hsm, --seed-phrase-file or --replay is set"
+                    .to_string(),
+            )
+        })?;
         AgentFactory::new(cli.global.ic, identity_suffix)
+            .with_credential_store(cli.global.credential_store)
     };
 
-    let context = CommandContext {
-        agent_factory,
-        identity_path,
+    let context = CommandContext::new(agent_factory, identity_path, session);
+
+    let command = command_name(&cli.command);
+    let ingests = matches!(
+        cli.command,
+        cli::Command::Add(_)
+            | cli::Command::Insert(_)
+            | cli::Command::InsertPdf(_)
+            | cli::Command::InsertImage(_)
+            | cli::Command::InsertMail(_)
+            | cli::Command::InsertBookmarks(_)
+            | cli::Command::InsertObsidian(_)
+            | cli::Command::MigratePayloads(_)
+            | cli::Command::Materialize(_)
+            | cli::Command::Sync(_)
+            | cli::Command::Capture(_)
+    );
+    // A replay has no recorded answers for the preflight queries.
+    if writes(&cli.command) && cli.global.replay.is_none() && cli.global.record.is_none() {
+        commands::preflight::warn(&context).await;
+    }
+    let inserts_into = insert_target(&cli.command);
+    if let Some(memory_id) = &inserts_into {
+        hooks::before(
+            "insert.before",
+            serde_json::json!({"command": command, "memory_id": memory_id}),
+        )
+        .await?;
+    }
+    let result = run_command(cli.command, context).await;
+    record_usage(&command, result.is_ok(), ingests);
+    if let Some(memory_id) = inserts_into {
+        let mut details = hooks::outcome(&result);
+        details["command"] = command.clone().into();
+        details["memory_id"] = memory_id.into();
+        details["usage"] = serde_json::json!(usage::RunUsage::current(&command, result.is_ok()));
+        hooks::after("insert.after", details).await;
+    }
+    result
+}
+
+/// Commands that change a canister or move tokens, which get the preflight
+/// warnings.
+#[cfg(feature = "native")]
+fn writes(command: &cli::Command) -> bool {
+    insert_target(command).is_some()
+        || matches!(
+            command,
+            cli::Command::Create(_)
+                | cli::Command::Config(_)
+                | cli::Command::Share(_)
+                | cli::Command::Update(_)
+                | cli::Command::SetMetadata(_)
+                | cli::Command::Reset(_)
+                | cli::Command::MigratePayloads(_)
+                | cli::Command::Materialize(_)
+                | cli::Command::Transfer(_)
+                | cli::Command::TopUp(_)
+                | cli::Command::Sync(cli::SyncArgs {
+                    command: cli::SyncCommand::Run(_)
+                })
+        )
+}
+
+/// The memory an insert command writes to, for the `insert.*` hooks; `None`
+/// inside when `add` or `capture` falls back to the configured default
+/// memory.
+#[cfg(feature = "native")]
+fn insert_target(command: &cli::Command) -> Option<Option<String>> {
+    let memory_id = match command {
+        cli::Command::Insert(args) => &args.memory_id,
+        cli::Command::InsertRaw(args) => &args.memory_id,
+        cli::Command::InsertPdf(args) => &args.memory_id,
+        cli::Command::InsertImage(args) => &args.memory_id,
+        cli::Command::InsertObsidian(args) => &args.memory_id,
+        cli::Command::InsertMail(args) => &args.memory_id,
+        cli::Command::InsertBookmarks(args) => &args.memory_id,
+        cli::Command::Add(args) => return Some(args.memory_id.clone()),
+        cli::Command::Capture(args) => return Some(args.memory_id.clone()),
+        _ => return None,
     };
+    Some(Some(memory_id.clone()))
+}
 
-    run_command(cli.command, context).await
+/// Appends this run to the usage ledger and, after ingestion, prints what
+/// it consumed. Runs without API or canister calls are not recorded.
+#[cfg(feature = "native")]
+fn record_usage(command: &str, ok: bool, print_summary: bool) {
+    let run = usage::RunUsage::current(command, ok);
+    if run.is_empty() {
+        return;
+    }
+    if print_summary {
+        output::note!("Usage: {run}");
+    }
+    if let Err(err) = usage::append(&run) {
+        eprintln!("warning: failed to record usage: {err:#}");
+    }
+}
+
+/// Subcommand name as typed, e.g. `insert-pdf`.
+#[cfg(feature = "native")]
+fn command_name(command: &cli::Command) -> String {
+    let debug = format!("{command:?}");
+    let variant = debug.split(['(', ' ', '{']).next().unwrap_or_default();
+    let mut name = String::new();
+    for (index, ch) in variant.chars().enumerate() {
+        if ch.is_ascii_uppercase() {
+            if index > 0 {
+                name.push('-');
+            }
+            name.push(ch.to_ascii_lowercase());
+        } else {
+            name.push(ch);
+        }
+    }
+    name
+}
+
+/// Sends logs to stderr or `--log-file`, as text or JSON, filtered by
+/// `--log-filter`, `RUST_LOG` or the `-v`/`-q` level, in that order.
+#[cfg(feature = "native")]
+fn init_logging(global: &GlobalOpts) -> Result<()> {
+    let level = match global.verbose {
+        _ if global.quiet => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let spec = global
+        .log_filter
+        .clone()
+        .or_else(|| std::env::var(EnvFilter::DEFAULT_ENV).ok());
+    let filter = match spec {
+        Some(spec) => EnvFilter::try_new(&spec).map_err(|err| {
+            CliError::Validation(format!("invalid log filter \"{spec}\": {err}"))
+        })?,
+        None => EnvFilter::default().add_directive(level.into()),
+    };
+
+    let writer = match &global.log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            BoxMakeWriter::new(Arc::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    let builder = fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(global.log_file.is_none());
+
+    // Timestamps only matter once logs outlive the terminal session.
+    let _ = match (global.log_format, global.log_file.is_some()) {
+        (LogFormat::Json, _) => builder.json().try_init(),
+        (LogFormat::Text, true) => builder.try_init(),
+        (LogFormat::Text, false) => builder.without_time().try_init(),
+    };
+    Ok(())
 }
 
 #[cfg(feature = "python-bindings")]
 #[pymodule]
-fn _lib(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
+fn _lib(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("KinicError", py.get_type::<KinicError>())?;
+    m.add("IdentityError", py.get_type::<IdentityError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("CanisterRejectError", py.get_type::<CanisterRejectError>())?;
+    m.add("EmbeddingApiError", py.get_type::<EmbeddingApiError>())?;
+    m.add("PaymentError", py.get_type::<PaymentError>())?;
+    m.add("ValidationError", py.get_type::<ValidationError>())?;
+    m.add_class::<KinicClient>()?;
+    m.add_class::<Memory>()?;
+    m.add_class::<ChunkIterator>()?;
     m.add_function(wrap_pyfunction!(greet, m)?)?;
     m.add_function(wrap_pyfunction!(create_memory, m)?)?;
     m.add_function(wrap_pyfunction!(list_memories, m)?)?;
@@ -108,9 +397,30 @@ fn _lib(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(update_instance, m)?)?;
     m.add_function(wrap_pyfunction!(reset_memory, m)?)?;
     m.add_function(wrap_pyfunction!(add_user, m)?)?;
+    m.add_function(wrap_pyfunction!(pdf_to_markdown, m)?)?;
+    m.add_function(wrap_pyfunction!(html_to_text, m)?)?;
+    m.add_function(wrap_pyfunction!(chunk_text, m)?)?;
+    m.add_function(wrap_pyfunction!(login, m)?)?;
+    m.add_function(wrap_pyfunction!(load_identity, m)?)?;
     Ok(())
 }
 
+// Subclasses RuntimeError so callers catching the previous error type keep working.
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, KinicError, PyRuntimeError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, IdentityError, KinicError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, NetworkError, KinicError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, CanisterRejectError, KinicError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, EmbeddingApiError, KinicError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, PaymentError, KinicError);
+#[cfg(feature = "python-bindings")]
+create_exception!(_lib, ValidationError, KinicError);
+
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 fn greet() -> PyResult<String> {
@@ -121,38 +431,46 @@ fn greet() -> PyResult<String> {
 #[pyfunction]
 #[pyo3(signature = (identity, name, description, ic=None))]
 fn create_memory(
+    py: Python<'_>,
     identity: &str,
     name: &str,
     description: &str,
     ic: Option<bool>,
 ) -> PyResult<String> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::create_memory(
-        ic,
-        identity.to_string(),
-        name.to_string(),
-        description.to_string(),
-    ))
+    block_on_py(
+        py,
+        python::create_memory(
+            ic,
+            identity.to_string(),
+            name.to_string(),
+            description.to_string(),
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, ic=None))]
-fn list_memories(identity: &str, ic: Option<bool>) -> PyResult<Vec<String>> {
+fn list_memories(py: Python<'_>, identity: &str, ic: Option<bool>) -> PyResult<Vec<String>> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::list_memories(ic, identity.to_string()))
+    block_on_py(py, python::list_memories(ic, identity.to_string()))
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
-#[pyo3(signature = (identity, memory_id, tag, text=None, file_path=None, ic=None))]
+#[pyo3(signature = (identity, memory_id, tag, text=None, file_path=None, ic=None, progress=None, allow_secrets=false))]
+#[allow(clippy::too_many_arguments)]
 fn insert_memory(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     tag: &str,
     text: Option<&str>,
     file_path: Option<&str>,
     ic: Option<bool>,
+    progress: Option<Py<PyAny>>,
+    allow_secrets: bool,
 ) -> PyResult<usize> {
     if text.is_none() && file_path.is_none() {
         return Err(PyValueError::new_err(
@@ -162,116 +480,165 @@ fn insert_memory(
 
     let ic = ic.unwrap_or(false);
     let path = file_path.map(PathBuf::from);
-    block_on_py(python::insert_memory(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        tag.to_string(),
-        text.map(|t| t.to_string()),
-        path,
-    ))
+    block_on_py(
+        py,
+        python::insert_memory(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            tag.to_string(),
+            text.map(|t| t.to_string()),
+            path,
+            allow_secrets,
+            progress.map(py_progress),
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
-#[pyo3(signature = (identity, memory_id, tag, text, embedding, ic=None))]
+#[pyo3(signature = (identity, memory_id, tag, text, embedding, ic=None, allow_secrets=false))]
+#[allow(clippy::too_many_arguments)]
 fn insert_memory_raw(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     tag: &str,
     text: &str,
     embedding: Vec<f32>,
     ic: Option<bool>,
+    allow_secrets: bool,
 ) -> PyResult<usize> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::insert_memory_raw(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        tag.to_string(),
-        text.to_string(),
-        embedding,
-    ))
+    block_on_py(
+        py,
+        python::insert_memory_raw(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            tag.to_string(),
+            text.to_string(),
+            embedding,
+            allow_secrets,
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
-#[pyo3(signature = (identity, memory_id, tag, file_path, ic=None))]
+#[pyo3(signature = (identity, memory_id, tag, file_path, ic=None, progress=None, allow_secrets=false))]
+#[allow(clippy::too_many_arguments)]
 fn insert_memory_pdf(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     tag: &str,
     file_path: &str,
     ic: Option<bool>,
+    progress: Option<Py<PyAny>>,
+    allow_secrets: bool,
 ) -> PyResult<usize> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::insert_memory_pdf(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        tag.to_string(),
-        PathBuf::from(file_path),
-    ))
+    block_on_py(
+        py,
+        python::insert_memory_pdf(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            tag.to_string(),
+            PathBuf::from(file_path),
+            allow_secrets,
+            progress.map(py_progress),
+        ),
+    )
+}
+
+#[cfg(feature = "python-bindings")]
+#[pyfunction]
+fn pdf_to_markdown(py: Python<'_>, file_path: &str) -> PyResult<String> {
+    let path = PathBuf::from(file_path);
+    py.detach(|| python::pdf_to_markdown(path))
+        .map_err(anyhow_to_pyerr)
+}
+
+#[cfg(feature = "python-bindings")]
+#[pyfunction]
+fn html_to_text(html: &str) -> String {
+    python::html_to_text(html)
+}
+
+#[cfg(feature = "python-bindings")]
+#[pyfunction]
+fn chunk_text(py: Python<'_>, text: &str) -> PyResult<Vec<String>> {
+    block_on_py(py, python::chunk_text(text.to_string()))
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, query, ic=None))]
 fn search_memories(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     query: &str,
     ic: Option<bool>,
 ) -> PyResult<Vec<(f32, String)>> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::search_memories(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        query.to_string(),
-    ))
+    block_on_py(
+        py,
+        python::search_memories(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            query.to_string(),
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, embedding, ic=None))]
 fn search_memories_raw(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     embedding: Vec<f32>,
     ic: Option<bool>,
 ) -> PyResult<Vec<(f32, String)>> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::search_memories_raw(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        embedding,
-    ))
+    block_on_py(
+        py,
+        python::search_memories_raw(ic, identity.to_string(), memory_id.to_string(), embedding),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, tag, ic=None))]
 fn tagged_embeddings(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     tag: &str,
     ic: Option<bool>,
 ) -> PyResult<Vec<Vec<f32>>> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::tagged_embeddings(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        tag.to_string(),
-    ))
+    block_on_py(
+        py,
+        python::tagged_embeddings(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            tag.to_string(),
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, query, top_k=None, language=None, ic=None))]
 fn ask_ai(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     query: &str,
@@ -281,59 +648,65 @@ fn ask_ai(
 ) -> PyResult<(String, String)> {
     let ic = ic.unwrap_or(false);
     let language = language.map(|s| s.to_string());
-    let result = block_on_py(python::ask_ai(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        query.to_string(),
-        top_k,
-        language,
-    ))?;
-    Ok((result.prompt, result.response))
+    block_on_py(
+        py,
+        python::ask_ai(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            query.to_string(),
+            top_k,
+            language,
+        ),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, ic=None))]
-fn get_balance(identity: &str, ic: Option<bool>) -> PyResult<(u128, f64)> {
+fn get_balance(py: Python<'_>, identity: &str, ic: Option<bool>) -> PyResult<(u128, f64)> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::balance(ic, identity.to_string()))
+    block_on_py(py, python::balance(ic, identity.to_string()))
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, ic=None))]
-fn update_instance(identity: &str, memory_id: &str, ic: Option<bool>) -> PyResult<()> {
+fn update_instance(
+    py: Python<'_>,
+    identity: &str,
+    memory_id: &str,
+    ic: Option<bool>,
+) -> PyResult<()> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::update_instance(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-    ))
+    block_on_py(
+        py,
+        python::update_instance(ic, identity.to_string(), memory_id.to_string()),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, dim, ic=None))]
 fn reset_memory(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     dim: usize,
     ic: Option<bool>,
 ) -> PyResult<()> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::reset_memory(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        dim,
-    ))
+    block_on_py(
+        py,
+        python::reset_memory(ic, identity.to_string(), memory_id.to_string(), dim),
+    )
 }
 
 #[cfg(feature = "python-bindings")]
 #[pyfunction]
 #[pyo3(signature = (identity, memory_id, user_id, role, ic=None))]
 fn add_user(
+    py: Python<'_>,
     identity: &str,
     memory_id: &str,
     user_id: &str,
@@ -341,28 +714,383 @@ fn add_user(
     ic: Option<bool>,
 ) -> PyResult<()> {
     let ic = ic.unwrap_or(false);
-    block_on_py(python::add_user(
-        ic,
-        identity.to_string(),
-        memory_id.to_string(),
-        user_id.to_string(),
-        role.to_string(),
-    ))
+    block_on_py(
+        py,
+        python::add_user(
+            ic,
+            identity.to_string(),
+            memory_id.to_string(),
+            user_id.to_string(),
+            role.to_string(),
+        ),
+    )
+}
+
+#[cfg(feature = "python-bindings")]
+#[pyfunction]
+#[pyo3(signature = (identity_path=None))]
+fn login(py: Python<'_>, identity_path: Option<&str>) -> PyResult<String> {
+    block_on_py(py, python::login(identity_path.map(PathBuf::from)))
+}
+
+#[cfg(feature = "python-bindings")]
+#[pyfunction]
+#[pyo3(signature = (identity_path=None))]
+fn load_identity(identity_path: Option<&str>) -> PyResult<String> {
+    python::load_identity(identity_path.map(PathBuf::from)).map_err(anyhow_to_pyerr)
+}
+
+/// Holds a built agent so the keyring/PEM is read once for many calls.
+#[cfg(feature = "python-bindings")]
+#[pyclass(frozen, module = "kinic_py._lib")]
+struct KinicClient {
+    agent: Agent,
+    ii: bool,
+}
+
+#[cfg(feature = "python-bindings")]
+#[pymethods]
+impl KinicClient {
+    /// Use either a dfx `identity` name or `ii=True` (delegation from `login()`).
+    #[new]
+    #[pyo3(signature = (identity=None, ic=None, *, ii=None, identity_path=None))]
+    fn new(
+        py: Python<'_>,
+        identity: Option<&str>,
+        ic: Option<bool>,
+        ii: Option<bool>,
+        identity_path: Option<&str>,
+    ) -> PyResult<Self> {
+        let ic = ic.unwrap_or(false);
+        let ii = ii.unwrap_or(false);
+        let agent = match (identity, ii) {
+            (Some(_), true) => {
+                return Err(PyValueError::new_err(
+                    "pass either `identity` or `ii=True`, not both",
+                ));
+            }
+            (Some(identity), false) => {
+                block_on_py(py, python::build_agent(ic, identity.to_string()))?
+            }
+            (None, true) => block_on_py(
+                py,
+                python::build_agent_with_ii(ic, identity_path.map(PathBuf::from)),
+            )?,
+            (None, false) => {
+                return Err(PyValueError::new_err(
+                    "`identity` is required unless `ii=True`",
+                ));
+            }
+        };
+        Ok(Self { agent, ii })
+    }
+
+    /// Principal of the identity this client acts as.
+    fn principal(&self) -> PyResult<String> {
+        self.agent
+            .get_principal()
+            .map(|principal| principal.to_text())
+            .map_err(KinicError::new_err)
+    }
+
+    /// List deployed memories as `Memory` handles sharing this client's agent.
+    fn memories(&self, py: Python<'_>) -> PyResult<Vec<Memory>> {
+        let ids = block_on_py(py, python::list_memories_with_agent(self.agent.clone()))?;
+        ids.iter().map(|id| self.memory(id)).collect()
+    }
+
+    /// Awaitable `memories()`.
+    fn amemories<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let agent = self.agent.clone();
+        future_py(py, async move {
+            let ids = python::list_memories_with_agent(agent.clone()).await?;
+            ids.iter()
+                .map(|id| {
+                    Ok(Memory {
+                        agent: agent.clone(),
+                        memory_id: Principal::from_text(id)
+                            .with_context(|| format!("invalid memory id {id}"))?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+    }
+
+    /// Get a handle for an existing memory canister.
+    fn memory(&self, memory_id: &str) -> PyResult<Memory> {
+        let memory_id = Principal::from_text(memory_id)
+            .map_err(|e| PyValueError::new_err(format!("invalid memory id {memory_id}: {e}")))?;
+        Ok(Memory {
+            agent: self.agent.clone(),
+            memory_id,
+        })
+    }
+
+    /// Deploy a new memory canister and return a handle to it.
+    fn create_memory(&self, py: Python<'_>, name: &str, description: &str) -> PyResult<Memory> {
+        if self.ii && !cfg!(feature = "experimental") {
+            return Err(KinicError::new_err(II_TRANSFER_WARNING));
+        }
+        let id = block_on_py(
+            py,
+            python::create_memory_with_agent(
+                self.agent.clone(),
+                name.to_string(),
+                description.to_string(),
+            ),
+        )?;
+        self.memory(&id)
+    }
+
+    /// Awaitable `create_memory()`.
+    fn acreate_memory<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        description: &str,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if self.ii && !cfg!(feature = "experimental") {
+            return Err(KinicError::new_err(II_TRANSFER_WARNING));
+        }
+        let agent = self.agent.clone();
+        let name = name.to_string();
+        let description = description.to_string();
+        future_py(py, async move {
+            let id = python::create_memory_with_agent(agent.clone(), name, description).await?;
+            let memory_id =
+                Principal::from_text(&id).with_context(|| format!("invalid memory id {id}"))?;
+            Ok(Memory { agent, memory_id })
+        })
+    }
 }
 
+/// A memory canister bound to the agent of the `KinicClient` that produced it.
 #[cfg(feature = "python-bindings")]
-fn block_on_py<F, T>(future: F) -> PyResult<T>
+#[pyclass(frozen, module = "kinic_py._lib")]
+struct Memory {
+    agent: Agent,
+    memory_id: Principal,
+}
+
+#[cfg(feature = "python-bindings")]
+#[pymethods]
+impl Memory {
+    #[getter]
+    fn id(&self) -> String {
+        self.memory_id.to_text()
+    }
+
+    /// Embed and insert markdown text; returns the number of chunks stored.
+    /// Text that looks like it contains credentials is refused unless
+    /// `allow_secrets` is set.
+    #[pyo3(signature = (text, *, tag, allow_secrets=false))]
+    fn insert(
+        &self,
+        py: Python<'_>,
+        text: &str,
+        tag: &str,
+        allow_secrets: bool,
+    ) -> PyResult<usize> {
+        block_on_py(
+            py,
+            python::insert_text_with_agent(
+                self.agent.clone(),
+                self.memory_id,
+                tag.to_string(),
+                text.to_string(),
+                allow_secrets,
+            ),
+        )
+    }
+
+    /// Semantic search; results are `(score, payload)` sorted by score.
+    #[pyo3(signature = (query, *, top_k=None))]
+    fn search(
+        &self,
+        py: Python<'_>,
+        query: &str,
+        top_k: Option<usize>,
+    ) -> PyResult<Vec<(f32, String)>> {
+        block_on_py(
+            py,
+            python::search_memories_with_agent(
+                self.agent.clone(),
+                self.memory_id,
+                query.to_string(),
+                top_k,
+            ),
+        )
+    }
+
+    /// Awaitable `insert()`.
+    #[pyo3(signature = (text, *, tag, allow_secrets=false))]
+    fn ainsert<'py>(
+        &self,
+        py: Python<'py>,
+        text: &str,
+        tag: &str,
+        allow_secrets: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        future_py(
+            py,
+            python::insert_text_with_agent(
+                self.agent.clone(),
+                self.memory_id,
+                tag.to_string(),
+                text.to_string(),
+                allow_secrets,
+            ),
+        )
+    }
+
+    /// Awaitable `search()`.
+    #[pyo3(signature = (query, *, top_k=None))]
+    fn asearch<'py>(
+        &self,
+        py: Python<'py>,
+        query: &str,
+        top_k: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        future_py(
+            py,
+            python::search_memories_with_agent(
+                self.agent.clone(),
+                self.memory_id,
+                query.to_string(),
+                top_k,
+            ),
+        )
+    }
+
+    /// Every chunk as `(id, payload)` in insertion order, read a page of
+    /// `page_size` chunks at a time; with `tag`, only that tag's chunks.
+    /// Works with both `for` and `async for`.
+    #[pyo3(signature = (*, tag=None, page_size=100))]
+    fn iter_chunks(&self, tag: Option<&str>, page_size: u32) -> ChunkIterator {
+        let cursor = python::ChunkCursor::new(
+            self.agent.clone(),
+            self.memory_id,
+            tag.map(str::to_string),
+            page_size,
+        );
+        ChunkIterator {
+            cursor: Arc::new(tokio::sync::Mutex::new(cursor)),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Memory('{}')", self.memory_id)
+    }
+}
+
+/// Iterator returned by `Memory.iter_chunks`.
+#[cfg(feature = "python-bindings")]
+#[pyclass(frozen, module = "kinic_py._lib")]
+struct ChunkIterator {
+    cursor: Arc<tokio::sync::Mutex<python::ChunkCursor>>,
+}
+
+#[cfg(feature = "python-bindings")]
+#[pymethods]
+impl ChunkIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<(u32, String)>> {
+        let cursor = self.cursor.clone();
+        block_on_py(py, async move { cursor.lock().await.next().await })
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let cursor = self.cursor.clone();
+        init_async_runtime()?;
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            match cursor.lock().await.next().await {
+                Ok(Some(chunk)) => Ok(chunk),
+                Ok(None) => Err(PyStopAsyncIteration::new_err(())),
+                Err(err) => Err(anyhow_to_pyerr(err)),
+            }
+        })
+    }
+}
+
+/// Calls a Python `callback(chunk_index, total, status)`, re-acquiring the
+/// GIL that `block_on_py` released. An exception it raises aborts the insert.
+#[cfg(feature = "python-bindings")]
+fn py_progress(callback: Py<PyAny>) -> python::Progress {
+    Box::new(move |index, total, status| {
+        Python::attach(|py| {
+            callback
+                .call1(py, (index, total, status))
+                .map(drop)
+                .map_err(|err| anyhow::anyhow!("progress callback raised: {err}"))
+        })
+    })
+}
+
+#[cfg(feature = "python-bindings")]
+fn block_on_py<F, T>(py: Python<'_>, future: F) -> PyResult<T>
 where
     F: std::future::Future<Output = Result<T>> + Send + 'static,
     T: Send + 'static,
 {
-    Runtime::new()
-        .map_err(|e| PyRuntimeError::new_err(format!("failed to start tokio runtime: {e}")))?
-        .block_on(future)
+    let runtime = shared_runtime()?;
+    // Release the GIL while waiting on the network so other Python threads
+    // (and asyncio executors via `asyncio.to_thread`) keep running.
+    py.detach(|| runtime.block_on(future))
         .map_err(anyhow_to_pyerr)
 }
 
+/// Wraps `future` in an asyncio awaitable running on the shared runtime.
+/// Cancelling the awaitable drops the future, which abandons its pending
+/// canister and embedding requests; an update call already submitted may
+/// still complete on the canister.
+#[cfg(feature = "python-bindings")]
+fn future_py<'py, F, T>(py: Python<'py>, future: F) -> PyResult<Bound<'py, PyAny>>
+where
+    F: std::future::Future<Output = Result<T>> + Send + 'static,
+    T: for<'a> IntoPyObject<'a> + Send + 'static,
+{
+    init_async_runtime()?;
+    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        future.await.map_err(anyhow_to_pyerr)
+    })
+}
+
+/// Points pyo3-async-runtimes at the runtime `block_on_py` uses.
+#[cfg(feature = "python-bindings")]
+fn init_async_runtime() -> PyResult<()> {
+    // Fails once the runtime is set, which is the same runtime every time.
+    let _ = pyo3_async_runtimes::tokio::init_with_runtime(shared_runtime()?);
+    Ok(())
+}
+
+#[cfg(feature = "python-bindings")]
+fn shared_runtime() -> PyResult<&'static Runtime> {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new()
+        .map_err(|e| PyRuntimeError::new_err(format!("failed to start tokio runtime: {e}")))?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
 #[cfg(feature = "python-bindings")]
 fn anyhow_to_pyerr(err: anyhow::Error) -> PyErr {
-    PyRuntimeError::new_err(format!("{err:?}"))
+    let message = format!("{err:?}");
+    match error::classify(&err) {
+        error::ErrorClass::Identity => IdentityError::new_err(message),
+        error::ErrorClass::Network => NetworkError::new_err(message),
+        error::ErrorClass::CanisterReject => CanisterRejectError::new_err(message),
+        error::ErrorClass::EmbeddingApi => EmbeddingApiError::new_err(message),
+        error::ErrorClass::Payment => PaymentError::new_err(message),
+        error::ErrorClass::Validation => ValidationError::new_err(message),
+        error::ErrorClass::Other => KinicError::new_err(message),
+    }
 }
@@ -0,0 +1,183 @@
+//! Opt-in Prometheus metrics (`--metrics-addr`): canister call and embedding
+//! API request counts and latencies, plus failures by error class, served as
+//! text on `/metrics` for as long as the process runs.
+//!
+//! Recording is a no-op until [`serve`] has been called, so commands pay
+//! nothing for it by default.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::{Router, http::header, response::IntoResponse, routing::get};
+
+use crate::error::classify;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+const BUCKETS: [f64; 12] = [
+    0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+}
+
+const CANISTER_CALLS: Metric = Metric {
+    name: "kinic_canister_calls_total",
+    help: "Canister calls by method and outcome.",
+};
+const CANISTER_CALL_SECONDS: Metric = Metric {
+    name: "kinic_canister_call_duration_seconds",
+    help: "Canister call latency by method.",
+};
+const EMBEDDING_REQUESTS: Metric = Metric {
+    name: "kinic_embedding_requests_total",
+    help: "Embedding/chat API requests by endpoint and outcome.",
+};
+const EMBEDDING_REQUEST_SECONDS: Metric = Metric {
+    name: "kinic_embedding_request_duration_seconds",
+    help: "Embedding/chat API request latency by endpoint.",
+};
+const ERRORS: Metric = Metric {
+    name: "kinic_errors_total",
+    help: "Failed canister calls and API requests by error class.",
+};
+
+#[derive(Default)]
+struct Registry {
+    counters: Mutex<BTreeMap<(&'static str, String), u64>>,
+    histograms: Mutex<BTreeMap<(&'static str, String), Histogram>>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/// Records a finished canister call; `method` is `canister.method`.
+pub(crate) fn record_canister_call<T>(method: &str, elapsed: Duration, result: &Result<T>) {
+    record(
+        &CANISTER_CALLS,
+        &CANISTER_CALL_SECONDS,
+        format!("method=\"{method}\""),
+        elapsed,
+        result,
+    );
+}
+
+/// Records a finished embedding/chat API request; `endpoint` is its URL path.
+pub(crate) fn record_embedding_request<T>(endpoint: &str, elapsed: Duration, result: &Result<T>) {
+    record(
+        &EMBEDDING_REQUESTS,
+        &EMBEDDING_REQUEST_SECONDS,
+        format!("endpoint=\"{endpoint}\""),
+        elapsed,
+        result,
+    );
+}
+
+fn record<T>(
+    total: &Metric,
+    latency: &Metric,
+    labels: String,
+    elapsed: Duration,
+    result: &Result<T>,
+) {
+    let Some(registry) = REGISTRY.get() else {
+        return;
+    };
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    registry.increment(total, format!("{labels},outcome=\"{outcome}\""));
+    if let Err(err) = result {
+        registry.increment(&ERRORS, format!("class=\"{:?}\"", classify(err)));
+    }
+
+    let seconds = elapsed.as_secs_f64();
+    let mut histograms = registry.histograms.lock().expect("metrics lock poisoned");
+    let histogram = histograms.entry((latency.name, labels)).or_default();
+    for (bucket, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+impl Registry {
+    fn increment(&self, metric: &Metric, labels: String) {
+        let mut counters = self.counters.lock().expect("metrics lock poisoned");
+        *counters.entry((metric.name, labels)).or_default() += 1;
+    }
+
+    /// Prometheus text exposition format, version 0.0.4.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = self.counters.lock().expect("metrics lock poisoned");
+        for metric in [&CANISTER_CALLS, &EMBEDDING_REQUESTS, &ERRORS] {
+            let _ = writeln!(out, "# HELP {} {}", metric.name, metric.help);
+            let _ = writeln!(out, "# TYPE {} counter", metric.name);
+            for ((_, labels), value) in counters.range(series(metric)) {
+                let _ = writeln!(out, "{}{{{labels}}} {value}", metric.name);
+            }
+        }
+        drop(counters);
+
+        let histograms = self.histograms.lock().expect("metrics lock poisoned");
+        for metric in [&CANISTER_CALL_SECONDS, &EMBEDDING_REQUEST_SECONDS] {
+            let name = metric.name;
+            let _ = writeln!(out, "# HELP {name} {}", metric.help);
+            let _ = writeln!(out, "# TYPE {name} histogram");
+            for ((_, labels), histogram) in histograms.range(series(metric)) {
+                for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+                    let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}");
+                }
+                let count = histogram.count;
+                let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}");
+                let _ = writeln!(out, "{name}_sum{{{labels}}} {}", histogram.sum);
+                let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+            }
+        }
+        out
+    }
+}
+
+/// Key range covering every label set of `metric`.
+fn series(metric: &Metric) -> std::ops::RangeInclusive<(&'static str, String)> {
+    (metric.name, String::new())..=(metric.name, char::MAX.to_string())
+}
+
+/// Router exposing `GET /metrics`, for mounting next to other routes.
+pub(crate) fn router() -> Router {
+    Router::new().route("/metrics", get(metrics_handler))
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    let body = REGISTRY.get().map(Registry::render).unwrap_or_default();
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+/// Starts recording and serves `/metrics` on `addr` in the background.
+pub(crate) async fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint to {addr}"))?;
+    let _ = REGISTRY.set(Registry::default());
+    tracing::info!(%addr, "serving metrics on /metrics");
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, router()).await {
+            eprintln!("Metrics endpoint failed: {err}");
+        }
+    });
+    Ok(())
+}
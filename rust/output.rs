@@ -0,0 +1,39 @@
+//! Where command output goes, so the CLI composes in pipelines.
+//!
+//! Data a script may consume (ids, principals, search hits, tables, JSON) is
+//! printed to stdout with `println!`. Everything written for a human reader
+//! (progress, previews, confirmations, "nothing found" notes) goes through
+//! [`note!`] to stderr and is dropped under `--quiet`. Warnings and errors
+//! use `eprintln!` directly and are always shown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub(crate) fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// `eprintln!` unless `--quiet` is set.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use note;
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
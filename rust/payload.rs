@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::error::KinicError;
+
+/// Metadata keys filled in by the CLI; `--meta` cannot override them.
+const RESERVED_KEYS: &[&str] = &[
+    "source",
+    "page",
+    "chunk_index",
+    "created_at",
+    "content_hash",
+    "auto_tags",
+    "part",
+    "parts",
+];
+
+/// Tag prefix of the chunk holding a document's LLM summary, written by
+/// `insert --summarize` as `summary:<tag>`.
+pub const SUMMARY_TAG_PREFIX: &str = "summary:";
+
+pub fn summary_tag(tag: &str) -> String {
+    format!("{SUMMARY_TAG_PREFIX}{tag}")
+}
+
+/// Schema version written into every new payload.
+///
+/// - 1: `tag` and `sentence`, optionally with `metadata`; no `v` field.
+/// - 2: `v`, `tag`, `sentence` and `metadata` with at least `content_hash`.
+pub const PAYLOAD_VERSION: u32 = 2;
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// The JSON payload stored next to each embedding. Chunks written before
+/// metadata existed have only `tag` and `sentence`; chunks written before
+/// versioning have no `v` and read as version 1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkPayload {
+    #[serde(default = "legacy_version")]
+    pub v: u32,
+    pub tag: String,
+    pub sentence: String,
+    #[serde(default, skip_serializing_if = "ChunkMetadata::is_empty")]
+    pub metadata: ChunkMetadata,
+}
+
+impl ChunkPayload {
+    pub fn parse(raw: &str) -> Option<Self> {
+        serde_json::from_str(raw).ok()
+    }
+
+    /// This payload in the current schema. Metadata the old chunk never had
+    /// stays unset, except the content hash, which is derived from the text.
+    pub fn upgrade(mut self) -> Self {
+        self.v = PAYLOAD_VERSION;
+        if self.metadata.content_hash.is_none() {
+            self.metadata.content_hash = Some(content_hash(&self.sentence));
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("chunk payload serializes to JSON")
+    }
+
+    pub fn is_summary(&self) -> bool {
+        self.tag.starts_with(SUMMARY_TAG_PREFIX)
+    }
+
+    /// Whether the chunk was inserted under `tag` or auto-tagged with it
+    /// (case-insensitively).
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tag == tag
+            || self
+                .metadata
+                .auto_tags
+                .iter()
+                .any(|auto| auto.eq_ignore_ascii_case(tag))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChunkMetadata {
+    /// File path or URL the text came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// 1-based page number, for PDFs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    /// Position of the chunk within its document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_index: Option<usize>,
+    /// RFC 3339 time of the insert.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Hex SHA-256 of the chunk text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Keywords extracted by `insert --auto-tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auto_tags: Vec<String>,
+    /// 1-based part number, when a chunk too large for one insert was
+    /// stored as several consecutive chunks with the same `chunk_index`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<u32>,
+    /// Number of parts the chunk was split into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parts: Option<u32>,
+    /// User-supplied `--meta key=value` pairs.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl ChunkMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none()
+            && self.page.is_none()
+            && self.chunk_index.is_none()
+            && self.created_at.is_none()
+            && self.content_hash.is_none()
+            && self.auto_tags.is_empty()
+            && self.part.is_none()
+            && self.parts.is_none()
+            && self.extra.is_empty()
+    }
+
+    /// `key=value` pairs for display, built-in fields first. The content
+    /// hash is left out as it is only useful for deduplication.
+    pub fn summary(&self) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        if let Some(source) = &self.source {
+            fields.push(("source".to_string(), source.clone()));
+        }
+        if let Some(page) = self.page {
+            fields.push(("page".to_string(), page.to_string()));
+        }
+        if let Some(index) = self.chunk_index {
+            fields.push(("chunk".to_string(), index.to_string()));
+        }
+        if let (Some(part), Some(parts)) = (self.part, self.parts) {
+            fields.push(("part".to_string(), format!("{part}/{parts}")));
+        }
+        if let Some(created_at) = &self.created_at {
+            fields.push(("created_at".to_string(), created_at.clone()));
+        }
+        if !self.auto_tags.is_empty() {
+            fields.push(("auto_tags".to_string(), self.auto_tags.join("; ")));
+        }
+        fields.extend(self.extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        fields
+    }
+}
+
+/// Metadata shared by every chunk of one insert.
+pub struct InsertMetadata {
+    tag: String,
+    source: Option<String>,
+    created_at: String,
+    auto_tags: Vec<String>,
+    extra: BTreeMap<String, String>,
+}
+
+impl InsertMetadata {
+    pub fn new(tag: &str, source: Option<String>, meta: &[String]) -> Result<Self> {
+        Ok(Self {
+            tag: tag.to_string(),
+            source,
+            created_at: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            auto_tags: Vec::new(),
+            extra: parse_meta_args(meta)?,
+        })
+    }
+
+    /// Keywords stored on every chunk of this insert.
+    pub fn with_auto_tags(mut self, auto_tags: Vec<String>) -> Self {
+        self.auto_tags = auto_tags;
+        self
+    }
+
+    /// Serialized payload for the chunk at `chunk_index`.
+    pub fn payload(&self, sentence: &str, chunk_index: usize, page: Option<u32>) -> String {
+        let payload = ChunkPayload {
+            v: PAYLOAD_VERSION,
+            tag: self.tag.clone(),
+            sentence: sentence.to_string(),
+            metadata: ChunkMetadata {
+                source: self.source.clone(),
+                page,
+                chunk_index: Some(chunk_index),
+                created_at: Some(self.created_at.clone()),
+                content_hash: Some(content_hash(sentence)),
+                auto_tags: self.auto_tags.clone(),
+                part: None,
+                parts: None,
+                extra: self.extra.clone(),
+            },
+        };
+        payload.to_json()
+    }
+
+    /// Serialized payload of the document summary chunk, stored under
+    /// [`summary_tag`] of this insert's tag.
+    pub fn summary_payload(&self, summary: &str) -> String {
+        let payload = ChunkPayload {
+            v: PAYLOAD_VERSION,
+            tag: summary_tag(&self.tag),
+            sentence: summary.to_string(),
+            metadata: ChunkMetadata {
+                source: self.source.clone(),
+                page: None,
+                chunk_index: None,
+                created_at: Some(self.created_at.clone()),
+                content_hash: Some(content_hash(summary)),
+                auto_tags: self.auto_tags.clone(),
+                part: None,
+                parts: None,
+                extra: self.extra.clone(),
+            },
+        };
+        payload.to_json()
+    }
+}
+
+/// Whether `key` is filled in by the CLI and so cannot be set with `--meta`.
+pub fn is_reserved_key(key: &str) -> bool {
+    RESERVED_KEYS.contains(&key)
+}
+
+fn parse_meta_args(meta: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut extra = BTreeMap::new();
+    for entry in meta {
+        let Some((key, value)) = entry.split_once('=') else {
+            return Err(KinicError::Validation(format!(
+                "--meta must be KEY=VALUE, got \"{entry}\""
+            ))
+            .into());
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(
+                KinicError::Validation(format!("--meta key is empty in \"{entry}\"")).into(),
+            );
+        }
+        if is_reserved_key(key) {
+            return Err(KinicError::Validation(format!(
+                "--meta cannot set \"{key}\"; it is filled in automatically"
+            ))
+            .into());
+        }
+        extra.insert(key.to_string(), value.to_string());
+    }
+    Ok(extra)
+}
+
+pub(crate) fn content_hash(text: &str) -> String {
+    hex::encode(digest::digest(&digest::SHA256, text.as_bytes()))
+}
+
+/// Maps chunks back to the page they came from. PDF text extraction
+/// separates pages with form feeds; chunks are located in order by their
+/// leading text, so a chunk the chunker rewrote simply gets no page.
+pub struct PageLocator<'a> {
+    text: &'a str,
+    cursor: usize,
+    page: usize,
+}
+
+impl<'a> PageLocator<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            cursor: 0,
+            page: 1,
+        }
+    }
+
+    pub fn page_of(&mut self, sentence: &str) -> Option<u32> {
+        let needle: String = sentence.trim().chars().take(32).collect();
+        if needle.is_empty() {
+            return None;
+        }
+        let found = self.text[self.cursor..].find(&needle)? + self.cursor;
+        self.page += self.text[self.cursor..found].matches('\u{c}').count();
+        self.cursor = found;
+        u32::try_from(self.page).ok()
+    }
+}
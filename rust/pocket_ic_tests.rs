@@ -0,0 +1,269 @@
+//! Runs the real `MemoryClient` against the stub memory canister
+//! (`canisters/stub_memory`) inside PocketIC, checking the Candid encodings
+//! of the memory interface without a replica or mainnet.
+//!
+//! ```bash
+//! scripts/build-stub-memory.sh
+//! cargo test --features integration-tests
+//! ```
+//!
+//! The wasm paths can be overridden with `KINIC_STUB_MEMORY_WASM` and
+//! `KINIC_STUB_MEMORY_BASE_WASM`; the PocketIC server binary is taken from
+//! `POCKET_IC_BIN` or downloaded.
+
+use std::path::PathBuf;
+
+use ic_agent::{Agent, export::Principal};
+use pocket_ic::nonblocking::PocketIc;
+
+use crate::{
+    clients::memory::MemoryClient,
+    payload::ChunkPayload,
+    vectors::{VectorProcessing, VectorTransport},
+};
+
+const WASM_ENV_VAR: &str = "KINIC_STUB_MEMORY_WASM";
+/// The stub built without its `extensions` feature.
+const BASE_WASM_ENV_VAR: &str = "KINIC_STUB_MEMORY_BASE_WASM";
+const DIM: usize = 4;
+
+struct Harness {
+    pic: PocketIc,
+    agent: Agent,
+    client: MemoryClient,
+}
+
+impl Harness {
+    /// Installs a fresh stub memory reset to `DIM` dimensions.
+    async fn start() -> Self {
+        Self::start_with(stub_wasm(WASM_ENV_VAR, "stub_memory.wasm")).await
+    }
+
+    /// Like [`Self::start`], with only the methods of the deployed memory
+    /// canisters.
+    async fn start_base() -> Self {
+        Self::start_with(stub_wasm(BASE_WASM_ENV_VAR, "stub_memory_base.wasm")).await
+    }
+
+    async fn start_with(wasm: Vec<u8>) -> Self {
+        let mut pic = PocketIc::new().await;
+        let canister = pic.create_canister().await;
+        pic.add_cycles(canister, 2_000_000_000_000).await;
+        pic.install_canister(canister, wasm, candid::encode_args(()).unwrap(), None)
+            .await;
+
+        let url = pic.make_live(None).await;
+        let agent = Agent::builder()
+            .with_url(url.as_str())
+            .build()
+            .expect("agent for PocketIC gateway");
+        agent.fetch_root_key().await.expect("PocketIC root key");
+
+        // There is no launcher to read vector processing from.
+        let client =
+            MemoryClient::new(agent.clone(), canister).with_processing(VectorProcessing::default());
+        client.reset(DIM).await.expect("reset stub memory");
+        Self { pic, agent, client }
+    }
+
+    async fn stop(self) {
+        self.pic.drop().await;
+    }
+}
+
+fn stub_wasm(env_var: &str, file: &str) -> Vec<u8> {
+    let path = std::env::var_os(env_var)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("canisters/stub_memory/target/wasm32-unknown-unknown/release")
+                .join(file)
+        });
+    std::fs::read(&path).unwrap_or_else(|err| {
+        panic!(
+            "stub memory wasm not found at {} ({err}); run scripts/build-stub-memory.sh or set {env_var}",
+            path.display()
+        )
+    })
+}
+
+fn payload(tag: &str, sentence: &str) -> String {
+    serde_json::json!({ "tag": tag, "sentence": sentence }).to_string()
+}
+
+#[tokio::test]
+async fn insert_and_search_round_trip() {
+    let harness = Harness::start().await;
+    let client = &harness.client;
+
+    assert_eq!(client.dim().await.unwrap(), Some(DIM));
+    let north = client
+        .insert(vec![1.0, 0.0, 0.0, 0.0], &payload("compass", "north"))
+        .await
+        .unwrap();
+    let east = client
+        .insert(vec![0.0, 1.0, 0.0, 0.0], &payload("compass", "east"))
+        .await
+        .unwrap();
+    assert_eq!((north, east), (0, 1));
+
+    let hits = client
+        .search_top_k(vec![0.9, 0.1, 0.0, 0.0], 1)
+        .await
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].id, Some(north));
+    let chunk = ChunkPayload::parse(&hits[0].text).unwrap();
+    assert_eq!(chunk.sentence, "north");
+
+    let stored = client.get(east).await.unwrap().unwrap();
+    assert_eq!(ChunkPayload::parse(&stored).unwrap().sentence, "east");
+    assert_eq!(client.get(99).await.unwrap(), None);
+
+    let legacy = client.search(vec![0.0, 1.0, 0.0, 0.0]).await.unwrap();
+    assert_eq!(legacy.len(), 2);
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+async fn search_pages_cover_every_chunk_in_order() {
+    let harness = Harness::start().await;
+    let client = &harness.client;
+
+    for i in 0..5 {
+        let embedding = vec![1.0, i as f32, 0.0, 0.0];
+        client
+            .insert(embedding, &payload("pages", &format!("chunk {i}")))
+            .await
+            .unwrap();
+    }
+
+    let mut pages = client.search_pages(vec![1.0, 0.0, 0.0, 0.0], 2);
+    let mut hits = Vec::new();
+    while let Some(page) = pages.next_page().await.unwrap() {
+        assert!(page.len() <= 2);
+        hits.extend(page);
+    }
+    let ids: Vec<Option<u32>> = hits.iter().map(|hit| hit.id).collect();
+    assert_eq!(ids, (0..5).map(Some).collect::<Vec<_>>());
+    assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+async fn tags_users_and_dimension_checks() {
+    let harness = Harness::start().await;
+    let client = &harness.client;
+
+    client
+        .insert(vec![0.0, 0.0, 1.0, 0.0], &payload("a", "first"))
+        .await
+        .unwrap();
+    client
+        .insert(vec![0.0, 0.0, 0.0, 1.0], &payload("b", "second"))
+        .await
+        .unwrap();
+    assert_eq!(
+        client.tagged_embeddings("b".to_string()).await.unwrap(),
+        vec![vec![0.0, 0.0, 0.0, 1.0]]
+    );
+
+    client
+        .add_new_user(Principal::anonymous(), 3)
+        .await
+        .unwrap();
+
+    // Caught by the client before reaching the canister.
+    assert!(client.insert(vec![1.0; DIM + 1], "{}").await.is_err());
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+async fn quantized_inserts_store_close_vectors() {
+    let harness = Harness::start().await;
+    let canister = *harness.client.canister_id();
+    let capabilities = harness.client.capabilities().await;
+    assert!(capabilities.iter().any(|c| c == "insert_f16"));
+
+    let vector = vec![0.5, -0.25, 0.125, 0.1];
+    for (transport, tolerance) in [(VectorTransport::F16, 1e-3), (VectorTransport::Int8, 1e-2)] {
+        let client = MemoryClient::new(harness.agent.clone(), canister)
+            .with_processing(VectorProcessing::default())
+            .with_transport(transport);
+        let tag = format!("{transport:?}");
+        client
+            .insert(vector.clone(), &payload(&tag, "quantized"))
+            .await
+            .unwrap();
+        let stored = client.tagged_embeddings(tag).await.unwrap().remove(0);
+        for (sent, kept) in vector.iter().zip(&stored) {
+            assert!(
+                (sent - kept).abs() <= tolerance,
+                "{transport:?}: {sent} vs {kept}"
+            );
+        }
+    }
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+async fn insert_and_search_without_optional_methods() {
+    let harness = Harness::start_base().await;
+    // A fresh client, since the harness's one remembers the dimension of
+    // its reset.
+    let client = &MemoryClient::new(harness.agent.clone(), *harness.client.canister_id())
+        .with_processing(VectorProcessing::default());
+
+    assert!(client.capabilities().await.is_empty());
+    assert_eq!(client.dim().await.unwrap(), None);
+    client.ping().await.unwrap();
+
+    for (vector, sentence) in [
+        (vec![1.0, 0.0, 0.0, 0.0], "north"),
+        (vec![0.0, 1.0, 0.0, 0.0], "east"),
+        (vec![0.0, 0.0, 1.0, 0.0], "up"),
+    ] {
+        client
+            .insert(vector, &payload("compass", sentence))
+            .await
+            .unwrap();
+    }
+
+    // Paged on the client from one `search` reply, without chunk ids.
+    let hits = client
+        .search_top_k(vec![0.9, 0.1, 0.0, 0.0], 2)
+        .await
+        .unwrap();
+    let sentences: Vec<String> = hits
+        .iter()
+        .map(|hit| ChunkPayload::parse(&hit.text).unwrap().sentence)
+        .collect();
+    assert_eq!(sentences, ["north", "east"]);
+    assert!(hits.iter().all(|hit| hit.id.is_none()));
+
+    let mut pages = client.search_pages(vec![0.0, 0.0, 1.0, 0.0], 2);
+    let mut all = Vec::new();
+    while let Some(page) = pages.next_page().await.unwrap() {
+        assert!(page.len() <= 2);
+        all.extend(page);
+    }
+    assert_eq!(all.len(), 3);
+    assert!(all.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+    assert_eq!(
+        client
+            .tagged_embeddings("compass".to_string())
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+    // Reading chunks back by id needs `get`, which is refused up front.
+    assert!(client.get(0).await.is_err());
+
+    harness.stop().await;
+}
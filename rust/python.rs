@@ -1,21 +1,54 @@
-use std::{cmp::Ordering, fs, path::PathBuf};
+use std::{cmp::Ordering, collections::VecDeque, fs, path::PathBuf};
 
-use anyhow::{Context, Result, anyhow, bail};
-use ic_agent::export::Principal;
-use serde_json::json;
+use anyhow::{Context, Result};
+use ic_agent::{Agent, Identity, export::Principal};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tracing::warn;
 
 use crate::{
     agent::AgentFactory,
     clients::{
-        LEDGER_CANISTER,
-        launcher::{LauncherClient, State},
+        launcher::{LauncherClient, MemoryMetadata},
         memory::MemoryClient,
     },
-    commands::ask_ai::{AskAiResult, ask_ai_flow},
-    commands::convert_pdf,
-    embedding::{fetch_embedding, late_chunking},
+    commands::{ask_ai, config::parse_grant, convert_pdf, ii_login, insert_bookmarks},
+    embedding::{embedding_dim, fetch_embedding, late_chunking},
+    error::KinicError,
+    guard,
+    identity_store::{self, SessionStore},
+    jobs::LlmSettings,
+    ledger,
+    payload::{ChunkPayload, InsertMetadata, PageLocator},
+    rag::{self, DEFAULT_CONTEXT_TOKENS},
 };
-use icrc_ledger_types::icrc1::account::Account;
+
+pub(crate) async fn build_agent(use_mainnet: bool, identity: String) -> Result<Agent> {
+    AgentFactory::new(use_mainnet, identity).build().await
+}
+
+pub(crate) async fn build_agent_with_ii(
+    use_mainnet: bool,
+    identity_path: Option<PathBuf>,
+) -> Result<Agent> {
+    let path = resolve_identity_path(identity_path)?;
+    let delegated = identity_store::load_delegated_identity(&path, None)?;
+    AgentFactory::new_with_identity(use_mainnet, delegated)
+        .build()
+        .await
+}
+
+pub(crate) async fn login(identity_path: Option<PathBuf>) -> Result<String> {
+    let path = resolve_identity_path(identity_path)?;
+    let principal = ii_login::login(&SessionStore::File(path), None).await?;
+    Ok(principal.to_text())
+}
+
+pub(crate) fn load_identity(identity_path: Option<PathBuf>) -> Result<String> {
+    let path = resolve_identity_path(identity_path)?;
+    let delegated = identity_store::load_delegated_identity(&path, None)?;
+    let principal = delegated.sender().map_err(anyhow::Error::msg)?;
+    Ok(principal.to_text())
+}
 
 pub(crate) async fn create_memory(
     use_mainnet: bool,
@@ -23,29 +56,62 @@ pub(crate) async fn create_memory(
     name: String,
     description: String,
 ) -> Result<String> {
-    let factory = AgentFactory::new(use_mainnet, identity);
-    let agent = factory.build().await?;
+    let agent = build_agent(use_mainnet, identity).await?;
+    create_memory_with_agent(agent, name, description).await
+}
+
+pub(crate) async fn create_memory_with_agent(
+    agent: Agent,
+    name: String,
+    description: String,
+) -> Result<String> {
     let client = LauncherClient::new(agent);
 
     let price = client.fetch_deployment_price().await?;
-    client.approve_launcher(&price).await?;
-    client.deploy_memory(&name, &description).await
+    let approval = ledger::allowance_for(ledger::nat_to_e8s(&price)?);
+    let metadata = MemoryMetadata {
+        name,
+        description,
+        created_at: Some(OffsetDateTime::now_utc().format(&Rfc3339)?),
+        ..Default::default()
+    };
+    client
+        .approve_launcher(&candid::Nat::from(approval))
+        .await?;
+    let deployed = client
+        .deploy_memory(&metadata, embedding_dim() as u64)
+        .await;
+    // Like `create`: revoke what the deploy left or, on failure, the whole
+    // approval, so the launcher cannot pull it later.
+    if let Err(err) = client.revoke_remaining().await {
+        warn!("could not revoke the launcher's remaining allowance: {err:#}");
+    }
+    deployed
 }
 
 pub(crate) async fn list_memories(use_mainnet: bool, identity: String) -> Result<Vec<String>> {
-    let factory = AgentFactory::new(use_mainnet, identity);
-    let agent = factory.build().await?;
+    let agent = build_agent(use_mainnet, identity).await?;
+    list_memories_with_agent(agent).await
+}
+
+pub(crate) async fn list_memories_with_agent(agent: Agent) -> Result<Vec<String>> {
     let client = LauncherClient::new(agent);
     let states = client.list_memories().await?;
 
     let principals = states
         .into_iter()
-        .filter_map(|state| state_principal(&state).cloned())
+        .filter_map(|state| state.principal().cloned())
         .map(|principal| principal.to_text())
         .collect();
     Ok(principals)
 }
 
+/// Called with `(chunk_index, total, status)` during an insert: `"converting"`
+/// (0, 0) before a PDF is converted, `"chunked"` (0, total) once the text is
+/// split, then `"inserted"` after each chunk. An error aborts the insert.
+pub(crate) type Progress = Box<dyn Fn(usize, usize, &str) -> Result<()> + Send + Sync>;
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn insert_memory(
     use_mainnet: bool,
     identity: String,
@@ -53,24 +119,87 @@ pub(crate) async fn insert_memory(
     tag: String,
     text: Option<String>,
     file_path: Option<PathBuf>,
+    allow_secrets: bool,
+    progress: Option<Progress>,
 ) -> Result<usize> {
-    let client = build_memory_client(use_mainnet, identity, memory_id).await?;
+    let source = file_path.as_ref().map(|path| path.display().to_string());
     let content = resolve_insert_content(text, file_path)?;
-    let chunks = late_chunking(&content).await?;
+    check_secrets(source.as_deref(), &content, allow_secrets)?;
+    let client = build_memory_client(use_mainnet, identity, memory_id).await?;
+    let metadata = InsertMetadata::new(&tag, source, &[])?;
+    insert_content(&client, &metadata, &content, false, progress.as_ref()).await
+}
+
+pub(crate) async fn insert_text_with_agent(
+    agent: Agent,
+    memory_id: Principal,
+    tag: String,
+    text: String,
+    allow_secrets: bool,
+) -> Result<usize> {
+    check_secrets(None, &text, allow_secrets)?;
+    let client = MemoryClient::new(agent, memory_id);
+    let metadata = InsertMetadata::new(&tag, None, &[])?;
+    insert_content(&client, &metadata, &text, false, None).await
+}
+
+/// The CLI's credential check, overridden by the `allow_secrets` argument
+/// rather than `--allow-secrets`.
+fn check_secrets(source: Option<&str>, text: &str, allow_secrets: bool) -> Result<()> {
+    guard::check_secrets_with(
+        source.unwrap_or("the text"),
+        text,
+        allow_secrets,
+        "allow_secrets",
+    )
+}
+
+async fn insert_content(
+    client: &MemoryClient,
+    metadata: &InsertMetadata,
+    content: &str,
+    paged: bool,
+    progress: Option<&Progress>,
+) -> Result<usize> {
+    let chunks = late_chunking(content, None).await?;
     let chunk_count = chunks.len();
+    let mut pages = PageLocator::new(content);
+    if let Some(progress) = progress {
+        progress(0, chunk_count, "chunked")?;
+    }
 
-    for chunk in chunks {
-        let payload = json!({
-            "tag": &tag,
-            "sentence": &chunk.sentence
-        })
-        .to_string();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let page = if paged {
+            pages.page_of(&chunk.sentence)
+        } else {
+            None
+        };
+        let payload = metadata.payload(&chunk.sentence, index, page);
         client.insert(chunk.embedding, &payload).await?;
+        if let Some(progress) = progress {
+            progress(index, chunk_count, "inserted")?;
+        }
     }
 
     Ok(chunk_count)
 }
 
+/// The markdown `insert_memory_pdf` would chunk and insert.
+pub(crate) fn pdf_to_markdown(file_path: PathBuf) -> Result<String> {
+    convert_pdf::pdf_to_markdown(&file_path)
+}
+
+/// The text `insert-bookmarks` keeps of a fetched HTML page.
+pub(crate) fn html_to_text(html: &str) -> String {
+    insert_bookmarks::html_to_text(html)
+}
+
+/// The chunks an insert of `text` would store, without their embeddings.
+pub(crate) async fn chunk_text(text: String) -> Result<Vec<String>> {
+    let chunks = late_chunking(&text, None).await?;
+    Ok(chunks.into_iter().map(|chunk| chunk.sentence).collect())
+}
+
 pub(crate) async fn insert_memory_raw(
     use_mainnet: bool,
     identity: String,
@@ -78,13 +207,11 @@ pub(crate) async fn insert_memory_raw(
     tag: String,
     text: String,
     embedding: Vec<f32>,
+    allow_secrets: bool,
 ) -> Result<usize> {
+    check_secrets(None, &text, allow_secrets)?;
     let client = build_memory_client(use_mainnet, identity, memory_id).await?;
-    let payload = json!({
-        "tag": &tag,
-        "sentence": &text
-    })
-    .to_string();
+    let payload = InsertMetadata::new(&tag, None, &[])?.payload(&text, 0, None);
     client.insert(embedding, &payload).await?;
     Ok(1)
 }
@@ -95,9 +222,18 @@ pub(crate) async fn insert_memory_pdf(
     memory_id: String,
     tag: String,
     file_path: PathBuf,
+    allow_secrets: bool,
+    progress: Option<Progress>,
 ) -> Result<usize> {
+    let client = build_memory_client(use_mainnet, identity, memory_id).await?;
+    if let Some(progress) = &progress {
+        progress(0, 0, "converting")?;
+    }
     let markdown = convert_pdf::pdf_to_markdown(&file_path)?;
-    insert_memory(use_mainnet, identity, memory_id, tag, Some(markdown), None).await
+    let source = file_path.display().to_string();
+    check_secrets(Some(&source), &markdown, allow_secrets)?;
+    let metadata = InsertMetadata::new(&tag, Some(source), &[])?;
+    insert_content(&client, &metadata, &markdown, true, progress.as_ref()).await
 }
 
 pub(crate) async fn search_memories(
@@ -107,10 +243,76 @@ pub(crate) async fn search_memories(
     query: String,
 ) -> Result<Vec<(f32, String)>> {
     let client = build_memory_client(use_mainnet, identity, memory_id).await?;
-    let embedding = fetch_embedding(&query).await?;
-    let mut results = client.search(embedding).await?;
-    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
-    Ok(results)
+    search_text(&client, &query, None).await
+}
+
+pub(crate) async fn search_memories_with_agent(
+    agent: Agent,
+    memory_id: Principal,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<(f32, String)>> {
+    let client = MemoryClient::new(agent, memory_id);
+    search_text(&client, &query, top_k).await
+}
+
+async fn search_text(
+    client: &MemoryClient,
+    query: &str,
+    top_k: Option<usize>,
+) -> Result<Vec<(f32, String)>> {
+    let embedding = fetch_embedding(query).await?;
+    let hits = match top_k {
+        Some(k) => client.search_top_k(embedding, k).await?,
+        None => client.search_all(embedding).await?,
+    };
+    Ok(hits.into_iter().map(Into::into).collect())
+}
+
+/// Reads the chunks of a memory as `(id, payload)` a page at a time, so a
+/// caller iterating over a large memory never holds all of it.
+pub(crate) struct ChunkCursor {
+    client: MemoryClient,
+    tag: Option<String>,
+    page_size: u32,
+    next: Option<u32>,
+    buffer: VecDeque<(u32, String)>,
+}
+
+impl ChunkCursor {
+    /// With a `tag`, only chunk payloads inserted or auto-tagged with it
+    /// are returned.
+    pub(crate) fn new(
+        agent: Agent,
+        memory_id: Principal,
+        tag: Option<String>,
+        page_size: u32,
+    ) -> Self {
+        Self {
+            client: MemoryClient::new(agent, memory_id),
+            tag,
+            page_size,
+            next: Some(0),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// The next chunk, fetching another page when the current one is used
+    /// up, or `None` after the last chunk.
+    pub(crate) async fn next(&mut self) -> Result<Option<(u32, String)>> {
+        while self.buffer.is_empty() {
+            let Some(start) = self.next else {
+                return Ok(None);
+            };
+            let (page, next) = self.client.chunk_page(start, self.page_size).await?;
+            self.next = next;
+            let tag = self.tag.as_deref();
+            self.buffer.extend(page.into_iter().filter(|(_, raw)| {
+                tag.is_none_or(|tag| ChunkPayload::parse(raw).is_some_and(|p| p.has_tag(tag)))
+            }));
+        }
+        Ok(self.buffer.pop_front())
+    }
 }
 
 pub(crate) async fn search_memories_raw(
@@ -142,39 +344,29 @@ pub(crate) async fn ask_ai(
     query: String,
     top_k: Option<usize>,
     language: Option<String>,
-) -> Result<AskAiResult> {
-    let factory = AgentFactory::new(use_mainnet, identity);
+) -> Result<(String, String)> {
+    let agent = build_agent(use_mainnet, identity).await?;
     let memory = Principal::from_text(memory_id).context("Failed to parse memory canister id")?;
     let top_k = top_k.unwrap_or(5);
     let language = language.unwrap_or_else(|| "en".to_string());
-    ask_ai_flow(&factory, &memory, &query, top_k, &language).await
+    let prepared = rag::prepare_prompt(
+        agent,
+        &memory,
+        &query,
+        &[],
+        top_k,
+        &language,
+        DEFAULT_CONTEXT_TOKENS,
+    )
+    .await?;
+    let response = ask_ai::call_llm(&prepared.prompt, &LlmSettings::configured(None)?).await?;
+    Ok((prepared.prompt, response))
 }
 
 pub(crate) async fn balance(use_mainnet: bool, identity: String) -> Result<(u128, f64)> {
     let factory = AgentFactory::new(use_mainnet, identity);
     let agent = factory.build().await?;
-    let principal = agent
-        .get_principal()
-        .map_err(|e| anyhow!("Failed to derive principal for current identity: {e}"))?;
-
-    let ledger_id =
-        Principal::from_text(LEDGER_CANISTER).context("Failed to parse ledger canister id")?;
-
-    let account = Account {
-        owner: principal,
-        subaccount: None,
-    };
-
-    let payload = candid::encode_one(account)?;
-    let response = agent
-        .query(&ledger_id, "icrc1_balance_of")
-        .with_arg(payload)
-        .call()
-        .await
-        .context("Failed to query ledger balance")?;
-
-    let balance: u128 =
-        candid::decode_one(&response).context("Failed to decode balance response")?;
+    let balance = ledger::fetch_balance(&agent).await?;
     let kinic = balance as f64 / 10_000_000f64;
 
     Ok((balance, kinic))
@@ -192,11 +384,10 @@ pub(crate) async fn add_user(
     let memory = Principal::from_text(memory_id).context("Failed to parse memory canister id")?;
     let client = MemoryClient::new(agent, memory);
 
-    let role_code = parse_role(&role)?;
-    let principal = parse_principal(&user_id, role_code)?;
+    let (principal, role) = parse_grant(&user_id, &role)?;
 
     client
-        .add_new_user(principal, role_code)
+        .add_new_user(principal, role.code())
         .await
         .context("Failed to add new user to memory canister")
 }
@@ -239,6 +430,13 @@ async fn build_memory_client(
     Ok(MemoryClient::new(agent, memory))
 }
 
+fn resolve_identity_path(identity_path: Option<PathBuf>) -> Result<PathBuf> {
+    match identity_path {
+        Some(path) => Ok(path),
+        None => identity_store::default_identity_path(),
+    }
+}
+
 fn resolve_insert_content(text: Option<String>, file_path: Option<PathBuf>) -> Result<String> {
     if let Some(text) = text {
         return Ok(text);
@@ -249,34 +447,9 @@ fn resolve_insert_content(text: Option<String>, file_path: Option<PathBuf>) -> R
             .with_context(|| format!("Failed to read file path {}", path.display()));
     }
 
-    bail!("either text or file_path must be provided");
-}
-
-fn parse_role(role: &str) -> Result<u8> {
-    match role.to_lowercase().as_str() {
-        "admin" => Ok(1),
-        "writer" => Ok(2),
-        "reader" => Ok(3),
-        _ => bail!("role must be one of: admin, writer, reader"),
-    }
-}
-
-fn parse_principal(user_id: &str, role_code: u8) -> Result<Principal> {
-    if user_id == "anonymous" {
-        if role_code == 1 {
-            bail!("cannot grant admin role to anonymous");
-        }
-        Ok(Principal::anonymous())
-    } else {
-        Principal::from_text(user_id).with_context(|| format!("invalid principal text: {user_id}"))
-    }
+    Err(invalid("either text or file_path must be provided"))
 }
 
-fn state_principal(state: &State) -> Option<&Principal> {
-    match state {
-        State::Installation(principal, _)
-        | State::SettingUp(principal)
-        | State::Running(principal) => Some(principal),
-        _ => None,
-    }
+fn invalid(message: &str) -> anyhow::Error {
+    KinicError::Validation(message.to_string()).into()
 }
@@ -0,0 +1,379 @@
+//! Retrieval-augmented prompt for `ask-ai`: searches a memory and packs the
+//! best chunks into an LLM prompt within a token budget. Makes no LLM call,
+//! so it also builds without the `native` feature.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use ic_agent::{Agent, export::Principal};
+
+use crate::{
+    clients::memory::MemoryClient,
+    embedding::fetch_embedding,
+    payload::{ChunkMetadata, ChunkPayload},
+    tokens,
+};
+
+pub(crate) const MAX_QUERY_LEN: usize = 150;
+const MAX_HITS_PER_DOC: usize = 6;
+/// Prompt budget when none is given, in `cl100k_base` tokens.
+pub const DEFAULT_CONTEXT_TOKENS: usize = 6000;
+/// A chunk cut shorter than this to fit the budget is left out instead.
+const MIN_PARTIAL_TOKENS: usize = 50;
+
+/// Retrieved context and the prompt built from it, before the LLM call.
+pub struct PreparedPrompt {
+    pub prompt: String,
+    /// Every search result, best first; the prompt uses the first
+    /// `top_k_used`.
+    pub results: Vec<(f32, String)>,
+    pub top_k_used: usize,
+    /// Tokens of chunk text packed for each of those results; the last one
+    /// may have been cut to fit the budget.
+    pub chunk_tokens: Vec<usize>,
+    /// The `<doc>` elements of the prompt, for checking the answer against.
+    pub context: String,
+}
+
+/// Searches the memory and builds the LLM prompt, without calling the LLM.
+/// The query and each of `sub_queries` are searched for `top_k` results;
+/// the merged results are packed until the prompt reaches `context_tokens`.
+pub async fn prepare_prompt(
+    agent: Agent,
+    memory_id: &Principal,
+    query: &str,
+    sub_queries: &[String],
+    top_k: usize,
+    language: &str,
+    context_tokens: usize,
+) -> Result<PreparedPrompt> {
+    let client = MemoryClient::new(agent, *memory_id);
+
+    let limit = top_k.max(1);
+    let mut result_lists = Vec::with_capacity(1 + sub_queries.len());
+    for text in std::iter::once(query).chain(sub_queries.iter().map(String::as_str)) {
+        let embedding = fetch_embedding(text).await?;
+        let results: Vec<(f32, String)> = client
+            .search_top_k(embedding, limit)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        result_lists.push(results);
+    }
+    let results = merge_results(result_lists);
+
+    let summaries = document_summaries(&client, &results, results.len()).await?;
+    let (prompt, docs) = build_prompt(
+        query,
+        &results,
+        &summaries,
+        results.len(),
+        language,
+        context_tokens,
+    );
+    let chunk_tokens = docs
+        .iter()
+        .map(|doc| doc.hits.iter().map(|hit| tokens::count(&hit.content)).sum())
+        .collect();
+
+    Ok(PreparedPrompt {
+        prompt,
+        context: format_docs(&docs),
+        top_k_used: docs.len(),
+        chunk_tokens,
+        results,
+    })
+}
+
+/// Interleaves result lists rank by rank, so every query keeps its best
+/// chunks near the front when the prompt budget runs out, and drops chunks
+/// found by more than one query after their first appearance.
+fn merge_results(lists: Vec<Vec<(f32, String)>>) -> Vec<(f32, String)> {
+    if lists.len() == 1 {
+        return lists.into_iter().flatten().collect();
+    }
+    let longest = lists.iter().map(Vec::len).max().unwrap_or(0);
+    let mut merged: Vec<(f32, String)> = Vec::new();
+    for rank in 0..longest {
+        for list in &lists {
+            let Some((score, text)) = list.get(rank) else {
+                continue;
+            };
+            if !merged.iter().any(|(_, known)| known == text) {
+                merged.push((*score, text.clone()));
+            }
+        }
+    }
+    merged
+}
+
+/// Summaries stored by `insert --summarize` for the documents among the
+/// prompt's results, keyed by tag; they become the document titles.
+async fn document_summaries(
+    client: &MemoryClient,
+    results: &[(f32, String)],
+    top_k: usize,
+) -> Result<HashMap<String, String>> {
+    let mut summaries = HashMap::new();
+    for (_, text) in results.iter().take(top_k) {
+        let Some(chunk) = ChunkPayload::parse(text) else {
+            continue;
+        };
+        if chunk.is_summary() || summaries.contains_key(&chunk.tag) {
+            continue;
+        }
+        if let Some(summary) = client.document_summary(&chunk.tag).await? {
+            summaries.insert(chunk.tag, summary);
+        }
+    }
+    Ok(summaries)
+}
+
+#[derive(Clone, Debug)]
+struct SearchHit {
+    index: usize,
+    score: f32,
+    content: String,
+}
+
+#[derive(Clone, Debug)]
+struct SearchResult {
+    url: String,
+    title: String,
+    score: f32,
+    hits: Vec<SearchHit>,
+}
+
+/// Builds the prompt from the best results that fit in `budget` tokens,
+/// cutting the first one that does not fit. Returns the prompt and the
+/// packed results.
+fn build_prompt(
+    query: &str,
+    raw_results: &[(f32, String)],
+    summaries: &HashMap<String, String>,
+    top_k: usize,
+    language: &str,
+    budget: usize,
+) -> (String, Vec<SearchResult>) {
+    let clipped_query = clip(query, MAX_QUERY_LEN);
+
+    let candidates: Vec<SearchResult> = raw_results
+        .iter()
+        .take(top_k)
+        .enumerate()
+        .map(|(i, (score, text))| {
+            let chunk = ChunkPayload::parse(text);
+            let content = chunk
+                .as_ref()
+                .map_or_else(|| text.clone(), |chunk| chunk.sentence.clone());
+            let url = chunk
+                .as_ref()
+                .and_then(|chunk| source_url(&chunk.metadata))
+                .unwrap_or_else(|| format!("memory://{}", i + 1));
+            let title = chunk
+                .as_ref()
+                .and_then(|chunk| summaries.get(&chunk.tag))
+                .unwrap_or(&content);
+            SearchResult {
+                url,
+                title: clip(title, 80),
+                score: *score,
+                hits: vec![SearchHit {
+                    index: chunk
+                        .as_ref()
+                        .and_then(|chunk| chunk.metadata.chunk_index)
+                        .unwrap_or(0),
+                    score: *score,
+                    content: strip_tags(&content),
+                }],
+            }
+        })
+        .collect();
+
+    let mut docs: Vec<SearchResult> = Vec::new();
+    let mut prompt = ask_ai_prompt(&clipped_query, &docs, language);
+    for candidate in candidates {
+        docs.push(candidate);
+        let with_doc = ask_ai_prompt(&clipped_query, &docs, language);
+        if tokens::count(&with_doc) <= budget {
+            prompt = with_doc;
+            continue;
+        }
+        // The first chunk that does not fit whole is cut; packing stops there.
+        let last = docs.pop();
+        if let Some(partial) =
+            last.and_then(|doc| pack_partial(&clipped_query, &mut docs, doc, language, budget))
+        {
+            prompt = partial;
+        }
+        break;
+    }
+    (prompt, docs)
+}
+
+/// Adds `doc` with its hit text cut to the longest prefix for which the
+/// prompt stays within `budget`, found by bisection. Returns the prompt, or
+/// `None` (leaving `docs` unchanged) when less than `MIN_PARTIAL_TOKENS`
+/// would fit.
+fn pack_partial(
+    query: &str,
+    docs: &mut Vec<SearchResult>,
+    mut doc: SearchResult,
+    language: &str,
+    budget: usize,
+) -> Option<String> {
+    let content = doc.hits.first()?.content.clone();
+    let mut fitting: Option<(usize, String)> = None;
+    let mut low = MIN_PARTIAL_TOKENS;
+    let mut high = tokens::count(&content).saturating_sub(1);
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        doc.hits[0].content = tokens::truncate(&content, mid);
+        docs.push(doc.clone());
+        let prompt = ask_ai_prompt(query, docs, language);
+        docs.pop();
+        if tokens::count(&prompt) <= budget {
+            fitting = Some((mid, prompt));
+            low = mid + 1;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    let (length, prompt) = fitting?;
+    doc.hits[0].content = tokens::truncate(&content, length);
+    docs.push(doc);
+    Some(prompt)
+}
+
+pub(crate) fn source_url(metadata: &ChunkMetadata) -> Option<String> {
+    let source = metadata.source.as_ref()?;
+    Some(match metadata.page {
+        Some(page) => format!("{source}#page={page}"),
+        None => source.clone(),
+    })
+}
+
+pub(crate) fn clip(s: &str, max: usize) -> String {
+    let clipped: String = s.chars().take(max).collect();
+    if s.chars().count() > max {
+        format!("{clipped}...")
+    } else {
+        clipped
+    }
+}
+
+pub(crate) fn strip_tags(s: &str) -> String {
+    s.replace("<thinking>", "")
+        .replace("</thinking>", "")
+        .replace("<answer>", "")
+        .replace("</answer>", "")
+        .replace("<THINKING>", "")
+        .replace("</THINKING>", "")
+        .replace("<ANSWER>", "")
+        .replace("</ANSWER>", "")
+}
+
+fn get_language_instruction(lang_code: &str) -> &'static str {
+    match lang_code {
+        "ja" => "日本語 (Japanese)",
+        "ko" => "한국어 (Korean)",
+        "zh" => "中文 (Chinese)",
+        "es" => "Español (Spanish)",
+        "fr" => "Français (French)",
+        "de" => "Deutsch (German)",
+        "it" => "Italiano (Italian)",
+        "pt" => "Português (Portuguese)",
+        "ru" => "Русский (Russian)",
+        _ => "English",
+    }
+}
+
+/// The `<doc>` elements listing `results` in a prompt.
+fn format_docs(results: &[SearchResult]) -> String {
+    let formatted_docs = results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let hits_xml = r
+                .hits
+                .iter()
+                .take(MAX_HITS_PER_DOC)
+                .map(|h| {
+                    format!(
+                        "<hit index=\"{}\" score=\"{}\">\n{}\n</hit>",
+                        h.index,
+                        h.score,
+                        h.content
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!(
+                "<doc index=\"{index}\">\n<url>{url}</url>\n<title>{title}</title>\n<score>{score}</score>\n<hits>\n{hits}\n</hits>\n</doc>",
+                index = i + 1,
+                url = r.url,
+                title = strip_tags(&r.title),
+                score = r.score,
+                hits = if hits_xml.is_empty() { r#"<hit index="0">(no hits)</hit>"#.to_string() } else { hits_xml },
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if formatted_docs.is_empty() {
+        r#"<doc index="1"><url></url><title></title><hits><hit index="0">(no hits)</hit></hits></doc>"#
+            .to_string()
+    } else {
+        formatted_docs
+    }
+}
+
+fn ask_ai_prompt(query: &str, results: &[SearchResult], language: &str) -> String {
+    let language_instruction = get_language_instruction(language);
+
+    let top_results = results.iter().collect::<Vec<_>>();
+
+    let docs_block = format_docs(results);
+
+    let full_document = top_results
+        .iter()
+        .flat_map(|r| r.hits.iter().take(MAX_HITS_PER_DOC))
+        .map(|h| h.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"You are an excellent AI assistant that summarizes the content of documents found as search results.
+Summarize the main points concisely, taking into account their relevance to the user's search query.
+
+# Instructions
+- Before responding, please describe your thinking process within the <thinking>...</thinking> tag (keep under 100 words).
+- After thinking, write your final summary within the <answer>...</answer> tag.
+- The summary should be objective and grounded in the documents.
+- If the documents do not contain the information asked for, say that you don't know instead of guessing.
+- Focus on information related to <user_query>, especially considering the content in <docs>.
+- Limit the final summary to 140 words or less.
+- Answer in {language_instruction} in <answer> tag. << IMPORTANT!!
+
+# Input
+
+<user_query>
+{query}
+</user_query>
+
+<docs>
+{docs}
+</docs>
+
+<full_document>
+{full_document}
+</full_document>"#,
+        docs = docs_block,
+        full_document = full_document,
+        language_instruction = language_instruction,
+        query = query,
+    )
+}
@@ -0,0 +1,422 @@
+//! `--redact pii`: personal data is masked before a document is chunked and
+//! embedded, so it reaches neither the embedding API nor the memory. Email
+//! addresses, phone numbers and card numbers (13 to 19 digits passing the
+//! Luhn check) are found locally; `--redact-ner` also asks the chat endpoint
+//! for person names and postal addresses. What was masked, by kind and line
+//! but never the value, is written to a local JSON report.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    cli::RedactArgs, commands::ask_ai::find_personal_entities, identity_store::config_dir,
+    output::note,
+};
+
+/// Text sent to the chat endpoint per request with `--redact-ner`.
+const NER_PIECE_CHARS: usize = 8000;
+/// Entities shorter than this would mask ordinary words.
+const MIN_ENTITY_CHARS: usize = 3;
+
+/// Documents redacted in one run and what was masked in each.
+pub struct Redactor {
+    ner: bool,
+    report_path: Option<PathBuf>,
+    documents: Vec<DocumentReport>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    command: &'a str,
+    time: u64,
+    ner: bool,
+    documents: &'a [DocumentReport],
+}
+
+#[derive(Serialize)]
+struct DocumentReport {
+    source: String,
+    counts: BTreeMap<&'static str, usize>,
+    redactions: Vec<Redaction>,
+}
+
+#[derive(Serialize)]
+struct Redaction {
+    kind: &'static str,
+    line: usize,
+}
+
+impl Redactor {
+    /// The redactor `args` ask for, or `None` without `--redact`.
+    pub fn from_args(args: &RedactArgs) -> Option<Self> {
+        args.redact.map(|_| Self {
+            ner: args.redact_ner,
+            report_path: args.redact_report.clone(),
+            documents: Vec::new(),
+        })
+    }
+
+    /// `text` with its personal data replaced by placeholders such as
+    /// `[EMAIL]`, recorded in the report under `source`.
+    pub async fn redact(&mut self, source: &str, text: &str) -> Result<String> {
+        let (mut text, mut redactions) = mask_patterns(text);
+        if self.ner {
+            let mut entities = Vec::new();
+            for piece in pieces(&text, NER_PIECE_CHARS) {
+                entities.extend(
+                    find_personal_entities(piece).await.with_context(|| {
+                        format!("Failed to find names and addresses in {source}")
+                    })?,
+                );
+            }
+            // Longer entities first, so a name inside an address is not
+            // masked on its own.
+            entities.sort_by_key(|(_, entity)| std::cmp::Reverse(entity.chars().count()));
+            for (kind, entity) in entities {
+                if entity.chars().count() >= MIN_ENTITY_CHARS {
+                    redactions.extend(mask_entity(&mut text, kind, &entity));
+                }
+            }
+        }
+        self.record(source, redactions);
+        Ok(text)
+    }
+
+    /// A metadata value (a sender, a frontmatter field) masked with the local
+    /// patterns only.
+    pub fn redact_field(&mut self, source: &str, value: &str) -> String {
+        let (value, redactions) = mask_patterns(value);
+        self.record(source, redactions);
+        value
+    }
+
+    fn record(&mut self, source: &str, redactions: Vec<Redaction>) {
+        if redactions.is_empty() {
+            return;
+        }
+        let document = match self
+            .documents
+            .iter_mut()
+            .position(|doc| doc.source == source)
+        {
+            Some(index) => &mut self.documents[index],
+            None => {
+                self.documents.push(DocumentReport {
+                    source: source.to_string(),
+                    counts: BTreeMap::new(),
+                    redactions: Vec::new(),
+                });
+                self.documents.last_mut().expect("just pushed")
+            }
+        };
+        for redaction in redactions {
+            *document.counts.entry(redaction.kind).or_default() += 1;
+            document.redactions.push(redaction);
+        }
+    }
+
+    /// Writes the report to `--redact-report` or
+    /// `~/.config/kinic/redactions/<unix time>-<command>.json` and says
+    /// where.
+    pub fn write_report(&self, command: &str) -> Result<PathBuf> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+        let path = match &self.report_path {
+            Some(path) => path.clone(),
+            None => config_dir()?
+                .join("redactions")
+                .join(format!("{time}-{command}.json")),
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let report = Report {
+            command,
+            time,
+            ner: self.ner,
+            documents: &self.documents,
+        };
+        fs::write(&path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        let masked: usize = self.documents.iter().map(|doc| doc.redactions.len()).sum();
+        note!(
+            "Redacted {masked} values; report written to {}",
+            path.display()
+        );
+        Ok(path)
+    }
+}
+
+/// `text` with emails, card numbers and phone numbers masked.
+fn mask_patterns(text: &str) -> (String, Vec<Redaction>) {
+    let mut spans = email_spans(text);
+    spans.extend(number_spans(text));
+    spans.sort_by_key(|&(start, _, _)| start);
+
+    let mut masked = String::with_capacity(text.len());
+    let mut redactions = Vec::new();
+    let mut cursor = 0;
+    for (start, end, kind) in spans {
+        if start < cursor {
+            continue;
+        }
+        masked.push_str(&text[cursor..start]);
+        masked.push_str(placeholder(kind));
+        redactions.push(Redaction {
+            kind,
+            line: line_of(text, start),
+        });
+        cursor = end;
+    }
+    masked.push_str(&text[cursor..]);
+    (masked, redactions)
+}
+
+fn placeholder(kind: &str) -> &'static str {
+    match kind {
+        "email" => "[EMAIL]",
+        "phone" => "[PHONE]",
+        "card" => "[CARD]",
+        "name" => "[NAME]",
+        _ => "[ADDRESS]",
+    }
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// `local@domain.tld` addresses, as byte ranges.
+fn email_spans(text: &str) -> Vec<(usize, usize, &'static str)> {
+    let bytes = text.as_bytes();
+    let local = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'%' | b'+' | b'-');
+    let domain = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-');
+    let mut spans = Vec::new();
+    for (at, _) in text.match_indices('@') {
+        let mut start = at;
+        while start > 0 && local(bytes[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at + 1;
+        while end < bytes.len() && domain(bytes[end]) {
+            end += 1;
+        }
+        // A sentence may end right after the address.
+        while end > at + 1 && matches!(bytes[end - 1], b'.' | b'-') {
+            end -= 1;
+        }
+        let host = &text[at + 1..end];
+        let tld = host.rsplit('.').next().unwrap_or_default();
+        let valid = start < at
+            && host.contains('.')
+            && tld.len() >= 2
+            && tld.bytes().all(|b| b.is_ascii_alphabetic());
+        if valid {
+            spans.push((start, end, "email"));
+        }
+    }
+    spans
+}
+
+/// Card numbers and phone numbers: runs of digits with single spaces,
+/// dashes or parentheses between them, optionally after a `+`. Thirteen to
+/// nineteen digits passing the Luhn check are a card. Nine to fifteen digits
+/// are a phone number in one of the formats of [`looks_like_phone`].
+fn number_spans(text: &str) -> Vec<(usize, usize, &'static str)> {
+    let bytes = text.as_bytes();
+    let mut spans = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let b = bytes[index];
+        let starts = (b.is_ascii_digit() || b == b'+' || b == b'(')
+            && (index == 0 || !bytes[index - 1].is_ascii_alphanumeric());
+        if !starts {
+            index += 1;
+            continue;
+        }
+        let mut end = index + 1;
+        while end < bytes.len() {
+            let next = bytes[end];
+            let separator =
+                matches!(next, b'-' | b'(' | b')') || (next == b' ' && bytes[end - 1] != b' ');
+            if !(next.is_ascii_digit() || separator) {
+                break;
+            }
+            end += 1;
+        }
+        while end > index && !bytes[end - 1].is_ascii_digit() {
+            end -= 1;
+        }
+        if end < bytes.len() && bytes[end].is_ascii_alphanumeric() {
+            index = end.max(index + 1);
+            continue;
+        }
+        let run = &text[index..end];
+        let digits: Vec<u32> = run.chars().filter_map(|c| c.to_digit(10)).collect();
+        if (13..=19).contains(&digits.len()) && !run.contains(['(', ')', '+']) && luhn(&digits) {
+            spans.push((index, end, "card"));
+        } else if (9..=15).contains(&digits.len()) && looks_like_phone(run) {
+            spans.push((index, end, "phone"));
+        }
+        index = end.max(index + 1);
+    }
+    spans
+}
+
+/// Whether a run of digits is written like a phone number: international
+/// (`+44 20 7946 0958`), with an area code in parentheses
+/// (`(555) 123-4567`), North American (`415-555-0132`), or national with a
+/// leading trunk `0` (`090-1234-5678`, `020 7946 0958`). Grouped numbers
+/// such as `1 000 000 000` or `123 456 789` are not.
+fn looks_like_phone(run: &str) -> bool {
+    let groups: Vec<&str> = run
+        .split([' ', '-', '(', ')', '+'])
+        .filter(|group| !group.is_empty())
+        .collect();
+    let area_code = matches!((run.find('('), run.find(')')), (Some(open), Some(close))
+        if close > open + 1 && run[open + 1..close].bytes().all(|b| b.is_ascii_digit()));
+    let leading = |group: &str| group.bytes().next().is_some_and(|b| b >= b'2');
+    let north_american = matches!(groups.as_slice(), [area, exchange, line]
+        if area.len() == 3 && exchange.len() == 3 && line.len() == 4
+            && leading(area) && leading(exchange));
+    let national = groups.len() > 1
+        && groups[0].starts_with('0')
+        && groups.iter().all(|group| group.len() >= 2);
+    run.starts_with('+') || area_code || north_american || national
+}
+
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(position, &digit)| match position % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Replaces every occurrence of `entity` in `text`.
+fn mask_entity(text: &mut String, kind: &'static str, entity: &str) -> Vec<Redaction> {
+    let lines: Vec<usize> = text
+        .match_indices(entity)
+        .map(|(start, _)| line_of(text, start))
+        .collect();
+    if !lines.is_empty() {
+        *text = text.replace(entity, placeholder(kind));
+    }
+    lines
+        .into_iter()
+        .map(|line| Redaction { kind, line })
+        .collect()
+}
+
+/// `text` cut at line breaks into pieces of about `max_chars`.
+fn pieces(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+    for (offset, c) in text.char_indices() {
+        chars += 1;
+        // A piece without line breaks is cut anywhere at twice the size.
+        if (c == '\n' && chars >= max_chars) || chars >= 2 * max_chars {
+            let end = offset + c.len_utf8();
+            pieces.push(&text[start..end]);
+            start = end;
+            chars = 0;
+        }
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn masked(text: &str) -> String {
+        mask_patterns(text).0
+    }
+
+    #[test]
+    fn emails() {
+        assert_eq!(
+            masked("write to jane.doe+kinic@example.co.uk."),
+            "write to [EMAIL]."
+        );
+        assert_eq!(
+            masked("a@b.c, user@localhost, @handle"),
+            "a@b.c, user@localhost, @handle"
+        );
+    }
+
+    #[test]
+    fn cards_pass_the_luhn_check() {
+        assert_eq!(masked("card 4111 1111 1111 1111 ok"), "card [CARD] ok");
+        assert_eq!(masked("card 4111-1111-1111-1111"), "card [CARD]");
+        assert_eq!(masked("id 4111111111111112"), "id 4111111111111112");
+    }
+
+    #[test]
+    fn phone_numbers() {
+        for phone in [
+            "+44 20 7946 0958",
+            "+81-90-1234-5678",
+            "(555) 123-4567",
+            "415-555-0132",
+            "415 555 0132",
+            "090-1234-5678",
+            "020 7946 0958",
+            "01 23 45 67 89",
+        ] {
+            assert_eq!(
+                masked(&format!("call {phone} now")),
+                "call [PHONE] now",
+                "{phone}"
+            );
+        }
+    }
+
+    #[test]
+    fn dates_are_not_phone_numbers() {
+        for date in ["2024-01-15", "2024-01-15 10 30", "15-01-2024", "2024 01 15"] {
+            assert_eq!(masked(date), date);
+        }
+    }
+
+    #[test]
+    fn plain_numbers_are_not_phone_numbers() {
+        for text in [
+            "1 000 000 000",
+            "order 123 456 789",
+            "order 123-456-7890",
+            "version 10.0.19045.3693",
+            "v1.2.3-beta 4",
+            "123456789012",
+            "ISBN 978-3-16-148410-0",
+        ] {
+            assert_eq!(masked(text), text);
+        }
+    }
+
+    #[test]
+    fn redactions_record_kind_and_line() {
+        let (_, redactions) = mask_patterns("a@example.com\n\n+1 555 123 4567");
+        let found: Vec<_> = redactions.iter().map(|r| (r.kind, r.line)).collect();
+        assert_eq!(found, [("email", 1), ("phone", 3)]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Incremental reader for `text/event-stream` responses (server-sent
+//! events), used to show chat answers while they are generated.
+//!
+//! Lines may end in LF or CRLF. `id` and `retry` fields are ignored since
+//! streams are never resumed.
+
+use anyhow::{Context, Result};
+use reqwest::Response;
+
+pub struct Event {
+    /// The `event:` field; `None` for the default `message` type.
+    pub event: Option<String>,
+    /// `data:` lines joined with `\n`.
+    pub data: String,
+}
+
+pub struct EventStream {
+    response: Response,
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    finished: bool,
+}
+
+impl EventStream {
+    pub fn new(response: Response) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+            event: None,
+            data: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// The next complete event, or `None` once the response has ended.
+    pub async fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(event) = self.process_line(line.trim_end_matches(['\n', '\r'])) {
+                    return Ok(Some(event));
+                }
+            }
+            if self.finished {
+                // Servers often close without the final blank line; dispatch
+                // what was received rather than dropping it.
+                if !self.buffer.is_empty() {
+                    let line =
+                        String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+                    self.process_line(line.trim_end_matches('\r'));
+                }
+                return Ok(self.dispatch());
+            }
+            match self
+                .response
+                .chunk()
+                .await
+                .context("Failed to read event stream")?
+            {
+                Some(bytes) => self.buffer.extend_from_slice(&bytes),
+                None => self.finished = true,
+            }
+        }
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(Event {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+        })
+    }
+}
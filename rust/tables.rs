@@ -0,0 +1,297 @@
+//! Tables in ingested documents. Late chunking splits text into sentences,
+//! which turns a table into a run of cells with no column names, so tables
+//! are taken out of the text and stored as chunks of a few rows each, every
+//! row spelled out with its headers:
+//!
+//! ```text
+//! Region: EU; Q1: 10.2; Q2: 12.9
+//! Region: US; Q1: 8.1; Q2: 9.4
+//! ```
+//!
+//! Tables are found as markdown pipe tables. HTML tables and the aligned
+//! columns of `pdftotext -layout` output are converted to that form when the
+//! document is converted to text.
+
+/// Rows stored in one chunk at most.
+const ROWS_PER_CHUNK: usize = 8;
+/// A chunk stops taking rows once it is this long.
+const CHUNK_BYTES: usize = 1_000;
+/// Lines that make a table in layout text, header included. Two body rows
+/// too often line up by chance in justified prose.
+const MIN_LAYOUT_ROWS: usize = 4;
+/// Fewer columns than this is more likely two-column prose than a table.
+const MIN_LAYOUT_COLUMNS: usize = 3;
+/// How far apart, in characters, the edges of a layout column may be.
+const ALIGNMENT_SLACK: usize = 2;
+
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// A run of text, or a table found between runs.
+pub enum Block {
+    Text(String),
+    Table(Table),
+}
+
+impl Table {
+    /// Rows are padded or cut to the number of headers. Empty headers are
+    /// fine: their cells are stored without a name.
+    pub fn new(headers: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        let columns = headers.len();
+        let rows = rows
+            .into_iter()
+            .map(|mut row| {
+                row.resize(columns, String::new());
+                row
+            })
+            .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+            .collect();
+        Self { headers, rows }
+    }
+
+    /// The rows grouped into chunks, each row on its own line as
+    /// `Header: value; ...`, skipping empty cells.
+    pub fn chunks(&self) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+        let mut rows = 0;
+        for row in &self.rows {
+            let line = self
+                .headers
+                .iter()
+                .zip(row)
+                .filter(|(_, cell)| !cell.is_empty())
+                .map(|(header, cell)| {
+                    if header.is_empty() {
+                        cell.clone()
+                    } else {
+                        format!("{header}: {cell}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            if rows == ROWS_PER_CHUNK || (rows > 0 && current.len() + 1 + line.len() > CHUNK_BYTES)
+            {
+                chunks.push(std::mem::take(&mut current));
+                rows = 0;
+            }
+            if rows > 0 {
+                current.push('\n');
+            }
+            current.push_str(&line);
+            rows += 1;
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
+    /// The table as a markdown pipe table.
+    pub fn to_markdown(&self) -> String {
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![
+            line(&self.headers),
+            format!("|{}", " --- |".repeat(self.headers.len())),
+        ];
+        lines.extend(self.rows.iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+}
+
+/// Splits markdown into runs of text and the pipe tables between them. Text
+/// without a table comes back whole as a single block.
+pub fn split(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut blocks = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let header = lines[index];
+        let columns = cells(header).len();
+        let starts_table = is_row(header)
+            && lines
+                .get(index + 1)
+                .is_some_and(|line| separator_columns(line) == Some(columns))
+            && lines.get(index + 2).is_some_and(|line| is_row(line));
+        if !starts_table {
+            prose.push(header);
+            index += 1;
+            continue;
+        }
+        let mut rows = Vec::new();
+        index += 2;
+        while let Some(line) = lines.get(index).filter(|line| is_row(line)) {
+            rows.push(cells(line));
+            index += 1;
+        }
+        push_text(&mut blocks, &mut prose);
+        blocks.push(Block::Table(Table::new(cells(header), rows)));
+    }
+    if blocks.is_empty() {
+        return vec![Block::Text(text.to_string())];
+    }
+    push_text(&mut blocks, &mut prose);
+    blocks
+}
+
+fn push_text(blocks: &mut Vec<Block>, prose: &mut Vec<&str>) {
+    let text = prose.join("\n");
+    if !text.trim().is_empty() {
+        blocks.push(Block::Text(text));
+    }
+    prose.clear();
+}
+
+fn is_row(line: &str) -> bool {
+    line.contains('|') && !line.trim().is_empty()
+}
+
+/// The number of columns of a `|---|:--:|` line (outer pipes optional), or
+/// `None` when `line` is not one.
+fn separator_columns(line: &str) -> Option<usize> {
+    let trimmed = line.trim();
+    let inner = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    let is_rule = |cell: &str| {
+        let cell = cell.trim();
+        let dashes = cell.strip_prefix(':').unwrap_or(cell);
+        let dashes = dashes.strip_suffix(':').unwrap_or(dashes);
+        !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+    };
+    (!inner.is_empty() && inner.split('|').all(is_rule)).then(|| inner.split('|').count())
+}
+
+/// The cells of a pipe table row; `\|` is a literal pipe.
+fn cells(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let inner = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let inner = match inner.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => inner,
+    };
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// Rewrites the tables of `pdftotext -layout` output as pipe tables: runs of
+/// at least `MIN_LAYOUT_ROWS` lines that split at gaps of two or more spaces
+/// into the same number of columns, whose edges line up below the first
+/// line. The first line of a run becomes the header.
+pub fn layout_to_markdown(text: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let end = layout_table_end(&lines, index);
+        if end - index < MIN_LAYOUT_ROWS {
+            out.push(lines[index].to_string());
+            index += 1;
+            continue;
+        }
+        let mut rows = lines[index..end].iter().map(|line| {
+            layout_cells(line)
+                .into_iter()
+                .map(|(_, cell)| cell.to_string())
+                .collect()
+        });
+        let headers = rows.next().unwrap_or_default();
+        let table = Table::new(headers, rows.collect());
+        if out.last().is_some_and(|line| !line.trim().is_empty()) {
+            out.push(String::new());
+        }
+        out.push(table.to_markdown());
+        if lines.get(end).is_some_and(|line| !line.trim().is_empty()) {
+            out.push(String::new());
+        }
+        index = end;
+    }
+    out.join("\n")
+}
+
+/// The end of the layout table starting at `start`, or `start` when none
+/// does.
+fn layout_table_end(lines: &[&str], start: usize) -> usize {
+    let columns = layout_cells(lines[start]).len();
+    if columns < MIN_LAYOUT_COLUMNS {
+        return start;
+    }
+    let mut end = start + 1;
+    while end < lines.len() && layout_cells(lines[end]).len() == columns {
+        end += 1;
+    }
+    // Headers are often centred over their columns; only the body needs to
+    // line up.
+    while end - start >= MIN_LAYOUT_ROWS && !aligned(&lines[start + 1..end]) {
+        end -= 1;
+    }
+    end
+}
+
+/// Whether every column starts or ends at about the same place on each line.
+fn aligned(lines: &[&str]) -> bool {
+    let rows: Vec<Vec<(usize, &str)>> = lines.iter().map(|line| layout_cells(line)).collect();
+    let columns = rows.first().map_or(0, Vec::len);
+    (0..columns).all(|column| {
+        let starts = rows.iter().map(|row| row[column].0);
+        let ends = rows
+            .iter()
+            .map(|row| row[column].0 + row[column].1.chars().count());
+        spread(starts) <= ALIGNMENT_SLACK || spread(ends) <= ALIGNMENT_SLACK
+    })
+}
+
+fn spread(values: impl Iterator<Item = usize> + Clone) -> usize {
+    values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+}
+
+/// The cells of a layout line with their starting column in characters.
+/// Cells are separated by two or more spaces; a page break (form feed)
+/// never belongs to a table, so page numbers stay countable.
+fn layout_cells(line: &str) -> Vec<(usize, &str)> {
+    if line.contains('\u{c}') {
+        return Vec::new();
+    }
+    let mut cells = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let mut spaces = 0;
+    let mut last_end = 0;
+    for (column, (offset, c)) in line.char_indices().enumerate() {
+        if c == ' ' {
+            spaces += 1;
+            if spaces == 2
+                && let Some((at, from)) = start.take()
+            {
+                cells.push((at, &line[from..last_end]));
+            }
+            continue;
+        }
+        spaces = 0;
+        if start.is_none() {
+            start = Some((column, offset));
+        }
+        last_end = offset + c.len_utf8();
+    }
+    if let Some((at, from)) = start {
+        cells.push((at, &line[from..last_end]));
+    }
+    cells
+}
@@ -0,0 +1,47 @@
+//! Token counts for prompt budgets, using the `cl100k_base` BPE. The chat
+//! endpoint's own tokenizer may differ slightly, so budgets should leave
+//! some headroom below the model's real limit. Without the `native` feature
+//! the BPE is not built in and tokens are estimated at four characters each.
+
+#[cfg(feature = "native")]
+use tiktoken_rs::{CoreBPE, cl100k_base_singleton};
+
+/// Characters per token when no tokenizer is available.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[cfg(feature = "native")]
+fn bpe() -> &'static CoreBPE {
+    cl100k_base_singleton()
+}
+
+#[cfg(feature = "native")]
+pub fn count(text: &str) -> usize {
+    bpe().encode_ordinary(text).len()
+}
+
+#[cfg(not(feature = "native"))]
+pub fn count(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The first `max_tokens` tokens of `text`.
+#[cfg(feature = "native")]
+pub fn truncate(text: &str, max_tokens: usize) -> String {
+    match bpe().split_by_token_ordinary(text) {
+        Ok(pieces) => pieces.into_iter().take(max_tokens).collect(),
+        // Only on invalid UTF-8 between tokens; fall back to the estimate.
+        Err(_) => estimate_truncate(text, max_tokens),
+    }
+}
+
+/// The first `max_tokens` tokens of `text`.
+#[cfg(not(feature = "native"))]
+pub fn truncate(text: &str, max_tokens: usize) -> String {
+    estimate_truncate(text, max_tokens)
+}
+
+fn estimate_truncate(text: &str, max_tokens: usize) -> String {
+    text.chars()
+        .take(max_tokens.saturating_mul(CHARS_PER_TOKEN))
+        .collect()
+}
@@ -0,0 +1,199 @@
+//! What a command consumed: embedding and chat API requests with an
+//! estimate of the tokens sent, and canister calls with an estimate of the
+//! cycles they burned. Counted in memory for the current process; each run
+//! is appended to `~/.config/kinic/usage.jsonl`, which `usage` sums up.
+//!
+//! Estimates only: tokens are counted over the request bodies with the
+//! `cl100k_base` BPE, and cycles use the ingress and execution base fees of
+//! a 13-node application subnet, ignoring instructions and storage.
+
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::PathBuf,
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{identity_store::config_dir, tokens};
+
+/// Cycles charged per update call for receiving the ingress message and
+/// starting execution.
+const UPDATE_BASE_CYCLES: u64 = 1_200_000 + 5_000_000;
+/// Cycles charged per byte of an ingress message.
+const UPDATE_BYTE_CYCLES: u64 = 2_000;
+/// Chat requests go to this path; every other API path embeds.
+const CHAT_PATH_SUFFIX: &str = "/chat";
+
+static EMBEDDING_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static CHAT_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static API_TOKENS: AtomicU64 = AtomicU64::new(0);
+static UPDATE_CALLS: AtomicU64 = AtomicU64::new(0);
+static UPDATE_BYTES: AtomicU64 = AtomicU64::new(0);
+static QUERY_CALLS: AtomicU64 = AtomicU64::new(0);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Marks the start of the run, from which [`RunUsage::duration_ms`] counts.
+pub(crate) fn start() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Records an embedding/chat API request; `endpoint` is its URL path.
+pub(crate) fn record_api_request(endpoint: &str, body: Option<&[u8]>) {
+    let counter = if endpoint.ends_with(CHAT_PATH_SUFFIX) {
+        &CHAT_REQUESTS
+    } else {
+        &EMBEDDING_REQUESTS
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    if let Some(body) = body {
+        let tokens = tokens::count(&String::from_utf8_lossy(body));
+        API_TOKENS.fetch_add(tokens as u64, Ordering::Relaxed);
+    }
+}
+
+/// Records a canister call with `arg_bytes` of encoded arguments.
+pub(crate) fn record_canister_call(query: bool, arg_bytes: usize) {
+    if query {
+        QUERY_CALLS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        UPDATE_CALLS.fetch_add(1, Ordering::Relaxed);
+        UPDATE_BYTES.fetch_add(arg_bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// One command run, as stored in the usage ledger.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RunUsage {
+    /// Unix seconds when the run finished.
+    pub time: u64,
+    pub command: String,
+    pub ok: bool,
+    pub embedding_requests: u64,
+    pub chat_requests: u64,
+    pub api_tokens: u64,
+    pub update_calls: u64,
+    pub update_bytes: u64,
+    pub query_calls: u64,
+    pub cycles: u64,
+    /// Wall-clock time of the run; 0 in runs recorded before it was tracked.
+    #[serde(default)]
+    pub duration_ms: u64,
+}
+
+impl RunUsage {
+    /// Totals of this process so far.
+    pub fn current(command: &str, ok: bool) -> Self {
+        let update_calls = UPDATE_CALLS.load(Ordering::Relaxed);
+        let update_bytes = UPDATE_BYTES.load(Ordering::Relaxed);
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs()),
+            command: command.to_string(),
+            ok,
+            embedding_requests: EMBEDDING_REQUESTS.load(Ordering::Relaxed),
+            chat_requests: CHAT_REQUESTS.load(Ordering::Relaxed),
+            api_tokens: API_TOKENS.load(Ordering::Relaxed),
+            update_calls,
+            update_bytes,
+            query_calls: QUERY_CALLS.load(Ordering::Relaxed),
+            cycles: update_cycles(update_calls, update_bytes),
+            duration_ms: STARTED
+                .get()
+                .map_or(0, |started| started.elapsed().as_millis() as u64),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embedding_requests + self.chat_requests + self.update_calls + self.query_calls == 0
+    }
+
+    /// Adds the counts of `other`; time and command are kept.
+    pub fn add(&mut self, other: &RunUsage) {
+        self.embedding_requests += other.embedding_requests;
+        self.chat_requests += other.chat_requests;
+        self.api_tokens += other.api_tokens;
+        self.update_calls += other.update_calls;
+        self.update_bytes += other.update_bytes;
+        self.query_calls += other.query_calls;
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.duration_ms += other.duration_ms;
+    }
+}
+
+impl fmt::Display for RunUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} embedding and {} chat requests (~{} tokens), {} update and {} query calls (~{} cycles)",
+            self.embedding_requests,
+            self.chat_requests,
+            self.api_tokens,
+            self.update_calls,
+            self.query_calls,
+            format_cycles(self.cycles)
+        )
+    }
+}
+
+/// Estimated cycles of `calls` update calls sending `bytes` in total.
+pub fn update_cycles(calls: u64, bytes: u64) -> u64 {
+    calls
+        .saturating_mul(UPDATE_BASE_CYCLES)
+        .saturating_add(bytes.saturating_mul(UPDATE_BYTE_CYCLES))
+}
+
+/// `1.25B`-style cycle counts.
+pub fn format_cycles(cycles: u64) -> String {
+    let cycles = cycles as f64;
+    if cycles >= 1e12 {
+        format!("{:.2}T", cycles / 1e12)
+    } else if cycles >= 1e9 {
+        format!("{:.2}B", cycles / 1e9)
+    } else if cycles >= 1e6 {
+        format!("{:.1}M", cycles / 1e6)
+    } else {
+        format!("{cycles}")
+    }
+}
+
+fn ledger_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("usage.jsonl"))
+}
+
+/// Appends `run` to the usage ledger.
+pub fn append(run: &RunUsage) -> Result<()> {
+    let path = ledger_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(run)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Every recorded run, oldest first. Lines that do not parse are skipped.
+pub fn load() -> Result<Vec<RunUsage>> {
+    let path = ledger_path()?;
+    match fs::read_to_string(&path) {
+        Ok(raw) => Ok(raw
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
@@ -0,0 +1,181 @@
+//! Post-processing applied to every vector before it is stored or searched
+//! with. A memory's choice is recorded in its launcher metadata, and
+//! [`crate::clients::memory::MemoryClient`] applies it on insert and search,
+//! so vectors from any command agree. Also the compact encodings used to send
+//! vectors to canisters that accept them.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Smallest positive normal `f16`, 2^-14.
+const F16_MIN_NORMAL: f32 = 6.103_515_6e-5;
+/// Spacing of `f16` subnormals, 2^-24.
+const F16_SUBNORMAL_STEP: f32 = 5.960_464_5e-8;
+/// Values this large round to infinity in `f16`.
+const F16_OVERFLOW: f32 = 65_520.0;
+/// Slack for the unit-length check; `f16` rounding moves the norm slightly.
+const NORM_TOLERANCE: f32 = 1e-2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VectorProcessing {
+    /// Keep only the first this many dimensions (Matryoshka embeddings).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<usize>,
+    /// Scale to unit length, after truncating.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub normalize: bool,
+    /// Round every component to the nearest `f16` value. The canister still
+    /// stores `f32`, so this trades precision for vectors that survive a
+    /// later `f16` export unchanged.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub f16: bool,
+}
+
+impl VectorProcessing {
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Truncates, normalizes and quantizes, in that order. Every step is
+    /// idempotent, so applying it to an already processed vector is a no-op.
+    pub fn apply(&self, mut vector: Vec<f32>) -> Vec<f32> {
+        if let Some(dim) = self.truncate {
+            vector.truncate(dim);
+        }
+        if self.normalize {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|x| *x /= norm);
+            }
+        }
+        if self.f16 {
+            vector.iter_mut().for_each(|x| *x = round_to_f16(*x));
+        }
+        vector
+    }
+
+    /// Whether a stored vector looks like the output of [`Self::apply`]; a
+    /// mismatch means some client inserted it without this processing.
+    pub fn conforms(&self, vector: &[f32]) -> bool {
+        if self.truncate.is_some_and(|dim| vector.len() != dim) {
+            return false;
+        }
+        if self.normalize {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 && (norm - 1.0).abs() > NORM_TOLERANCE {
+                return false;
+            }
+        }
+        !self.f16 || vector.iter().all(|x| round_to_f16(*x) == *x)
+    }
+}
+
+impl fmt::Display for VectorProcessing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut steps = Vec::new();
+        if let Some(dim) = self.truncate {
+            steps.push(format!("truncate to {dim}"));
+        }
+        if self.normalize {
+            steps.push("L2 normalize".to_string());
+        }
+        if self.f16 {
+            steps.push("f16".to_string());
+        }
+        if steps.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&steps.join(", "))
+        }
+    }
+}
+
+/// How vectors are encoded in insert calls. Only `F32` works with every
+/// memory canister; the others need the canister to advertise the matching
+/// method in `capabilities`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VectorTransport {
+    /// `F16` when the canister supports it, otherwise `F32`.
+    #[default]
+    Auto,
+    F32,
+    /// Half the size of `F32`; rounding changes scores by about 1e-3.
+    F16,
+    /// A quarter of the size, with one scale factor per vector. Lossier, so
+    /// only used when asked for.
+    Int8,
+}
+
+impl VectorTransport {
+    /// Parses `auto`, `f32`, `f16` or `int8`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "f32" => Some(Self::F32),
+            "f16" => Some(Self::F16),
+            "int8" | "i8" => Some(Self::Int8),
+            _ => None,
+        }
+    }
+}
+
+/// IEEE 754 half-precision bits of every component, rounded to nearest.
+pub fn to_f16_bits(vector: &[f32]) -> Vec<u16> {
+    vector.iter().map(|x| f16_bits(*x)).collect()
+}
+
+/// Symmetric int8 quantization: `vector[i] ≈ quantized[i] * scale`.
+pub fn quantize_i8(vector: &[f32]) -> (Vec<i8>, f32) {
+    let max = vector.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+    if max == 0.0 || !max.is_finite() {
+        return (vec![0; vector.len()], 0.0);
+    }
+    let scale = max / f32::from(i8::MAX);
+    let quantized = vector
+        .iter()
+        .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+        .collect();
+    (quantized, scale)
+}
+
+fn f16_bits(x: f32) -> u16 {
+    let sign = ((x.to_bits() >> 16) & 0x8000) as u16;
+    if x.is_nan() {
+        return sign | 0x7e00;
+    }
+    let abs = round_to_f16(x).abs();
+    if abs.is_infinite() {
+        return sign | 0x7c00;
+    }
+    if abs < F16_MIN_NORMAL {
+        // Exact: subnormals are whole multiples of the step below 1024.
+        return sign | (abs / F16_SUBNORMAL_STEP) as u16;
+    }
+    let bits = abs.to_bits();
+    let exponent = ((bits >> 23) & 0xff) + 15 - 127;
+    let mantissa = (bits >> 13) & 0x3ff;
+    sign | ((exponent << 10) | mantissa) as u16
+}
+
+/// Rounds to the nearest `f16` value (ties to even) and widens it back.
+fn round_to_f16(x: f32) -> f32 {
+    if !x.is_finite() {
+        return x;
+    }
+    let abs = x.abs();
+    if abs >= F16_OVERFLOW {
+        return f32::INFINITY.copysign(x);
+    }
+    if abs < F16_MIN_NORMAL {
+        return (x / F16_SUBNORMAL_STEP).round_ties_even() * F16_SUBNORMAL_STEP;
+    }
+    // f16 keeps 10 of the 23 mantissa bits; drop the other 13.
+    let bits = x.to_bits();
+    let rounded = bits + 0x0fff + ((bits >> 13) & 1);
+    f32::from_bits(rounded & !0x1fff)
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
@@ -0,0 +1,14 @@
+#!/bin/bash
+# Builds the stub memory canister used by `cargo test --features integration-tests`
+# and prints the paths of the resulting wasms: the full stub, and
+# stub_memory_base.wasm with only the methods of the deployed memory canisters.
+set -euo pipefail
+
+cd "$(dirname "$0")/../canisters/stub_memory"
+rustup target add wasm32-unknown-unknown >/dev/null
+release=target/wasm32-unknown-unknown/release
+cargo build --release --target wasm32-unknown-unknown --no-default-features
+cp "$release/stub_memory.wasm" "$release/stub_memory_base.wasm"
+cargo build --release --target wasm32-unknown-unknown
+echo "$(pwd)/$release/stub_memory.wasm"
+echo "$(pwd)/$release/stub_memory_base.wasm"